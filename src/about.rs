@@ -0,0 +1,35 @@
+use std::env::consts::{ARCH, OS};
+
+use crate::{config::config_file_path, features, get_history_file_path};
+
+const DECODER_BACKEND: &str = "minimp3 (streaming MP3)";
+
+pub fn version_line() -> String {
+    format!("nts_cli {} ({})", env!("CARGO_PKG_VERSION"), env!("NTS_CLI_GIT_HASH"))
+}
+
+/// Everything a bug report needs, one fact per line, ready to paste.
+pub fn report(recent_errors: &[String]) -> Vec<String> {
+    let enabled = features::built();
+    let mut lines = vec![
+        version_line(),
+        format!(
+            "features: {}",
+            if enabled.is_empty() { "none".to_string() } else { enabled.join(", ") }
+        ),
+        format!("decoder:  {}", DECODER_BACKEND),
+        format!("os:       {} {}", OS, ARCH),
+        format!(
+            "config:   {}",
+            config_file_path().map_or("none".to_string(), |path| path.display().to_string())
+        ),
+        format!("history:  {}", get_history_file_path().display()),
+    ];
+    if recent_errors.is_empty() {
+        lines.push("errors:   none".to_string());
+    } else {
+        lines.push("errors:".to_string());
+        lines.extend(recent_errors.iter().map(|error| format!("  {}", error)));
+    }
+    lines
+}
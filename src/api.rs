@@ -0,0 +1,329 @@
+use std::{error::Error, fs, io, path::PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fetch_scheduler::{self, Priority},
+    Airtime, Stream, UpcomingShow, STREAM_URL_1,
+};
+
+const LIVE_URL: &str = "https://www.nts.live/api/v2/live";
+const MIXTAPES_URL: &str = "https://www.nts.live/api/v2/mixtapes";
+const CACHE_FILE_NAME: &str = "nts_cli/streams.json";
+
+// Only the parts of the API responses the app uses. The same types are
+// written to the disk cache, so parsing the API and reading the cache can't
+// drift apart. Every field is optional in practice, hence the defaults.
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MixtapeResponse {
+    pub results: Vec<Mixtape>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Mixtape {
+    pub mixtape_alias: Option<String>,
+    pub title: String,
+    pub subtitle: String,
+    pub description: String,
+    pub audio_stream_endpoint: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LiveResponse {
+    pub results: Vec<LiveChannel>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LiveChannel {
+    pub channel_name: Option<String>,
+    pub now: Broadcast,
+    pub next: Option<Broadcast>,
+    pub next2: Option<Broadcast>,
+    pub next3: Option<Broadcast>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Broadcast {
+    pub broadcast_title: String,
+    pub start_timestamp: Option<String>,
+    pub end_timestamp: Option<String>,
+    pub embeds: BroadcastEmbeds,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BroadcastEmbeds {
+    pub details: ShowDetails,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ShowDetails {
+    pub description: String,
+    pub show_alias: String,
+}
+
+/// Both responses as last fetched, for starting without the network.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct StreamsCache {
+    pub live: LiveResponse,
+    pub mixtapes: MixtapeResponse,
+}
+
+impl From<Mixtape> for Stream {
+    fn from(mixtape: Mixtape) -> Self {
+        Stream {
+            id: mixtape.mixtape_alias.unwrap_or_else(|| mixtape.title.clone()),
+            title: mixtape.title,
+            subtitle: mixtape.subtitle,
+            description: mixtape.description,
+            audio_stream_endpoint: mixtape.audio_stream_endpoint,
+            show_alias: String::new(),
+            schedule: Vec::new(),
+            airtime: None,
+        }
+    }
+}
+
+/// Converts as the first channel; the caller renames the second one.
+impl From<LiveChannel> for Stream {
+    fn from(channel: LiveChannel) -> Self {
+        let mut schedule: Vec<UpcomingShow> = [channel.next, channel.next2, channel.next3]
+            .into_iter()
+            .flatten()
+            .filter_map(UpcomingShow::from_broadcast)
+            .collect();
+        // A show without an end runs until the next slot starts, titled or
+        // not; untitled slots are only left out after that
+        for i in 1..schedule.len() {
+            if schedule[i - 1].end.is_none() {
+                schedule[i - 1].end = Some(schedule[i].start);
+            }
+        }
+        schedule.retain(|show| !show.title.is_empty());
+        let airtime = parse_timestamp(&channel.now.start_timestamp).map(|start| Airtime {
+            start,
+            end: parse_timestamp(&channel.now.end_timestamp),
+        });
+        Stream {
+            id: channel.channel_name.unwrap_or_else(|| "1".to_string()),
+            title: "NTS Live 1".to_string(),
+            subtitle: channel.now.broadcast_title,
+            description: channel.now.embeds.details.description,
+            audio_stream_endpoint: STREAM_URL_1.to_string(),
+            show_alias: channel.now.embeds.details.show_alias,
+            schedule,
+            airtime,
+        }
+    }
+}
+
+impl UpcomingShow {
+    /// None for slots without a start time.
+    fn from_broadcast(broadcast: Broadcast) -> Option<UpcomingShow> {
+        Some(UpcomingShow {
+            start: parse_timestamp(&broadcast.start_timestamp)?,
+            end: parse_timestamp(&broadcast.end_timestamp),
+            title: broadcast.broadcast_title,
+        })
+    }
+}
+
+fn parse_timestamp(timestamp: &Option<String>) -> Option<DateTime<Local>> {
+    let timestamp = DateTime::parse_from_rfc3339(timestamp.as_deref()?).ok()?;
+    Some(timestamp.with_timezone(&Local))
+}
+
+/// Both responses from one fetch, with the cached copy standing in for
+/// one that failed, and why it failed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Fetched {
+    pub streams: StreamsCache,
+    pub live_error: Option<String>,
+    pub mixtapes_error: Option<String>,
+}
+
+/// Fetches the live channels and the mixtapes independently, so one failing
+/// doesn't take the other down, and caches whatever came back.
+pub fn fetch() -> Result<Fetched, String> {
+    let live = fetch_live().map_err(|err| err.to_string());
+    let mixtapes = fetch_mixtapes().map_err(|err| err.to_string());
+    let fetched = combine(live, mixtapes, load_cache())?;
+    if fetched.live_error.is_none() || fetched.mixtapes_error.is_none() {
+        let _ = save_cache(&fetched.streams);
+    }
+    Ok(fetched)
+}
+
+/// Fills in a failed response from `cache`, or with nothing if there is no
+/// cache. Errors only when both failed and there is no cache either.
+pub fn combine(
+    live: Result<LiveResponse, String>,
+    mixtapes: Result<MixtapeResponse, String>,
+    cache: Option<StreamsCache>,
+) -> Result<Fetched, String> {
+    if let (Err(err), Err(_), None) = (&live, &mixtapes, &cache) {
+        return Err(err.clone());
+    }
+    let cache = cache.unwrap_or_default();
+    let (live, live_error) = match live {
+        Ok(live) => (live, None),
+        Err(err) => (cache.live, Some(err)),
+    };
+    let (mixtapes, mixtapes_error) = match mixtapes {
+        Ok(mixtapes) => (mixtapes, None),
+        Err(err) => (cache.mixtapes, Some(err)),
+    };
+    Ok(Fetched {
+        streams: StreamsCache { live, mixtapes },
+        live_error,
+        mixtapes_error,
+    })
+}
+
+pub fn fetch_live() -> Result<LiveResponse, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fetch_scheduler::get(LIVE_URL, Priority::Schedule)?)?)
+}
+
+pub fn fetch_mixtapes() -> Result<MixtapeResponse, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fetch_scheduler::get(MIXTAPES_URL, Priority::Listing)?)?)
+}
+
+/// Finds the mixtape titled `title` in a fresh listing, for when its old
+/// endpoint `stale_url` stopped answering. Errors unless it has a new one.
+pub fn find_moved_mixtape(mixtapes: MixtapeResponse, title: &str, stale_url: &str) -> Result<Mixtape, String> {
+    let mixtape = mixtapes
+        .results
+        .into_iter()
+        .find(|mixtape| mixtape.title == title)
+        .ok_or("no longer listed")?;
+    if mixtape.audio_stream_endpoint == stale_url {
+        return Err("still listed at the same endpoint".to_string());
+    }
+    Ok(mixtape)
+}
+
+fn cache_file_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(CACHE_FILE_NAME))
+}
+
+pub fn load_cache() -> Option<StreamsCache> {
+    let contents = fs::read_to_string(cache_file_path()?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+pub fn save_cache(cache: &StreamsCache) -> io::Result<()> {
+    let path = cache_file_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(cache)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed from real responses, including fields the types don't keep
+    const LIVE: &str = r#"{
+        "results": [
+            {
+                "channel_name": "1",
+                "now": {
+                    "broadcast_title": "Breakfast Show",
+                    "start_timestamp": "2024-06-01T08:00:00Z",
+                    "end_timestamp": "2024-06-01T11:00:00Z",
+                    "embeds": {"details": {"description": "Live from London.", "show_alias": "breakfast", "genres": []}}
+                },
+                "next": {"broadcast_title": "Lunch Show", "start_timestamp": "2024-06-01T11:00:00Z"},
+                "next2": {"broadcast_title": "", "start_timestamp": "2024-06-01T13:00:00Z"},
+                "next3": {"broadcast_title": "Afternoon Tea", "start_timestamp": "2024-06-01T14:00:00Z"}
+            },
+            {"channel_name": "2", "now": {"broadcast_title": "Late Junction"}}
+        ],
+        "links": []
+    }"#;
+
+    const MIXTAPES: &str = r#"{
+        "results": [
+            {"mixtape_alias": "poolside", "title": "Poolside", "subtitle": "Sun-drenched sounds",
+             "description": "Balearic, boogie and beyond.", "audio_stream_endpoint": "https://example.com/poolside",
+             "credits": []},
+            {"title": "No Alias"}
+        ]
+    }"#;
+
+    fn fixture() -> StreamsCache {
+        StreamsCache {
+            live: serde_json::from_str(LIVE).unwrap(),
+            mixtapes: serde_json::from_str(MIXTAPES).unwrap(),
+        }
+    }
+
+    #[test]
+    fn api_responses_convert_to_streams() {
+        let cache = fixture();
+        let station = Stream::from(cache.live.results[0].clone());
+        assert_eq!(station.id, "1");
+        assert_eq!(station.subtitle, "Breakfast Show");
+        assert_eq!(station.description, "Live from London.");
+        assert_eq!(station.show_alias, "breakfast");
+        let schedule: Vec<_> = station.schedule.iter().map(|show| show.title.as_str()).collect();
+        assert_eq!(schedule, ["Lunch Show", "Afternoon Tea"]);
+        // Untitled slots are left out, but Lunch Show still ends when the
+        // one after it starts
+        let lunch = &station.schedule[0];
+        assert_eq!(lunch.end.unwrap(), parse_timestamp(&Some("2024-06-01T13:00:00Z".to_string())).unwrap());
+        assert!(station.schedule[1].end.is_none());
+        let airtime = station.airtime.unwrap();
+        assert_eq!(airtime.end.unwrap() - airtime.start, chrono::TimeDelta::hours(3));
+        assert!(Stream::from(cache.live.results[1].clone()).airtime.is_none());
+
+        let mixtapes: Vec<Stream> = cache.mixtapes.results.into_iter().map(Stream::from).collect();
+        assert_eq!(mixtapes[0].id, "poolside");
+        assert_eq!(mixtapes[0].audio_stream_endpoint, "https://example.com/poolside");
+        assert_eq!(mixtapes[1].id, "No Alias");
+    }
+
+    #[test]
+    fn moved_mixtape_is_found_by_title() {
+        let mixtapes = fixture().mixtapes;
+        let moved = find_moved_mixtape(mixtapes.clone(), "Poolside", "https://example.com/old-poolside").unwrap();
+        assert_eq!(moved.audio_stream_endpoint, "https://example.com/poolside");
+        assert!(find_moved_mixtape(mixtapes.clone(), "Poolside", "https://example.com/poolside").is_err());
+        assert!(find_moved_mixtape(mixtapes, "Gone", "https://example.com/gone").is_err());
+    }
+
+    #[test]
+    fn a_failed_response_falls_back_to_its_cached_copy() {
+        fn failed<T>() -> Result<T, String> {
+            Err("timed out".to_string())
+        }
+        let fetched = combine(failed(), Ok(fixture().mixtapes), Some(fixture())).unwrap();
+        assert_eq!(fetched.streams, fixture());
+        assert_eq!(fetched.live_error.as_deref(), Some("timed out"));
+        assert_eq!(fetched.mixtapes_error, None);
+
+        let fetched = combine(Ok(fixture().live), failed(), None).unwrap();
+        assert!(fetched.streams.mixtapes.results.is_empty());
+        assert_eq!(fetched.streams.live, fixture().live);
+        assert!(combine(failed(), failed(), None).is_err());
+        assert!(combine(failed(), failed(), Some(fixture())).is_ok());
+    }
+
+    #[test]
+    fn cache_round_trips_through_the_same_types() {
+        let cache = fixture();
+        let written = serde_json::to_string(&cache).unwrap();
+        let read: StreamsCache = serde_json::from_str(&written).unwrap();
+        assert_eq!(read, cache);
+    }
+}
@@ -0,0 +1,300 @@
+use std::cell::Cell;
+
+use chrono::DateTime;
+use serde_json::Value;
+
+use crate::{
+    fetch_scheduler::{self, Priority},
+    Stream,
+};
+
+const SHOW_URL: &str = "https://www.nts.live/api/v2/shows/";
+/// Episodes fetched per request.
+pub const PAGE_SIZE: usize = 30;
+/// How close to the last loaded episode the selection gets before the next
+/// page is fetched.
+const PREFETCH_MARGIN: usize = 5;
+
+/// An episode in a show's archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Episode {
+    pub alias: String,
+    pub name: String,
+    pub description: String,
+    /// When it went out, in Unix seconds.
+    pub broadcast: i64,
+    /// A direct audio URL, which the player can stream.
+    pub audio_url: Option<String>,
+    /// Where it can be heard on Mixcloud instead, when there is no direct URL.
+    pub mixcloud: Option<String>,
+}
+
+impl Episode {
+    pub fn date(&self) -> String {
+        DateTime::from_timestamp(self.broadcast, 0)
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    }
+
+    /// As a stream in the "Archive" section, which uses the episode alias
+    /// as its id. None when it has no direct URL.
+    pub fn to_stream(&self, show_alias: &str, show_name: &str) -> Option<Stream> {
+        Some(Stream {
+            id: self.alias.clone(),
+            title: self.name.clone(),
+            subtitle: format!("{} ({})", show_name, self.date()),
+            description: self.description.clone(),
+            audio_stream_endpoint: self.audio_url.clone()?,
+            show_alias: show_alias.to_string(),
+            schedule: Vec::new(),
+            airtime: None,
+        })
+    }
+
+    fn matches(&self, search: &str) -> bool {
+        self.name.to_lowercase().contains(search) || self.date().contains(search)
+    }
+}
+
+/// The show name `Episode::to_stream` put in the subtitle of `stream`.
+pub fn show_name(stream: &Stream) -> &str {
+    stream.subtitle.rsplit_once(" (").map_or(&stream.subtitle, |(show_name, _)| show_name)
+}
+
+/// One page of episodes, and how many the show has in all.
+#[derive(Debug, PartialEq)]
+pub struct Page {
+    pub episodes: Vec<Episode>,
+    pub total: usize,
+}
+
+/// Fetches `PAGE_SIZE` episodes of `show_alias` from `offset`, newest first.
+pub fn fetch_page(show_alias: &str, offset: usize) -> Result<Page, String> {
+    let url = format!("{}{}/episodes?offset={}&limit={}", SHOW_URL, show_alias, offset, PAGE_SIZE);
+    let json: Value = serde_json::from_str(&fetch_scheduler::get(&url, Priority::Listing)?).map_err(|err| err.to_string())?;
+    parse_page(&json, offset)
+}
+
+fn parse_page(json: &Value, offset: usize) -> Result<Page, String> {
+    let results = json["results"].as_array().ok_or("no episodes in the response")?;
+    let episodes: Vec<Episode> = results.iter().map(parse_episode).collect();
+    let total = json["metadata"]["resultset"]["count"]
+        .as_u64()
+        .map_or(offset + episodes.len(), |count| count as usize);
+    Ok(Page { episodes, total })
+}
+
+/// The first of an episode's audio sources that the player can stream.
+/// Shared with the followed shows.
+pub fn audio_url(episode: &Value) -> Option<String> {
+    let sources = episode["audio_sources"].as_array().map(Vec::as_slice).unwrap_or_default();
+    // Mixcloud and SoundCloud pages aren't audio the player can decode
+    sources
+        .iter()
+        .filter(|source| {
+            let hosted = |name: &str| {
+                source["source"].as_str().is_some_and(|source| source.eq_ignore_ascii_case(name))
+                    || source["url"].as_str().is_some_and(|url| url.contains(&format!("{}.com", name)))
+            };
+            !hosted("mixcloud") && !hosted("soundcloud")
+        })
+        .find_map(|source| source["url"].as_str())
+        .map(str::to_string)
+}
+
+fn parse_episode(episode: &Value) -> Episode {
+    let text = |key: &str| episode[key].as_str().unwrap_or_default().to_string();
+    let audio_url = audio_url(episode);
+    Episode {
+        alias: text("episode_alias"),
+        name: text("name"),
+        description: text("description"),
+        broadcast: episode["broadcast"]
+            .as_str()
+            .and_then(|broadcast| DateTime::parse_from_rfc3339(broadcast).ok())
+            .map_or(0, |broadcast| broadcast.timestamp()),
+        audio_url,
+        mixcloud: episode["mixcloud"].as_str().filter(|url| !url.is_empty()).map(str::to_string),
+    }
+}
+
+/// The show alias in `input`, which is either the alias itself or a link
+/// to the show, e.g. `https://www.nts.live/shows/the-breakfast-show`.
+pub fn parse_show(input: &str) -> Option<String> {
+    let input = input.trim().trim_end_matches('/');
+    let alias = match input.split_once("/shows/") {
+        Some((_, rest)) => rest.split('/').next().unwrap_or_default(),
+        None => input,
+    };
+    let valid = !alias.is_empty() && alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    valid.then(|| alias.to_lowercase())
+}
+
+/// A show's archive while its popup is open. Episodes are fetched a page at
+/// a time as the selection nears the end of what's loaded, or all of them
+/// while searching.
+#[derive(Debug)]
+pub struct Archive {
+    pub show_alias: String,
+    pub show_name: String,
+    pub episodes: Vec<Episode>,
+    /// How many the show has in all, once a page came back.
+    pub total: Option<usize>,
+    pub loading: bool,
+    pub error: Option<String>,
+    /// Narrows the list to episodes whose name or date contains it.
+    pub search: String,
+    /// Whether keys are typed into the search.
+    pub searching: bool,
+    /// Position of the selection among the episodes that match the search.
+    pub selected: usize,
+    /// The first row in sight, kept from one draw to the next.
+    pub offset: Cell<usize>,
+}
+
+impl Archive {
+    pub fn new(show_alias: String, show_name: String) -> Self {
+        Archive {
+            show_alias,
+            show_name,
+            episodes: Vec::new(),
+            total: None,
+            loading: false,
+            error: None,
+            search: String::new(),
+            searching: false,
+            selected: 0,
+            offset: Cell::new(0),
+        }
+    }
+
+    /// The episodes that match the search, in order.
+    pub fn visible(&self) -> Vec<&Episode> {
+        let search = self.search.to_lowercase();
+        self.episodes.iter().filter(|episode| episode.matches(&search)).collect()
+    }
+
+    pub fn selected(&self) -> Option<&Episode> {
+        self.visible().get(self.selected).copied()
+    }
+
+    /// Moves the selection by `delta` rows, stopping at either end.
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.visible().len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn set_search(&mut self, search: String) {
+        self.search = search;
+        self.selected = 0;
+        self.offset.set(0);
+    }
+
+    /// Where the next page starts, if one should be fetched now.
+    pub fn next_page(&self) -> Option<usize> {
+        let loaded = self.episodes.len();
+        let more = self.total.is_none_or(|total| loaded < total);
+        let wanted = self.total.is_none()
+            || !self.search.is_empty()
+            || self.selected + PREFETCH_MARGIN >= self.visible().len();
+        (more && wanted && !self.loading && self.error.is_none()).then_some(loaded)
+    }
+
+    /// Adds a fetched page. Pages that don't start where the list ends are
+    /// left out, so a slow response can't duplicate episodes.
+    pub fn add_page(&mut self, offset: usize, page: Result<Page, String>) {
+        self.loading = false;
+        match page {
+            Ok(page) if offset == self.episodes.len() => {
+                // An empty page means the count was off, so stop there
+                self.total = Some(if page.episodes.is_empty() { offset } else { page.total });
+                self.episodes.extend(page.episodes);
+            }
+            Ok(_) => {}
+            Err(err) => self.error = Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_episodes_and_tells_mixcloud_only_ones_apart() {
+        let json = serde_json::json!({
+            "metadata": {"resultset": {"count": 412, "offset": 0, "limit": 2}},
+            "results": [
+                {
+                    "episode_alias": "the-breakfast-show-30th-may-2024",
+                    "name": "The Breakfast Show",
+                    "broadcast": "2024-05-30T07:00:00Z",
+                    "mixcloud": "https://www.mixcloud.com/NTSRadio/breakfast/",
+                    "audio_sources": [
+                        {"url": "https://www.mixcloud.com/NTSRadio/breakfast/", "source": "mixcloud"},
+                        {"url": "https://media.example.com/breakfast.mp3", "source": "nts"}
+                    ]
+                },
+                {
+                    "episode_alias": "the-breakfast-show-29th-may-2024",
+                    "name": "The Breakfast Show w/ Guest",
+                    "broadcast": "2024-05-29T07:00:00Z",
+                    "mixcloud": "https://www.mixcloud.com/NTSRadio/breakfast-guest/",
+                    "audio_sources": [{"url": "https://soundcloud.com/nts/breakfast-guest", "source": "soundcloud"}]
+                }
+            ]
+        });
+        let page = parse_page(&json, 0).unwrap();
+        assert_eq!(page.total, 412);
+        let [direct, mixcloud] = &page.episodes[..] else {
+            panic!("expected two episodes");
+        };
+        assert_eq!(direct.audio_url.as_deref(), Some("https://media.example.com/breakfast.mp3"));
+        assert_eq!(direct.date(), "2024-05-30");
+        assert_eq!(mixcloud.audio_url, None);
+        assert!(mixcloud.to_stream("breakfast", "Breakfast").is_none());
+        assert!(parse_page(&serde_json::json!({}), 0).is_err());
+
+        assert_eq!(parse_show(" https://www.nts.live/shows/the-breakfast-show/episodes "), Some("the-breakfast-show".to_string()));
+        assert_eq!(parse_show("Slow-Focus"), Some("slow-focus".to_string()));
+        assert_eq!(parse_show("not a show"), None);
+    }
+
+    #[test]
+    fn pages_load_near_the_end_and_while_searching() {
+        let episode = |name: &str| Episode {
+            alias: name.to_lowercase(),
+            name: name.to_string(),
+            description: String::new(),
+            broadcast: 0,
+            audio_url: None,
+            mixcloud: None,
+        };
+        let mut archive = Archive::new("show".to_string(), "Show".to_string());
+        assert_eq!(archive.next_page(), Some(0));
+        let page = |names: &[&str]| {
+            Ok(Page {
+                episodes: names.iter().map(|name| episode(name)).collect(),
+                total: 20,
+            })
+        };
+        archive.add_page(0, page(&["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"]));
+        assert_eq!(archive.next_page(), None);
+        // A late response for a page already in doesn't add it twice
+        archive.add_page(0, page(&["a"]));
+        assert_eq!(archive.episodes.len(), 10);
+
+        archive.move_selection(-3);
+        assert_eq!(archive.selected, 0);
+        archive.move_selection(100);
+        assert_eq!(archive.selected().map(|episode| episode.name.as_str()), Some("j"));
+        assert_eq!(archive.next_page(), Some(10));
+
+        archive.set_search("C".to_string());
+        assert_eq!(archive.visible().len(), 1);
+        assert_eq!(archive.selected, 0);
+        assert_eq!(archive.next_page(), Some(10));
+        archive.add_page(10, Err("timed out".to_string()));
+        assert_eq!(archive.next_page(), None);
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{Cursor, Read},
+    time::{Duration, Instant},
+};
+
+use rodio::{source::SineWave, OutputStream, Sink, Source};
+
+use crate::{http, mp3_decoder::Mp3StreamDecoder};
+
+const TONE_FREQUENCY: f32 = 440.0;
+const TONE_DURATION: Duration = Duration::from_secs(2);
+const TONE_VOLUME: f32 = 0.2;
+/// One second of silence at 48 kHz mono, 42 frames of 1152 samples. Silence
+/// because there is no encoder at build time; the decoder still has to find
+/// and unpack every frame for the count to match.
+const FIXTURE: &[u8] = include_bytes!("../assets/selftest.mp3");
+const FIXTURE_SAMPLES: usize = 42 * 1152;
+const LISTEN_DURATION: Duration = Duration::from_secs(3);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The parts of the audio path that can each be why there is no sound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Device,
+    Decoder,
+    Network,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Stage::Device => "device",
+            Stage::Decoder => "decoder",
+            Stage::Network => "network",
+        })
+    }
+}
+
+/// The order the stages run in, from the speakers back to the server.
+pub const STAGES: [Stage; 3] = [Stage::Device, Stage::Decoder, Stage::Network];
+
+/// What one stage found: a short summary either way.
+pub type Outcome = Result<String, String>;
+
+/// Plays a 440 Hz tone on the default device and waits for it to finish.
+pub fn check_device() -> Outcome {
+    let (_stream, handle) = OutputStream::try_default().map_err(|err| format!("No audio output device: {}", err))?;
+    let sink = Sink::try_new(&handle).map_err(|err| format!("Could not open a sink: {}", err))?;
+    sink.set_volume(TONE_VOLUME);
+    sink.append(
+        SineWave::new(TONE_FREQUENCY)
+            .take_duration(TONE_DURATION)
+            .fade_in(Duration::from_millis(20)),
+    );
+    let started = Instant::now();
+    sink.sleep_until_end();
+    // A sink whose device never pulls samples finishes at once
+    if started.elapsed() < TONE_DURATION / 2 {
+        return Err("The device did not play the tone".to_string());
+    }
+    Ok(format!(
+        "played a {} Hz tone for {}s",
+        TONE_FREQUENCY,
+        TONE_DURATION.as_secs()
+    ))
+}
+
+/// Decodes the bundled fixture and checks nothing went missing.
+pub fn check_decoder() -> Outcome {
+    let decoder = Mp3StreamDecoder::new(Cursor::new(FIXTURE), 8096, None)
+        .map_err(|_| "The fixture was not recognised as MP3".to_string())?;
+    let (channels, rate) = (decoder.channels(), decoder.sample_rate());
+    let samples = decoder.count();
+    if samples != FIXTURE_SAMPLES {
+        return Err(format!("decoded {} samples, expected {}", samples, FIXTURE_SAMPLES));
+    }
+    Ok(format!("decoded {} samples ({} Hz, {} ch)", samples, rate, channels))
+}
+
+/// Reads from `stream_url` for a few seconds and counts what arrives.
+pub fn check_network(stream_url: &str) -> Outcome {
+    let client = http::client(stream_url)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(CONNECT_TIMEOUT + LISTEN_DURATION)
+        .build()
+        .map_err(|err| format!("Could not set up HTTP client: {}", err))?;
+    let mut response = client
+        .get(stream_url)
+        .send()
+        .and_then(http::error_for_status)
+        .map_err(|err| format!("Could not connect: {}", http::describe_error(&err)))?;
+    let started = Instant::now();
+    let mut chunk = [0u8; 8192];
+    let mut received = 0u64;
+    while started.elapsed() < LISTEN_DURATION {
+        match response.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => received += read as u64,
+            Err(err) if received == 0 => return Err(format!("Stream read failed: {}", err)),
+            Err(_) => break,
+        }
+    }
+    if received == 0 {
+        return Err("The stream sent no data".to_string());
+    }
+    Ok(format!(
+        "received {} KB in {:.1}s",
+        received / 1024,
+        started.elapsed().as_secs_f32()
+    ))
+}
+
+/// Where the player would connect for the `listed` endpoint: its override
+/// from `network.stream_overrides`, if there is one.
+pub fn endpoint<'a>(listed: &'a str, overrides: &'a BTreeMap<String, String>) -> &'a str {
+    overrides.get(listed).map_or(listed, String::as_str)
+}
+
+pub fn check(stage: Stage, stream_url: &str) -> Outcome {
+    match stage {
+        Stage::Device => check_device(),
+        Stage::Decoder => check_decoder(),
+        Stage::Network => check_network(stream_url),
+    }
+}
+
+/// Runs every stage in order. Later stages run even when an earlier one
+/// fails, so one report covers the whole path.
+pub fn run_all(stream_url: &str) -> Vec<(Stage, Outcome)> {
+    STAGES.iter().map(|&stage| (stage, check(stage, stream_url))).collect()
+}
+
+/// One line per stage, e.g. "device   pass  played a 440 Hz tone for 2s".
+pub fn report_lines(results: &[(Stage, Outcome)]) -> Vec<String> {
+    results
+        .iter()
+        .map(|(stage, outcome)| match outcome {
+            Ok(summary) => format!("{:<8} pass  {}", stage, summary),
+            Err(problem) => format!("{:<8} FAIL  {}", stage, problem),
+        })
+        .collect()
+}
+
+/// `nts_cli doctor --audio`. Returns whether every stage passed.
+pub fn run(stream_url: &str) -> bool {
+    println!("nts_cli doctor --audio (you should hear a short tone)");
+    let results = run_all(stream_url);
+    for line in report_lines(&results) {
+        println!("  {}", line);
+    }
+    results.iter().all(|(_, outcome)| outcome.is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixture_decodes_in_full() {
+        assert_eq!(
+            check_decoder(),
+            Ok("decoded 48384 samples (48000 Hz, 1 ch)".to_string())
+        );
+    }
+
+    #[test]
+    fn overridden_endpoints_are_checked_in_place_of_the_listed_ones() {
+        let overrides = BTreeMap::from([("https://a/stream".to_string(), "http://b/stream".to_string())]);
+        assert_eq!(endpoint("https://a/stream", &overrides), "http://b/stream");
+        assert_eq!(endpoint("https://c/stream", &overrides), "https://c/stream");
+    }
+
+    #[test]
+    fn report_marks_each_stage() {
+        let lines = report_lines(&[
+            (Stage::Decoder, Ok("decoded 48384 samples".to_string())),
+            (Stage::Network, Err("The stream sent no data".to_string())),
+        ]);
+        assert_eq!(
+            lines,
+            [
+                "decoder  pass  decoded 48384 samples",
+                "network  FAIL  The stream sent no data",
+            ]
+        );
+    }
+}
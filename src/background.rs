@@ -0,0 +1,94 @@
+use std::{env, time::Duration};
+
+use crate::config::Background;
+
+/// How long to wait for the terminal to answer the background color query.
+/// Terminals that don't support it never answer, so this bounds startup.
+#[cfg(unix)]
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Works out whether the terminal background is dark or light. An explicit
+/// setting wins; otherwise the terminal is asked (OSC 11), then `COLORFGBG`
+/// is consulted, and dark is assumed if neither says. Expects raw mode, so
+/// call it after the terminal is set up and before input is read.
+pub fn detect(configured: Background) -> Background {
+    if configured != Background::Auto {
+        return configured;
+    }
+    query_terminal()
+        .or_else(from_colorfgbg)
+        .unwrap_or(Background::Dark)
+}
+
+#[cfg(unix)]
+fn query_terminal() -> Option<Background> {
+    use std::{
+        io::{self, Write},
+        time::Instant,
+    };
+
+    if unsafe { libc::isatty(libc::STDIN_FILENO) == 0 || libc::isatty(libc::STDOUT_FILENO) == 0 } {
+        return None;
+    }
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x1b]11;?\x07").ok()?;
+    stdout.flush().ok()?;
+
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    let mut response = Vec::new();
+    while !(response.ends_with(b"\x07") || response.ends_with(b"\x1b\\")) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        let mut poll_fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as libc::c_int) } <= 0 {
+            break;
+        }
+        let mut chunk = [0u8; 64];
+        let read = unsafe { libc::read(libc::STDIN_FILENO, chunk.as_mut_ptr().cast(), chunk.len()) };
+        if read <= 0 {
+            break;
+        }
+        response.extend_from_slice(&chunk[..read as usize]);
+    }
+    parse_osc11(&String::from_utf8_lossy(&response))
+}
+
+#[cfg(not(unix))]
+fn query_terminal() -> Option<Background> {
+    None
+}
+
+/// Parses a reply like `ESC ]11;rgb:ffff/ffff/dddd BEL`.
+fn parse_osc11(response: &str) -> Option<Background> {
+    let rgb = &response[response.find("rgb:")? + 4..];
+    let rgb = rgb.trim_end_matches(['\x07', '\\', '\x1b']);
+    let mut channels = rgb.split('/').map(|channel| {
+        let value = u32::from_str_radix(channel, 16).ok()?;
+        let max = (1u32 << (4 * channel.len().min(4))) - 1;
+        Some(value as f32 / max as f32)
+    });
+    let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(if luminance > 0.5 {
+        Background::Light
+    } else {
+        Background::Dark
+    })
+}
+
+/// `COLORFGBG` is set by some terminals (rxvt, Konsole) as `fg;bg`, where the
+/// background is an ANSI color index.
+fn from_colorfgbg() -> Option<Background> {
+    let value = env::var("COLORFGBG").ok()?;
+    let background: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(match background {
+        7 | 9..=15 => Background::Light,
+        _ => Background::Dark,
+    })
+}
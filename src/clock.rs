@@ -0,0 +1,91 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicI64, Ordering},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use reqwest::{blocking::Response, header::DATE};
+
+use crate::{format, tls};
+
+/// Skews under this are left alone: the `Date` header has whole seconds
+/// only, and the request takes some of them.
+const THRESHOLD: Duration = Duration::from_secs(120);
+
+/// How far the NTS servers' clock is ahead of this machine's, in seconds.
+/// Zero until a response shows a skew past `THRESHOLD`.
+static OFFSET_SECS: AtomicI64 = AtomicI64::new(0);
+/// Whether the skew was reported yet, so it is only once.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// The offset `date`, an HTTP `Date` header, shows against `local`, the
+/// time here when the response came: zero under `THRESHOLD`, None when the
+/// header doesn't parse.
+fn offset(date: &str, local: DateTime<Utc>) -> Option<i64> {
+    let server = DateTime::parse_from_rfc2822(date).ok()?.with_timezone(&Utc);
+    let offset = (server - local).num_seconds();
+    Some(if offset.unsigned_abs() < THRESHOLD.as_secs() { 0 } else { offset })
+}
+
+/// Measures the skew from the `Date` header of `response` when it comes
+/// from NTS. Called on every API response, so a clock put right is noticed
+/// on the next refresh.
+pub fn observe(response: &Response) {
+    if !response.url().host_str().is_some_and(tls::is_nts_host) {
+        return;
+    }
+    let date = response.headers().get(DATE).and_then(|date| date.to_str().ok());
+    if let Some(offset) = date.and_then(|date| offset(date, Utc::now())) {
+        OFFSET_SECS.store(offset, Ordering::Relaxed);
+    }
+}
+
+fn corrected_by(time: DateTime<Local>, offset: i64) -> DateTime<Local> {
+    time + TimeDelta::seconds(offset)
+}
+
+/// `time` on the NTS clock. Everything compared with the schedule goes
+/// through here, so a skew corrects them all alike.
+pub fn corrected(time: DateTime<Local>) -> DateTime<Local> {
+    corrected_by(time, OFFSET_SECS.load(Ordering::Relaxed))
+}
+
+/// Now, on the NTS clock.
+pub fn now() -> DateTime<Local> {
+    corrected(Local::now())
+}
+
+fn describe(offset: i64) -> String {
+    format!(
+        "System clock appears off by {} ({} NTS), correcting the show times",
+        format::duration_words(Duration::from_secs(offset.unsigned_abs())),
+        if offset > 0 { "behind" } else { "ahead of" }
+    )
+}
+
+/// The warning about a skew, the first time one is seen.
+pub fn warning() -> Option<String> {
+    let offset = OFFSET_SECS.load(Ordering::Relaxed);
+    (offset != 0 && !WARNED.swap(true, Ordering::Relaxed)).then(|| describe(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn only_a_skew_past_the_threshold_corrects_the_time() {
+        let local = Utc.with_ymd_and_hms(2024, 6, 1, 13, 50, 0).unwrap();
+        assert_eq!(offset("Sat, 01 Jun 2024 14:32:00 GMT", local), Some(42 * 60));
+        assert_eq!(offset("Sat, 01 Jun 2024 13:51:30 GMT", local), Some(0));
+        assert_eq!(offset("Sat, 01 Jun 2024 11:20:00 +0000", local), Some(-150 * 60));
+        assert_eq!(offset("yesterday", local), None);
+
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 14, 0, 0).unwrap();
+        assert_eq!(corrected_by(now, 42 * 60), Local.with_ymd_and_hms(2024, 6, 1, 14, 42, 0).unwrap());
+        assert_eq!(describe(42 * 60), "System clock appears off by 42 min (behind NTS), correcting the show times");
+        assert!(describe(-150 * 60).starts_with("System clock appears off by 2 h 30 min (ahead of NTS)"));
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+/// What the caller should do about a request for the work.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Turn {
+    /// Do it now.
+    Now,
+    /// Call `due` after this long, when it will run once for every request
+    /// made until then.
+    After(Duration),
+    /// Nothing; a run is already coming.
+    Queued,
+}
+
+/// Lets work asked for in bursts, like reading the history again after each
+/// of a flood of results, run at most once per interval. A request after a
+/// quiet spell runs right away and the ones that follow within the interval
+/// fold into a single run at its end. While suspended, as for a bulk change,
+/// requests only pile up until `resume`.
+#[derive(Debug)]
+pub struct Coalescer {
+    interval: Duration,
+    last_run: Option<Instant>,
+    pending: bool,
+    /// Whether the caller was told to come back with `due`.
+    scheduled: bool,
+    suspended: usize,
+    runs: u64,
+}
+
+impl Coalescer {
+    pub fn new(interval: Duration) -> Self {
+        Coalescer {
+            interval,
+            last_run: None,
+            pending: false,
+            scheduled: false,
+            suspended: 0,
+            runs: 0,
+        }
+    }
+
+    pub fn request(&mut self, now: Instant) -> Turn {
+        self.pending = true;
+        self.turn(now)
+    }
+
+    /// For the `After` wake-up: whether the requests since the last run
+    /// should run now.
+    pub fn due(&mut self, now: Instant) -> bool {
+        self.scheduled = false;
+        if !self.pending || self.suspended > 0 {
+            return false;
+        }
+        self.ran(now);
+        true
+    }
+
+    /// Holds requests until the matching `resume`, which nests.
+    pub fn suspend(&mut self) {
+        self.suspended += 1;
+    }
+
+    /// Ends a suspension. Everything requested during it counts as one
+    /// request made now.
+    pub fn resume(&mut self, now: Instant) -> Turn {
+        self.suspended = self.suspended.saturating_sub(1);
+        if self.pending {
+            self.turn(now)
+        } else {
+            Turn::Queued
+        }
+    }
+
+    /// How many times the work ran, for the debug overlay.
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    fn turn(&mut self, now: Instant) -> Turn {
+        if self.suspended > 0 || self.scheduled {
+            return Turn::Queued;
+        }
+        let wait = self
+            .last_run
+            .map_or(Duration::ZERO, |last| self.interval.saturating_sub(now.duration_since(last)));
+        if wait.is_zero() {
+            self.ran(now);
+            Turn::Now
+        } else {
+            self.scheduled = true;
+            Turn::After(wait)
+        }
+    }
+
+    fn ran(&mut self, now: Instant) {
+        self.last_run = Some(now);
+        self.pending = false;
+        self.runs += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(250);
+
+    #[test]
+    fn a_burst_runs_once_now_and_once_at_the_end_of_the_interval() {
+        let start = Instant::now();
+        let mut coalescer = Coalescer::new(INTERVAL);
+        assert_eq!(coalescer.request(start), Turn::Now);
+        assert_eq!(coalescer.request(start + INTERVAL / 5), Turn::After(INTERVAL * 4 / 5));
+        for i in 0..1000 {
+            assert_eq!(coalescer.request(start + INTERVAL / 5 + Duration::from_micros(i)), Turn::Queued);
+        }
+        assert!(coalescer.due(start + INTERVAL));
+        assert!(!coalescer.due(start + INTERVAL * 2));
+        assert_eq!(coalescer.runs(), 2);
+        // A quiet spell later it runs right away again
+        assert_eq!(coalescer.request(start + INTERVAL * 3), Turn::Now);
+    }
+
+    #[test]
+    fn a_suspended_bulk_change_runs_once_when_resumed() {
+        let start = Instant::now();
+        let mut coalescer = Coalescer::new(INTERVAL);
+        coalescer.suspend();
+        coalescer.suspend();
+        for _ in 0..10_000 {
+            assert_eq!(coalescer.request(start), Turn::Queued);
+        }
+        assert!(!coalescer.due(start));
+        assert_eq!(coalescer.resume(start), Turn::Queued);
+        assert_eq!(coalescer.resume(start), Turn::Now);
+        assert_eq!(coalescer.runs(), 1);
+
+        coalescer.suspend();
+        assert_eq!(coalescer.resume(start + INTERVAL * 2), Turn::Queued);
+        assert_eq!(coalescer.runs(), 1);
+    }
+}
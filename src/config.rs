@@ -0,0 +1,743 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, str::FromStr};
+
+use std::time::Duration;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::{features, StreamType};
+
+const CONFIG_DIR_NAME: &str = "nts_cli";
+const CONFIG_FILE_NAME: &str = "config.toml";
+/// Sections whose keys are names the user picks, so none of them is unknown.
+const MAP_KEYS: [&str; 4] = ["network.stream_overrides", "loudness.offsets", "http.headers", "http.cookies"];
+/// Sections whose values are secrets, printed as "<redacted>".
+const SECRET_KEYS: [&str; 2] = ["http.headers", "http.cookies"];
+
+/// User configuration read from `config.toml` in the platform config directory.
+/// Every key is optional and falls back to the built-in default.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub light_mode: bool,
+    pub background: Background,
+    /// Write decimals as "1,5" in reports meant to be read as numbers.
+    pub decimal_comma: bool,
+    /// Where recognized tracks are written. Empty for `nts_cli/history.txt`
+    /// in the platform data directory; `~/` is expanded.
+    pub history_path: PathBuf,
+    /// How the time in front of each history entry is written.
+    pub history_timestamps: TimestampStyle,
+    pub recognition: RecognitionConfig,
+    pub announcements: AnnouncementsConfig,
+    pub playback: PlaybackConfig,
+    pub loudness: LoudnessConfig,
+    pub network: NetworkConfig,
+    pub http: HttpConfig,
+    pub tls: TlsConfig,
+    pub shutdown: ShutdownConfig,
+    pub output: OutputConfig,
+    pub discord: DiscordConfig,
+    pub retention: RetentionConfig,
+    pub share: ShareConfig,
+    pub colors: ColorsConfig,
+    pub guard: GuardConfig,
+}
+
+/// Discord Rich Presence, in builds with the `discord` feature. Discord
+/// needs the id of an application registered with it, named e.g. "NTS".
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DiscordConfig {
+    pub enabled: bool,
+    pub client_id: String,
+}
+
+/// Keys that need a second press, so a slip doesn't stop a live show.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GuardConfig {
+    /// Space
+    pub stop: GuardMode,
+    /// q
+    pub quit: GuardMode,
+    /// Del in the history pane
+    pub delete_history: GuardMode,
+}
+
+/// `double` wants the second press within half a second, `confirm` shows a
+/// hint and waits a few seconds for it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GuardMode {
+    #[default]
+    Off,
+    Double,
+    Confirm,
+}
+
+/// Overrides for the default colors: a name like "yellow" or "lightblue", a
+/// palette index like "208", or "#rrggbb". Empty keeps the color picked for
+/// the background.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ColorsConfig {
+    pub heading: String,
+    pub highlight: String,
+    pub stream: String,
+    pub description: String,
+    pub text: String,
+    pub muted: String,
+}
+
+impl ColorsConfig {
+    pub fn entries(&self) -> [(&'static str, &str); 6] {
+        [
+            ("heading", &self.heading),
+            ("highlight", &self.highlight),
+            ("stream", &self.stream),
+            ("description", &self.description),
+            ("text", &self.text),
+            ("muted", &self.muted),
+        ]
+    }
+}
+
+/// A color from the `[colors]` section, or None when it is left empty.
+pub fn parse_color(value: &str) -> Result<Option<Color>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    Color::from_str(value)
+        .map(Some)
+        .map_err(|_| format!("unknown color '{}'", value))
+}
+
+/// How much the app keeps on disk over a long session. Retained samples
+/// older than `sample_max_age_days` are deleted once a day; 0 keeps them
+/// until `recognition.sample_retention_cap` pushes them out.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub debug_log_max_kb: u64,
+    pub sample_max_age_days: u64,
+    /// How long a recognition that failed offline waits to be retried
+    /// before its sample is given up on.
+    pub pending_id_max_age_hours: u64,
+}
+
+impl RetentionConfig {
+    pub fn pending_id_max_age(&self) -> Duration {
+        Duration::from_secs(self.pending_id_max_age_hours * 60 * 60)
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            debug_log_max_kb: 1024,
+            sample_max_age_days: 30,
+            pending_id_max_age_hours: 48,
+        }
+    }
+}
+
+/// What `E` shares a session's tracks as, and where it keeps a copy.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShareConfig {
+    pub template: ShareTemplate,
+    /// Empty for `nts_cli/notes` in the platform data directory; `~/` is
+    /// expanded.
+    pub notes_dir: PathBuf,
+}
+
+/// `plain` text to paste anywhere, or `markdown` for notes apps.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareTemplate {
+    #[default]
+    Plain,
+    Markdown,
+}
+
+/// Where decoded audio goes: the sound device, or a named pipe for an
+/// external DSP chain. The pipe format is described on `PipeOutput`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub mode: OutputMode,
+    pub pipe_path: PathBuf,
+    pub resampler: ResampleQuality,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            mode: OutputMode::Device,
+            pipe_path: std::env::temp_dir().join("nts_cli.pcm"),
+            resampler: ResampleQuality::Linear,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    #[default]
+    Device,
+    Pipe,
+}
+
+/// How the stream is converted when the sound device runs at another rate.
+/// `native` asks the device for the stream's own rate and only falls back
+/// to linear conversion when it can't.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ResampleQuality {
+    #[default]
+    Linear,
+    High,
+    Native,
+}
+
+/// How long quitting waits for history and state writes to finish. Raise it
+/// for slow disks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    pub timeout_ms: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig { timeout_ms: 2000 }
+    }
+}
+
+/// Watching for network changes so a stream can be reconnected right away
+/// instead of stalling on a dead connection.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub watch_changes: bool,
+    pub check_interval_secs: u64,
+    /// Stream endpoints to use in place of the listed ones, keyed by the
+    /// listed URL. An override is used as is, without trying other hosts.
+    pub stream_overrides: BTreeMap<String, String>,
+    /// Requests per minute for listings, schedules and the like; the audio
+    /// streams don't count.
+    pub fetch_budget_per_minute: usize,
+    /// On a metered connection the budget is a quarter of that.
+    pub metered: bool,
+    /// Look up roughly where the stream's edge server is, for the debug
+    /// details. Sends its address to ipinfo.io, so it is off by default.
+    pub locate_edge: bool,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            watch_changes: true,
+            check_interval_secs: 5,
+            stream_overrides: BTreeMap::new(),
+            fetch_budget_per_minute: 30,
+            metered: false,
+            locate_edge: false,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn fetch_budget(&self) -> usize {
+        let budget = if self.metered { self.fetch_budget_per_minute / 4 } else { self.fetch_budget_per_minute };
+        budget.max(1)
+    }
+}
+
+/// Extra headers and cookies sent with every request to NTS, the API and
+/// the streams alike, for when an endpoint wants a session. Their values
+/// are kept out of logs and `config check`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub headers: BTreeMap<String, String>,
+    pub cookies: BTreeMap<String, String>,
+}
+
+/// Strict TLS for nts.live and the stream hosts: TLS 1.2 or later, checked
+/// against the roots built into nts_cli instead of the system's. With
+/// `pins`, a key in the certificate chain must also be one of them.
+/// `nts_cli doctor` prints the current ones; `NTS_CLI_PIN_NTS_CERTS=0`
+/// turns it all off for a run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub pin_nts_certs: bool,
+    /// "sha256/<base64>" of a public key, as curl's `--pinnedpubkey` takes
+    /// them. Pin the root or intermediate to survive a renewal.
+    pub pins: Vec<String>,
+}
+
+/// Leading silence skipping, set separately for mixtapes and live stations.
+/// Live stations default to off since a quiet moment there is part of the show.
+/// Archive episodes follow the mixtape setting.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PlaybackConfig {
+    /// Volume at startup, from 0.0 to 1.0. Changes made while running are
+    /// not written back.
+    pub volume: f32,
+    /// Decoded samples buffered ahead of the output.
+    pub buffer_size: usize,
+    pub skip_silence_mixtapes: bool,
+    pub skip_silence_stations: bool,
+    pub max_silence_skip_secs: u64,
+    /// Attempts to reconnect a stream that dropped before giving up; 0
+    /// leaves it stopped.
+    pub reconnect_attempts: u32,
+}
+
+impl Default for PlaybackConfig {
+    fn default() -> Self {
+        PlaybackConfig {
+            volume: 1.0,
+            buffer_size: 8096,
+            skip_silence_mixtapes: true,
+            skip_silence_stations: false,
+            max_silence_skip_secs: 3,
+            reconnect_attempts: 5,
+        }
+    }
+}
+
+impl PlaybackConfig {
+    /// Brings a volume outside 0.0–1.0 and an empty buffer back in range,
+    /// with a warning for each.
+    fn clamp(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !(0.0..=1.0).contains(&self.volume) {
+            let volume = if self.volume.is_nan() { 1.0 } else { self.volume.clamp(0.0, 1.0) };
+            warnings.push(format!("playback.volume: {} is outside 0.0–1.0, using {}", self.volume, volume));
+            self.volume = volume;
+        }
+        if self.buffer_size == 0 {
+            let buffer_size = PlaybackConfig::default().buffer_size;
+            warnings.push(format!("playback.buffer_size: must be more than 0, using {}", buffer_size));
+            self.buffer_size = buffer_size;
+        }
+        warnings
+    }
+
+    /// How much leading silence may be skipped for a stream in `section`,
+    /// or None if skipping is off for it.
+    pub fn max_silence_skip(&self, section: StreamType) -> Option<Duration> {
+        let enabled = match section {
+            StreamType::Mixtape | StreamType::Episode | StreamType::Archive => self.skip_silence_mixtapes,
+            StreamType::Station | StreamType::Custom => self.skip_silence_stations,
+        };
+        enabled.then(|| Duration::from_secs(self.max_silence_skip_secs))
+    }
+}
+
+/// Evening out the levels of different streams. Each stream's loudness is
+/// learned from its first minute of playback, and the next time it starts
+/// its gain is corrected towards `target_dbfs`, by at most 6 dB.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LoudnessConfig {
+    pub normalize: bool,
+    pub target_dbfs: f64,
+    /// Gain in dB for streams by title, used instead of the learned
+    /// correction.
+    pub offsets: BTreeMap<String, f64>,
+}
+
+impl Default for LoudnessConfig {
+    fn default() -> Self {
+        LoudnessConfig {
+            normalize: true,
+            target_dbfs: -20.0,
+            offsets: BTreeMap::new(),
+        }
+    }
+}
+
+/// Terminal background the default colors are picked for. `auto` asks the
+/// terminal.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+/// How history timestamps are written: `local` as "2024-06-01 14:32", or
+/// `iso8601` as "2024-06-01T14:32" for grepping. Files can mix both.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampStyle {
+    #[default]
+    Local,
+    Iso8601,
+}
+
+impl TimestampStyle {
+    pub fn parse(value: &str) -> Result<TimestampStyle, String> {
+        match value {
+            "local" => Ok(TimestampStyle::Local),
+            "iso8601" => Ok(TimestampStyle::Iso8601),
+            other => Err(format!("Unknown timestamp style {}, expected local or iso8601", other)),
+        }
+    }
+}
+
+/// How a new show starting on the playing station is announced.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AnnouncementsConfig {
+    pub banner: bool,
+    pub notification: bool,
+    pub chime: bool,
+}
+
+impl Default for AnnouncementsConfig {
+    fn default() -> Self {
+        AnnouncementsConfig {
+            banner: true,
+            notification: false,
+            chime: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RecognitionConfig {
+    /// Seconds of audio sent to the recognizer at startup; `=` and `-`
+    /// change it for the session.
+    pub duration_secs: u64,
+    pub keep_failed_samples: bool,
+    pub sample_retention_cap: usize,
+    pub timeout_secs: u64,
+    pub auto_id: AutoId,
+    pub auto_id_interval_secs: u64,
+    /// How far back Shift+r looks for the track that just ended.
+    pub lookback_secs: u64,
+    /// Trailing bracketed suffixes dropped from recognized titles, matched
+    /// case-insensitively against the start of the bracket's contents.
+    pub strip_suffixes: Vec<String>,
+    /// Start in incognito mode instead of toggling it with `i` each time.
+    pub start_incognito: bool,
+    /// A track recognized again within this many minutes of its entry at
+    /// the end of the history isn't written twice. 0 writes every match.
+    pub repeat_window_mins: u64,
+    /// The command that identifies a sample, with `{file}` standing for its
+    /// path, printing JSON. Empty for the built-in recognizer, or vibra in
+    /// builds without it.
+    pub recognizer_command: Vec<String>,
+    /// Where the title and artist are in the recognizer's JSON, as dotted
+    /// paths.
+    pub title_path: String,
+    pub artist_path: String,
+}
+
+impl RecognitionConfig {
+    #[cfg(feature = "recognition")]
+    pub fn repeat_window(&self) -> Duration {
+        Duration::from_secs(self.repeat_window_mins * 60)
+    }
+}
+
+/// When to identify tracks automatically while a stream is playing, besides
+/// the recognition run when it starts.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoId {
+    #[default]
+    Off,
+    /// Every `auto_id_interval_secs`.
+    Interval,
+    /// When the audio suggests a new track has started.
+    Change,
+}
+
+impl Default for RecognitionConfig {
+    fn default() -> Self {
+        RecognitionConfig {
+            duration_secs: 5,
+            keep_failed_samples: true,
+            sample_retention_cap: 5,
+            timeout_secs: 30,
+            auto_id: AutoId::Off,
+            auto_id_interval_secs: 300,
+            lookback_secs: 30,
+            strip_suffixes: [
+                "Official Video",
+                "Official Audio",
+                "Official Music Video",
+                "Official Visualizer",
+                "Lyric Video",
+                "Visualizer",
+                "Audio",
+            ]
+            .map(String::from)
+            .to_vec(),
+            start_incognito: false,
+            repeat_window_mins: 10,
+            recognizer_command: Vec::new(),
+            title_path: "track.title".to_string(),
+            artist_path: "track.subtitle".to_string(),
+        }
+    }
+}
+
+/// Result of validating a config file: the parsed config plus warnings about
+/// keys that were ignored.
+pub struct CheckedConfig {
+    pub config: Config,
+    pub warnings: Vec<String>,
+}
+
+impl Config {
+    /// Reads and validates the config file. A missing file yields the defaults;
+    /// a malformed one is reported so the caller can warn and carry on with defaults.
+    pub fn load() -> Result<CheckedConfig, String> {
+        let Some(path) = config_file_path() else {
+            return Ok(CheckedConfig {
+                config: Config::default(),
+                warnings: Vec::new(),
+            });
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => Config::check(&contents)
+                .map_err(|err| format!("Invalid config {}: {}", path.display(), err)),
+            Err(_) => Ok(CheckedConfig {
+                config: Config::default(),
+                warnings: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn check(contents: &str) -> Result<CheckedConfig, String> {
+        let mut config: Config = toml::from_str(contents).map_err(|err| {
+            let message = err.message().to_string();
+            match err.span() {
+                Some(span) => {
+                    let (line, column) = line_column(contents, span.start);
+                    match key_at(contents, span.start) {
+                        Some(key) => format!("line {}, column {}, '{}': {}", line, column, key, message),
+                        None => format!("line {}, column {}: {}", line, column, message),
+                    }
+                }
+                None => message,
+            }
+        })?;
+
+        let known = Config::default().keys();
+        let mut warnings = Vec::new();
+        let raw: toml::Table = toml::from_str(contents).map_err(|err| err.message().to_string())?;
+        for key in flatten(&raw) {
+            if known.iter().any(|(known_key, _)| *known_key == key) || under(&key, &MAP_KEYS) {
+                continue;
+            }
+            let suggestion = known
+                .iter()
+                .map(|(known_key, _)| (levenshtein(&key, known_key), known_key))
+                .filter(|(distance, _)| *distance <= 3)
+                .min_by_key(|(distance, _)| *distance);
+            warnings.push(match suggestion {
+                Some((_, known_key)) => {
+                    format!("unknown config key '{}', did you mean '{}'?", key, known_key)
+                }
+                None => format!("unknown config key '{}'", key),
+            });
+        }
+
+        for (key, value) in config.colors.entries() {
+            if let Err(err) = parse_color(value) {
+                warnings.push(format!("colors.{}: {}, keeping the default", key, err));
+            }
+        }
+        warnings.extend(config.playback.clamp());
+        warnings.extend(config.http.header_map().1);
+        warnings.extend(config.tls.warnings());
+        warnings.extend(features::warnings(&config));
+
+        Ok(CheckedConfig { config, warnings })
+    }
+
+    /// Every config key with its value, as dotted paths like `recognition.sample_retention_cap`.
+    pub fn keys(&self) -> Vec<(String, String)> {
+        let table = toml::Table::try_from(self).unwrap_or_default();
+        let mut keys = Vec::new();
+        collect_values(&table, "", &mut keys);
+        keys
+    }
+}
+
+pub fn config_file_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+fn flatten(table: &toml::Table) -> Vec<String> {
+    let mut keys = Vec::new();
+    collect_values(table, "", &mut keys);
+    keys.into_iter().map(|(key, _)| key).collect()
+}
+
+/// Whether `key` is in one of `sections`.
+fn under(key: &str, sections: &[&str]) -> bool {
+    sections
+        .iter()
+        .any(|section| key.strip_prefix(section).is_some_and(|rest| rest.starts_with('.')))
+}
+
+fn collect_values(table: &toml::Table, prefix: &str, keys: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(nested) => collect_values(nested, &path, keys),
+            value => keys.push((path, value.to_string())),
+        }
+    }
+}
+
+/// The dotted key whose line holds `offset`, like `playback.volume`.
+fn key_at(contents: &str, offset: usize) -> Option<String> {
+    let before = &contents[..offset.min(contents.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let line = contents[line_start..].lines().next()?;
+    let (key, _) = line.split_once('=')?;
+    let key = key.trim().trim_matches('"');
+    let section = before[..line_start]
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| line.starts_with('['))
+        .map(|header| header.split('#').next().unwrap_or(header).trim().trim_matches(['[', ']']).trim());
+    Some(match section {
+        Some(section) => format!("{}.{}", section, key),
+        None => key.to_string(),
+    })
+}
+
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    (line, column)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// `nts_cli config check`: prints the resolved config and any problems with it.
+/// Returns false on a hard parse error.
+pub fn run_check() -> bool {
+    let Some(path) = config_file_path() else {
+        println!("No config directory on this platform, using defaults");
+        return true;
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => {
+            println!("{} not found, using defaults:", path.display());
+            print_keys(&Config::default());
+            return true;
+        }
+    };
+
+    match Config::check(&contents) {
+        Ok(checked) => {
+            println!("{}:", path.display());
+            print_keys(&checked.config);
+            for warning in &checked.warnings {
+                println!("warning: {}", warning);
+            }
+            true
+        }
+        Err(err) => {
+            eprintln!("error: {}: {}", path.display(), err);
+            false
+        }
+    }
+}
+
+/// Prints every key, with the features a section needs at its first key.
+fn print_keys(config: &Config) {
+    let mut last_section = String::new();
+    for (key, value) in config.keys() {
+        let section = key.split_once('.').map_or("", |(section, _)| section);
+        if section != last_section {
+            if let Some(features) = features::availability(section) {
+                println!("  [{}] {}", section, features);
+            }
+            last_section = section.to_string();
+        }
+        let value = if under(&key, &SECRET_KEYS) { "<redacted>" } else { &value };
+        println!("  {} = {}", key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_name_the_bad_key() {
+        let err = Config::check("light_mode = false\n\n[playback]  # startup\nvolume = \"loud\"\n").err().unwrap();
+        assert!(err.starts_with("line 4, column 10, 'playback.volume': "), "{}", err);
+    }
+
+    #[test]
+    fn unknown_colors_are_warned_about() {
+        let checked = Config::check("[colors]\nheading = \"#ff8800\"\nmuted = \"greyish\"\n").unwrap();
+        assert_eq!(parse_color(&checked.config.colors.heading), Ok(Some(Color::Rgb(255, 136, 0))));
+        assert_eq!(checked.warnings, ["colors.muted: unknown color 'greyish', keeping the default"]);
+    }
+
+    #[test]
+    fn out_of_range_playback_values_are_clamped() {
+        let checked = Config::check("[playback]\nvolume = 1.5\nbuffer_size = 0\n").unwrap();
+        assert_eq!(checked.config.playback.volume, 1.0);
+        assert_eq!(checked.config.playback.buffer_size, PlaybackConfig::default().buffer_size);
+        assert_eq!(
+            checked.warnings,
+            [
+                "playback.volume: 1.5 is outside 0.0–1.0, using 1",
+                "playback.buffer_size: must be more than 0, using 8096"
+            ]
+        );
+    }
+
+    #[test]
+    fn chosen_names_are_not_unknown_keys() {
+        let checked = Config::check(
+            "[http.headers]\nX-Session = \"abc\"\n\n[http.cookies]\nsession = \"secret\"\n\n[network.stream_overrides]\n\"https://a\" = \"https://b\"\n\n[http]\nheader = 1\n",
+        )
+        .unwrap();
+        assert_eq!(checked.warnings.len(), 1);
+        assert!(checked.warnings[0].starts_with("unknown config key 'http.header'"), "{:?}", checked.warnings);
+        assert!(under("http.cookies.session", &SECRET_KEYS));
+        assert!(!under("http.cookiesession", &SECRET_KEYS));
+    }
+}
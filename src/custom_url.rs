@@ -0,0 +1,80 @@
+use reqwest::Url;
+
+use crate::Stream;
+
+/// How many recently played custom URLs the state file keeps.
+pub const RECENT_CAPACITY: usize = 10;
+
+/// Checks a pasted stream URL: it has to be http or https and name a host.
+/// Returns it trimmed.
+pub fn parse(input: &str) -> Result<String, String> {
+    let input = input.trim();
+    let url = Url::parse(input).map_err(|err| format!("Not a URL ({})", err))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Only http and https streams can be played, not {}", url.scheme()));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err("The URL has no host".to_string());
+    }
+    Ok(input.to_string())
+}
+
+/// Moves `url` to the front of the recent list, dropping the oldest past
+/// [`RECENT_CAPACITY`].
+pub fn remember(recent: &mut Vec<String>, url: &str) {
+    recent.retain(|known| known != url);
+    recent.insert(0, url.to_string());
+    recent.truncate(RECENT_CAPACITY);
+}
+
+/// As a stream in the "Custom" section. The URL is its id; the host is its
+/// name, so the listening log and the history say where it came from.
+pub fn to_stream(url: &str) -> Stream {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| url.to_string());
+    Stream {
+        id: url.to_string(),
+        title: host,
+        subtitle: "Custom URL".to_string(),
+        description: url.to_string(),
+        audio_stream_endpoint: url.to_string(),
+        show_alias: String::new(),
+        schedule: Vec::new(),
+        airtime: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_http_urls_with_a_host_pass() {
+        assert_eq!(parse("  https://radio.example.com/live.mp3 ").unwrap(), "https://radio.example.com/live.mp3");
+        assert!(parse("ftp://radio.example.com/live.mp3").is_err());
+        assert!(parse("radio.example.com/live.mp3").is_err());
+        assert!(parse("file:///tmp/live.mp3").is_err());
+    }
+
+    #[test]
+    fn recent_list_is_newest_first_and_capped() {
+        let mut recent = Vec::new();
+        for i in 0..12 {
+            remember(&mut recent, &format!("https://example.com/{}", i));
+        }
+        remember(&mut recent, "https://example.com/5");
+        assert_eq!(recent.len(), RECENT_CAPACITY);
+        assert_eq!(recent[0], "https://example.com/5");
+        assert_eq!(recent[1], "https://example.com/11");
+        assert_eq!(recent.iter().filter(|url| url.ends_with("/5")).count(), 1);
+    }
+
+    #[test]
+    fn stream_is_named_after_the_host() {
+        let stream = to_stream("https://radio.example.com:8000/live.mp3");
+        assert_eq!(stream.title, "radio.example.com");
+        assert_eq!(stream.id, stream.audio_stream_endpoint);
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use chrono::Local;
+
+use crate::config::RetentionConfig;
+
+const DEBUG_LOG_FILE_NAME: &str = "nts_cli/debug.log";
+/// Rotated logs kept besides the current one, as `debug.log.1` (newer) and
+/// `debug.log.2`.
+const GENERATIONS: usize = 2;
+
+/// Where the debug log goes: the cache directory, next to the retained
+/// samples.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(DEBUG_LOG_FILE_NAME))
+}
+
+/// The log at its default path, capped as configured.
+pub fn open_default(retention: &RetentionConfig) -> Option<DebugLog> {
+    default_path().map(|path| DebugLog::new(path, retention.debug_log_max_kb * 1024))
+}
+
+/// Everything the status log shows, kept on disk so it outlives the session.
+/// The file is rotated once it passes `max_bytes`. Writing happens on a
+/// thread of its own, so logging from the UI thread never waits on the disk.
+#[derive(Clone, Debug)]
+pub struct DebugLog {
+    writes: Sender<Request>,
+}
+
+/// What the writer thread is asked to do.
+enum Request {
+    Line(String),
+    Rotate,
+}
+
+/// The log file itself, only touched by the writer thread.
+struct LogFile {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl DebugLog {
+    /// Starts the writer thread, which runs until every handle is dropped.
+    pub fn new(path: PathBuf, max_bytes: u64) -> DebugLog {
+        let (writes, requests) = mpsc::channel();
+        let file = LogFile { path, max_bytes };
+        thread::spawn(move || {
+            for request in requests {
+                let _ = match request {
+                    Request::Line(line) => file.append(&line),
+                    Request::Rotate => match file.rotate_if_full() {
+                        Ok(true) => file.append(&stamped("Maintenance: rotated the debug log")),
+                        rotated => rotated.map(|_| ()),
+                    },
+                };
+            }
+        });
+        DebugLog { writes }
+    }
+
+    /// Queues a line, timestamped now, for the writer thread.
+    pub fn append(&self, message: &str) {
+        let _ = self.writes.send(Request::Line(stamped(message)));
+    }
+
+    /// Has the writer thread rotate the log if it is full, noting so in the
+    /// new one.
+    pub fn rotate_if_full(&self) {
+        let _ = self.writes.send(Request::Rotate);
+    }
+}
+
+fn stamped(message: &str) -> String {
+    format!("{} {}\n", Local::now().format("%Y-%m-%d %H:%M:%S"), message.replace('\n', " "))
+}
+
+impl LogFile {
+    /// Appends a line, rotating first if the log is full.
+    fn append(&self, line: &str) -> io::Result<()> {
+        self.rotate_if_full()?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// Moves a log past the cap to `.1`, and `.1` to `.2`, dropping the
+    /// oldest generation. Returns whether it rotated.
+    fn rotate_if_full(&self) -> io::Result<bool> {
+        let size = fs::metadata(&self.path).map(|metadata| metadata.len()).unwrap_or(0);
+        if size < self.max_bytes {
+            return Ok(false);
+        }
+        for generation in (1..GENERATIONS).rev() {
+            let from = self.generation(generation);
+            if from.exists() {
+                fs::rename(&from, self.generation(generation + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.generation(1))?;
+        Ok(true)
+    }
+
+    fn generation(&self, generation: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", generation));
+        PathBuf::from(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn keeps_two_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = LogFile {
+            path: dir.path().join("debug.log"),
+            max_bytes: 64,
+        };
+        for i in 0..20 {
+            log.append(&stamped(&format!("line {}", i))).unwrap();
+        }
+        let mut files: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["debug.log", "debug.log.1", "debug.log.2"]);
+        let current = fs::read_to_string(&log.path).unwrap();
+        assert!(current.ends_with("line 19\n"));
+        assert!(fs::metadata(&log.path).unwrap().len() < 64 + 40);
+        assert!(!fs::read_to_string(dir.path().join("debug.log.2")).unwrap().contains("line 0\n"));
+    }
+
+    #[test]
+    fn lines_are_written_in_order_off_the_calling_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/debug.log");
+        let log = DebugLog::new(path.clone(), 1024 * 1024);
+        for i in 0..100 {
+            log.append(&format!("line {}\nwrapped", i));
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let written = loop {
+            let written = fs::read_to_string(&path).unwrap_or_default();
+            if written.lines().count() == 100 || Instant::now() > deadline {
+                break written;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let lines: Vec<_> = written.lines().map(|line| line.split_once(' ').unwrap().1.split_once(' ').unwrap().1).collect();
+        assert_eq!(lines, (0..100).map(|i| format!("line {} wrapped", i)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{Local, NaiveDate, NaiveTime};
+
+use crate::{format, history, history_store::HistoryStore, listening};
+
+pub const USAGE: &str = "Usage: nts_cli digest [--week] [--from YYYY-MM-DD] [--to YYYY-MM-DD]";
+const TOP_ARTISTS: usize = 10;
+
+/// The last seven days up to and including `today`.
+pub fn last_week(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    (today - chrono::Days::new(6), today)
+}
+
+/// A markdown summary of the days from `from` to `to`, both included: time
+/// listened per stream from the listening log, and the recognized tracks
+/// from the history, as top artists and grouped by day. Only depends on its
+/// arguments, so the same files always give the same digest.
+pub fn build(history_contents: &str, listening_log: &str, from: NaiveDate, to: NaiveDate) -> String {
+    let range_start = from.and_time(NaiveTime::MIN);
+    let range_end = (to + chrono::Days::new(1)).and_time(NaiveTime::MIN);
+
+    let mut listened: BTreeMap<String, Duration> = BTreeMap::new();
+    for session in listening::parse(listening_log) {
+        let start = session.start.max(range_start);
+        let end = session.end.min(range_end);
+        if let Ok(length) = (end - start).to_std() {
+            *listened.entry(session.title).or_default() += length;
+        }
+    }
+    listened.retain(|_, length| !length.is_zero());
+
+    let mut days: BTreeMap<NaiveDate, Vec<(NaiveTime, &str)>> = BTreeMap::new();
+    let mut artists: BTreeMap<&str, usize> = BTreeMap::new();
+    for line in history_contents.lines() {
+        let Some(heard_at) = history::parse_timestamp(line) else {
+            continue;
+        };
+        if heard_at < range_start || heard_at >= range_end {
+            continue;
+        }
+        let track = history::entry_text(line);
+        days.entry(heard_at.date()).or_default().push((heard_at.time(), track));
+        if let Some((_, artist)) = track.rsplit_once(" - ") {
+            *artists.entry(artist.trim()).or_default() += 1;
+        }
+    }
+    let track_count: usize = days.values().map(Vec::len).sum();
+
+    let mut out = format!("# Listening digest, {} to {}\n\n## Listening\n\n", from, to);
+    if listened.is_empty() {
+        out.push_str("Nothing played.\n");
+    } else {
+        let mut streams: Vec<_> = listened.iter().collect();
+        streams.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (title, length) in streams {
+            out.push_str(&format!("- {}: {}\n", title, format::duration_words(*length)));
+        }
+        let total: Duration = listened.values().sum();
+        out.push_str(&format!("\nTotal: {}\n", format::duration_words(total)));
+    }
+
+    out.push_str(&format!("\n## Recognized tracks: {}\n", track_count));
+    if !artists.is_empty() {
+        out.push_str("\n### Top artists\n\n");
+        let mut top: Vec<_> = artists.into_iter().collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (rank, (artist, count)) in top.into_iter().take(TOP_ARTISTS).enumerate() {
+            out.push_str(&format!("{}. {} ({})\n", rank + 1, artist, count));
+        }
+    }
+    for (day, tracks) in days {
+        out.push_str(&format!("\n### {}\n\n", day.format("%a %Y-%m-%d")));
+        for (time, track) in tracks {
+            out.push_str(&format!("- {} {}\n", time.format("%H:%M"), track));
+        }
+    }
+    out
+}
+
+/// `nts_cli digest`: prints the digest for the last week or the given range.
+pub fn run(args: &[&str]) -> bool {
+    let (mut from, mut to) = (None, None);
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let date = match arg {
+            "--week" => continue,
+            "--from" => &mut from,
+            "--to" => &mut to,
+            _ => {
+                eprintln!("Unknown option {}\n{}", arg, USAGE);
+                return false;
+            }
+        };
+        match args.next().and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()) {
+            Some(value) => *date = Some(value),
+            None => {
+                eprintln!("{} needs a date like 2024-06-01\n{}", arg, USAGE);
+                return false;
+            }
+        }
+    }
+    let to = to.unwrap_or_else(|| Local::now().date_naive());
+    let from = from.unwrap_or_else(|| last_week(to).0);
+    if from > to {
+        eprintln!("--from is after --to");
+        return false;
+    }
+
+    let history_contents = HistoryStore::open_default()
+        .map(|store| store.contents())
+        .unwrap_or_default();
+    print!("{}", build(&history_contents, &listening::read(), from, to));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = "\
+2024-05-24 23:50  Too Early - Someone
+2024-05-31 22:10  Journey in Satchidananda - Alice Coltrane
+2024-05-31 22:41  Blue Nile - Alice Coltrane
+2024-06-01 13:05  Jelly - Laurel Halo
+untimestamped entry
+2024-06-02 00:00  Too Late - Someone
+";
+
+    const LISTENING: &str = "\
+2024-05-25 23:00:00\t2024-05-26 01:00:00\tNTS Live 1
+2024-05-31 22:00:00\t2024-05-31 23:00:00\tPoolside
+2024-06-01 12:30:00\t2024-06-01 14:00:00\tNTS Live 1
+garbage
+";
+
+    #[test]
+    fn digest_clips_to_the_range_and_groups_by_day() {
+        let (from, to) = last_week(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap());
+        let digest = build(HISTORY, LISTENING, from, to);
+        assert_eq!(
+            digest,
+            "\
+# Listening digest, 2024-05-26 to 2024-06-01
+
+## Listening
+
+- NTS Live 1: 2 h 30 min
+- Poolside: 1 h
+
+Total: 3 h 30 min
+
+## Recognized tracks: 3
+
+### Top artists
+
+1. Alice Coltrane (2)
+2. Laurel Halo (1)
+
+### Fri 2024-05-31
+
+- 22:10 Journey in Satchidananda - Alice Coltrane
+- 22:41 Blue Nile - Alice Coltrane
+
+### Sat 2024-06-01
+
+- 13:05 Jelly - Laurel Halo
+"
+        );
+    }
+
+    #[test]
+    fn empty_range_says_so() {
+        let day = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        assert_eq!(
+            build(HISTORY, LISTENING, day, day),
+            "# Listening digest, 2023-01-01 to 2023-01-01\n\n## Listening\n\nNothing played.\n\n## Recognized tracks: 0\n"
+        );
+    }
+}
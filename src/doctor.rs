@@ -0,0 +1,108 @@
+use std::fs;
+
+use chrono::Utc;
+
+use crate::{
+    config::{config_file_path, Config},
+    debug_log, endpoint_health, features, format, get_history_file_path,
+    history_store::HistoryStore,
+    http,
+    recognition_metrics, samples, tls,
+};
+
+/// Prints a short report about the files and directories the app uses, to
+/// help diagnose problems without starting the TUI.
+pub fn run() {
+    println!("nts_cli doctor");
+    let config = Config::load().map(|checked| checked.config).unwrap_or_default();
+    let decimal_comma = config.decimal_comma;
+
+    match config_file_path() {
+        Some(path) if path.exists() => println!("  config file:      {}", path.display()),
+        Some(path) => println!("  config file:      {} (not found, using defaults)", path.display()),
+        None => println!("  config file:      no config directory on this platform"),
+    }
+    let missing: Vec<&str> = features::FEATURES
+        .iter()
+        .filter(|feature| !feature.built)
+        .map(|feature| feature.name)
+        .collect();
+    let built = features::built();
+    let built = if built.is_empty() { "none".to_string() } else { built.join(", ") };
+    if missing.is_empty() {
+        println!("  features:         {}", built);
+    } else {
+        println!("  features:         {} (not built: {})", built, missing.join(", "));
+    }
+    for warning in features::warnings(&config) {
+        println!("  features:         {}", warning);
+    }
+
+    let history_file_path = get_history_file_path();
+    let entries = HistoryStore::open(&history_file_path)
+        .map(|store| store.lines().len())
+        .unwrap_or(0);
+    println!(
+        "  history file:     {} ({} entries)",
+        history_file_path.display(),
+        entries
+    );
+
+    match (samples::retention_dir(), samples::usage()) {
+        (Some(dir), Ok((count, size))) => println!(
+            "  sample retention: {} ({} samples, {})",
+            dir.display(),
+            count,
+            format::with_decimal_comma(&format::bytes(size), decimal_comma)
+        ),
+        (Some(dir), Err(err)) => println!("  sample retention: {} (unreadable: {})", dir.display(), err),
+        (None, _) => println!("  sample retention: no cache directory on this platform"),
+    }
+
+    match debug_log::default_path() {
+        Some(path) => {
+            let size = fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+            println!(
+                "  debug log:        {} ({}, rotated at {})",
+                path.display(),
+                format::with_decimal_comma(&format::bytes(size), decimal_comma),
+                format::with_decimal_comma(&format::bytes(config.retention.debug_log_max_kb * 1024), decimal_comma)
+            );
+        }
+        None => println!("  debug log:        no cache directory on this platform"),
+    }
+
+    let endpoints = endpoint_health::load().summary_lines(Utc::now().timestamp());
+    if endpoints.is_empty() {
+        println!("  stream hosts:     no failures or successes remembered");
+    }
+    for line in endpoints {
+        println!("  stream hosts:     {}", line);
+    }
+    for (listed, url) in &config.network.stream_overrides {
+        println!("  stream override:  {} -> {}", listed, url);
+    }
+    println!("  http headers:     {}", config.http.describe());
+    match http::check_forbidden(&config.http) {
+        Some(Ok(line) | Err(line)) => println!("  403 endpoint:     {}", line),
+        None => println!("  403 endpoint:     none seen"),
+    }
+    println!("  strict tls:       {}", config.tls.describe());
+    for host in tls::DOCTOR_HOSTS {
+        match tls::handshake(host, &config.tls) {
+            Ok(handshake) => {
+                println!("  tls:              {}: {}", host, handshake.summary());
+                println!("  tls keys:         {}: {}", host, handshake.keys());
+            }
+            Err(err) => println!("  tls:              {}: {}", host, err),
+        }
+    }
+
+    let runs = recognition_metrics::parse(&recognition_metrics::read());
+    if runs.is_empty() {
+        println!("  recognizers:      no recognitions timed yet");
+    }
+    for line in recognition_metrics::summary_lines(&runs) {
+        println!("  recognizers:      {}", format::with_decimal_comma(&line, decimal_comma));
+    }
+}
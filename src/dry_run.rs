@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use crate::{config::Config, format, player, plain, recognition, StreamsCollection};
+
+pub const PLAY_USAGE: &str =
+    "Usage: nts_cli play [--stream] <number|name> [--dry-run [--duration <e.g. 2h, 90m>] [--recognize]]";
+
+/// Arguments of `nts_cli play`.
+pub struct PlayArgs {
+    pub stream: String,
+    pub dry_run: bool,
+    pub duration: Option<Duration>,
+    pub recognize: bool,
+}
+
+impl PlayArgs {
+    pub fn parse(args: &[&str]) -> Result<PlayArgs, String> {
+        let mut stream = None;
+        let mut dry_run = false;
+        let mut duration = None;
+        let mut recognize = false;
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            match arg {
+                "--dry-run" => dry_run = true,
+                "--recognize" => recognize = true,
+                "--stream" => stream = Some(args.next().ok_or("--stream needs a name")?.to_string()),
+                "--duration" => {
+                    let value = args.next().ok_or("--duration needs a value")?;
+                    duration = Some(parse_duration(value).ok_or(format!("Not a duration: {}", value))?);
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+                name if stream.is_none() => stream = Some(name.to_string()),
+                extra => return Err(format!("Unexpected argument {}", extra)),
+            }
+        }
+        let stream = stream.ok_or("No stream given")?;
+        if !dry_run && (duration.is_some() || recognize) {
+            return Err("--duration and --recognize are only checked with --dry-run".to_string());
+        }
+        Ok(PlayArgs {
+            stream,
+            dry_run,
+            duration,
+            recognize,
+        })
+    }
+}
+
+/// Checks everything `play` would need without producing any audio: the
+/// stream name resolves, its endpoint answers, and the recognizer runs if
+/// asked for. Prints one line per check and returns whether all passed.
+pub fn check(args: &PlayArgs) -> bool {
+    let mut ok = true;
+    let mut report = |passed: bool, line: String| {
+        println!("  {} {}", if passed { "ok  " } else { "FAIL" }, line);
+        ok &= passed;
+    };
+    println!("nts_cli play --dry-run");
+
+    let collection = match StreamsCollection::populate_collection() {
+        Ok(collection) => collection,
+        Err(err) => {
+            report(false, format!("stream list: {}", err));
+            return false;
+        }
+    };
+    let streams = plain::all_streams(&collection);
+    let Some((_, stream)) = plain::find_stream(&streams, &args.stream) else {
+        report(false, format!("stream: no stream matches \"{}\"", args.stream));
+        return false;
+    };
+    report(true, format!("stream: {} — {}", stream.title, stream.subtitle));
+
+    match player::probe(&stream.audio_stream_endpoint) {
+        Ok(probe) => {
+            let content_type = probe.content_type.as_deref().unwrap_or("unknown type");
+            report(
+                true,
+                format!("endpoint: {} ({}, {} kbps)", stream.audio_stream_endpoint, content_type, probe.bitrate_kbps),
+            );
+            if let Some(duration) = args.duration {
+                println!(
+                    "       estimated data: {} over {}{}",
+                    format::bytes(player::estimated_size(probe.bitrate_kbps, duration)),
+                    format::duration_words(duration),
+                    if probe.bitrate_announced { "" } else { " (bitrate not announced, assumed)" }
+                );
+            }
+        }
+        Err(err) => report(false, format!("endpoint: {}", err)),
+    }
+
+    if args.recognize {
+        let config = Config::load().map(|checked| checked.config).unwrap_or_default();
+        match recognition::check_recognizer(&config.recognition) {
+            Ok(found) => report(true, format!("recognizer: {}", found)),
+            Err(err) => report(false, format!("recognizer: {}", err)),
+        }
+    }
+
+    println!("{}", if ok { "All checks passed" } else { "Some checks failed" });
+    ok
+}
+
+/// Parses durations like "2h", "90m", "45s" or "1h30m". A bare number is
+/// minutes.
+fn parse_duration(text: &str) -> Option<Duration> {
+    if let Ok(minutes) = text.parse::<u64>() {
+        return Some(Duration::from_secs(minutes * 60));
+    }
+    let mut total = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: u64 = number.parse().ok()?;
+        number.clear();
+        total += value
+            * match c {
+                'h' => 3600,
+                'm' => 60,
+                's' => 1,
+                _ => return None,
+            };
+    }
+    (number.is_empty() && total > 0).then(|| Duration::from_secs(total))
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::PathBuf,
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::player::ConnectError;
+
+const HEALTH_FILE_NAME: &str = "nts_cli/endpoint_health.json";
+/// How long it takes a score to fall to half, so a host that failed last
+/// night is tried first again by the next day.
+const HALF_LIFE_SECS: f64 = 6.0 * 3600.0;
+/// Scores closer to zero than this count as no opinion.
+const FORGOTTEN: f64 = 0.1;
+/// Bounds a long run of successes, so a few failures still move a host down.
+const MAX_SCORE: f64 = 5.0;
+
+/// The geo-routed hosts have a plain counterpart serving the same streams.
+const GEO_SUFFIX: &str = "-geo.ntslive.net";
+const PLAIN_SUFFIX: &str = ".ntslive.net";
+
+/// The endpoints that serve the same stream as `stream_url`, starting with
+/// itself.
+pub fn equivalents(stream_url: &str) -> Vec<String> {
+    let mut urls = vec![stream_url.to_string()];
+    if let Some((scheme, rest)) = stream_url.split_once("://") {
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if let Some(name) = host.strip_suffix(GEO_SUFFIX) {
+            urls.push(format!("{}://{}{}/{}", scheme, name, PLAIN_SUFFIX, path));
+        }
+    }
+    urls
+}
+
+/// Successes and failures of one endpoint, folded into a score that
+/// decays towards zero.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Health {
+    pub score: f64,
+    /// When the score was last changed, in Unix seconds.
+    pub updated: i64,
+}
+
+impl Health {
+    /// The score as of `now`.
+    pub fn score_at(&self, now: i64) -> f64 {
+        let elapsed = (now - self.updated).max(0) as f64;
+        let score = self.score * 0.5f64.powf(elapsed / HALF_LIFE_SECS);
+        if score.abs() < FORGOTTEN {
+            0.0
+        } else {
+            score
+        }
+    }
+}
+
+/// What this network has seen of each stream host, kept across sessions.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct EndpointHealth {
+    pub endpoints: BTreeMap<String, Health>,
+}
+
+impl EndpointHealth {
+    pub fn score(&self, url: &str, now: i64) -> f64 {
+        self.endpoints.get(url).map_or(0.0, |health| health.score_at(now))
+    }
+
+    pub fn record(&mut self, url: &str, success: bool, now: i64) {
+        let change = if success { 1.0 } else { -1.0 };
+        let score = (self.score(url, now) + change).clamp(-MAX_SCORE, MAX_SCORE);
+        self.endpoints.insert(url.to_string(), Health { score, updated: now });
+    }
+
+    /// `candidates` healthiest first. Equal scores keep their order, so
+    /// unknown and forgiven hosts go in the order given.
+    pub fn order(&self, candidates: &[String], now: i64) -> Vec<String> {
+        let mut ordered = candidates.to_vec();
+        ordered.sort_by(|a, b| self.score(b, now).total_cmp(&self.score(a, now)));
+        ordered
+    }
+
+    /// One line per endpoint still remembered, for `nts_cli doctor`.
+    pub fn summary_lines(&self, now: i64) -> Vec<String> {
+        self.endpoints
+            .iter()
+            .map(|(url, health)| (url, health.score_at(now)))
+            .filter(|(_, score)| *score != 0.0)
+            .map(|(url, score)| format!("{} ({:+.1})", url, score))
+            .collect()
+    }
+}
+
+fn health_file_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(HEALTH_FILE_NAME))
+}
+
+pub fn load() -> EndpointHealth {
+    health_file_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Writes the scores, dropping the ones that have decayed away.
+pub fn save(health: &EndpointHealth) -> io::Result<()> {
+    let path = health_file_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let now = Utc::now().timestamp();
+    let mut kept = health.clone();
+    kept.endpoints.retain(|_, health| health.score_at(now) != 0.0);
+    fs::write(path, serde_json::to_string_pretty(&kept)?)
+}
+
+/// Connects to `stream_url` or an equivalent endpoint, healthiest first,
+/// falling back through the rest on failure and recording every outcome.
+/// An entry in `overrides` replaces the endpoint outright and is the only
+/// one tried. A 404 or a cancel ends the attempt, since another host won't
+/// change either.
+pub fn connect_healthiest<T>(
+    stream_url: &str,
+    overrides: &BTreeMap<String, String>,
+    mut connect: impl FnMut(&str) -> Result<T, ConnectError>,
+) -> Result<T, ConnectError> {
+    if let Some(url) = overrides.get(stream_url) {
+        return connect(url);
+    }
+    let mut health = load();
+    let candidates = health.order(&equivalents(stream_url), Utc::now().timestamp());
+    let mut result = Err(ConnectError::Cancelled);
+    for url in &candidates {
+        result = connect(url);
+        match &result {
+            Err(ConnectError::Cancelled) => return result,
+            outcome => health.record(url, outcome.is_ok(), Utc::now().timestamp()),
+        }
+        if !matches!(result, Err(ConnectError::Failed(_))) {
+            break;
+        }
+    }
+    let _ = save(&health);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GEO: &str = "https://stream-mixtape-geo.ntslive.net/stream2";
+    const PLAIN: &str = "https://stream-mixtape.ntslive.net/stream2";
+
+    #[test]
+    fn failures_move_a_host_down_until_they_decay() {
+        assert_eq!(equivalents(GEO), [GEO, PLAIN]);
+        assert_eq!(equivalents("https://example.com/live"), ["https://example.com/live"]);
+
+        let candidates = equivalents(GEO);
+        let mut health = EndpointHealth::default();
+        let start = 1_700_000_000;
+        health.record(GEO, false, start);
+        health.record(GEO, false, start);
+        assert_eq!(health.order(&candidates, start), [PLAIN, GEO]);
+
+        // The working host stays ahead until the failures decay
+        health.record(PLAIN, true, start);
+        assert_eq!(health.order(&candidates, start + 3600), [PLAIN, GEO]);
+        assert_eq!(health.order(&candidates, start + 2 * 24 * 3600), [GEO, PLAIN]);
+        assert!(health.summary_lines(start + 2 * 24 * 3600).is_empty());
+        assert_eq!(health.summary_lines(start), [format!("{} (-2.0)", GEO), format!("{} (+1.0)", PLAIN)]);
+    }
+}
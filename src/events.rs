@@ -0,0 +1,86 @@
+use std::io::{self, Write};
+
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+
+/// How `--plain` and `--headless` report what happens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EventFormat {
+    /// Lines for people, on stdout.
+    #[default]
+    Text,
+    /// One JSON object per event on stdout, for supervisors and log
+    /// shippers. The lines for people go to stderr instead.
+    Json,
+}
+
+impl EventFormat {
+    pub fn parse(value: &str) -> Result<EventFormat, String> {
+        match value {
+            "json" => Ok(EventFormat::Json),
+            "text" => Ok(EventFormat::Text),
+            other => Err(format!("Unknown event format {}, expected json or text", other)),
+        }
+    }
+}
+
+/// Prints what happens in the plain and headless modes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Reporter {
+    format: EventFormat,
+}
+
+impl Reporter {
+    pub fn new(format: EventFormat) -> Self {
+        Reporter { format }
+    }
+
+    /// A line that isn't an event, like the help or a listing.
+    pub fn say(&self, line: &str) {
+        match self.format {
+            EventFormat::Text => println!("{}", line),
+            EventFormat::Json => eprintln!("{}", line),
+        }
+    }
+
+    /// Something that happened: `line` for people and, with JSON events, an
+    /// object of type `kind` carrying `payload`, flushed right away.
+    pub fn event(&self, kind: &str, line: &str, payload: Value) {
+        self.say(line);
+        if self.format == EventFormat::Json {
+            let mut stdout = io::stdout().lock();
+            let _ = writeln!(stdout, "{}", to_json(kind, payload, Local::now()));
+            let _ = stdout.flush();
+        }
+    }
+}
+
+fn to_json(kind: &str, payload: Value, at: DateTime<Local>) -> String {
+    json!({
+        "timestamp": at.to_rfc3339(),
+        "event": kind,
+        "payload": payload,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn events_are_one_object_per_line() {
+        let at = Local.with_ymd_and_hms(2024, 6, 1, 14, 32, 0).unwrap();
+        let line = to_json("show_changed", json!({"station": "NTS 1", "show": "Lunch\nShow"}), at);
+        assert!(!line.contains('\n'));
+        let event: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(event["event"], "show_changed");
+        assert_eq!(event["payload"]["show"], "Lunch\nShow");
+        assert_eq!(event["timestamp"], at.to_rfc3339());
+
+        assert_eq!(EventFormat::parse("json"), Ok(EventFormat::Json));
+        assert!(EventFormat::parse("yaml").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use std::{fs, io, path::Path};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::{config::TimestampStyle, history, history_store::HistoryStore, listening};
+
+pub const USAGE: &str = "Usage: nts_cli export [--format csv|json] [<path>]";
+/// Where the export goes unless another path is given.
+pub const DEFAULT_PATH: &str = "nts_recognized_tracks.csv";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Result<Format, String> {
+        match value {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            other => Err(format!("Unknown export format {}, expected csv or json", other)),
+        }
+    }
+
+    /// JSON for a `.json` path, CSV for anything else.
+    pub fn for_path(path: &Path) -> Format {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// A recognized track as exported.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Record {
+    /// When it was heard, as 2024-06-01T14:32. Entries from before
+    /// timestamps were kept have none.
+    pub timestamp: Option<String>,
+    pub artist: String,
+    pub title: String,
+    /// The stream that was playing then, from the listening log.
+    pub stream: Option<String>,
+}
+
+/// Splits a "Title - Artist" entry. Titles can have " - " in them, as in
+/// "Song - Extended Mix - Artist", so the artist is what follows the last
+/// one. An entry without one is all title.
+pub fn parse_track(text: &str) -> (&str, &str) {
+    match text.rsplit_once(" - ") {
+        Some((title, artist)) => (title.trim(), artist.trim()),
+        None => (text.trim(), ""),
+    }
+}
+
+/// The history's entries as records, oldest first, with the stream each
+/// was heard on where a session in the listening log covers it.
+pub fn records(history_contents: &str, listening_log: &str) -> Vec<Record> {
+    let sessions = listening::parse(listening_log);
+    let stream_at = |heard_at: NaiveDateTime| {
+        sessions
+            .iter()
+            .find(|session| session.start <= heard_at && heard_at <= session.end)
+            .map(|session| session.title.clone())
+    };
+    history_contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let heard_at = history::parse_timestamp(line);
+            let (title, artist) = parse_track(history::entry_text(line));
+            Record {
+                timestamp: heard_at.map(|heard_at| history::format_timestamp(heard_at, TimestampStyle::Iso8601)),
+                artist: artist.to_string(),
+                title: title.to_string(),
+                stream: heard_at.and_then(stream_at),
+            }
+        })
+        .collect()
+}
+
+/// A header line and one line per record, quoted where a field needs it.
+pub fn to_csv(records: &[Record]) -> String {
+    let field = |value: &str| {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+    let mut out = String::from("timestamp,artist,title,stream\n");
+    for record in records {
+        let fields = [
+            record.timestamp.as_deref().unwrap_or_default(),
+            &record.artist,
+            &record.title,
+            record.stream.as_deref().unwrap_or_default(),
+        ];
+        out.push_str(&fields.map(field).join(","));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn to_json(records: &[Record]) -> String {
+    let mut out = serde_json::to_string_pretty(records).expect("records serialize");
+    out.push('\n');
+    out
+}
+
+/// Writes the history file's entries to `path`. Returns how many.
+pub fn write(path: &Path, format: Format) -> io::Result<usize> {
+    let history_contents = HistoryStore::open_default()?.contents();
+    let records = records(&history_contents, &listening::read());
+    let out = match format {
+        Format::Csv => to_csv(&records),
+        Format::Json => to_json(&records),
+    };
+    fs::write(path, out)?;
+    Ok(records.len())
+}
+
+/// `nts_cli export`: writes the history as CSV or JSON.
+pub fn run(args: &[&str]) -> bool {
+    let (mut format, mut path) = (None, None);
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        match arg {
+            "--format" => match args.next().map(|value| Format::parse(value)) {
+                Some(Ok(value)) => format = Some(value),
+                Some(Err(err)) => {
+                    eprintln!("{}\n{}", err, USAGE);
+                    return false;
+                }
+                None => {
+                    eprintln!("--format needs csv or json\n{}", USAGE);
+                    return false;
+                }
+            },
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(Path::new(arg).to_path_buf()),
+            _ => {
+                eprintln!("Unknown option {}\n{}", arg, USAGE);
+                return false;
+            }
+        }
+    }
+    let path = match (path, format) {
+        (Some(path), _) => path,
+        (None, Some(Format::Json)) => Path::new(DEFAULT_PATH).with_extension("json"),
+        (None, _) => Path::new(DEFAULT_PATH).to_path_buf(),
+    };
+    let format = format.unwrap_or_else(|| Format::for_path(&path));
+    match write(&path, format) {
+        Ok(count) => {
+            println!("Exported {} tracks to {}", count, path.display());
+            true
+        }
+        Err(err) => {
+            eprintln!("Could not export to {}: {}", path.display(), err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_split_at_the_last_dash_and_find_their_stream() {
+        let history = "\
+Untimed Title - Old Artist
+2024-06-01 13:05  Jelly - Laurel Halo
+2024-06-01T14:20  At Les - Remix - Carl Craig\tAt Les - Remix - Carl Craig (Official)
+";
+        let log = "2024-06-01 14:00:00\t2024-06-01 15:00:00\tNTS 1\n";
+        let records = records(history, log);
+        assert_eq!(
+            records[2],
+            Record {
+                timestamp: Some("2024-06-01T14:20".to_string()),
+                artist: "Carl Craig".to_string(),
+                title: "At Les - Remix".to_string(),
+                stream: Some("NTS 1".to_string()),
+            }
+        );
+        assert_eq!((records[0].timestamp.as_deref(), records[0].title.as_str()), (None, "Untimed Title"));
+        assert_eq!(records[1].stream, None);
+        assert_eq!(parse_track("No Artist"), ("No Artist", ""));
+
+        let csv = to_csv(&records);
+        assert_eq!(csv.lines().next(), Some("timestamp,artist,title,stream"));
+        assert_eq!(csv.lines().nth(3), Some("2024-06-01T14:20,Carl Craig,At Les - Remix,NTS 1"));
+        let quoted = to_csv(&[Record {
+            timestamp: None,
+            artist: "Earth, Wind & Fire".to_string(),
+            title: "\"September\"".to_string(),
+            stream: None,
+        }]);
+        assert_eq!(quoted.lines().nth(1), Some(",\"Earth, Wind & Fire\",\"\"\"September\"\"\","));
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&records)).unwrap();
+        assert_eq!(json[1]["artist"], "Laurel Halo");
+        assert_eq!(json[0]["timestamp"], serde_json::Value::Null);
+        assert_eq!(Format::for_path(Path::new("tracks.JSON")), Format::Json);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::config::{AutoId, Config};
+
+/// An optional cargo feature, the config section for it, and whether the
+/// config asks for what only it provides.
+pub struct Feature {
+    pub name: &'static str,
+    pub built: bool,
+    pub section: &'static str,
+    requested: fn(&Config) -> bool,
+}
+
+/// The optional features, consulted by the config check, the doctor and
+/// the About screen alike.
+pub const FEATURES: &[Feature] = &[
+    Feature {
+        name: "recognition",
+        built: cfg!(feature = "recognition"),
+        section: "recognition",
+        requested: |config| config.recognition.auto_id != AutoId::Off || !config.recognition.recognizer_command.is_empty(),
+    },
+    Feature {
+        name: "shazam",
+        built: cfg!(feature = "shazam"),
+        section: "recognition",
+        // Without it the recognizer command is run instead
+        requested: |_| false,
+    },
+    Feature {
+        name: "discord",
+        built: cfg!(feature = "discord"),
+        section: "discord",
+        requested: |config| config.discord.enabled,
+    },
+];
+
+/// The names of the features this build has.
+pub fn built() -> Vec<&'static str> {
+    FEATURES.iter().filter(|feature| feature.built).map(|feature| feature.name).collect()
+}
+
+/// A warning for each feature the config asks for that this build lacks,
+/// whose settings are ignored.
+pub fn warnings(config: &Config) -> Vec<String> {
+    FEATURES
+        .iter()
+        .filter(|feature| !feature.built && (feature.requested)(config))
+        .map(|feature| {
+            format!(
+                "config enables [{}] but this build lacks the '{}' feature, ignoring it",
+                feature.section, feature.name
+            )
+        })
+        .collect()
+}
+
+/// What `config check` says next to `section`, if a feature provides it.
+pub fn availability(section: &str) -> Option<String> {
+    let features: Vec<String> = FEATURES
+        .iter()
+        .filter(|feature| feature.section == section)
+        .map(|feature| format!("'{}' {}", feature.name, if feature.built { "built in" } else { "not in this build" }))
+        .collect();
+    (!features.is_empty()).then(|| features.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_requested_missing_feature_is_warned_about() {
+        let mut config = Config::default();
+        assert_eq!(warnings(&config), Vec::<String>::new());
+        config.discord.enabled = true;
+        config.recognition.auto_id = AutoId::Change;
+        let expected: Vec<&str> = [("recognition", cfg!(feature = "recognition")), ("discord", cfg!(feature = "discord"))]
+            .into_iter()
+            .filter(|(_, built)| !built)
+            .map(|(name, _)| name)
+            .collect();
+        let warned = warnings(&config);
+        assert_eq!(warned.len(), expected.len());
+        for (warning, name) in warned.iter().zip(expected) {
+            assert!(warning.starts_with(&format!("config enables [{}] but this build lacks the '{}' feature", name, name)));
+        }
+        assert!(availability("discord").unwrap().starts_with("'discord' "));
+        assert!(availability("recognition").unwrap().contains("'shazam' "));
+        assert_eq!(availability("playback"), None);
+    }
+}
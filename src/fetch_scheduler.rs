@@ -0,0 +1,280 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{clock, http};
+
+/// The budget is for requests started within this long.
+const WINDOW: Duration = Duration::from_secs(60);
+/// Requests per minute until `configure` is called, as in the subcommands.
+const DEFAULT_BUDGET: usize = 30;
+
+static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+
+/// How urgent a request is. When the budget is spent, waiting requests go
+/// out most urgent first, and in the order they were made within a priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// What is on air and next.
+    Schedule,
+    /// Listings someone is looking at: the mixtapes and show archives.
+    Listing,
+    /// Polling nobody waits on, like the newest episodes of followed shows.
+    Background,
+}
+
+/// What the scheduler has done, for the debug overlay.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counters {
+    pub budget: usize,
+    /// Requests started in the last minute.
+    pub recent: usize,
+    pub waiting: usize,
+    pub sent: u64,
+    /// Requests that had to wait for the budget.
+    pub throttled: u64,
+    /// Requests answered by an identical one already in flight.
+    pub deduplicated: u64,
+}
+
+type Response = Arc<(Mutex<Option<Result<String, String>>>, Condvar)>;
+
+struct Ticket {
+    priority: Priority,
+    number: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    started: VecDeque<Instant>,
+    waiting: Vec<Ticket>,
+    /// Responses to come, by request, for identical requests to wait on.
+    in_flight: HashMap<String, Response>,
+    next_ticket: u64,
+    counters: Counters,
+    /// The keys in the order they were let go, which the threads running
+    /// them may not start in.
+    #[cfg(test)]
+    released: Vec<String>,
+}
+
+/// Holds requests to a budget per window and shares a response between
+/// identical requests made while one is in flight. Callers block on their
+/// own threads until their turn.
+pub struct Scheduler {
+    budget: usize,
+    window: Duration,
+    inner: Mutex<Inner>,
+    turn: Condvar,
+}
+
+impl Scheduler {
+    pub fn new(budget: usize, window: Duration) -> Self {
+        Scheduler {
+            budget: budget.max(1),
+            window,
+            inner: Mutex::default(),
+            turn: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Runs `fetch` for the request `key` once the budget allows and nothing
+    /// more urgent is waiting, or waits for the response to an identical
+    /// request already in flight.
+    pub fn run(&self, key: &str, priority: Priority, fetch: impl FnOnce() -> Result<String, String>) -> Result<String, String> {
+        let mut inner = self.lock();
+        if let Some(response) = inner.in_flight.get(key).cloned() {
+            inner.counters.deduplicated += 1;
+            drop(inner);
+            let (result, ready) = &*response;
+            let result = ready
+                .wait_while(result.lock().unwrap_or_else(|poisoned| poisoned.into_inner()), |result| {
+                    result.is_none()
+                })
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            return result.clone().expect("waited for the response");
+        }
+        let response = Response::default();
+        inner.in_flight.insert(key.to_string(), response.clone());
+        let number = inner.next_ticket;
+        inner.next_ticket += 1;
+        inner.waiting.push(Ticket { priority, number });
+
+        let mut throttled = false;
+        loop {
+            let now = Instant::now();
+            while inner.started.front().is_some_and(|started| now.duration_since(*started) >= self.window) {
+                inner.started.pop_front();
+            }
+            let next = inner.waiting.iter().min_by_key(|ticket| (ticket.priority, ticket.number)).map(|ticket| ticket.number);
+            let oldest = inner.started.front().copied().filter(|_| inner.started.len() >= self.budget);
+            match oldest {
+                None if next == Some(number) => break,
+                None => inner = self.turn.wait(inner).unwrap_or_else(|poisoned| poisoned.into_inner()),
+                Some(oldest) => {
+                    throttled = true;
+                    let until_free = self.window.saturating_sub(now.duration_since(oldest));
+                    inner = self
+                        .turn
+                        .wait_timeout(inner, until_free)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0;
+                }
+            }
+        }
+        inner.waiting.retain(|ticket| ticket.number != number);
+        inner.started.push_back(Instant::now());
+        inner.counters.sent += 1;
+        inner.counters.throttled += u64::from(throttled);
+        #[cfg(test)]
+        inner.released.push(key.to_string());
+        drop(inner);
+        // Whoever is next may go now
+        self.turn.notify_all();
+
+        let result = fetch();
+        let (shared, ready) = &*response;
+        *shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(result.clone());
+        ready.notify_all();
+        self.lock().in_flight.remove(key);
+        result
+    }
+
+    pub fn counters(&self) -> Counters {
+        let inner = self.lock();
+        let now = Instant::now();
+        Counters {
+            budget: self.budget,
+            recent: inner.started.iter().filter(|started| now.duration_since(**started) < self.window).count(),
+            waiting: inner.waiting.len(),
+            ..inner.counters
+        }
+    }
+}
+
+/// Sets the requests allowed per minute. Only the first call counts, so
+/// it goes before anything is fetched.
+pub fn configure(budget: usize) {
+    let _ = SCHEDULER.set(Scheduler::new(budget, WINDOW));
+}
+
+fn scheduler() -> &'static Scheduler {
+    SCHEDULER.get_or_init(|| Scheduler::new(DEFAULT_BUDGET, WINDOW))
+}
+
+/// The body of `url`. Every request other than the audio streams goes
+/// through here.
+pub fn get(url: &str, priority: Priority) -> Result<String, String> {
+    scheduler().run(url, priority, || {
+        http::client(url)
+            .build()
+            .and_then(|client| client.get(url).send())
+            .inspect(clock::observe)
+            .and_then(http::error_for_status)
+            .and_then(|response| response.text())
+            .map_err(|err| http::describe_error(&err))
+    })
+}
+
+pub fn counters() -> Counters {
+    scheduler().counters()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+
+    /// Waits until `count` requests are queued behind the budget.
+    fn wait_for_queue(scheduler: &Scheduler, count: usize) {
+        while scheduler.counters().waiting < count {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn a_flood_keeps_to_the_budget_most_urgent_first() {
+        let window = Duration::from_millis(300);
+        let scheduler = Arc::new(Scheduler::new(2, window));
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let request = |key: String, priority: Priority| {
+            let (scheduler, started) = (scheduler.clone(), started.clone());
+            thread::spawn(move || {
+                scheduler.run(&key, priority, || {
+                    started.lock().unwrap().push((key.clone(), Instant::now()));
+                    Ok(key.clone())
+                })
+            })
+        };
+        // Two spend the budget, then a flood queues up behind them
+        for key in ["first", "second"] {
+            request(key.to_string(), Priority::Listing).join().unwrap().unwrap();
+        }
+        let flood = [
+            ("artwork-1", Priority::Background),
+            ("mixtapes", Priority::Listing),
+            ("live-1", Priority::Schedule),
+            ("artwork-2", Priority::Background),
+            ("live-2", Priority::Schedule),
+        ];
+        let mut handles = Vec::new();
+        for (i, (key, priority)) in flood.into_iter().enumerate() {
+            handles.push(request(key.to_string(), priority));
+            wait_for_queue(&scheduler, i + 1);
+        }
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        let order = scheduler.lock().released.clone();
+        assert_eq!(order, ["first", "second", "live-1", "live-2", "mixtapes", "artwork-1", "artwork-2"]);
+        let mut started = started.lock().unwrap().clone();
+        started.sort_by_key(|(_, at)| *at);
+        // Start times are taken a moment after the scheduler's own, so allow
+        // for that
+        for pair in started.windows(3) {
+            assert!(pair[2].1 - pair[0].1 >= window - Duration::from_millis(20), "three requests in one window: {:?}", order);
+        }
+        let counters = scheduler.counters();
+        assert_eq!((counters.sent, counters.throttled, counters.waiting), (7, 5, 0));
+    }
+
+    #[test]
+    fn identical_requests_in_flight_share_one_response() {
+        let scheduler = Arc::new(Scheduler::new(10, WINDOW));
+        let (release, hold) = mpsc::channel::<()>();
+        let fetches = Arc::new(Mutex::new(0));
+        let first = {
+            let (scheduler, fetches) = (scheduler.clone(), fetches.clone());
+            thread::spawn(move || {
+                scheduler.run("https://example.com/live", Priority::Schedule, || {
+                    *fetches.lock().unwrap() += 1;
+                    hold.recv().unwrap();
+                    Ok("body".to_string())
+                })
+            })
+        };
+        while *fetches.lock().unwrap() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let second = {
+            let scheduler = scheduler.clone();
+            thread::spawn(move || scheduler.run("https://example.com/live", Priority::Schedule, || unreachable!()))
+        };
+        while scheduler.counters().deduplicated == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        release.send(()).unwrap();
+        assert_eq!(first.join().unwrap(), Ok("body".to_string()));
+        assert_eq!(second.join().unwrap(), Ok("body".to_string()));
+        assert_eq!(*fetches.lock().unwrap(), 1);
+        assert_eq!(scheduler.counters().sent, 1);
+    }
+}
@@ -0,0 +1,96 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf};
+
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    archive,
+    fetch_scheduler::{self, Priority},
+    Stream,
+};
+
+const SHOW_URL: &str = "https://www.nts.live/api/v2/shows/";
+const CACHE_FILE_NAME: &str = "nts_cli/followed_episodes.json";
+
+/// The newest archive episode of a followed show.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Episode {
+    pub show_alias: String,
+    pub show_name: String,
+    pub episode_alias: String,
+    pub name: String,
+    pub description: String,
+    /// When it went out, in Unix seconds.
+    pub broadcast: i64,
+    pub audio_url: String,
+}
+
+impl Episode {
+    /// As a stream in the "Followed shows" section, which lists one episode
+    /// per show and so uses the show alias as its id.
+    pub fn to_stream(&self) -> Stream {
+        let date = DateTime::from_timestamp(self.broadcast, 0)
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        Stream {
+            id: self.show_alias.clone(),
+            title: self.show_name.clone(),
+            subtitle: format!("{} ({})", self.name, date),
+            description: self.description.clone(),
+            audio_stream_endpoint: self.audio_url.clone(),
+            show_alias: self.show_alias.clone(),
+            schedule: Vec::new(),
+            airtime: None,
+        }
+    }
+}
+
+/// Fetches the newest episode of each show. Every show gets its own result
+/// so one failing doesn't hide the others.
+pub fn fetch_latest(shows: &[String]) -> Vec<(String, Result<Episode, String>)> {
+    shows.iter().map(|show| (show.clone(), fetch_show(show))).collect()
+}
+
+fn fetch_show(show_alias: &str) -> Result<Episode, String> {
+    let body = fetch_scheduler::get(&format!("{}{}", SHOW_URL, show_alias), Priority::Background)?;
+    let json: Value = serde_json::from_str(&body).map_err(|err| err.to_string())?;
+    let episode = json["embeds"]["episodes"]["results"]
+        .as_array()
+        .and_then(|episodes| episodes.first())
+        .ok_or("no episodes yet")?;
+    let audio_url = archive::audio_url(episode).ok_or("episode has no audio the player can stream")?;
+    Ok(Episode {
+        show_alias: show_alias.to_string(),
+        show_name: json["name"].as_str().unwrap_or(show_alias).to_string(),
+        episode_alias: episode["episode_alias"].as_str().unwrap_or_default().to_string(),
+        name: episode["name"].as_str().unwrap_or_default().to_string(),
+        description: episode["description"].as_str().unwrap_or_default().to_string(),
+        broadcast: episode["broadcast"]
+            .as_str()
+            .and_then(|broadcast| DateTime::parse_from_rfc3339(broadcast).ok())
+            .map_or(0, |broadcast| broadcast.timestamp()),
+        audio_url,
+    })
+}
+
+fn cache_file_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(CACHE_FILE_NAME))
+}
+
+/// Episodes from the last fetch, keyed by show alias, so the section is
+/// filled right away on startup.
+pub fn load_cache() -> BTreeMap<String, Episode> {
+    cache_file_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_cache(episodes: &BTreeMap<String, Episode>) -> io::Result<()> {
+    let path = cache_file_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(episodes)?)
+}
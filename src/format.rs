@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const BYTE_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// A fraction as a whole percentage, e.g. 0.5 as "50%". Rounds to the
+/// nearest percent and never shows "-0%".
+pub fn percent(fraction: f64) -> String {
+    percent_with(fraction, 0)
+}
+
+/// A fraction as a percentage with `places` decimals, e.g. "12.5%".
+pub fn percent_with(fraction: f64, places: usize) -> String {
+    let value = if fraction.is_finite() { fraction * 100.0 } else { 0.0 };
+    format!("{:.*}%", places, positive_zero(round_to(value, places)))
+}
+
+/// Seconds with `places` decimals, e.g. "1.23s", for timings.
+pub fn seconds(duration: Duration, places: usize) -> String {
+    format!("{:.*}s", places, duration.as_secs_f64())
+}
+
+/// A clock-style duration: "3:05", or "1:23:45" from an hour up.
+pub fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Hours and minutes for things that tick once a minute: "1:12", "0:05".
+pub fn clock_minutes(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// A rough duration in words: "45 s", "23 min", "1 h 5 min".
+pub fn duration_words(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 59.5 {
+        return format!("{} s", secs.round() as u64);
+    }
+    let minutes = (secs / 60.0).round() as u64;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{} min", minutes),
+        (hours, 0) => format!("{} h", hours),
+        (hours, minutes) => format!("{} h {} min", hours, minutes),
+    }
+}
+
+/// When a show is on: "08:00–10:00", with the weekday when it isn't on
+/// `today`, and "from 10:00" when its end isn't known. A show crossing
+/// midnight reads "23:00–01:00".
+pub fn time_slot(start: NaiveDateTime, end: Option<NaiveDateTime>, today: NaiveDate) -> String {
+    let day = if start.date() == today { String::new() } else { start.format("%a ").to_string() };
+    match end {
+        Some(end) => format!("{}{}–{}", day, start.format("%H:%M"), end.format("%H:%M")),
+        None => format!("{}from {}", day, start.format("%H:%M")),
+    }
+}
+
+/// A size with binary prefixes: "512 B", "1.5 KiB", "84.0 MiB".
+pub fn bytes(size: u64) -> String {
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64;
+    let mut unit = 0;
+    // Promote before rounding could show "1024.0 KiB"
+    while unit < BYTE_UNITS.len() - 1 && round_to(value, 1) >= 1024.0 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, BYTE_UNITS[unit])
+}
+
+/// Swaps the decimal point in formatted numbers for a comma, for locales
+/// that write "1,5". Only points between two digits are touched.
+pub fn with_decimal_comma(text: &str, decimal_comma: bool) -> String {
+    if !decimal_comma {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let between_digits = i > 0
+                && chars[i - 1].is_ascii_digit()
+                && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+            if c == '.' && between_digits {
+                ','
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Cuts `text` to at most `max_width` terminal columns, ending in "…" when
+/// anything was cut. Wide characters count double and are never split.
+pub fn truncate(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let mut width = 0;
+    let mut cut: String = text
+        .chars()
+        .take_while(|c| {
+            width += c.width().unwrap_or(0);
+            width < max_width
+        })
+        .collect();
+    if max_width > 0 {
+        cut.push('…');
+    }
+    cut
+}
+
+fn round_to(value: f64, places: usize) -> f64 {
+    let scale = 10f64.powi(places as i32);
+    (value * scale).round() / scale
+}
+
+fn positive_zero(value: f64) -> f64 {
+    if value == 0.0 {
+        0.0
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_rounds_float_noise() {
+        assert_eq!(percent(1.000_000_01), "100%");
+        assert_eq!(percent(0.5), "50%");
+        assert_eq!(percent(0.004), "0%");
+        assert_eq!(percent(-0.0001), "0%");
+        assert_eq!(percent(f64::NAN), "0%");
+        assert_eq!(percent_with(0.1255, 1), "12.6%");
+        assert_eq!(percent_with(-0.00001, 1), "0.0%");
+    }
+
+    #[test]
+    fn clock_switches_to_hours() {
+        assert_eq!(clock(Duration::from_millis(59_999)), "0:59");
+        assert_eq!(clock(Duration::from_secs(185)), "3:05");
+        assert_eq!(clock(Duration::from_secs(3599)), "59:59");
+        assert_eq!(clock(Duration::from_secs(3600)), "1:00:00");
+        assert_eq!(clock(Duration::from_secs(5025)), "1:23:45");
+    }
+
+    #[test]
+    fn clock_minutes_drops_seconds() {
+        assert_eq!(clock_minutes(Duration::from_secs(59)), "0:00");
+        assert_eq!(clock_minutes(Duration::from_secs(300)), "0:05");
+        assert_eq!(clock_minutes(Duration::from_secs(4379)), "1:12");
+        assert_eq!(clock_minutes(Duration::from_secs(7200)), "2:00");
+    }
+
+    #[test]
+    fn duration_words_round_across_units() {
+        assert_eq!(duration_words(Duration::from_secs(45)), "45 s");
+        assert_eq!(duration_words(Duration::from_millis(59_600)), "1 min");
+        assert_eq!(duration_words(Duration::from_secs(89)), "1 min");
+        assert_eq!(duration_words(Duration::from_secs(90)), "2 min");
+        assert_eq!(duration_words(Duration::from_secs(3599)), "1 h");
+        assert_eq!(duration_words(Duration::from_secs(3900)), "1 h 5 min");
+    }
+
+    #[test]
+    fn time_slot_names_the_day_only_when_it_isnt_today() {
+        let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let at = |day, hour| NaiveDate::from_ymd_opt(2024, 6, day).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+        assert_eq!(time_slot(at(1, 8), Some(at(1, 10)), today), "08:00–10:00");
+        assert_eq!(time_slot(at(1, 23), Some(at(2, 1)), today), "23:00–01:00");
+        assert_eq!(time_slot(at(2, 1), None, today), "Sun from 01:00");
+    }
+
+    #[test]
+    fn bytes_use_binary_prefixes_without_overflowing_a_unit() {
+        assert_eq!(bytes(0), "0 B");
+        assert_eq!(bytes(1023), "1023 B");
+        assert_eq!(bytes(1024), "1.0 KiB");
+        assert_eq!(bytes(1536), "1.5 KiB");
+        assert_eq!(bytes(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(bytes(88 * 1024 * 1024), "88.0 MiB");
+    }
+
+    #[test]
+    fn truncate_counts_columns_not_chars() {
+        assert_eq!(truncate("Lovers Rock", 20), "Lovers Rock");
+        assert_eq!(truncate("Lovers Rock", 8), "Lovers …");
+        // Each of these takes two columns; one can't be half shown
+        assert_eq!(truncate("坂本龍一 Radio", 6), "坂本…");
+        assert_eq!(truncate("坂本龍一 Radio", 7), "坂本龍…");
+        assert_eq!(truncate("anything", 0), "");
+    }
+
+    #[test]
+    fn decimal_comma_only_touches_numbers() {
+        assert_eq!(with_decimal_comma("1.5 KiB in 2.25s", true), "1,5 KiB in 2,25s");
+        assert_eq!(with_decimal_comma("end. 2.5", true), "end. 2,5");
+        assert_eq!(with_decimal_comma("2.5", false), "2.5");
+    }
+}
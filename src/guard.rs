@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+use crate::config::GuardMode;
+
+/// Keys that lose something when pressed by accident.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuardedAction {
+    Stop,
+    Quit,
+    DeleteHistory,
+}
+
+impl GuardedAction {
+    /// "Press Space again to stop", for the hint shown after the first press.
+    pub fn hint(self) -> &'static str {
+        match self {
+            GuardedAction::Stop => "Press Space again to stop",
+            GuardedAction::Quit => "Press q again to quit",
+            GuardedAction::DeleteHistory => "Press Del again to delete the entry",
+        }
+    }
+}
+
+impl GuardMode {
+    /// How long after the first press the second one counts, or None when
+    /// one press is enough.
+    pub fn window(self) -> Option<Duration> {
+        match self {
+            GuardMode::Off => None,
+            GuardMode::Double => Some(Duration::from_millis(500)),
+            GuardMode::Confirm => Some(Duration::from_secs(3)),
+        }
+    }
+}
+
+/// Remembers the first press of a guarded key until the second one comes
+/// or its window runs out. Nothing needs to happen on expiry: a late second
+/// press just counts as a new first one.
+#[derive(Debug, Default)]
+pub struct Guard {
+    pending: Option<(GuardedAction, Instant, Duration)>,
+}
+
+impl Guard {
+    /// Whether `action` goes ahead on this press. The first press of a
+    /// guarded action returns false and starts its window.
+    pub fn allow(&mut self, action: GuardedAction, mode: GuardMode, now: Instant) -> bool {
+        let Some(window) = mode.window() else {
+            return true;
+        };
+        match self.pending.take() {
+            Some((pending, at, _)) if pending == action && now.duration_since(at) <= window => true,
+            _ => {
+                self.pending = Some((action, now, window));
+                false
+            }
+        }
+    }
+
+    /// The hint for a first press still waiting for its second.
+    pub fn hint(&self, now: Instant) -> Option<&'static str> {
+        self.pending
+            .filter(|(_, at, window)| now.duration_since(*at) <= *window)
+            .map(|(action, _, _)| action.hint())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_press_within_the_window_goes_ahead() {
+        let start = Instant::now();
+        let mut guard = Guard::default();
+        assert!(guard.allow(GuardedAction::Quit, GuardMode::Off, start));
+
+        assert!(!guard.allow(GuardedAction::Stop, GuardMode::Double, start));
+        assert_eq!(guard.hint(start), Some("Press Space again to stop"));
+        assert!(guard.allow(GuardedAction::Stop, GuardMode::Double, start + Duration::from_millis(400)));
+        assert_eq!(guard.hint(start), None);
+
+        // Too late: the hint is gone and this press starts over
+        assert!(!guard.allow(GuardedAction::Stop, GuardMode::Double, start));
+        let late = start + Duration::from_millis(600);
+        assert_eq!(guard.hint(late), None);
+        assert!(!guard.allow(GuardedAction::Stop, GuardMode::Double, late));
+
+        // Another guarded key in between doesn't count as the second press
+        assert!(!guard.allow(GuardedAction::Quit, GuardMode::Confirm, late));
+        assert!(!guard.allow(GuardedAction::Stop, GuardMode::Confirm, late));
+        assert!(guard.allow(GuardedAction::Stop, GuardMode::Confirm, late + Duration::from_secs(2)));
+    }
+}
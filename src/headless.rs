@@ -0,0 +1,124 @@
+use std::{
+    error::Error,
+    sync::mpsc::{self, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use crate::{clock, config::Config, events::Reporter, network, plain, player::Player, refresh, shutdown, Stream, StreamType, StreamsCollection};
+
+/// How often the loop looks at signals and finished recognitions.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// `nts_cli --headless`: plays `stream` without touching the terminal and
+/// reports each event, for scripts, tmux popups and supervisors. With
+/// `recognize_every` set, identifies the track on that interval, one run at
+/// a time. A station's show changes are reported as the listing moves on,
+/// and a network change reconnects. Runs until Ctrl+C or another
+/// termination signal.
+pub fn run(
+    config: Config,
+    section: StreamType,
+    stream: &Stream,
+    recognize_every: Option<Duration>,
+    reporter: Reporter,
+) -> Result<(), Box<dyn Error>> {
+    shutdown::install_signal_handlers();
+    let mut player = Player::new(config.playback.volume);
+    player.set_output(config.output.clone());
+    player.set_stream_overrides(config.network.stream_overrides.clone());
+
+    if !plain::play(&mut player, stream, section, &config, reporter) {
+        return Err("Could not start playback".into());
+    }
+
+    let (network_tx, network_changed) = mpsc::channel();
+    if config.network.watch_changes {
+        let interval = Duration::from_secs(config.network.check_interval_secs.max(1));
+        network::watch(interval, move || network_tx.send(()).is_ok());
+    }
+    let (listing_tx, listings) = mpsc::channel();
+    if section == StreamType::Station {
+        watch_listing(stream, listing_tx);
+    }
+
+    let (finished_tx, finished) = mpsc::channel();
+    let mut show = stream.subtitle.clone();
+    let mut next_recognition = recognize_every.map(|every| Instant::now() + every);
+    let mut recognizing = false;
+    while !shutdown::signalled() {
+        if finished.recv_timeout(POLL_INTERVAL).is_ok() {
+            recognizing = false;
+        }
+        if network_changed.try_recv().is_ok() {
+            let payload = json!({ "stream": stream.title, "reason": "network changed" });
+            reporter.event("reconnecting", &format!("Network changed, reconnecting to {}", stream.title), payload);
+            player.stop();
+            plain::play(&mut player, stream, section, &config, reporter);
+        }
+        if let Some(warning) = clock::warning() {
+            reporter.say(&warning);
+        }
+        while let Ok(listing) = listings.try_recv() {
+            match listing {
+                Ok(current) if !current.is_empty() && current != show => {
+                    let line = format!("Now on {}: {}", stream.title, current);
+                    reporter.event("show_changed", &line, json!({ "station": stream.title, "show": current }));
+                    show = current;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    let line = format!("Could not refresh the listing: {}", err);
+                    reporter.event("error", &line, json!({ "during": "refresh", "error": err }));
+                }
+            }
+        }
+        let (Some(every), Some(due)) = (recognize_every, next_recognition) else {
+            continue;
+        };
+        if recognizing || Instant::now() < due {
+            continue;
+        }
+        if let Some(tap) = player.mp3_tap() {
+            let finished_tx = finished_tx.clone();
+            plain::identify(tap, &config.recognition, config.history_timestamps, reporter, move || {
+                let _ = finished_tx.send(());
+            });
+            recognizing = true;
+        }
+        next_recognition = Some(Instant::now() + every);
+    }
+
+    shutdown::request();
+    player.stop();
+    reporter.event("stopped", "Stopping", json!({ "stream": stream.title }));
+    shutdown::wait_for_critical(Duration::from_millis(config.shutdown.timeout_ms));
+    Ok(())
+}
+
+/// Fetches the listing again just after the show on `station` ends, like
+/// the TUI does, and sends what is on air then.
+fn watch_listing(station: &Stream, listing_tx: Sender<Result<String, String>>) {
+    let id = station.id.clone();
+    let mut show_ends: Vec<_> = station.airtime.and_then(|airtime| airtime.end).into_iter().collect();
+    thread::spawn(move || {
+        let mut stale_retried = false;
+        loop {
+            let next = refresh::next(&show_ends, clock::now(), stale_retried);
+            stale_retried = next.stale_retry;
+            thread::sleep(next.wait);
+            let fetched = StreamsCollection::populate_collection().map_err(|err| err.to_string());
+            let station = fetched.map(|collection| collection.stations.into_iter().find(|station| station.id == id));
+            show_ends = match &station {
+                Ok(Some(station)) => station.airtime.and_then(|airtime| airtime.end).into_iter().collect(),
+                _ => Vec::new(),
+            };
+            let show = station.map(|station| station.map(|station| station.subtitle).unwrap_or_default());
+            if listing_tx.send(show).is_err() {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,337 @@
+use std::{collections::HashSet, time::Duration};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+
+use crate::config::TimestampStyle;
+
+/// Format of the timestamp prefix written in front of each history entry.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// The same with the ISO 8601 separator. Both are the same length, and
+/// both are read whichever one is written.
+const ISO_8601_FORMAT: &str = "%Y-%m-%dT%H:%M";
+const TIMESTAMP_LEN: usize = "2024-06-01 14:32".len();
+
+/// Parses the timestamp prefix of a history line. Entries written before
+/// timestamps were added have none.
+pub fn parse_timestamp(line: &str) -> Option<NaiveDateTime> {
+    let prefix = line.get(..TIMESTAMP_LEN)?;
+    NaiveDateTime::parse_from_str(prefix, TIMESTAMP_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(prefix, ISO_8601_FORMAT))
+        .ok()
+}
+
+pub fn format_timestamp(timestamp: NaiveDateTime, style: TimestampStyle) -> String {
+    let format = match style {
+        TimestampStyle::Local => TIMESTAMP_FORMAT,
+        TimestampStyle::Iso8601 => ISO_8601_FORMAT,
+    };
+    timestamp.format(format).to_string()
+}
+
+/// Separates the normalized title from the raw recognizer output, which is
+/// only written when the two differ.
+pub const RAW_SEPARATOR: char = '\t';
+
+/// A history line: the timestamp written in `style`, the normalized `text`,
+/// and the raw recognizer output after a tab when it differs.
+pub fn format_entry(timestamp: NaiveDateTime, style: TimestampStyle, text: &str, raw: &str) -> String {
+    let mut line = format!("{}  {}", format_timestamp(timestamp, style), text);
+    if raw != text {
+        line.push(RAW_SEPARATOR);
+        line.push_str(&raw.replace(['\t', '\n', '\r'], " "));
+    }
+    line
+}
+
+/// The raw recognizer output kept with the entry, if it differed.
+pub fn raw_text(line: &str) -> Option<&str> {
+    line.split(RAW_SEPARATOR).nth(1).filter(|raw| !raw.is_empty())
+}
+
+/// The number the history store gave the entry, counting up as entries are
+/// added, after the raw output (empty when there is none). It keeps entries
+/// written in the same minute in order. Entries from before it was kept
+/// have none.
+pub fn sequence(line: &str) -> Option<u64> {
+    line.split(RAW_SEPARATOR).nth(2)?.trim().parse().ok()
+}
+
+/// `line` numbered `sequence`, in place of any number it had.
+pub fn with_sequence(line: &str, sequence: u64) -> String {
+    let mut fields = line.split(RAW_SEPARATOR);
+    let display = fields.next().unwrap_or_default();
+    let raw = fields.next().unwrap_or_default();
+    format!("{}{}{}{}{}", display, RAW_SEPARATOR, raw, RAW_SEPARATOR, sequence)
+}
+
+/// Why `line` can't be read as an entry, for `history verify`: a track
+/// missing after the timestamp, characters no recognizer writes, as a torn
+/// write leaves, or more fields than an entry has. Lines from before
+/// timestamps and numbers were kept are fine.
+pub fn malformed(line: &str) -> Option<&'static str> {
+    if line.chars().any(|c| c.is_control() && c != RAW_SEPARATOR) {
+        Some("control characters")
+    } else if entry_text(line).is_empty() {
+        Some("no track")
+    } else if line.split(RAW_SEPARATOR).count() > 3 {
+        Some("too many fields")
+    } else if line.split(RAW_SEPARATOR).nth(2).is_some_and(|sequence| sequence.trim().parse::<u64>().is_err()) {
+        Some("sequence number is not a number")
+    } else {
+        None
+    }
+}
+
+/// What the history is ordered by: when the entry was heard, then its
+/// sequence number for entries from the same minute. Entries without a
+/// timestamp come first.
+pub type OrderKey = (Option<NaiveDateTime>, Option<u64>);
+
+pub fn order_key(line: &str) -> OrderKey {
+    (parse_timestamp(line), sequence(line))
+}
+
+/// Where `line` goes among `lines` by its order key: after every line that
+/// sorts before it or with it.
+pub fn position_for<'a>(line: &str, mut lines: impl DoubleEndedIterator<Item = &'a str> + ExactSizeIterator) -> usize {
+    let key = order_key(line);
+    lines.rposition(|existing| order_key(existing) <= key).map_or(0, |position| position + 1)
+}
+
+/// The line as shown in the history pane, without the raw original.
+pub fn display_line(line: &str) -> &str {
+    line.split(RAW_SEPARATOR).next().unwrap_or(line)
+}
+
+/// The normalized entry without its timestamp prefix, used to spot
+/// repeated tracks.
+pub fn entry_text(line: &str) -> &str {
+    split_timestamp(display_line(line)).1.trim_start()
+}
+
+/// Splits a line into its timestamp prefix, empty for entries without one,
+/// and the rest.
+pub fn split_timestamp(line: &str) -> (&str, &str) {
+    match parse_timestamp(line) {
+        Some(_) => line.split_at(TIMESTAMP_LEN),
+        None => ("", line),
+    }
+}
+
+/// Whether `text`, heard at `heard_at`, repeats `last`, the newest entry on
+/// file: the same track, written less than `window` apart. Entries without
+/// a timestamp can't tell, so they are never repeated.
+#[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+pub fn repeats(last: &str, text: &str, heard_at: NaiveDateTime, window: Duration) -> bool {
+    entry_text(last) == text
+        && parse_timestamp(last)
+            .and_then(|written| (heard_at - written).abs().to_std().ok())
+            .is_some_and(|apart| apart < window)
+}
+
+/// Cleans up a recognized title before it is stored: trims and collapses
+/// whitespace, drops trailing bracketed suffixes such as "(Official Video)"
+/// that start with one of `strip_suffixes`, and writes "feat." consistently.
+pub fn normalize_title(raw: &str, strip_suffixes: &[String]) -> String {
+    let mut title = raw
+        .split_whitespace()
+        .map(|word| {
+            let inner = word.trim_start_matches(['(', '[']);
+            let opening = &word[..word.len() - inner.len()];
+            match inner.to_lowercase().as_str() {
+                "feat" | "feat." | "ft" | "ft." | "featuring" => format!("{}feat.", opening),
+                _ => word.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    loop {
+        let trimmed = title.trim_end();
+        let Some(close) = trimmed.chars().last().filter(|c| *c == ')' || *c == ']') else {
+            break;
+        };
+        let open = if close == ')' { '(' } else { '[' };
+        let Some(start) = trimmed.rfind(open) else {
+            break;
+        };
+        let inner = trimmed[start + 1..trimmed.len() - 1].trim().to_lowercase();
+        let junk = strip_suffixes
+            .iter()
+            .any(|suffix| inner.starts_with(&suffix.to_lowercase()));
+        if !junk {
+            break;
+        }
+        title = trimmed[..start].trim_end().to_string();
+    }
+    title
+}
+
+/// One row of the history pane. `line` is the file line it shows and
+/// `streak` the first line of the run of identical entries it belongs to.
+#[derive(Debug)]
+pub struct HistoryRow {
+    pub line: usize,
+    pub streak: usize,
+    pub text: String,
+    /// Heard in the same minute as the entry above it, which the pane marks
+    /// since the times alone don't tell them apart.
+    pub same_minute: bool,
+}
+
+/// Builds the history pane rows, collapsing runs of the same track into one
+/// row with a count unless the run's first line is in `expanded`. Only the
+/// view is collapsed; the file keeps every entry.
+pub fn rows(contents: &str, expanded: &HashSet<usize>) -> Vec<HistoryRow> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let same_minute = |line: usize| {
+        line > 0 && parse_timestamp(lines[line]).is_some_and(|heard_at| parse_timestamp(lines[line - 1]) == Some(heard_at))
+    };
+    let mut rows = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let text = entry_text(lines[start]);
+        let end = lines[start..]
+            .iter()
+            .position(|line| entry_text(line) != text)
+            .map_or(lines.len(), |offset| start + offset);
+        let count = end - start;
+        if count > 1 && !expanded.contains(&start) {
+            rows.push(HistoryRow {
+                line: start,
+                streak: start,
+                text: format!("{} ×{}", display_line(lines[start]), count),
+                same_minute: same_minute(start),
+            });
+        } else {
+            rows.extend((start..end).map(|line| HistoryRow {
+                line,
+                streak: start,
+                text: display_line(lines[line]).to_string(),
+                same_minute: same_minute(line),
+            }));
+        }
+        start = end;
+    }
+    rows
+}
+
+/// Line numbers where each day starts in the history file, for jumping
+/// around long histories without scanning them on every key press.
+#[derive(Default, Debug)]
+pub struct HistoryIndex {
+    day_starts: Vec<(NaiveDate, usize)>,
+    lines: usize,
+}
+
+impl HistoryIndex {
+    pub fn build(contents: &str) -> Self {
+        let mut index = HistoryIndex::default();
+        for line in contents.lines() {
+            index.push(line);
+        }
+        index
+    }
+
+    /// Brings the index up to date with the file contents. Appends are indexed
+    /// incrementally; anything else (imports, deletes) triggers a rebuild.
+    pub fn sync(&mut self, previous_contents: &str, contents: &str) {
+        if contents.starts_with(previous_contents) && previous_contents.ends_with('\n') {
+            for line in contents[previous_contents.len()..].lines() {
+                self.push(line);
+            }
+        } else {
+            *self = HistoryIndex::build(contents);
+        }
+    }
+
+    fn push(&mut self, line: &str) {
+        if let Some(timestamp) = parse_timestamp(line) {
+            let date = timestamp.date();
+            if self.day_starts.last().is_none_or(|(last, _)| *last != date) {
+                self.day_starts.push((date, self.lines));
+            }
+        }
+        self.lines += 1;
+    }
+
+    pub fn next_day(&self, from_line: usize) -> Option<usize> {
+        self.day_starts
+            .iter()
+            .map(|(_, line)| *line)
+            .find(|line| *line > from_line)
+    }
+
+    pub fn previous_day(&self, from_line: usize) -> Option<usize> {
+        self.day_starts
+            .iter()
+            .map(|(_, line)| *line)
+            .rev()
+            .find(|line| *line < from_line)
+    }
+
+    /// First entry on or after the given date, e.g. for "2023-03".
+    pub fn first_from(&self, date: NaiveDate) -> Option<usize> {
+        self.day_starts
+            .iter()
+            .find(|(day, _)| *day >= date)
+            .map(|(_, line)| *line)
+    }
+}
+
+/// Parses a date-jump target: `2023`, `2023-03` or `2023-03-14`.
+pub fn parse_jump_target(input: &str) -> Option<NaiveDate> {
+    let mut parts = input.trim().splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next().map(str::parse).transpose().ok()?.unwrap_or(1);
+    let day = parts.next().map(str::parse).transpose().ok()?.unwrap_or(1);
+    NaiveDate::from_ymd_opt(year, month, day).filter(|date| date.year() == year)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_timestamp_styles_read_back_and_untimed_lines_still_show() {
+        let heard_at = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(14, 32, 0).unwrap();
+        let iso = format!("{}  Laurel Halo - Jelly", format_timestamp(heard_at, TimestampStyle::Iso8601));
+        assert_eq!(iso, "2024-06-01T14:32  Laurel Halo - Jelly");
+        assert_eq!(parse_timestamp(&iso), Some(heard_at));
+        assert_eq!(split_timestamp(&iso), ("2024-06-01T14:32", "  Laurel Halo - Jelly"));
+        assert_eq!(entry_text(&format_entry(heard_at, TimestampStyle::Local, "Laurel Halo - Jelly", "Laurel Halo - Jelly")), "Laurel Halo - Jelly");
+
+        // Written before timestamps were added
+        let contents = "Actress - Hubble\nActress - Hubble\nCarl Craig - At Les\n";
+        let rows = rows(contents, &HashSet::new());
+        let texts: Vec<&str> = rows.iter().map(|row| row.text.as_str()).collect();
+        assert_eq!(texts, ["Actress - Hubble ×2", "Carl Craig - At Les"]);
+        assert_eq!(split_timestamp(texts[1]), ("", "Carl Craig - At Les"));
+
+        let contents = "2024-06-01 14:32  A - B\t\t1\n2024-06-01 14:32  C - D\t\t2\n2024-06-01 14:33  E - F\n";
+        let marked: Vec<bool> = super::rows(contents, &HashSet::new()).iter().map(|row| row.same_minute).collect();
+        assert_eq!(marked, [false, true, false]);
+    }
+
+    #[test]
+    fn a_track_heard_again_soon_after_repeats_the_last_entry() {
+        let at = |minute| NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(14, minute, 30).unwrap();
+        let window = Duration::from_secs(10 * 60);
+        let last = format_entry(at(32), TimestampStyle::Local, "Laurel Halo - Jelly", "Laurel Halo - Jelly (Official Video)");
+        assert!(last.contains(RAW_SEPARATOR));
+        // The file keeps minutes only, so 14:32:30 reads back as 14:32:00
+        assert!(repeats(&last, "Laurel Halo - Jelly", at(41), window));
+        assert!(!repeats(&last, "Laurel Halo - Jelly", at(42), window));
+        assert!(!repeats(&last, "Actress - Hubble", at(33), window));
+        // A pending ID identified late can be older than the last entry
+        assert!(repeats(&last, "Laurel Halo - Jelly", at(25), window));
+        assert!(!repeats(&last, "Laurel Halo - Jelly", at(33), Duration::ZERO));
+        assert!(!repeats("Laurel Halo - Jelly", "Laurel Halo - Jelly", at(33), window));
+        assert!(repeats(
+            "2024-06-01T14:32  Laurel Halo - Jelly",
+            "Laurel Halo - Jelly",
+            at(33),
+            window
+        ));
+    }
+}
@@ -0,0 +1,501 @@
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{config::{Config, TimestampStyle}, get_history_file_path, history, legacy_history_file_path, shutdown};
+
+pub const USAGE: &str =
+    "Usage: nts_cli history import <file> | nts_cli history normalize | nts_cli history verify [--repair]";
+
+/// Something in the history file that breaks the order the store keeps.
+#[derive(Debug, PartialEq)]
+pub struct Discrepancy {
+    /// File line, counting from 0.
+    pub line: usize,
+    pub problem: String,
+}
+
+impl std::fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line + 1, self.problem)
+    }
+}
+
+/// Owns the history file. Every feature that changes the history goes
+/// through it: changes are made in memory and written by [`commit`], so a
+/// bulk operation costs one write however many entries it touches. Plain
+/// appends are written as an append; anything else rewrites the file
+/// atomically through a temporary file.
+///
+/// [`commit`]: HistoryStore::commit
+pub struct HistoryStore {
+    path: PathBuf,
+    lines: Vec<String>,
+    /// Lines from here on were appended since the last commit.
+    appended_from: usize,
+    /// Whether lines before `appended_from` changed, needing a full rewrite.
+    rewrite: bool,
+    writes: usize,
+    /// The sequence number the next entry gets.
+    next_sequence: u64,
+}
+
+impl HistoryStore {
+    /// Reads the history file at `path`. A missing file is an empty history.
+    pub fn open(path: &Path) -> io::Result<HistoryStore> {
+        let lines: Vec<String> = match fs::read_to_string(path) {
+            Ok(contents) => contents.lines().map(str::to_string).collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(HistoryStore {
+            path: path.to_path_buf(),
+            appended_from: lines.len(),
+            next_sequence: next_sequence(&lines),
+            lines,
+            rewrite: false,
+            writes: 0,
+        })
+    }
+
+    /// The history file in the usual place.
+    pub fn open_default() -> io::Result<HistoryStore> {
+        HistoryStore::open(&get_history_file_path())
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// The history as it reads from the file, one entry per line.
+    pub fn contents(&self) -> String {
+        self.lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// Adds an entry, numbered after every other. Only recognitions add
+    /// entries one at a time. One heard before the last entry, as a pending
+    /// ID identified late is, goes in where it belongs. Returns the line as
+    /// numbered.
+    #[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+    pub fn append(&mut self, line: String) -> String {
+        let line = history::with_sequence(&line, self.next_sequence);
+        self.next_sequence += 1;
+        let position = history::position_for(&line, self.lines.iter().map(String::as_str));
+        if position < self.appended_from {
+            self.appended_from += 1;
+            self.rewrite = true;
+        }
+        self.lines.insert(position, line.clone());
+        line
+    }
+
+    /// Removes the entry on file line `line`. Returns false if there is none.
+    pub fn delete(&mut self, line: usize) -> bool {
+        if line >= self.lines.len() {
+            return false;
+        }
+        self.lines.remove(line);
+        if line < self.appended_from {
+            self.appended_from -= 1;
+            self.rewrite = true;
+        }
+        true
+    }
+
+    /// Adds the entries of another history that aren't already in this one
+    /// and puts everything in order of timestamp and sequence number, see
+    /// [`history::order_key`]. An entry is already there when one has the
+    /// same timestamp and track, whatever its number or raw output, so a
+    /// copy of this history imported back adds nothing. Entries without a
+    /// timestamp stay at the top, in their order. Returns how many were added.
+    pub fn merge(&mut self, other: impl IntoIterator<Item = String>) -> usize {
+        let entry = |line: &str| (history::parse_timestamp(line), history::entry_text(line).to_string());
+        let mut known: HashSet<_> = self.lines.iter().map(|line| entry(line)).collect();
+        let before = self.lines.len();
+        for line in other {
+            if !line.trim().is_empty() && known.insert(entry(&line)) {
+                self.lines.push(line);
+            }
+        }
+        let added = self.lines.len() - before;
+        if added > 0 {
+            self.lines.sort_by_key(|line| history::order_key(line));
+            self.next_sequence = next_sequence(&self.lines);
+            self.rewrite = true;
+        }
+        added
+    }
+
+    /// Normalizes every title again with the current rules, from the raw
+    /// recognizer output where it was kept, writing timestamps in `style`.
+    /// Returns how many entries changed.
+    pub fn rewrite_normalized(&mut self, strip_suffixes: &[String], style: TimestampStyle) -> usize {
+        let mut changed = 0;
+        for line in &mut self.lines {
+            let Some(timestamp) = history::parse_timestamp(line) else {
+                continue;
+            };
+            let raw = history::raw_text(line).unwrap_or_else(|| history::entry_text(line)).to_string();
+            let mut normalized = history::format_entry(timestamp, style, &history::normalize_title(&raw, strip_suffixes), &raw);
+            if let Some(sequence) = history::sequence(line) {
+                normalized = history::with_sequence(&normalized, sequence);
+            }
+            if normalized != *line {
+                *line = normalized;
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.rewrite = true;
+        }
+        changed
+    }
+
+    /// Checks the file is what the store would have written: no blank or
+    /// unreadable lines and entries in timestamp order. Edits made outside
+    /// the app are what usually break this. Repeated lines are not a
+    /// problem: the same track can be heard twice in a minute, and entries
+    /// from before timestamps were kept repeat with nothing to tell them
+    /// apart.
+    pub fn verify(&self) -> Vec<Discrepancy> {
+        let mut discrepancies = Vec::new();
+        let mut previous: Option<(usize, history::OrderKey)> = None;
+        for (line, entry) in self.lines.iter().enumerate() {
+            if entry.trim().is_empty() {
+                discrepancies.push(Discrepancy {
+                    line,
+                    problem: "blank line".to_string(),
+                });
+                continue;
+            }
+            if let Some(problem) = history::malformed(entry) {
+                discrepancies.push(Discrepancy {
+                    line,
+                    problem: format!("{}: {}", problem, entry.escape_debug()),
+                });
+                continue;
+            }
+            let key = history::order_key(entry);
+            if let Some((previous_line, previous_key)) = previous {
+                if key < previous_key {
+                    discrepancies.push(Discrepancy {
+                        line,
+                        problem: format!(
+                            "older than line {} above it: {}",
+                            previous_line + 1,
+                            history::display_line(entry)
+                        ),
+                    });
+                    continue;
+                }
+            }
+            previous = Some((line, key));
+        }
+        discrepancies
+    }
+
+    /// Drops the blank and unreadable lines [`verify`] reports. Entries out
+    /// of order are left for whoever edited the file to put right, as
+    /// guessing where they belong could bury one. Returns how many lines
+    /// went away.
+    ///
+    /// [`verify`]: HistoryStore::verify
+    pub fn repair(&mut self) -> usize {
+        let before = self.lines.len();
+        self.lines.retain(|line| !line.trim().is_empty() && history::malformed(line).is_none());
+        let changed = before - self.lines.len();
+        if changed > 0 {
+            self.appended_from = self.appended_from.min(self.lines.len());
+            self.rewrite = true;
+        }
+        changed
+    }
+
+    /// Writes what changed since the last commit, if anything, creating the
+    /// directory it goes in.
+    pub fn commit(&mut self) -> io::Result<()> {
+        let _critical = shutdown::critical();
+        if self.rewrite || self.appended_from < self.lines.len() {
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        if self.rewrite {
+            let temp_path = self.path.with_extension("txt.tmp");
+            fs::write(&temp_path, self.contents())?;
+            fs::rename(&temp_path, &self.path)?;
+            self.writes += 1;
+        } else if self.appended_from < self.lines.len() {
+            let appended: String = self.lines[self.appended_from..]
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect();
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?
+                .write_all(appended.as_bytes())?;
+            self.writes += 1;
+        }
+        self.appended_from = self.lines.len();
+        self.rewrite = false;
+        Ok(())
+    }
+
+    /// How many times the file was written, to keep bulk operations honest.
+    #[allow(dead_code)]
+    pub fn writes(&self) -> usize {
+        self.writes
+    }
+}
+
+/// One past the highest sequence number in `lines`.
+fn next_sequence(lines: &[String]) -> u64 {
+    lines.iter().filter_map(|line| history::sequence(line)).max().map_or(1, |highest| highest + 1)
+}
+
+/// Moves the history from where it used to be kept, in the home directory,
+/// to the data directory, unless there is one there already. For runs
+/// without `history_path`; returns a line for the log if it did anything.
+pub fn migrate_legacy() -> Option<String> {
+    let legacy = legacy_history_file_path()?;
+    let target = get_history_file_path();
+    match migrate(&legacy, &target) {
+        Ok(false) => None,
+        Ok(true) => Some(format!("Moved the history from {} to {}", legacy.display(), target.display())),
+        Err(err) => Some(format!("Could not move the history from {}: {}", legacy.display(), err)),
+    }
+}
+
+fn migrate(legacy: &Path, target: &Path) -> io::Result<bool> {
+    if legacy == target || target.exists() || !legacy.exists() {
+        return Ok(false);
+    }
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // The data directory can be on another filesystem
+    if fs::rename(legacy, target).is_err() {
+        fs::copy(legacy, target)?;
+        fs::remove_file(legacy)?;
+    }
+    Ok(true)
+}
+
+/// `nts_cli history ...`: bulk changes to the history file.
+pub fn run(args: &[&str]) -> bool {
+    let config = Config::load().map(|checked| checked.config).unwrap_or_default();
+    if config.history_path.as_os_str().is_empty() {
+        if let Some(note) = migrate_legacy() {
+            eprintln!("{}", note);
+        }
+    }
+    let mut store = match HistoryStore::open_default() {
+        Ok(store) => store,
+        Err(err) => {
+            eprintln!("Could not read the history: {}", err);
+            return false;
+        }
+    };
+    let summary = match args {
+        ["import", path] => match fs::read_to_string(path) {
+            Ok(contents) => format!(
+                "Imported {}: {} entries added",
+                path,
+                store.merge(contents.lines().map(str::to_string))
+            ),
+            Err(err) => {
+                eprintln!("Could not read {}: {}", path, err);
+                return false;
+            }
+        },
+        ["verify", flags @ ..] if flags.is_empty() || flags == ["--repair"] => {
+            let discrepancies = store.verify();
+            if discrepancies.is_empty() {
+                println!("History is consistent ({} entries)", store.lines().len());
+                return true;
+            }
+            for discrepancy in &discrepancies {
+                println!("{}", discrepancy);
+            }
+            if flags.is_empty() {
+                eprintln!(
+                    "{} problems in {}; `nts_cli history verify --repair` drops the blank and unreadable lines",
+                    discrepancies.len(),
+                    store.path.display()
+                );
+                return false;
+            }
+            format!("Repaired: {} lines removed", store.repair())
+        }
+        ["normalize"] => format!(
+            "{} entries normalized",
+            store.rewrite_normalized(&config.recognition.strip_suffixes, config.history_timestamps)
+        ),
+        _ => {
+            eprintln!("{}", USAGE);
+            return false;
+        }
+    };
+    if let Err(err) = store.commit() {
+        eprintln!("Could not write the history: {}", err);
+        return false;
+    }
+    println!("{}", summary);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_with(contents: &str) -> (tempfile::TempDir, HistoryStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.txt");
+        fs::write(&path, contents).unwrap();
+        let store = HistoryStore::open(&path).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn the_legacy_file_moves_once_into_new_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("nts_cli_song_history.txt");
+        let target = dir.path().join("share/nts_cli/history.txt");
+        assert!(!migrate(&legacy, &target).unwrap());
+        fs::write(&legacy, "2024-06-01 14:32  Title - Artist\n").unwrap();
+        assert!(migrate(&legacy, &target).unwrap());
+        assert!(!legacy.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "2024-06-01 14:32  Title - Artist\n");
+
+        // One already there is kept, and so is the old file
+        fs::write(&legacy, "2024-06-02 09:00  Other - Artist\n").unwrap();
+        assert!(!migrate(&legacy, &target).unwrap());
+        assert!(legacy.exists());
+
+        let mut store = HistoryStore::open(&dir.path().join("new/history.txt")).unwrap();
+        store.append("2024-06-01 14:32  Title - Artist".to_string());
+        store.commit().unwrap();
+        assert!(dir.path().join("new/history.txt").exists());
+    }
+
+    #[test]
+    fn large_merge_is_one_write() {
+        let (_dir, mut store) = store_with("2024-06-01 12:00  Existing - Entry\n");
+        let imported = (0..10_000).map(|i| format!("2024-05-{:02} {:02}:{:02}  Track {} - Artist", i % 28 + 1, i / 60 % 24, i % 60, i));
+        assert_eq!(store.merge(imported), 10_000);
+        store.commit().unwrap();
+        assert_eq!(store.writes(), 1);
+
+        let reopened = HistoryStore::open(&store.path).unwrap();
+        assert_eq!(reopened.lines().len(), 10_001);
+        assert_eq!(reopened.lines().last().unwrap(), "2024-06-01 12:00  Existing - Entry");
+    }
+
+    #[test]
+    fn merge_skips_entries_already_there() {
+        let (_dir, mut store) = store_with("2024-06-01 12:00  A - B\n");
+        assert_eq!(store.merge(["2024-06-01 12:00  A - B".to_string(), String::new()]), 0);
+        store.commit().unwrap();
+        assert_eq!(store.writes(), 0);
+    }
+
+    #[test]
+    fn appends_and_deletes_reach_the_file() {
+        let (_dir, mut store) = store_with("2024-06-01 12:00  A - B\n2024-06-01 12:05  C - D\n");
+        store.append("2024-06-01 12:10  E - F".to_string());
+        store.commit().unwrap();
+        assert!(store.delete(0));
+        store.commit().unwrap();
+        assert_eq!(store.writes(), 2);
+        assert_eq!(
+            fs::read_to_string(&store.path).unwrap(),
+            "2024-06-01 12:05  C - D\n2024-06-01 12:10  E - F\t\t1\n"
+        );
+    }
+
+    #[test]
+    fn verify_reports_what_repair_fixes() {
+        // Repeats, timed or not, are entries like any other
+        let (_dir, mut store) = store_with(
+            "Untimed - Repeat\nUntimed - Repeat\n2024-06-01 12:00  A - B\n\n2024-06-01 12:00  A - B\n2024-06-01 12:05  \n\
+             2024-06-01 12:06  C - D\t\tseven\n2024-06-01 12:07  C\0D\n2024-06-01 12:08  E - F\n",
+        );
+        let problems: Vec<String> = store.verify().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            problems,
+            [
+                "line 4: blank line",
+                "line 6: no track: 2024-06-01 12:05  ",
+                "line 7: sequence number is not a number: 2024-06-01 12:06  C - D\\t\\tseven",
+                "line 8: control characters: 2024-06-01 12:07  C\\0D",
+            ]
+        );
+        assert_eq!(store.repair(), 4);
+        store.commit().unwrap();
+        let reopened = HistoryStore::open(&store.path).unwrap();
+        assert_eq!(reopened.verify(), []);
+        assert_eq!(
+            reopened.contents(),
+            "Untimed - Repeat\nUntimed - Repeat\n2024-06-01 12:00  A - B\n2024-06-01 12:00  A - B\n2024-06-01 12:08  E - F\n"
+        );
+
+        // Entries out of order are reported but stay where they are
+        let (_dir, mut store) = store_with("2024-06-01 12:05  C - D\n2024-06-01 11:00  E - F\n");
+        let problems: Vec<String> = store.verify().iter().map(ToString::to_string).collect();
+        assert_eq!(problems, ["line 2: older than line 1 above it: 2024-06-01 11:00  E - F"]);
+        assert_eq!(store.repair(), 0);
+    }
+
+    #[test]
+    fn recognitions_in_the_same_second_keep_their_order() {
+        let (_dir, mut store) = store_with("2024-06-01 12:00  Old - Entry\n");
+        let heard_at = history::parse_timestamp("2024-06-01 14:32").unwrap() + chrono::Duration::seconds(7);
+        // Auto-ID and a manual run finishing together, the later one sorting first by name
+        store.append(history::format_entry(heard_at, TimestampStyle::Local, "Zz - First", "Zz - First"));
+        store.append(history::format_entry(heard_at, TimestampStyle::Local, "Aa - Second", "Aa - Second (Official Video)"));
+        store.commit().unwrap();
+        let saved = fs::read_to_string(&store.path).unwrap();
+        assert_eq!(
+            saved,
+            "2024-06-01 12:00  Old - Entry\n\
+             2024-06-01 14:32  Zz - First\t\t1\n\
+             2024-06-01 14:32  Aa - Second\tAa - Second (Official Video)\t2\n"
+        );
+
+        // The order survives reading back, a shuffled copy being imported
+        // and normalizing
+        let mut reopened = HistoryStore::open(&store.path).unwrap();
+        assert_eq!(reopened.verify(), []);
+        let mut shuffled: Vec<String> = saved.lines().rev().map(str::to_string).collect();
+        shuffled.push("2024-06-01 14:32  Aa - Second\t\t4".to_string());
+        shuffled.push("2024-06-01 14:33  Cc - Third\t\t5".to_string());
+        assert_eq!(reopened.merge(shuffled.clone()), 1);
+        reopened.rewrite_normalized(&["Official Video".to_string()], TimestampStyle::Local);
+        let texts: Vec<&str> = reopened.lines().iter().map(|line| history::entry_text(line)).collect();
+        assert_eq!(texts, ["Old - Entry", "Zz - First", "Aa - Second", "Cc - Third"]);
+
+        // Numbering carries on from the highest, and one heard earlier goes
+        // in where it belongs
+        reopened.append(history::format_entry(heard_at - chrono::Duration::hours(1), TimestampStyle::Local, "Late - Pending", "Late - Pending"));
+        assert_eq!(reopened.lines()[1], "2024-06-01 13:32  Late - Pending\t\t6");
+        reopened.commit().unwrap();
+        assert_eq!(HistoryStore::open(&reopened.path).unwrap().verify(), []);
+    }
+
+    #[test]
+    fn normalizing_uses_the_raw_title() {
+        let (_dir, mut store) = store_with("2024-06-01 12:00  Song - Artist\tSong - Artist (Official Video)\n");
+        let suffixes = ["Official Video".to_string()];
+        assert_eq!(store.rewrite_normalized(&suffixes, TimestampStyle::Local), 0);
+        let (_dir, mut store) = store_with("2024-06-01 12:00  Song  ft Someone (Official Video)\n");
+        assert_eq!(store.rewrite_normalized(&suffixes, TimestampStyle::Local), 1);
+        assert_eq!(
+            store.lines()[0],
+            "2024-06-01 12:00  Song feat. Someone\tSong  ft Someone (Official Video)"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::{fs, path::PathBuf, sync::OnceLock};
+
+use reqwest::{
+    blocking::{Client, ClientBuilder, Response},
+    header::{HeaderMap, HeaderName, HeaderValue, COOKIE},
+    StatusCode,
+};
+
+use crate::{
+    config::{Config, HttpConfig},
+    tls,
+};
+
+/// The last NTS endpoint that answered 403, kept for `nts_cli doctor`.
+const FORBIDDEN_FILE_NAME: &str = "nts_cli/forbidden_endpoint.txt";
+/// What a 403 adds to its error, since the way past it is in the config.
+pub const FORBIDDEN_HINT: &str = "NTS may want a session: set [http] headers or cookies in the config";
+
+static HEADERS: OnceLock<HeaderMap> = OnceLock::new();
+
+impl HttpConfig {
+    /// The configured headers, with the cookies joined into one `Cookie`
+    /// header after any given outright. Ones that aren't valid in a request
+    /// are left out with a warning each.
+    pub fn header_map(&self) -> (HeaderMap, Vec<String>) {
+        let mut headers = HeaderMap::new();
+        let mut warnings = Vec::new();
+        for (name, value) in &self.headers {
+            match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                (Ok(name), Ok(value)) => {
+                    headers.append(name, value);
+                }
+                (Err(_), _) => warnings.push(format!("http.headers: '{}' is not a header name, leaving it out", name)),
+                (_, Err(_)) => warnings.push(format!("http.headers.{}: not a valid header value, leaving it out", name)),
+            }
+        }
+        if !self.cookies.is_empty() {
+            let cookies = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join("; ");
+            match HeaderValue::from_str(&cookies) {
+                Ok(value) => {
+                    headers.append(COOKIE, value);
+                }
+                Err(_) => warnings.push("http.cookies: not valid in a Cookie header, leaving them out".to_string()),
+            }
+        }
+        (headers, warnings)
+    }
+
+    /// The header names sent, without their values, for logs.
+    pub fn describe(&self) -> String {
+        let mut names: Vec<&str> = self.headers.keys().map(String::as_str).collect();
+        if !self.cookies.is_empty() {
+            names.push("Cookie");
+        }
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(", ")
+        }
+    }
+}
+
+/// The headers from `[http]` go on every request to NTS, both the API and
+/// the streams. Read from the config on first use.
+fn headers() -> &'static HeaderMap {
+    HEADERS.get_or_init(|| {
+        let config = Config::load().map(|checked| checked.config).unwrap_or_default();
+        config.http.header_map().0
+    })
+}
+
+/// `headers` for a request to `url`: all of them to NTS, none elsewhere,
+/// so a session cookie never reaches a third-party host.
+fn headers_for(url: &str, headers: &HeaderMap) -> HeaderMap {
+    if tls::is_nts_url(url) {
+        headers.clone()
+    } else {
+        HeaderMap::new()
+    }
+}
+
+/// A client builder for `url` with the `[http]` headers set when it is an
+/// NTS host, and strict TLS when it is one and `[tls]` asks for it.
+pub fn client(url: &str) -> ClientBuilder {
+    let builder = Client::builder().default_headers(headers_for(url, headers()));
+    match tls::strict_config_for(url) {
+        Some(config) => builder.use_preconfigured_tls(config).https_only(true),
+        None => builder,
+    }
+}
+
+/// Like `Response::error_for_status`, remembering a 403 from NTS for the
+/// doctor.
+pub fn error_for_status(response: Response) -> reqwest::Result<Response> {
+    if response.status() == StatusCode::FORBIDDEN && tls::is_nts_url(response.url().as_str()) {
+        remember_forbidden(response.url().as_str());
+    }
+    response.error_for_status()
+}
+
+/// The error as text, with the hint for a 403 from NTS and the reason for
+/// a failed strict TLS check.
+pub fn describe_error(err: &reqwest::Error) -> String {
+    let strict = err.url().is_some_and(|url| tls::strict_config_for(url.as_str()).is_some());
+    let nts = err.url().is_some_and(|url| tls::is_nts_url(url.as_str()));
+    match (err.status(), tls::failure(err)) {
+        (Some(StatusCode::FORBIDDEN), _) if nts => format!("{} ({})", err, FORBIDDEN_HINT),
+        (_, Some(reason)) if strict => format!("{}: {} ({})", err, reason, tls::PIN_HINT),
+        (_, Some(reason)) => format!("{}: {}", err, reason),
+        _ => err.to_string(),
+    }
+}
+
+fn forbidden_file_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(FORBIDDEN_FILE_NAME))
+}
+
+fn remember_forbidden(url: &str) {
+    if let Some(path) = forbidden_file_path() {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, url);
+    }
+}
+
+/// The endpoint that last answered 403, if any did.
+fn last_forbidden() -> Option<String> {
+    let url = fs::read_to_string(forbidden_file_path()?).ok()?;
+    let url = url.trim();
+    (!url.is_empty()).then(|| url.to_string())
+}
+
+/// Tries the endpoint that last answered 403 again with the configured
+/// headers, and forgets it once it answers. For `nts_cli doctor`.
+pub fn check_forbidden(config: &HttpConfig) -> Option<Result<String, String>> {
+    let url = last_forbidden()?;
+    let sending = config.describe();
+    let result = Client::builder()
+        .default_headers(config.header_map().0)
+        .build()
+        .and_then(|client| client.get(&url).send())
+        .map_err(|err| format!("{}: {}", url, err))
+        .and_then(|response| match response.status() {
+            StatusCode::FORBIDDEN if sending == "none" => {
+                Err(format!("{} still answers 403; no [http] headers are configured", url))
+            }
+            StatusCode::FORBIDDEN => Err(format!("{} still answers 403 with headers {}", url, sending)),
+            status => {
+                if let Some(path) = forbidden_file_path() {
+                    let _ = fs::remove_file(path);
+                }
+                Ok(format!("{} answers {} with headers {}", url, status, sending))
+            }
+        });
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn cookies_join_into_one_header_and_bad_headers_are_left_out() {
+        let config = HttpConfig {
+            headers: BTreeMap::from([
+                ("X-Session".to_string(), "abc".to_string()),
+                ("Bad Name".to_string(), "x".to_string()),
+                ("X-Newline".to_string(), "a\nb".to_string()),
+            ]),
+            cookies: BTreeMap::from([
+                ("session".to_string(), "secret".to_string()),
+                ("region".to_string(), "uk".to_string()),
+            ]),
+        };
+        let (headers, warnings) = config.header_map();
+        assert_eq!(headers.get("x-session").unwrap(), "abc");
+        assert_eq!(headers.get(COOKIE).unwrap(), "region=uk; session=secret");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(config.describe(), "Bad Name, X-Newline, X-Session, Cookie");
+        assert_eq!(HttpConfig::default().describe(), "none");
+    }
+
+    #[test]
+    fn headers_only_go_to_nts() {
+        let (headers, _) = HttpConfig {
+            cookies: BTreeMap::from([("session".to_string(), "secret".to_string())]),
+            ..Default::default()
+        }
+        .header_map();
+        assert_eq!(headers_for("https://www.nts.live/api/v2/live", &headers).len(), 1);
+        assert_eq!(headers_for("https://stream-relay-geo.ntslive.net/stream", &headers).len(), 1);
+        assert!(headers_for("https://ipinfo.io/json", &headers).is_empty());
+        assert!(headers_for("https://nts.live.example.com/stream", &headers).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use std::time::{Duration, Instant};
+
+/// What asked for a recognition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    Manual,
+    Scheduled,
+}
+
+/// What the caller should do about a recognition request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Start run `id`.
+    Start(u64),
+    /// Cancel run `cancel`, which is still in flight, and start run `start`.
+    Supersede { cancel: u64, start: u64 },
+    /// Leave the run in flight alone.
+    Ignore,
+}
+
+/// How long the indicator shows how the last run went.
+const FLASH: Duration = Duration::from_secs(3);
+/// Spinner frames, one per tick while a run is in flight.
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// What the recognition indicator shows, kept apart from the text in the
+/// Info pane so it can't be lost among other messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indicator {
+    /// A run is in flight; the spinner frame to draw.
+    Running(&'static str),
+    Succeeded,
+    Failed,
+}
+
+impl Indicator {
+    pub fn label(self) -> String {
+        match self {
+            Indicator::Running(frame) => format!("{} ID", frame),
+            Indicator::Succeeded => "✓ ID".to_string(),
+            Indicator::Failed => "✗ ID".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Run {
+    id: u64,
+    trigger: Trigger,
+}
+
+/// Decides when recognitions run so that auto-ID and `r` never overlap: at
+/// most one run is in flight, a scheduled run never interrupts anything, and
+/// a manual one supersedes a scheduled run and restarts the interval.
+#[derive(Debug, Default)]
+pub struct IdScheduler {
+    interval: Option<Duration>,
+    last_started: Option<Instant>,
+    running: Option<Run>,
+    next_id: u64,
+    /// Whether the last run found a track, and when it finished.
+    outcome: Option<(bool, Instant)>,
+}
+
+impl IdScheduler {
+    /// With `interval` set, runs are due that long after the last one started.
+    pub fn new(interval: Option<Duration>) -> Self {
+        IdScheduler {
+            interval,
+            ..Default::default()
+        }
+    }
+
+    pub fn manual(&mut self, now: Instant) -> Decision {
+        match self.running {
+            Some(Run {
+                trigger: Trigger::Manual,
+                ..
+            }) => Decision::Ignore,
+            Some(Run { id, .. }) => Decision::Supersede {
+                cancel: id,
+                start: self.start(Trigger::Manual, now),
+            },
+            None => Decision::Start(self.start(Trigger::Manual, now)),
+        }
+    }
+
+    /// A scheduled run came due, by the interval or a detected track change.
+    pub fn scheduled(&mut self, now: Instant) -> Decision {
+        if self.running.is_some() {
+            return Decision::Ignore;
+        }
+        Decision::Start(self.start(Trigger::Scheduled, now))
+    }
+
+    /// Whether the interval has passed since the last run started.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next_in(now).is_some_and(|next| next.is_zero())
+    }
+
+    /// Time until the next interval run, or None without an interval.
+    pub fn next_in(&self, now: Instant) -> Option<Duration> {
+        let interval = self.interval?;
+        Some(match self.last_started {
+            Some(started) => (started + interval).saturating_duration_since(now),
+            None => Duration::ZERO,
+        })
+    }
+
+    /// Marks run `id` finished. Returns false for a run that was superseded
+    /// or cancelled, whose result should be dropped.
+    pub fn finished(&mut self, id: u64) -> bool {
+        self.running.take_if(|run| run.id == id).is_some()
+    }
+
+    /// Stops tracking a scheduled run in flight, returning its id to cancel
+    /// it. Manual runs are left to finish.
+    pub fn cancel_scheduled(&mut self) -> Option<u64> {
+        self.running
+            .take_if(|run| run.trigger == Trigger::Scheduled)
+            .map(|run| run.id)
+    }
+
+    /// Stops tracking the run in flight, whatever started it, returning its
+    /// id to cancel it.
+    pub fn cancel_running(&mut self) -> Option<u64> {
+        self.running.take().map(|run| run.id)
+    }
+
+    pub fn running(&self) -> Option<Trigger> {
+        self.running.map(|run| run.trigger)
+    }
+
+    /// Records how the run that just finished went, for the indicator.
+    pub fn report(&mut self, found: bool, now: Instant) {
+        self.outcome = Some((found, now));
+    }
+
+    /// The indicator: a spinner while a run is in flight, then a brief
+    /// flash of how it went.
+    pub fn indicator(&self, now: Instant) -> Option<Indicator> {
+        if self.running.is_some() {
+            let elapsed = self.last_started.map_or(0, |started| now.saturating_duration_since(started).as_secs());
+            return Some(Indicator::Running(SPINNER[elapsed as usize % SPINNER.len()]));
+        }
+        let (found, finished) = self.outcome?;
+        if now.saturating_duration_since(finished) >= FLASH {
+            return None;
+        }
+        Some(if found { Indicator::Succeeded } else { Indicator::Failed })
+    }
+
+    fn start(&mut self, trigger: Trigger, now: Instant) -> u64 {
+        self.next_id += 1;
+        self.running = Some(Run {
+            id: self.next_id,
+            trigger,
+        });
+        self.last_started = Some(now);
+        self.outcome = None;
+        self.next_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(300);
+
+    fn scheduler() -> (IdScheduler, Instant) {
+        (IdScheduler::new(Some(INTERVAL)), Instant::now())
+    }
+
+    #[test]
+    fn manual_supersedes_a_scheduled_run() {
+        let (mut ids, t0) = scheduler();
+        assert_eq!(ids.scheduled(t0), Decision::Start(1));
+        assert_eq!(ids.manual(t0 + Duration::from_secs(5)), Decision::Supersede { cancel: 1, start: 2 });
+        assert_eq!(ids.running(), Some(Trigger::Manual));
+        // The superseded run reporting late is dropped
+        assert!(!ids.finished(1));
+        assert!(ids.finished(2));
+        assert_eq!(ids.running(), None);
+    }
+
+    #[test]
+    fn scheduled_run_never_interrupts() {
+        let (mut ids, t0) = scheduler();
+        assert_eq!(ids.manual(t0), Decision::Start(1));
+        assert_eq!(ids.scheduled(t0 + INTERVAL), Decision::Ignore);
+        assert_eq!(ids.manual(t0 + INTERVAL), Decision::Ignore);
+        assert_eq!(ids.running(), Some(Trigger::Manual));
+    }
+
+    #[test]
+    fn manual_run_restarts_the_interval() {
+        let (mut ids, t0) = scheduler();
+        assert!(ids.is_due(t0));
+        ids.scheduled(t0);
+        ids.finished(1);
+        let manual_at = t0 + Duration::from_secs(200);
+        ids.manual(manual_at);
+        ids.finished(2);
+        assert!(!ids.is_due(t0 + INTERVAL));
+        assert_eq!(ids.next_in(t0 + INTERVAL), Some(Duration::from_secs(200)));
+        assert!(ids.is_due(manual_at + INTERVAL));
+    }
+
+    #[test]
+    fn at_most_one_run_in_flight() {
+        let (mut ids, t0) = scheduler();
+        let mut in_flight = Vec::new();
+        let requests = [true, false, true, true, false, false, true];
+        for (i, manual) in requests.into_iter().enumerate() {
+            let now = t0 + Duration::from_secs(i as u64);
+            let decision = if manual { ids.manual(now) } else { ids.scheduled(now) };
+            match decision {
+                Decision::Start(id) => in_flight.push(id),
+                Decision::Supersede { cancel, start } => {
+                    in_flight.retain(|&id| id != cancel);
+                    in_flight.push(start);
+                }
+                Decision::Ignore => {}
+            }
+            assert!(in_flight.len() <= 1, "{:?} in flight", in_flight);
+        }
+    }
+
+    #[test]
+    fn cancelling_drops_a_scheduled_result_only() {
+        let (mut ids, t0) = scheduler();
+        ids.scheduled(t0);
+        assert_eq!(ids.cancel_scheduled(), Some(1));
+        assert!(!ids.finished(1));
+        assert_eq!(ids.manual(t0), Decision::Start(2));
+        assert_eq!(ids.cancel_scheduled(), None);
+        assert!(ids.finished(2));
+
+        // Unless it is cancelled outright, which drops a manual result too
+        assert_eq!(ids.manual(t0), Decision::Start(3));
+        assert_eq!(ids.cancel_running(), Some(3));
+        assert!(!ids.finished(3));
+        assert_eq!(ids.cancel_running(), None);
+    }
+
+    #[test]
+    fn indicator_spins_while_running_then_flashes_the_outcome() {
+        let (mut ids, t0) = scheduler();
+        assert_eq!(ids.indicator(t0), None);
+        ids.manual(t0);
+        assert_eq!(ids.indicator(t0), Some(Indicator::Running("⠋")));
+        assert_eq!(ids.indicator(t0 + Duration::from_secs(1)), Some(Indicator::Running("⠙")));
+        ids.finished(1);
+        let done = t0 + Duration::from_secs(12);
+        ids.report(false, done);
+        assert_eq!(ids.indicator(done).map(Indicator::label), Some("✗ ID".to_string()));
+        assert_eq!(ids.indicator(done + FLASH), None);
+
+        // A new run clears the last outcome
+        ids.report(true, done);
+        ids.scheduled(done + Duration::from_secs(1));
+        assert!(matches!(ids.indicator(done + Duration::from_secs(1)), Some(Indicator::Running(_))));
+    }
+}
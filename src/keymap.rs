@@ -0,0 +1,243 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// What a key does. `handle_key_press` looks keys up here and matches on
+/// the action, so the help overlay lists exactly what the keys do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Play,
+    TuneIn(usize),
+    /// Selects the mixtape listed with number 3 and up; the first is 0.
+    SelectMixtape(usize),
+    Pause,
+    Stop,
+    VolumeUp,
+    VolumeDown,
+    Output,
+    Resampler,
+    PlayUrl,
+    RetryStreams,
+    Enqueue,
+    LongerSlot,
+    ShorterSlot,
+    Rotate,
+    ClearQueue,
+    Follow,
+    Archive,
+    SelectNext,
+    SelectPrevious,
+    Filter,
+    SwitchFocus,
+    DeleteHistory,
+    HistoryDown,
+    HistoryUp,
+    DescriptionDown,
+    DescriptionUp,
+    NextDay,
+    PreviousDay,
+    JumpToDate,
+    Recognize,
+    RecognizePast,
+    LongerSample,
+    ShorterSample,
+    RevealSample,
+    CopyTrack,
+    Incognito,
+    Digest,
+    Export,
+    ShareSession,
+    Diagnostics,
+    About,
+    CopyAbout,
+    Logs,
+    Debug,
+    Help,
+    Redraw,
+    Close,
+    Quit,
+}
+
+/// The headings the help overlay groups keys under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Group {
+    Playback,
+    Navigation,
+    Recognition,
+    Other,
+}
+
+impl Group {
+    pub const ALL: [Group; 4] = [Group::Playback, Group::Navigation, Group::Recognition, Group::Other];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Group::Playback => "Playback",
+            Group::Navigation => "Navigation",
+            Group::Recognition => "Recognition",
+            Group::Other => "Other",
+        }
+    }
+}
+
+/// A key, alone or held with Ctrl. Shift only changes the character, so it
+/// isn't told apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub ctrl: bool,
+}
+
+impl Key {
+    fn matches(&self, event: &KeyEvent) -> bool {
+        self.code == event.code && self.ctrl == event.modifiers.contains(KeyModifiers::CONTROL)
+    }
+}
+
+const fn key(code: KeyCode) -> Key {
+    Key { code, ctrl: false }
+}
+
+const fn ctrl(c: char) -> Key {
+    Key {
+        code: KeyCode::Char(c),
+        ctrl: true,
+    }
+}
+
+/// One line of help: the keys as written there and what each one does.
+pub struct Binding {
+    pub label: &'static str,
+    pub description: &'static str,
+    pub group: Group,
+    pub keys: &'static [(Key, Action)],
+    /// The short form listed in the Controls pane, for the keys worth
+    /// having in sight.
+    pub controls: Option<&'static str>,
+}
+
+const fn binding(
+    label: &'static str,
+    description: &'static str,
+    group: Group,
+    keys: &'static [(Key, Action)],
+) -> Binding {
+    Binding {
+        label,
+        description,
+        group,
+        keys,
+        controls: None,
+    }
+}
+
+const fn essential(binding: Binding, controls: &'static str) -> Binding {
+    Binding {
+        controls: Some(controls),
+        ..binding
+    }
+}
+
+use Action::*;
+use Group::*;
+use KeyCode::Char;
+
+pub const BINDINGS: &[Binding] = &[
+    essential(binding("Enter", "Play the selected stream (expands repeats in the history)", Playback, &[(key(KeyCode::Enter), Play)]), "Play"),
+    essential(binding("1/2", "Tune in to NTS 1 or 2", Playback, &[(key(Char('1')), TuneIn(0)), (key(Char('2')), TuneIn(1))]), "Tune in"),
+    essential(binding("p", "Pause or resume", Playback, &[(key(Char('p')), Pause)]), "Pause"),
+    essential(binding("Space", "Stop", Playback, &[(key(Char(' ')), Stop)]), "Stop"),
+    essential(binding("</>", "Volume down/up", Playback, &[(key(Char('<')), VolumeDown), (key(Char('>')), VolumeUp)]), "Volume"),
+    binding("o", "Switch between the output device and the pipe", Playback, &[(key(Char('o')), Output)]),
+    binding("H", "Cycle the resampler", Playback, &[(key(Char('H')), Resampler)]),
+    binding("U", "Play a URL", Playback, &[(key(Char('U')), PlayUrl)]),
+    binding("u", "Retry loading the streams", Playback, &[(key(Char('u')), RetryStreams)]),
+    binding("Q", "Add the selected stream to the queue", Playback, &[(key(Char('Q')), Enqueue)]),
+    binding("+/_", "Lengthen or shorten the last queue slot", Playback, &[(key(Char('+')), LongerSlot), (key(Char('_')), ShorterSlot)]),
+    binding("N", "Rotate to the next queued stream", Playback, &[(key(Char('N')), Rotate)]),
+    binding("X", "Clear the queue", Playback, &[(key(Char('X')), ClearQueue)]),
+    binding("f", "Follow or unfollow the show, or favorite the mixtape", Playback, &[(key(Char('f')), Follow)]),
+    binding("e", "Browse the show's archive (/ searches, Enter plays)", Playback, &[(key(Char('e')), Archive)]),
+    binding("Up/Down", "Select a stream", Navigation, &[(key(KeyCode::Up), SelectPrevious), (key(KeyCode::Down), SelectNext)]),
+    binding(
+        "3-9",
+        "Select the mixtape with that number (Enter plays)",
+        Navigation,
+        &[
+            (key(Char('3')), SelectMixtape(0)),
+            (key(Char('4')), SelectMixtape(1)),
+            (key(Char('5')), SelectMixtape(2)),
+            (key(Char('6')), SelectMixtape(3)),
+            (key(Char('7')), SelectMixtape(4)),
+            (key(Char('8')), SelectMixtape(5)),
+            (key(Char('9')), SelectMixtape(6)),
+        ],
+    ),
+    binding("/", "Filter the mixtapes", Navigation, &[(key(Char('/')), Filter)]),
+    binding("Tab", "Focus the history", Navigation, &[(key(KeyCode::Tab), SwitchFocus)]),
+    binding("Del", "Delete the history entry (history focused)", Navigation, &[(key(KeyCode::Delete), DeleteHistory)]),
+    binding("j/k", "Scroll recognized tracks", Navigation, &[(key(Char('j')), HistoryDown), (key(Char('k')), HistoryUp)]),
+    binding("J/K", "Scroll the description", Navigation, &[(key(Char('J')), DescriptionDown), (key(Char('K')), DescriptionUp)]),
+    binding("[/]", "Previous/next day in the history", Navigation, &[(key(Char('[')), PreviousDay), (key(Char(']')), NextDay)]),
+    binding(":", "Jump to a date in the history", Navigation, &[(key(Char(':')), JumpToDate)]),
+    essential(binding("r", "Recognise what is playing (again to cancel)", Recognition, &[(key(Char('r')), Recognize)]), "Recognise"),
+    binding("R", "Recognise what played ~30s ago", Recognition, &[(key(Char('R')), RecognizePast)]),
+    binding("=/-", "Longer or shorter samples", Recognition, &[(key(Char('=')), LongerSample), (key(Char('-')), ShorterSample)]),
+    binding("S", "Reveal the last saved sample", Recognition, &[(key(Char('S')), RevealSample)]),
+    binding("y", "Copy the last recognized track", Recognition, &[(key(Char('y')), CopyTrack)]),
+    binding("i", "Incognito: keep recognitions out of the history", Recognition, &[(key(Char('i')), Incognito)]),
+    binding("D", "Weekly digest", Other, &[(key(Char('D')), Digest)]),
+    binding("x", "Export the history as CSV or JSON", Other, &[(key(Char('x')), Export)]),
+    binding("E", "Copy and save this session's tracks", Other, &[(key(Char('E')), ShareSession)]),
+    binding("A", "Audio diagnostics", Other, &[(key(Char('A')), Diagnostics)]),
+    binding("a", "About", Other, &[(key(Char('a')), About)]),
+    binding("c", "Copy the report (About screen)", Other, &[(key(Char('c')), CopyAbout)]),
+    binding("l", "Logs", Other, &[(key(Char('l')), Logs)]),
+    binding("F12", "Debug overlay, and stream details in the Description", Other, &[(key(KeyCode::F(12)), Debug)]),
+    binding("Ctrl+L", "Redraw the screen", Other, &[(ctrl('l'), Redraw)]),
+    binding("Esc", "Close a popup, or cancel connecting or recognising", Other, &[(key(KeyCode::Esc), Close)]),
+    essential(binding("?", "This help", Other, &[(key(Char('?')), Help)]), "Help"),
+    essential(binding("q", "Quit", Other, &[(key(Char('q')), Quit)]), "Quit"),
+];
+
+/// The bindings the help lists: all of them, less the recognition keys in
+/// builds without it, which only say so.
+pub fn listed() -> impl Iterator<Item = &'static Binding> {
+    BINDINGS
+        .iter()
+        .filter(|binding| binding.group != Recognition || cfg!(feature = "recognition"))
+}
+
+pub fn action_for(event: &KeyEvent) -> Option<Action> {
+    BINDINGS
+        .iter()
+        .flat_map(|binding| binding.keys)
+        .find(|(key, _)| key.matches(event))
+        .map(|(_, action)| *action)
+}
+
+/// "Enter: Play | 1/2: Tune in | …" for the Controls pane.
+pub fn controls_line() -> String {
+    listed()
+        .filter_map(|binding| Some(format!("{}: {}", binding.label, binding.controls?)))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_does_one_thing() {
+        let keys: Vec<Key> = BINDINGS.iter().flat_map(|binding| binding.keys).map(|(key, _)| *key).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert!(!keys[i + 1..].contains(key), "{:?} is bound twice", key);
+        }
+        assert_eq!(action_for(&KeyEvent::from(Char('2'))), Some(TuneIn(1)));
+        assert_eq!(action_for(&KeyEvent::from(Char('9'))), Some(SelectMixtape(6)));
+        assert_eq!(action_for(&KeyEvent::from(Char('Z'))), None);
+        assert_eq!(action_for(&KeyEvent::new(Char('l'), KeyModifiers::CONTROL)), Some(Redraw));
+        assert_eq!(action_for(&KeyEvent::new(Char('L'), KeyModifiers::SHIFT)), None);
+        assert_eq!(action_for(&KeyEvent::from(Char('l'))), Some(Logs));
+        assert!(BINDINGS.iter().all(|binding| Group::ALL.contains(&binding.group)));
+    }
+}
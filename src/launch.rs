@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+use crate::{config::TimestampStyle, events::EventFormat, StreamRef, StreamType, StreamsCollection};
+
+pub const USAGE: &str = "Usage: nts_cli [--plain | --headless [--recognize-every <seconds>]] [--events json] \
+[--station <1|2> | --mixtape <title>] [--volume <0.0-1.0>] [--duration <seconds>] [--timestamps <local|iso8601>] [--history-file <path>]";
+
+/// What to play at startup, picked on the command line.
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    /// A live channel, numbered from 1.
+    Station(usize),
+    Mixtape(String),
+}
+
+/// Options for starting the TUI or the plain mode, as opposed to the
+/// subcommands.
+#[derive(Debug, Default, PartialEq)]
+pub struct LaunchArgs {
+    pub plain: bool,
+    /// Play without the TUI, printing status lines. Needs a target.
+    pub headless: bool,
+    /// Seconds between recognitions in the headless mode.
+    pub recognize_every: Option<u64>,
+    pub target: Option<Target>,
+    pub volume: Option<f32>,
+    /// Recognition sample length in seconds.
+    pub duration: Option<u64>,
+    /// How the plain and headless modes report what happens.
+    pub events: EventFormat,
+    /// Overrides `history_timestamps` in the config.
+    pub timestamps: Option<TimestampStyle>,
+    /// Overrides `history_path` in the config.
+    pub history_file: Option<PathBuf>,
+}
+
+impl LaunchArgs {
+    pub fn parse(args: &[&str]) -> Result<LaunchArgs, String> {
+        let mut launch = LaunchArgs::default();
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            let mut value = || args.next().copied().ok_or(format!("{} needs a value", arg));
+            match arg {
+                "--plain" => launch.plain = true,
+                "--headless" => launch.headless = true,
+                "--recognize-every" => {
+                    let value = value()?;
+                    let seconds = value
+                        .parse()
+                        .ok()
+                        .filter(|&seconds| seconds >= 1)
+                        .ok_or(format!("Not a number of seconds: {}", value))?;
+                    launch.recognize_every = Some(seconds);
+                }
+                "--station" | "--mixtape" if launch.target.is_some() => {
+                    return Err("Give one of --station and --mixtape".to_string())
+                }
+                "--station" => {
+                    let value = value()?;
+                    let number = value
+                        .parse()
+                        .ok()
+                        .filter(|&number| number >= 1)
+                        .ok_or(format!("Not a station number: {}", value))?;
+                    launch.target = Some(Target::Station(number));
+                }
+                "--mixtape" => launch.target = Some(Target::Mixtape(value()?.to_string())),
+                "--events" => launch.events = EventFormat::parse(value()?)?,
+                "--timestamps" => launch.timestamps = Some(TimestampStyle::parse(value()?)?),
+                "--history-file" => launch.history_file = Some(PathBuf::from(value()?)),
+                "--volume" => {
+                    let value = value()?;
+                    let volume = value
+                        .parse()
+                        .ok()
+                        .filter(|volume| (0.0..=1.0).contains(volume))
+                        .ok_or(format!("Volume goes from 0.0 to 1.0, not {}", value))?;
+                    launch.volume = Some(volume);
+                }
+                "--duration" => {
+                    let value = value()?;
+                    let seconds = value
+                        .parse()
+                        .ok()
+                        .filter(|&seconds| seconds >= 1)
+                        .ok_or(format!("Not a number of seconds: {}", value))?;
+                    launch.duration = Some(seconds);
+                }
+                other => return Err(format!("Unknown option {}", other)),
+            }
+        }
+        if launch.headless && launch.plain {
+            return Err("Give one of --plain and --headless".to_string());
+        }
+        if launch.headless && launch.target.is_none() {
+            return Err("--headless needs --station or --mixtape".to_string());
+        }
+        if launch.recognize_every.is_some() && !launch.headless {
+            return Err("--recognize-every only applies to --headless".to_string());
+        }
+        if launch.recognize_every.is_some() && !cfg!(feature = "recognition") {
+            return Err("--recognize-every needs a build with the recognition feature".to_string());
+        }
+        if launch.events != EventFormat::Text && !launch.plain && !launch.headless {
+            return Err("--events only applies to --plain and --headless".to_string());
+        }
+        Ok(launch)
+    }
+}
+
+/// Finds the stream `target` names. A mixtape title matches regardless of
+/// case, in full or, failing that, as the only title containing it. When
+/// nothing matches, the error lists what there is.
+pub fn resolve(collection: &StreamsCollection, target: &Target) -> Result<StreamRef, String> {
+    let (section, streams) = match target {
+        Target::Station(_) => (StreamType::Station, &collection.stations),
+        Target::Mixtape(_) => (StreamType::Mixtape, &collection.mixtapes),
+    };
+    let found = match target {
+        Target::Station(number) => streams.get(number - 1),
+        Target::Mixtape(title) => {
+            let needle = title.to_lowercase();
+            let containing: Vec<_> = streams
+                .iter()
+                .filter(|stream| stream.title.to_lowercase().contains(&needle))
+                .collect();
+            streams
+                .iter()
+                .find(|stream| stream.title.to_lowercase() == needle)
+                .or(match containing.as_slice() {
+                    [only] => Some(*only),
+                    _ => None,
+                })
+        }
+    };
+    match found {
+        Some(stream) => Ok(StreamRef {
+            section,
+            id: stream.id.clone(),
+        }),
+        None => {
+            let wanted = match target {
+                Target::Station(number) => format!("No station {}", number),
+                Target::Mixtape(title) => format!("No mixtape matches \"{}\"", title),
+            };
+            let available: Vec<String> = streams
+                .iter()
+                .enumerate()
+                .map(|(i, stream)| match section {
+                    StreamType::Station => format!("  {}. {}", i + 1, stream.title),
+                    _ => format!("  {}", stream.title),
+                })
+                .collect();
+            Err(format!("{}. Available:\n{}", wanted, available.join("\n")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Stream;
+
+    fn stream(id: &str, title: &str) -> Stream {
+        Stream {
+            id: id.to_string(),
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn collection() -> StreamsCollection {
+        StreamsCollection {
+            stations: vec![stream("1", "Live on 1"), stream("2", "Live on 2")],
+            mixtapes: vec![
+                stream("slow-focus", "Slow Focus"),
+                stream("poolside", "Poolside"),
+                stream("poolside-2", "Poolside Nights"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_launch_options() {
+        assert_eq!(
+            LaunchArgs::parse(&["--mixtape", "slow focus", "--volume", "0.3", "--duration", "10"]).unwrap(),
+            LaunchArgs {
+                target: Some(Target::Mixtape("slow focus".to_string())),
+                volume: Some(0.3),
+                duration: Some(10),
+                ..Default::default()
+            }
+        );
+        let headless = LaunchArgs::parse(&["--headless", "--station", "2", "--recognize-every", "300"]);
+        if cfg!(feature = "recognition") {
+            let headless = headless.unwrap();
+            assert_eq!((headless.target, headless.recognize_every), (Some(Target::Station(2)), Some(300)));
+        } else {
+            assert!(headless.is_err());
+        }
+        assert!(LaunchArgs::parse(&["--headless"]).is_err());
+        assert!(LaunchArgs::parse(&["--recognize-every", "300"]).is_err());
+        assert_eq!(LaunchArgs::parse(&["--plain", "--events", "json"]).unwrap().events, EventFormat::Json);
+        assert!(LaunchArgs::parse(&["--events", "json"]).is_err());
+        assert_eq!(LaunchArgs::parse(&["--timestamps", "iso8601"]).unwrap().timestamps, Some(TimestampStyle::Iso8601));
+        assert!(LaunchArgs::parse(&["--timestamps", "unix"]).is_err());
+        assert_eq!(
+            LaunchArgs::parse(&["--plain", "--history-file", "/tmp/history.txt"]).unwrap().history_file,
+            Some(PathBuf::from("/tmp/history.txt"))
+        );
+        assert!(LaunchArgs::parse(&["--history-file"]).is_err());
+        assert!(LaunchArgs::parse(&["--station", "0"]).is_err());
+        assert!(LaunchArgs::parse(&["--volume", "3"]).is_err());
+        assert!(LaunchArgs::parse(&["--station", "1", "--mixtape", "x"]).is_err());
+        assert!(LaunchArgs::parse(&["--stattion", "1"]).is_err());
+    }
+
+    #[test]
+    fn matches_titles_regardless_of_case() {
+        let collection = collection();
+        let resolve = |target| resolve(&collection, &target).map(|stream_ref| stream_ref.id);
+        assert_eq!(resolve(Target::Station(2)), Ok("2".to_string()));
+        assert_eq!(resolve(Target::Mixtape("slow focus".to_string())), Ok("slow-focus".to_string()));
+        // An exact title wins over a longer one containing it
+        assert_eq!(resolve(Target::Mixtape("POOLSIDE".to_string())), Ok("poolside".to_string()));
+        assert_eq!(resolve(Target::Mixtape("nights".to_string())), Ok("poolside-2".to_string()));
+        let err = resolve(Target::Mixtape("side".to_string())).unwrap_err();
+        assert!(err.starts_with("No mixtape matches \"side\". Available:\n  Slow Focus\n"), "{}", err);
+        assert!(resolve(Target::Station(3)).unwrap_err().contains("  2. Live on 2"));
+    }
+}
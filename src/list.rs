@@ -0,0 +1,110 @@
+use serde::Serialize;
+
+use crate::{Stream, StreamsCollection};
+
+pub const LIST_USAGE: &str = "Usage: nts_cli list [--plain] [--stations | --mixtapes]";
+
+/// Arguments of `nts_cli list`. Neither group flag means both groups.
+#[derive(Debug, Default, PartialEq)]
+pub struct ListArgs {
+    pub plain: bool,
+    pub stations: bool,
+    pub mixtapes: bool,
+}
+
+impl ListArgs {
+    pub fn parse(args: &[&str]) -> Result<ListArgs, String> {
+        let mut list = ListArgs::default();
+        for &arg in args {
+            match arg {
+                "--plain" => list.plain = true,
+                "--stations" => list.stations = true,
+                "--mixtapes" => list.mixtapes = true,
+                other => return Err(format!("Unknown option {}", other)),
+            }
+        }
+        if !list.stations && !list.mixtapes {
+            list.stations = true;
+            list.mixtapes = true;
+        }
+        Ok(list)
+    }
+}
+
+/// The groups asked for, leaving the others out rather than empty.
+#[derive(Serialize)]
+struct Listing<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stations: Option<&'a [Stream]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mixtapes: Option<&'a [Stream]>,
+}
+
+/// The listing as pretty JSON, or with `--plain` one title per line.
+pub fn render(collection: &StreamsCollection, list: &ListArgs) -> String {
+    let listing = Listing {
+        stations: list.stations.then_some(collection.stations.as_slice()),
+        mixtapes: list.mixtapes.then_some(collection.mixtapes.as_slice()),
+    };
+    if list.plain {
+        let streams = listing.stations.into_iter().chain(listing.mixtapes).flatten();
+        return streams.map(|stream| format!("{}\n", stream.title)).collect();
+    }
+    serde_json::to_string_pretty(&listing).expect("streams serialize") + "\n"
+}
+
+/// `nts_cli list`: prints the streams for scripts without starting the TUI.
+/// Returns false if they could not be fetched or read from the cache.
+pub fn run(list: &ListArgs) -> bool {
+    match StreamsCollection::populate_collection() {
+        Ok(collection) => {
+            print!("{}", render(&collection, list));
+            true
+        }
+        Err(err) => {
+            eprintln!("error: Could not load streams: {}", err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection() -> StreamsCollection {
+        let stream = |title: &str, url: &str| Stream {
+            id: title.to_lowercase(),
+            title: title.to_string(),
+            subtitle: "Sub".to_string(),
+            audio_stream_endpoint: url.to_string(),
+            ..Default::default()
+        };
+        StreamsCollection {
+            stations: vec![stream("NTS Live 1", "https://example.com/1")],
+            mixtapes: vec![stream("Poolside", "https://example.com/poolside"), stream("Slow Focus", "")],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lists_the_groups_asked_for() {
+        let both = ListArgs::parse(&["--plain"]).unwrap();
+        assert_eq!(render(&collection(), &both), "NTS Live 1\nPoolside\nSlow Focus\n");
+
+        let mixtapes = ListArgs::parse(&["--mixtapes"]).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&render(&collection(), &mixtapes)).unwrap();
+        assert!(json.get("stations").is_none());
+        assert_eq!(
+            json["mixtapes"][0],
+            serde_json::json!({
+                "id": "poolside",
+                "title": "Poolside",
+                "subtitle": "Sub",
+                "description": "",
+                "audio_stream_endpoint": "https://example.com/poolside",
+            })
+        );
+        assert!(ListArgs::parse(&["--json"]).is_err());
+    }
+}
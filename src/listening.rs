@@ -0,0 +1,61 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+
+use crate::{migrate, shutdown};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// One stretch of listening to a stream, in local time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub title: String,
+}
+
+pub fn log_file_path() -> PathBuf {
+    migrate::LISTENING_LOG.path()
+}
+
+/// Appends a session to the log at `path` as `start<TAB>end<TAB>title`.
+pub fn append(path: &Path, title: &str, start: DateTime<Local>, end: DateTime<Local>) -> io::Result<()> {
+    let _critical = shutdown::critical();
+    let line = format!(
+        "{}\t{}\t{}\n",
+        start.format(TIMESTAMP_FORMAT),
+        end.format(TIMESTAMP_FORMAT),
+        title.replace(['\t', '\n', '\r'], " ")
+    );
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+/// The whole log, or nothing if there is none yet.
+pub fn read() -> String {
+    fs::read_to_string(log_file_path()).unwrap_or_default()
+}
+
+/// Parses the log, skipping lines it can't make sense of.
+pub fn parse(contents: &str) -> Vec<Session> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let start = NaiveDateTime::parse_from_str(fields.next()?, TIMESTAMP_FORMAT).ok()?;
+            let end = NaiveDateTime::parse_from_str(fields.next()?, TIMESTAMP_FORMAT).ok()?;
+            let title = fields.next()?.to_string();
+            (end >= start).then_some(Session { start, end, title })
+        })
+        .collect()
+}
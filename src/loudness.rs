@@ -0,0 +1,125 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::LoudnessConfig;
+
+const LOUDNESS_FILE_NAME: &str = "nts_cli/loudness.json";
+/// How much of a stream is measured after it starts.
+pub const MEASURE_FOR: Duration = Duration::from_secs(60);
+/// The most a learned correction moves the gain either way.
+const MAX_CORRECTION_DB: f64 = 6.0;
+/// How far a new measurement moves a stream's estimate, so one loud show
+/// on a station doesn't undo what was learned before.
+const LEARNING_RATE: f64 = 0.25;
+/// Measurements quieter than this are of silence, not of the stream.
+const SILENCE_DBFS: f64 = -60.0;
+
+/// The loudness learned for each stream, by title, in dBFS.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Loudness {
+    pub streams: BTreeMap<String, f64>,
+}
+
+impl Loudness {
+    /// Folds a measurement into the estimate for `title`. Returns false when
+    /// it was of silence and ignored.
+    pub fn learn(&mut self, title: &str, dbfs: f64) -> bool {
+        if dbfs < SILENCE_DBFS {
+            return false;
+        }
+        let estimate = self.streams.get(title).map_or(dbfs, |old| old + LEARNING_RATE * (dbfs - old));
+        self.streams.insert(title.to_string(), estimate);
+        true
+    }
+
+    /// The gain in dB to play `title` at: the configured offset if there is
+    /// one, otherwise the learned correction towards the target.
+    pub fn correction_db(&self, title: &str, config: &LoudnessConfig) -> f64 {
+        if let Some((_, offset)) = config.offsets.iter().find(|(name, _)| name.eq_ignore_ascii_case(title)) {
+            return *offset;
+        }
+        if !config.normalize {
+            return 0.0;
+        }
+        self.streams.get(title).map_or(0.0, |estimate| {
+            (config.target_dbfs - estimate).clamp(-MAX_CORRECTION_DB, MAX_CORRECTION_DB)
+        })
+    }
+}
+
+/// `db` as a linear gain factor.
+pub fn gain(db: f64) -> f32 {
+    10f64.powf(db / 20.0) as f32
+}
+
+/// The RMS level of `samples` in dBFS, or None for no samples.
+pub fn rms_dbfs(samples: &[i16]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let sum_of_squares: f64 = samples
+        .iter()
+        .map(|&sample| {
+            let sample = sample as f64 / i16::MAX as f64;
+            sample * sample
+        })
+        .sum();
+    let rms = (sum_of_squares / samples.len() as f64).sqrt();
+    Some(20.0 * rms.max(1e-9).log10())
+}
+
+fn loudness_file_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(LOUDNESS_FILE_NAME))
+}
+
+pub fn load() -> Loudness {
+    loudness_file_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(loudness: &Loudness) -> io::Result<()> {
+    let path = loudness_file_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(loudness)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrects_towards_the_target_within_bounds() {
+        let config = LoudnessConfig {
+            offsets: BTreeMap::from([("poolside".to_string(), -2.0)]),
+            ..Default::default()
+        };
+        let mut loudness = Loudness::default();
+        assert_eq!(loudness.correction_db("NTS Live 1", &config), 0.0);
+
+        loudness.learn("NTS Live 1", -24.0);
+        assert_eq!(loudness.correction_db("NTS Live 1", &config), 4.0);
+        // One loud session only moves the estimate part of the way
+        loudness.learn("NTS Live 1", -8.0);
+        assert_eq!(loudness.streams["NTS Live 1"], -20.0);
+        assert!(!loudness.learn("NTS Live 1", -90.0));
+
+        loudness.learn("Slow Focus", -40.0);
+        assert_eq!(loudness.correction_db("Slow Focus", &config), MAX_CORRECTION_DB);
+        loudness.learn("Poolside", -5.0);
+        assert_eq!(loudness.correction_db("Poolside", &config), -2.0);
+        let off = LoudnessConfig {
+            normalize: false,
+            ..config
+        };
+        assert_eq!(loudness.correction_db("Slow Focus", &off), 0.0);
+
+        assert_eq!(rms_dbfs(&[i16::MAX, -i16::MAX]), Some(0.0));
+        assert!((gain(-6.0) - 0.501).abs() < 0.001);
+    }
+}
@@ -5,64 +5,401 @@
 // DEPENDENCIES
 //
 
+mod about;
+mod api;
+mod archive;
+mod audio_check;
+mod background;
+mod clipboard;
+mod clock;
+mod coalesce;
+mod config;
+mod custom_url;
+mod debug_log;
+mod digest;
+mod doctor;
+mod dry_run;
+mod endpoint_health;
+mod events;
+mod export;
+mod features;
+mod fetch_scheduler;
+mod follows;
+mod format;
+mod guard;
+mod headless;
+mod history;
+mod history_store;
+mod http;
+mod id_schedule;
+mod keymap;
+mod launch;
+mod list;
+mod listening;
+mod loudness;
+mod maintenance;
+mod migrate;
 mod mp3_decoder;
+mod mp3_frames;
+mod mp3_tap;
+mod network;
+mod notify;
+mod pcm_tap;
+#[cfg(feature = "recognition")]
+mod pending_ids;
+mod pipe_output;
+mod plain;
+mod play_intent;
+mod player;
+mod presence;
+mod queue;
+#[cfg(feature = "recognition")]
+mod recognition;
+#[cfg(not(feature = "recognition"))]
+#[path = "recognition_unavailable.rs"]
+mod recognition;
+mod recognition_attempt;
+mod recognition_metrics;
+mod reconnect;
+mod refresh;
+mod resample;
+mod samples;
+#[cfg(feature = "shazam")]
+mod shazam;
+mod share;
+mod shutdown;
+mod state;
+mod terminal;
+mod tls;
+mod track_change;
+mod view_state;
+#[cfg(test)]
+mod ui_snapshots;
 
+use audio_check::{Outcome, Stage};
+use coalesce::{Coalescer, Turn};
+use chrono::{DateTime, Local};
+use config::{AutoId, Background, ColorsConfig, Config, OutputMode, ResampleQuality};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent},
+    event::{self, EnableMouseCapture, Event, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind},
     execute,
-    terminal::{disable_raw_mode, LeaveAlternateScreen},
 };
-use mp3_decoder::Mp3StreamDecoder;
+use debug_log::DebugLog;
+use guard::{Guard, GuardedAction};
+use history::{HistoryIndex, HistoryRow};
+use history_store::HistoryStore;
+use id_schedule::{Decision, IdScheduler, Indicator, Trigger};
+use keymap::Action;
+use loudness::Loudness;
+use play_intent::PlayIntents;
+use reconnect::Backoff;
+use player::{ConnectError, ConnectPhase, Player, PreparedStream};
+use presence::Presence;
+use queue::{QueueEntry, Rotation, DEFAULT_SLOT_MINUTES};
+use recognition_attempt::Attempt;
 use ratatui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    backend::Backend,
+    layout::{Alignment, Constraint, Direction, Layout, Position, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
     widgets::{
-        Block, Borders, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
-        Wrap,
+        Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Wrap,
     },
-    Terminal,
+    Frame, Terminal,
 };
-use reqwest::blocking::Client;
-use rodio::{OutputStream, Sink};
-use serde_json::Value;
-use std::io::Write;
+use recognition::{PendingMatch, RecognitionOutcome};
+use serde::{Deserialize, Serialize};
+use state::State;
+use track_change::TrackChangeDetector;
+use view_state::{View, ViewState, Views};
 use std::{
+    any::Any,
+    cell::Cell,
+    collections::{BTreeMap, HashSet, VecDeque},
     env,
-    fs::OpenOptions,
-    io::{self, BufReader, Read},
-    path::PathBuf,
-    process::Command,
-    sync::mpsc::{self, Receiver, Sender},
+    fs,
+    io,
+    net::IpAddr,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, OnceLock,
+    },
     thread,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime},
 };
-use tempfile::tempdir;
 
 //
 // CONSTANTS
 //
 
-const HISTORY_FILE_PATH: &str = "./nts_cli_song_history.txt";
+/// The history file in the platform data directory.
+const HISTORY_FILE_NAME: &str = "nts_cli/history.txt";
+/// Where the history was kept before it moved to the data directory, in the
+/// home directory.
+const LEGACY_HISTORY_FILE_NAME: &str = "nts_cli_song_history.txt";
 const STREAM_URL_1: &str = "https://stream-mixtape-geo.ntslive.net/stream";
 const STREAM_URL_2: &str = "https://stream-mixtape-geo.ntslive.net/stream2";
-const DEFAULT_DURATION_SEC: u64 = 5;
-const DEFAULT_VOLUME: f32 = 1.0;
 const RECOGNITION_INFO_TIMER: u64 = 12;
 const DURATION_INFO_TIMER: u64 = 1;
 const VOLUME_INFO_TIMER: u64 = 2;
+const ANNOUNCEMENT_BANNER_TIMER: u64 = 10;
+const TICK_INTERVAL_MS: u64 = 1000;
+/// The history pane is rebuilt at most this often while results pour in.
+const HISTORY_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+const LIGHT_TICK_INTERVAL_MS: u64 = 5000;
+const DECODE_LOAD_THRESHOLD: f32 = 0.5;
+const DECODE_LOAD_SUSTAIN_TICKS: u32 = 5;
+const MIN_TERMINAL_WIDTH: u16 = 40;
+const MIN_TERMINAL_HEIGHT: u16 = 12;
+const RECENT_ERRORS_CAPACITY: usize = 3;
+const STATUS_LOG_CAPACITY: usize = 100;
+/// How long quitting may wait silently before showing that it is busy.
+const SHUTDOWN_NOTICE_DELAY: Duration = Duration::from_millis(300);
+/// How soon a section that failed to fetch is tried again, instead of
+/// waiting for the hourly refresh.
+const SECTION_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// How many of the archive episodes played this session stay listed.
+const ARCHIVE_SECTION_CAPACITY: usize = 5;
+/// A pause longer than this resumes with a fresh connection: the server
+/// will have moved on or dropped the stalled one, and what's buffered is
+/// too old to pick up from.
+const STALE_PAUSE: Duration = Duration::from_secs(120);
+const COLOR_IDENTITY_GLYPH: &str = "■ ";
+const FAVORITE_MARKER: &str = "★";
+/// Mixtapes are numbered on from NTS 1 and 2, up to the last digit key.
+const FIRST_MIXTAPE_NUMBER: usize = 3;
+const MIXTAPE_PALETTE: [(Color, Color); 8] = [
+    (Color::Rgb(239, 71, 111), Color::LightRed),
+    (Color::Rgb(255, 166, 43), Color::LightYellow),
+    (Color::Rgb(255, 209, 102), Color::Yellow),
+    (Color::Rgb(6, 214, 160), Color::LightGreen),
+    (Color::Rgb(17, 138, 178), Color::LightBlue),
+    (Color::Rgb(131, 56, 236), Color::Magenta),
+    (Color::Rgb(255, 112, 166), Color::LightMagenta),
+    (Color::Rgb(76, 201, 240), Color::LightCyan),
+];
+
+/// Rows the banner across the top takes, borders included.
+const BANNER_HEIGHT: u16 = 3;
+
+/// Rows the idle screen leaves the selection's description before it makes
+/// room for the wordmark.
+const IDLE_DESCRIPTION_ROOM: usize = 4;
+
+const NTS_WORDMARK: [&str; 5] = [
+    " _   _ _____ ____  ",
+    "| \\ | |_   _/ ___| ",
+    "|  \\| | | | \\___ \\ ",
+    "| |\\  | | |  ___) |",
+    "|_| \\_| |_| |____/ ",
+];
+
+thread_local! {
+    static RENDERING: Cell<bool> = const { Cell::new(false) };
+}
 
 //
 // MAIN
 //
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["--reset-terminal"] => {
+            terminal::run_reset();
+            return Ok(());
+        }
+        ["--version"] => {
+            println!("{}", about::version_line());
+            return Ok(());
+        }
+        ["--version", "--verbose"] => {
+            for line in about::report(&[]) {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        ["doctor"] => {
+            doctor::run();
+            return Ok(());
+        }
+        ["doctor", "--audio"] => {
+            let config = Config::load().map(|checked| checked.config).unwrap_or_default();
+            let ok = audio_check::run(audio_check::endpoint(STREAM_URL_1, &config.network.stream_overrides));
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        ["config", "check"] => {
+            let ok = config::run_check();
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        ["state", "export"] => {
+            let ok = state::run_export();
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        ["state", "import", path] => {
+            let ok = state::run_import(path);
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        ["history", rest @ ..] => {
+            let ok = history_store::run(rest);
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        ["digest", rest @ ..] => {
+            let ok = digest::run(rest);
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        ["export", rest @ ..] => {
+            let ok = export::run(rest);
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        ["migrate", rest @ ..] => {
+            let ok = migrate::run(rest);
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        ["list", rest @ ..] => match list::ListArgs::parse(rest) {
+            Ok(list) => {
+                let ok = list::run(&list);
+                std::process::exit(if ok { 0 } else { 1 });
+            }
+            Err(err) => {
+                eprintln!("error: {}\n{}", err, list::LIST_USAGE);
+                std::process::exit(2);
+            }
+        },
+        ["play", rest @ ..] => match dry_run::PlayArgs::parse(rest) {
+            Ok(play) if play.dry_run => {
+                let ok = dry_run::check(&play);
+                std::process::exit(if ok { 0 } else { 1 });
+            }
+            Ok(play) => {
+                let config = Config::load().map(|checked| checked.config).unwrap_or_default();
+                return plain::run(config, Some(&play.stream), events::Reporter::default());
+            }
+            Err(err) => {
+                eprintln!("error: {}\n{}", err, dry_run::PLAY_USAGE);
+                std::process::exit(2);
+            }
+        },
+        _ => {}
+    }
+
+    let launch = match launch::LaunchArgs::parse(&args.iter().map(String::as_str).collect::<Vec<_>>()) {
+        Ok(launch) => launch,
+        Err(err) => {
+            eprintln!("error: {}\n{}", err, launch::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let (mut config, mut config_warnings) = match Config::load() {
+        Ok(checked) => (checked.config, checked.warnings),
+        Err(err) => (Config::default(), vec![err]),
+    };
+    if let Some(volume) = launch.volume {
+        config.playback.volume = volume;
+    }
+    fetch_scheduler::configure(config.network.fetch_budget());
+    if let Some(duration) = launch.duration {
+        config.recognition.duration_secs = duration;
+    }
+    if let Some(timestamps) = launch.timestamps {
+        config.history_timestamps = timestamps;
+    }
+    match launch.history_file {
+        Some(path) => set_history_file_path(path),
+        None => {
+            set_history_file_path(resolve_history_path(&config.history_path));
+            if config.history_path.as_os_str().is_empty() {
+                config_warnings.extend(history_store::migrate_legacy());
+            }
+        }
+    }
+
+    // Fetched before the TUI takes over the screen, so a stream asked for on
+    // the command line that doesn't exist can be reported and exited on
+    let fetched = (!launch.plain || launch.target.is_some())
+        .then(|| StreamsCollection::populate_collection().map_err(|err| err.to_string()));
+    let start = match (&launch.target, &fetched) {
+        (Some(target), Some(Ok(collection))) => match launch::resolve(collection, target) {
+            Ok(stream_ref) => Some(stream_ref),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+        },
+        (Some(_), Some(Err(err))) => {
+            eprintln!("error: Could not load streams: {}", err);
+            std::process::exit(1);
+        }
+        _ => None,
+    };
+
+    if launch.headless {
+        for warning in config_warnings {
+            eprintln!("warning: {}", warning);
+        }
+        let stream_ref = start.expect("--headless needs a stream");
+        let stream = fetched
+            .as_ref()
+            .and_then(|fetched| fetched.as_ref().ok())
+            .and_then(|collection| collection.resolve(&stream_ref))
+            .cloned()
+            .expect("resolved from this collection");
+        let recognize_every = launch.recognize_every.map(Duration::from_secs);
+        return headless::run(config, stream_ref.section, &stream, recognize_every, events::Reporter::new(launch.events));
+    }
+
+    if launch.plain {
+        for warning in config_warnings {
+            eprintln!("warning: {}", warning);
+        }
+        // The plain mode numbers stations, then mixtapes
+        let initial = start.zip(fetched.and_then(Result::ok)).and_then(|(stream_ref, collection)| {
+            plain::all_streams(&collection)
+                .iter()
+                .position(|(section, stream)| *section == stream_ref.section && stream.id == stream_ref.id)
+                .map(|i| (i + 1).to_string())
+        });
+        return plain::run(config, initial.as_deref(), events::Reporter::new(launch.events));
+    }
+
     let (ui_tx, ui_rx): (Sender<UIMessage>, Receiver<UIMessage>) = mpsc::channel();
     let ui_tx_clone = ui_tx.clone();
 
     let mut terminal = ratatui::init();
-    let mut radio = Radio::new(ui_tx_clone);
+    execute!(io::stdout(), EnableMouseCapture)?;
+    // Panics caught while rendering are reported in the status log instead.
+    // Any other panic resets the style before ratatui's hook leaves the
+    // alternate screen and prints it.
+    let ratatui_panic_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !RENDERING.with(Cell::get) {
+            terminal::restore();
+            ratatui_panic_hook(info);
+        }
+    }));
+    shutdown::install_signal_handlers();
+    let fetched = fetched.expect("streams are always fetched for the TUI");
+    let mut radio = Radio::new(ui_tx_clone, config, fetched);
+    if let Some(stream_ref) = start {
+        radio.views.streams.selection = Some(stream_ref);
+        radio.tune_in();
+    }
+    radio.refresh_followed_episodes();
+    for line in about::report(&[]) {
+        radio.log_status(line);
+    }
+    for warning in config_warnings {
+        radio.log_status(warning);
+    }
+    let moves = migrate::plan();
+    if !moves.is_empty() {
+        radio.migration = Some(moves);
+    }
 
     ui_tx.send(UIMessage::UpdateUI).unwrap();
 
@@ -70,36 +407,56 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     thread::spawn(move || loop {
         match event::read().unwrap() {
              Event::Key(key) => ui_tx.send(UIMessage::KeyPress(key)).unwrap(),
-             Event::Resize(_, _) => ui_tx.send(UIMessage::UpdateUI).unwrap(),
+             Event::Mouse(mouse) => ui_tx.send(UIMessage::Mouse(mouse)).unwrap(),
+             Event::Resize(width, height) => ui_tx.send(UIMessage::Resize(width, height)).unwrap(),
              _ => {}
          }
     });
 
+    if radio.config.network.watch_changes {
+        let ui_tx_network = ui_tx_clone.clone();
+        let interval = Duration::from_secs(radio.config.network.check_interval_secs.max(1));
+        network::watch(interval, move || ui_tx_network.send(UIMessage::NetworkChanged).is_ok());
+    }
+
+    let ui_tx_tick = ui_tx_clone.clone();
+    let tick_interval = radio.tick_interval.clone();
     thread::spawn(move || loop {
-        let duration = duration_until_next_hour();
-        thread::sleep(duration);
-        ui_tx_clone
-            .send(UIMessage::UpdateStreamsCollection)
-            .unwrap();
+        thread::sleep(Duration::from_millis(tick_interval.load(Ordering::Relaxed)));
+        if ui_tx_tick.send(UIMessage::Tick).is_err() {
+            break;
+        }
     });
 
-    loop {
-        match ui_rx.recv()? {
-            UIMessage::UpdateUI => radio.render_ui(&mut terminal)?,
-            UIMessage::KeyPress(key) => {
-                radio.handle_key_press(key)?;
-                radio.render_ui(&mut terminal)?
+    // The streams are fetched again just after the live shows end, so the
+    // schedule moves on with the broadcast, and at least hourly
+    let retention = radio.config.retention.clone();
+    let debug_log = radio.debug_log.clone();
+    let mut show_ends = refresh::show_ends(&radio.streams_collection);
+    thread::spawn(move || {
+        let mut last_maintenance: Option<Instant> = None;
+        let mut stale_retried = false;
+        loop {
+            let next = refresh::next(&show_ends, clock::now(), stale_retried);
+            stale_retried = next.stale_retry;
+            thread::sleep(next.wait);
+            if last_maintenance.is_none_or(|at| at.elapsed() >= maintenance::INTERVAL) {
+                maintenance::run(&retention, debug_log.as_ref());
+                last_maintenance = Some(Instant::now());
             }
-            UIMessage::RecognitionResult => {
-                radio.handle_recognition_result();
-                radio.render_ui(&mut terminal)?
-            }
-            UIMessage::UpdateStreamsCollection => {
-                radio.update_collection();
-                radio.render_ui(&mut terminal)?
+            let fetched = StreamsCollection::populate_collection().map_err(|err| err.to_string());
+            show_ends = fetched.as_ref().map(refresh::show_ends).unwrap_or_default();
+            if ui_tx_clone.send(UIMessage::StreamsFetched(fetched)).is_err() {
+                break;
             }
         }
-    }
+    });
+
+    // Errors leave the TUI the same way quitting and panics do, so they can
+    // be read afterwards
+    let result = radio.run(&mut terminal, &ui_rx);
+    terminal::restore();
+    result
 }
 
 //
@@ -108,496 +465,3800 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 // DEALING WITH STREAMS
 
-#[derive(Default, Clone, Debug)]
+/// Serializes to what `nts_cli list` prints.
+#[derive(Default, Clone, Debug, Serialize)]
 struct Stream {
+    id: String,
     title: String,
     subtitle: String,
     description: String,
     audio_stream_endpoint: String,
+    /// The show on air for stations, or the show an episode belongs to.
+    #[serde(skip)]
+    show_alias: String,
+    /// Shows coming up next on a station, empty for everything else.
+    #[serde(skip)]
+    schedule: Vec<UpcomingShow>,
+    /// When the show on air started, and ends if known. Stations only.
+    #[serde(skip)]
+    airtime: Option<Airtime>,
+}
+
+impl Stream {
+    /// Whether the title or subtitle contains `filter`, which is lowercase.
+    fn matches(&self, filter: &str) -> bool {
+        self.title.to_lowercase().contains(filter) || self.subtitle.to_lowercase().contains(filter)
+    }
 }
 
 #[derive(Clone, Debug)]
+struct UpcomingShow {
+    title: String,
+    start: DateTime<Local>,
+    end: Option<DateTime<Local>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Airtime {
+    start: DateTime<Local>,
+    end: Option<DateTime<Local>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 enum StreamType {
     Mixtape,
     Station,
+    /// The newest archive episode of a followed show.
+    Episode,
+    /// A stream URL entered by hand, which is also its id.
+    Custom,
+    /// An episode played from a show's archive, by episode alias.
+    Archive,
+}
+
+/// Identifies a stream by section and stable id, so selection survives
+/// refreshes that reorder or shrink the collection.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct StreamRef {
+    section: StreamType,
+    id: String,
 }
 
 #[derive(Default, Clone, Debug)]
 struct StreamsCollection {
     mixtapes: Vec<Stream>,
     stations: Vec<Stream>,
+    episodes: Vec<Stream>,
+    custom: Vec<Stream>,
+    /// Episodes played from the archive this session, newest first.
+    archive: Vec<Stream>,
+    /// Why the stations could not be fetched last time, if they couldn't.
+    /// The section then holds the cached copy, or nothing.
+    stations_error: Option<String>,
+    mixtapes_error: Option<String>,
 }
 
 impl StreamsCollection {
+    /// Fetches the stations and mixtapes, each falling back to the copy
+    /// cached by the last successful fetch when it can't be reached.
     fn populate_collection() -> Result<StreamsCollection, Box<dyn std::error::Error>> {
-        let mixtapes =
-            Self::fetch_streams("https://www.nts.live/api/v2/mixtapes", |item| Stream {
-                title: item["title"].as_str().unwrap_or_default().to_string(),
-                subtitle: item["subtitle"].as_str().unwrap_or_default().to_string(),
-                description: item["description"].as_str().unwrap_or_default().to_string(),
-                audio_stream_endpoint: item["audio_stream_endpoint"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string(),
-            })?;
-
-        let mut stations =
-            Self::fetch_streams("https://www.nts.live/api/v2/live", |item| Stream {
-                title: "NTS Live 1".to_string(),
-                subtitle: item["now"]["broadcast_title"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string(),
-                description: item["now"]["embeds"]["details"]["description"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string(),
-                audio_stream_endpoint: STREAM_URL_1.to_string(),
-            })?;
+        let fetched = api::fetch()?;
+        let mut collection = Self::from_responses(fetched.streams);
+        collection.stations_error = fetched.live_error;
+        collection.mixtapes_error = fetched.mixtapes_error;
+        Ok(collection)
+    }
+
+    /// Fetches one section again, caching it on success. Stations and
+    /// mixtapes only.
+    fn fetch_section(section: StreamType) -> Result<Vec<Stream>, String> {
+        let mut cache = api::load_cache().unwrap_or_default();
+        let streams = match section {
+            StreamType::Station => {
+                cache.live = api::fetch_live().map_err(|err| err.to_string())?;
+                Self::stations_from(cache.live.clone())
+            }
+            _ => {
+                cache.mixtapes = api::fetch_mixtapes().map_err(|err| err.to_string())?;
+                cache.mixtapes.results.iter().cloned().map(Stream::from).collect()
+            }
+        };
+        let _ = api::save_cache(&cache);
+        Ok(streams)
+    }
+
+    fn from_responses(responses: api::StreamsCache) -> StreamsCollection {
+        StreamsCollection {
+            mixtapes: responses.mixtapes.results.into_iter().map(Stream::from).collect(),
+            stations: Self::stations_from(responses.live),
+            ..Default::default()
+        }
+    }
 
+    fn stations_from(live: api::LiveResponse) -> Vec<Stream> {
+        let mut stations: Vec<Stream> = live.results.into_iter().map(Stream::from).collect();
         if let Some(second_station) = stations.get_mut(1) {
+            if second_station.id == "1" {
+                second_station.id = "2".to_string();
+            }
             second_station.title = "NTS Live 2".to_string();
             second_station.audio_stream_endpoint = STREAM_URL_2.to_string();
         }
+        stations
+    }
 
-        Ok(StreamsCollection { mixtapes, stations })
+    /// The error of a section that failed to fetch, for stations and
+    /// mixtapes.
+    fn section_error(&self, section: StreamType) -> Option<&str> {
+        match section {
+            StreamType::Station => self.stations_error.as_deref(),
+            StreamType::Mixtape => self.mixtapes_error.as_deref(),
+            _ => None,
+        }
     }
 
-   fn fetch_streams<F>(url: &str, parse_item: F) -> Result<Vec<Stream>, Box<dyn std::error::Error>>
-    where
-        F: Fn(&Value) -> Stream,
-    {
-        let client = Client::new();
-        let response = client.get(url).send()?.text()?;
+    /// Every stream in display order: stations, mixtapes with `favorites`
+    /// first, followed shows, custom URLs, then archive episodes.
+    fn view(&self, favorites: &[String]) -> Vec<StreamRef> {
+        let refs = |section: StreamType, streams: &[Stream]| {
+            streams
+                .iter()
+                .map(|stream| StreamRef {
+                    section,
+                    id: stream.id.clone(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut view = refs(StreamType::Station, &self.stations);
+        view.extend(self.listed_mixtapes(favorites).into_iter().map(|mixtape| StreamRef {
+            section: StreamType::Mixtape,
+            id: mixtape.id.clone(),
+        }));
+        view.extend(refs(StreamType::Episode, &self.episodes));
+        view.extend(refs(StreamType::Custom, &self.custom));
+        view.extend(refs(StreamType::Archive, &self.archive));
+        view
+    }
 
-        let json: Value = serde_json::from_str(&response)?;
-        let collection: Vec<Stream> = json["results"]
-            .as_array()
-            .unwrap_or(&Vec::new())
+    /// The mixtapes as listed: the ones titled as in `favorites` first, then
+    /// the rest, each in the API's order. Favorites no longer listed are left
+    /// out.
+    fn listed_mixtapes(&self, favorites: &[String]) -> Vec<&Stream> {
+        let (mut listed, rest): (Vec<&Stream>, Vec<&Stream>) = self
+            .mixtapes
             .iter()
-            .map(parse_item)
-            .collect();
+            .partition(|mixtape| is_favorite(favorites, mixtape));
+        listed.extend(rest);
+        listed
+    }
 
-        Ok(collection)
+    fn resolve(&self, stream_ref: &StreamRef) -> Option<&Stream> {
+        let streams = match stream_ref.section {
+            StreamType::Station => &self.stations,
+            StreamType::Mixtape => &self.mixtapes,
+            StreamType::Episode => &self.episodes,
+            StreamType::Custom => &self.custom,
+            StreamType::Archive => &self.archive,
+        };
+        streams.iter().find(|stream| stream.id == stream_ref.id)
+    }
+
+    fn live_summaries(&self) -> Vec<(String, String)> {
+        self.stations
+            .iter()
+            .map(|station| (station.title.clone(), station.subtitle.clone()))
+            .collect()
     }
 }
 
+fn is_favorite(favorites: &[String], mixtape: &Stream) -> bool {
+    favorites.contains(&mixtape.title)
+}
+
 // DEALING WITH THE UI AND EVENTS
 
 enum UIMessage {
     UpdateUI,
     KeyPress(KeyEvent),
+    Mouse(MouseEvent),
+    Resize(u16, u16),
     RecognitionResult,
-    UpdateStreamsCollection,
+    /// A fetch of the stations and mixtapes finished, hourly or on `u`.
+    StreamsFetched(Result<StreamsCollection, String>),
+    /// A retry of the stations or mixtapes alone, after they failed to fetch.
+    SectionFetched(StreamType, Result<Vec<Stream>, String>),
+    NetworkChanged,
+    Tick,
+    ConnectProgress(u64, ConnectPhase),
+    PlaybackReady(u64, Box<PreparedStream>),
+    PlaybackFailed(u64, String),
+    /// A mixtape's endpoint 404'd and a fresh listing had it elsewhere:
+    /// title, old endpoint, new endpoint.
+    MixtapeMoved(String, String, String),
+    FollowedEpisodes(Vec<(String, Result<follows::Episode, String>)>),
+    /// A stage of the audio self-test finished, for the run with this id.
+    AudioCheck(u64, Stage, Outcome),
+    /// The first minute of a stream measured this loud, in dBFS.
+    LoudnessMeasured(String, f64),
+    /// Queued recognitions were retried: what matched, and how many are
+    /// still pending.
+    PendingIdsRetried(Vec<PendingMatch>, usize),
+    /// A page of a show's archive, by show alias and offset.
+    ArchivePage(String, usize, Result<archive::Page, String>),
+    /// Roughly where the edge server at this address is, with `locate_edge`.
+    EdgeLocated(IpAddr, String),
+    /// The history reads asked for since the last one can happen now.
+    HistoryRefreshDue,
+}
+
+/// Whether the stream listing ever loaded. Once it has, failed refreshes
+/// keep the old one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StreamsLoad {
+    Loaded,
+    Failed,
+    Retrying,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PromptKind {
+    HistoryDate,
+    CustomUrl,
+    /// Narrows the mixtape list as it is typed.
+    StreamFilter,
+    /// Answered with a single y or n.
+    ResumeRotation,
+    /// The show whose archive to browse, as an alias or a link.
+    ArchiveShow,
+    /// Where to export the history; the extension picks CSV or JSON.
+    Export,
+}
+
+/// Where each pane goes on screen. Drawing and mouse hit-testing both
+/// work from this, so a click lands where the row was drawn.
+struct ScreenLayout {
+    stations: Rect,
+    /// How far into its show the live station is, shown when one is
+    /// selected or playing.
+    show_gauge: Option<Rect>,
+    mixtapes: Rect,
+    /// Followed shows and custom URLs below the mixtapes, each only once
+    /// there are any.
+    extra: Vec<(StreamType, Rect)>,
+    description: Rect,
+    queue: Rect,
+    history: Rect,
+    info: Rect,
+    controls: Rect,
+}
+
+/// A single-line text input shown at the bottom of the screen.
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+/// A connection attempt running on a worker thread. Messages about it carry
+/// the generation `PlayIntents` gave it so results of superseded attempts can
+/// be told apart.
+struct Connecting {
+    stream_ref: Option<StreamRef>,
+    title: String,
+    phase: ConnectPhase,
+    phase_started: Instant,
+    cancel: Arc<AtomicBool>,
 }
 
 struct Radio {
     streams_collection: StreamsCollection,
-    selected_stream_index: usize,
-    sink: Option<Sink>,
-    current_stream_url: Option<String>,
+    stream_view: Vec<StreamRef>,
+    player: Player,
+    connecting: PlayIntents<Connecting>,
+    /// Reconnecting the stream that dropped, and the attempts so far.
+    /// Cleared by any play asked for, and once it has played a while again.
+    auto_reconnect: Option<(StreamRef, Backoff)>,
+    /// Why the stream stopped for good once reconnecting gave up, kept in
+    /// the Info pane until something plays.
+    stream_lost: Option<String>,
+    playing_stream: Option<StreamRef>,
+    announcement: Option<String>,
+    announcement_display_timeout: Option<SystemTime>,
     recognition_result: Option<String>,
     duration: u64,
-    recognition_result_tx: Sender<String>,
-    recognition_result_rx: Receiver<String>,
+    recognition_result_tx: Sender<(u64, Result<RecognitionOutcome, String>)>,
+    recognition_result_rx: Receiver<(u64, Result<RecognitionOutcome, String>)>,
+    id_scheduler: IdScheduler,
+    recognition_attempt: Arc<Attempt>,
+    track_change: TrackChangeDetector,
+    last_saved_sample: Option<PathBuf>,
+    /// The last track recognized this session, for `y` to copy.
+    last_track: Option<String>,
     ui_tx: Sender<UIMessage>,
-    _stream: Option<OutputStream>,
-    volume: f32,
     volume_display_timeout: Option<SystemTime>,
     duration_display_timeout: Option<SystemTime>,
     recognition_result_display_timeout: Option<SystemTime>,
     recognition_list: String,
-    vertical_scroll_state: ScrollbarState,
-    vertical_scroll: usize,
+    /// How much of `recognition_list` came from the history file; the
+    /// incognito entries follow.
+    history_file_len: usize,
+    /// The history file, as `get_history_file_path` settled it.
+    history_path: PathBuf,
+    /// Coalesces bringing the history pane up to date, so a burst of results
+    /// doesn't rebuild it once each.
+    history_refresh: Coalescer,
+    /// Lines the app wrote to the history file since the pane was last
+    /// brought up to date.
+    history_written: Vec<String>,
+    /// How many times the history pane's rows were rebuilt, for F12.
+    history_rebuilds: u64,
+    history_index: HistoryIndex,
+    history_rows: Vec<HistoryRow>,
+    expanded_streaks: HashSet<usize>,
+    views: Views<StreamRef>,
+    prompt: Option<Prompt>,
+    guard: Guard,
+    tick_interval: Arc<AtomicU64>,
+    decode_load_sample: Option<(Instant, Duration)>,
+    decode_load: f32,
+    overloaded_ticks: u32,
+    recovered_ticks: u32,
+    light_mode: bool,
+    forced_light_mode: bool,
+    status_log: VecDeque<String>,
+    /// The status log on disk, outside of tests.
+    debug_log: Option<DebugLog>,
+    recent_errors: VecDeque<String>,
+    /// Newest episode per followed show, keyed by show alias.
+    followed_episodes: BTreeMap<String, follows::Episode>,
+    /// The stream last started and its show, whose tracks `E` shares. Kept
+    /// after it stops, until another starts.
+    session: Option<share::Session>,
+    /// What is playing and since when, written to the listening log on stop.
+    listening_since: Option<(String, DateTime<Local>)>,
+    /// Private listening: recognitions show for this session only, and
+    /// nothing goes to the history, listening log, notifications or presence.
+    incognito: bool,
+    /// History lines recognized while incognito, shown after the ones on file.
+    incognito_entries: String,
+    /// Recognitions queued while offline, retried after the next successful
+    /// fetch.
+    pending_ids: usize,
+    retrying_pending_ids: bool,
+    /// The weekly digest while its popup is open.
+    digest: Option<String>,
+    /// A show's archive while its popup is open.
+    archive: Option<archive::Archive>,
+    /// The audio self-test while its popup is open: the stages finished so
+    /// far, and which run they belong to.
+    diagnostics: Option<Vec<(Stage, Outcome)>>,
+    diagnostics_run: u64,
+    show_about: bool,
+    show_help: bool,
+    /// Files an older version left in the home directory, while the popup
+    /// offering to move them is open.
+    migration: Option<Vec<migrate::Move>>,
+    quitting: bool,
+    streams_load: StreamsLoad,
+    /// Sections with a retry on its way.
+    retrying_sections: Vec<StreamType>,
+    show_status_log: bool,
+    render_failed: bool,
+    show_debug_overlay: bool,
+    /// The screen size last drawn at, for finding what a click landed on.
+    frame_area: Cell<Rect>,
+    /// Clear the terminal before the next draw, so it is repainted in full.
+    clear_screen: bool,
+    color_support: ColorSupport,
+    theme: Theme,
+    clock: fn() -> DateTime<Local>,
+    /// Where copied text goes: the terminal's clipboard, or a list in tests.
+    copy: fn(&str) -> io::Result<()>,
+    /// Lays out a frame; tests swap in one that panics.
+    draw: fn(&Radio, &mut Frame),
+    state: State,
+    /// Where `state` is saved: the state file, as `new` finds it.
+    state_path: PathBuf,
+    /// The listening log, found the same way.
+    listening_log: PathBuf,
+    rotation: Rotation,
+    presence: Presence,
+    loudness: Loudness,
+    /// The stream whose first minute is being measured, and when it started.
+    loudness_measure: Option<(String, Instant)>,
+    /// Where the edge server at this address is, once looked up.
+    edge_location: Option<(IpAddr, String)>,
+    config: Config,
 }
 
 impl Radio {
-    fn new(ui_tx: Sender<UIMessage>) -> Self {
-        let mut buf = String::new();
-        let history_file_path = get_history_file_path();
-        let _ = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .read(true)
-            .open(history_file_path)
-            .unwrap()
-            .read_to_string(&mut buf);
-        let history_len = buf.lines().count();
-        let streams_collection = StreamsCollection::populate_collection().unwrap();
-        let selected_stream_index = 0;
+    fn new(ui_tx: Sender<UIMessage>, mut config: Config, fetched: Result<StreamsCollection, String>) -> Self {
+        let store = HistoryStore::open_default().ok();
+        let buf = store.as_ref().map(HistoryStore::contents).unwrap_or_default();
+        // Start without streams rather than not at all; `u` tries again
+        let (streams_collection, streams_error) = match fetched {
+            Ok(collection) => (collection, None),
+            Err(err) => (StreamsCollection::default(), Some(err)),
+        };
+        let state = State::load();
+        config.light_mode =
+            config.light_mode || env::var("NTS_CLI_LIGHT_MODE").is_ok_and(|v| v == "1");
+        let theme = Theme::for_background(background::detect(config.background)).with_overrides(&config.colors);
+
+        let mut radio = Radio::from_parts(ui_tx, config, streams_collection, buf, state);
+        radio.history_path = get_history_file_path();
+        radio.state_path = migrate::STATE.path();
+        radio.listening_log = listening::log_file_path();
+        radio.presence = Presence::start(&radio.config.discord);
+        radio.loudness = loudness::load();
+        radio.debug_log = debug_log::open_default(&radio.config.retention);
+        if let Some(err) = streams_error {
+            radio.log_error(format!("Could not load streams: {}", err));
+            radio.streams_load = StreamsLoad::Failed;
+        }
+        radio.retry_failed_sections();
+        radio.color_support = ColorSupport::detect();
+        radio.theme = theme;
+        radio.pending_ids = recognition::pending_count(radio.config.retention.pending_id_max_age());
+        radio.followed_episodes = follows::load_cache();
+        radio.followed_episodes.retain(|show, _| radio.state.followed_shows.contains(show));
+        radio.sync_episode_streams();
+        radio.sync_custom_streams();
+        // Only report: which of two clashing entries is right is the user's call
+        let discrepancies = store.map(|store| store.verify()).unwrap_or_default();
+        if let Some(first) = discrepancies.first() {
+            radio.log_error(format!(
+                "History file has {} problems ({}); run `nts_cli history verify`",
+                discrepancies.len(),
+                first
+            ));
+        }
+        radio
+    }
+
+    /// Builds the app from already loaded data, without touching the network,
+    /// the terminal or the environment. `new` then points it at the files
+    /// and services of the running user.
+    fn from_parts(
+        ui_tx: Sender<UIMessage>,
+        config: Config,
+        streams_collection: StreamsCollection,
+        buf: String,
+        state: State,
+    ) -> Self {
+        let history_rows = history::rows(&buf, &HashSet::new());
+        let history_len = history_rows.len();
+        let stream_view = streams_collection.view(&state.favorites);
+        let selected_stream = stream_view.first().cloned();
         let (recognition_result_tx, recognition_result_rx) = mpsc::channel();
+        let (rotation, prompt) = Self::restore_rotation(&state);
+        let auto_id_interval = (config.recognition.auto_id == AutoId::Interval)
+            .then(|| Duration::from_secs(config.recognition.auto_id_interval_secs));
+        let incognito = config.recognition.start_incognito;
+        let mut player = Player::new(config.playback.volume);
+        player.set_output(config.output.clone());
+        player.set_stream_overrides(config.network.stream_overrides.clone());
+        let forced_light_mode = config.light_mode;
+        let tick_interval = if forced_light_mode {
+            LIGHT_TICK_INTERVAL_MS
+        } else {
+            TICK_INTERVAL_MS
+        };
         Radio {
             streams_collection,
-            selected_stream_index,
-            sink: None,
-            current_stream_url: None,
+            stream_view,
+            player,
+            connecting: PlayIntents::default(),
+            auto_reconnect: None,
+            stream_lost: None,
+            playing_stream: None,
+            announcement: None,
+            announcement_display_timeout: None,
             recognition_result: Some("No song recognized".to_string()),
-            duration: DEFAULT_DURATION_SEC,
+            duration: config.recognition.duration_secs.clamp(1, mp3_tap::KEEP.as_secs()),
             recognition_result_tx,
             recognition_result_rx,
+            id_scheduler: IdScheduler::new(auto_id_interval),
+            recognition_attempt: Arc::new(Attempt::unbounded()),
+            track_change: TrackChangeDetector::new(),
+            last_saved_sample: None,
+            last_track: None,
             ui_tx,
-            _stream: None,
-            volume: DEFAULT_VOLUME,
             volume_display_timeout: None,
             duration_display_timeout: None,
             recognition_result_display_timeout: None,
+            history_file_len: buf.len(),
+            history_path: PathBuf::new(),
+            history_refresh: Coalescer::new(HISTORY_REFRESH_INTERVAL),
+            history_written: Vec::new(),
+            history_rebuilds: 0,
+            history_index: HistoryIndex::build(&buf),
+            history_rows,
+            expanded_streaks: HashSet::new(),
             recognition_list: buf,
-            vertical_scroll_state: ScrollbarState::default(),
-            vertical_scroll: history_len.saturating_sub(5),
+            views: Views::new(
+                ViewState {
+                    selection: selected_stream,
+                    ..Default::default()
+                },
+                ViewState {
+                    scroll: history_len.saturating_sub(5),
+                    ..Default::default()
+                },
+            ),
+            prompt,
+            guard: Guard::default(),
+            tick_interval: Arc::new(AtomicU64::new(tick_interval)),
+            decode_load_sample: None,
+            decode_load: 0.0,
+            overloaded_ticks: 0,
+            recovered_ticks: 0,
+            light_mode: forced_light_mode,
+            forced_light_mode,
+            status_log: VecDeque::with_capacity(STATUS_LOG_CAPACITY),
+            debug_log: None,
+            recent_errors: VecDeque::with_capacity(RECENT_ERRORS_CAPACITY),
+            followed_episodes: BTreeMap::new(),
+            session: None,
+            listening_since: None,
+            incognito,
+            incognito_entries: String::new(),
+            pending_ids: 0,
+            retrying_pending_ids: false,
+            digest: None,
+            archive: None,
+            diagnostics: None,
+            diagnostics_run: 0,
+            show_about: false,
+            show_help: false,
+            migration: None,
+            quitting: false,
+            streams_load: StreamsLoad::Loaded,
+            retrying_sections: Vec::new(),
+            show_status_log: false,
+            render_failed: false,
+            show_debug_overlay: false,
+            frame_area: Cell::new(Rect::default()),
+            clear_screen: false,
+            color_support: ColorSupport::Basic,
+            theme: Theme::for_background(Background::Dark),
+            clock: Local::now,
+            copy: clipboard::copy,
+            draw: Radio::draw,
+            state,
+            state_path: PathBuf::new(),
+            listening_log: PathBuf::new(),
+            rotation,
+            presence: Presence::off(),
+            loudness: Loudness::default(),
+            loudness_measure: None,
+            edge_location: None,
+            config,
         }
     }
 
-    fn update_collection(&mut self) {
-        self.streams_collection = StreamsCollection::populate_collection().unwrap();
+    /// Wall-clock time as the UI sees it; fixed in snapshot tests.
+    fn now(&self) -> DateTime<Local> {
+        (self.clock)()
     }
 
-    fn stop(&mut self) {
-        if let Some(sink) = self.sink.take() {
-                sink.stop();
-            }
-            self.current_stream_url = None;
-            self._stream = None;
+    /// `now` on the NTS clock, for comparing with the schedule.
+    fn schedule_now(&self) -> DateTime<Local> {
+        clock::corrected(self.now())
     }
 
-    fn play(&mut self, stream_type: StreamType) {
-        let selected_stream = match stream_type {
-            StreamType::Mixtape => {
-                &self.streams_collection.mixtapes[self.selected_stream_index - 2]
+    fn handle_message(&mut self, message: UIMessage) -> Result<(), Box<dyn std::error::Error>> {
+        match message {
+            UIMessage::UpdateUI => {}
+            UIMessage::Tick => self.handle_tick(),
+            UIMessage::KeyPress(key) => self.handle_key_press(key)?,
+            UIMessage::Mouse(mouse) => self.handle_mouse(mouse),
+            UIMessage::Resize(width, height) => self.handle_resize(width, height),
+            UIMessage::RecognitionResult => self.handle_recognition_result(),
+            UIMessage::StreamsFetched(fetched) => self.update_collection(fetched),
+            UIMessage::SectionFetched(section, fetched) => self.handle_section_fetched(section, fetched),
+            UIMessage::FollowedEpisodes(results) => self.handle_followed_episodes(results),
+            UIMessage::AudioCheck(run, stage, outcome) => {
+                if let Some(results) = self.diagnostics.as_mut().filter(|_| run == self.diagnostics_run) {
+                    results.push((stage, outcome));
+                }
             }
-            StreamType::Station => {
-                &self.streams_collection.stations[self.selected_stream_index % 2]
+            UIMessage::LoudnessMeasured(title, dbfs) => self.handle_loudness_measured(&title, dbfs),
+            UIMessage::PendingIdsRetried(matches, pending) => self.handle_pending_ids_retried(matches, pending),
+            UIMessage::ArchivePage(show, offset, page) => self.handle_archive_page(&show, offset, page),
+            UIMessage::HistoryRefreshDue => {
+                if self.history_refresh.due(Instant::now()) {
+                    self.refresh_history();
+                }
+            }
+            UIMessage::EdgeLocated(ip, location) => {
+                let playing = self.player.connect_info().and_then(|info| info.remote_addr);
+                if playing.is_some_and(|addr| addr.ip() == ip) {
+                    self.log_status(format!("Stream edge {} is in {}", ip, location));
+                }
+                self.edge_location = Some((ip, location));
+            }
+            UIMessage::NetworkChanged => self.reconnect("Network changed"),
+            UIMessage::ConnectProgress(generation, phase) => {
+                if let Some(connecting) = self.connecting.pending_mut(generation) {
+                    if connecting.phase != phase {
+                        if !matches!(
+                            (connecting.phase, phase),
+                            (ConnectPhase::Prebuffering(_), ConnectPhase::Prebuffering(_))
+                        ) {
+                            connecting.phase_started = Instant::now();
+                        }
+                        connecting.phase = phase;
+                    }
+                }
+            }
+            UIMessage::PlaybackReady(generation, prepared) => {
+                self.handle_playback_ready(generation, prepared)
+            }
+            UIMessage::PlaybackFailed(generation, err) => {
+                self.handle_playback_failed(generation, err)
+            }
+            UIMessage::MixtapeMoved(title, from, to) => self.handle_mixtape_moved(&title, &from, to),
+        }
+        Ok(())
+    }
+
+    /// Takes a freshly fetched collection. A failed fetch keeps the streams
+    /// there are, and only asks for a retry when there are none.
+    fn update_collection(&mut self, fetched: Result<StreamsCollection, String>) {
+        let collection = match fetched {
+            Ok(collection) => collection,
+            Err(err) => {
+                if self.streams_load == StreamsLoad::Loaded {
+                    self.log_error(format!("Could not refresh streams, keeping the current list: {}", err));
+                } else {
+                    self.log_error(format!("Could not load streams: {}", err));
+                    self.streams_load = StreamsLoad::Failed;
+                }
+                return;
             }
         };
+        self.streams_load = StreamsLoad::Loaded;
+        if let Some(warning) = clock::warning() {
+            self.log_status(warning.clone());
+            self.show_info(warning);
+        }
+        self.retry_pending_ids();
+        let previous_broadcast = self.playing_station_broadcast();
+        let previous_selection = self.selected_by_title();
+        let played = std::mem::take(&mut self.streams_collection.archive);
+        self.streams_collection = collection;
+        self.streams_collection.archive = played;
+        self.sync_episode_streams();
+        self.sync_custom_streams();
+        self.reselect_by_title(previous_selection);
+        self.refresh_followed_episodes();
+        self.retry_failed_sections();
 
-        let stream_url = selected_stream.audio_stream_endpoint.clone();
-        self.stop();
+        let current_broadcast = self.playing_station_broadcast();
+        if let (Some(previous), Some((title, broadcast))) = (previous_broadcast, current_broadcast) {
+            if previous.1 != broadcast && !broadcast.is_empty() {
+                if !self.incognito {
+                    self.presence.playing(&broadcast);
+                }
+                self.announce_show_change(&title, &broadcast);
+            }
+        }
+    }
 
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        let sink = Sink::try_new(&stream_handle).unwrap();
+    /// Schedules a quick retry of each section that failed to fetch, unless
+    /// one is already on its way.
+    fn retry_failed_sections(&mut self) {
+        for section in [StreamType::Station, StreamType::Mixtape] {
+            let Some(err) = self.streams_collection.section_error(section) else {
+                continue;
+            };
+            if self.retrying_sections.contains(&section) {
+                continue;
+            }
+            self.log_error(format!(
+                "Could not fetch {}, retrying in {}: {}",
+                section_name(section),
+                format::seconds(SECTION_RETRY_INTERVAL, 0),
+                err
+            ));
+            self.retrying_sections.push(section);
+            let ui_tx = self.ui_tx.clone();
+            thread::spawn(move || {
+                thread::sleep(SECTION_RETRY_INTERVAL);
+                let _ = ui_tx.send(UIMessage::SectionFetched(section, StreamsCollection::fetch_section(section)));
+            });
+        }
+    }
 
-        let response = reqwest::blocking::get(&stream_url).unwrap();
-        let source = Mp3StreamDecoder::new(BufReader::new(response), 8096).unwrap();
+    /// Takes in a retried section. A failure retries again, unless a full
+    /// refresh has brought the section back in the meantime.
+    fn handle_section_fetched(&mut self, section: StreamType, fetched: Result<Vec<Stream>, String>) {
+        self.retrying_sections.retain(|retrying| *retrying != section);
+        let previous_selection = self.selected_by_title();
+        let collection = &mut self.streams_collection;
+        let (streams, error) = match section {
+            StreamType::Station => (&mut collection.stations, &mut collection.stations_error),
+            _ => (&mut collection.mixtapes, &mut collection.mixtapes_error),
+        };
+        match fetched {
+            Ok(fetched) => {
+                *streams = fetched;
+                *error = None;
+                self.rebuild_stream_view();
+                self.reselect_by_title(previous_selection);
+                self.retry_pending_ids();
+                self.log_status(format!("Fetched {} again", section_name(section)));
+            }
+            Err(err) if error.is_some() => {
+                *error = Some(err);
+                self.retry_failed_sections();
+            }
+            Err(_) => {}
+        }
+    }
 
-        thread::sleep(Duration::from_millis(500));
+    /// Fetches the newest episodes of followed shows in the background.
+    fn refresh_followed_episodes(&self) {
+        if self.state.followed_shows.is_empty() {
+            return;
+        }
+        let shows = self.state.followed_shows.clone();
+        let ui_tx = self.ui_tx.clone();
+        thread::spawn(move || {
+            let _ = ui_tx.send(UIMessage::FollowedEpisodes(follows::fetch_latest(&shows)));
+        });
+    }
 
-        sink.append(source);
-        sink.set_volume(self.volume);
+    /// Takes in freshly fetched episodes. A show that failed keeps its cached
+    /// episode, if any, so the rest of the section is unaffected.
+    fn handle_followed_episodes(&mut self, results: Vec<(String, Result<follows::Episode, String>)>) {
+        for (show, result) in results {
+            if !self.state.followed_shows.contains(&show) {
+                continue;
+            }
+            match result {
+                Ok(episode) => {
+                    self.followed_episodes.insert(show, episode);
+                }
+                Err(err) => self.log_status(format!("Could not fetch episodes of {}: {}", show, err)),
+            }
+        }
+        if let Err(err) = follows::save_cache(&self.followed_episodes) {
+            self.log_status(format!("Could not cache followed episodes: {}", err));
+        }
+        self.sync_episode_streams();
+    }
 
-        self.sink = Some(sink);
-        self.current_stream_url = Some(stream_url);
-        self._stream = Some(_stream);
+    /// Rebuilds the "Followed shows" section from the known episodes.
+    fn sync_episode_streams(&mut self) {
+        self.streams_collection.episodes = self
+            .state
+            .followed_shows
+            .iter()
+            .filter_map(|show| self.followed_episodes.get(show))
+            .map(follows::Episode::to_stream)
+            .collect();
+        self.rebuild_stream_view();
     }
 
-    fn start_recognition(&mut self) {
-        self.recognition_result = None;
-        let stream_url = self.current_stream_url.clone();
-        let duration = self.duration;
-        let recognition_result_tx = self.recognition_result_tx.clone();
+    /// Fetches the streams again on a worker thread after they failed to load.
+    fn retry_streams(&mut self) {
+        if self.streams_load != StreamsLoad::Failed {
+            return;
+        }
+        self.streams_load = StreamsLoad::Retrying;
         let ui_tx = self.ui_tx.clone();
-
         thread::spawn(move || {
-            let dir = tempdir().unwrap();
-            let temp_file_path = dir.path().join("sample.mp3");
-
-            if let Ok(response) = reqwest::blocking::get(stream_url.unwrap()) {
-                let mut temp_file = std::fs::File::create(&temp_file_path).unwrap();
-                let max_bytes = duration as usize * 128 * 1024;
-
-                io::copy(&mut response.take(max_bytes as u64), &mut temp_file).unwrap();
-
-                if let Ok(output) = Command::new("vibra")
-                    .args(["-R", "--file", temp_file_path.to_str().unwrap()])
-                    .output()
-                {
-               if output.status.success() {
-                        let json: Value =
-                            serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).unwrap();
-
-                        let recognition_text = json
-                            .get("track")
-                            .map(|track| {
-                                format!(
-                                    "{} - {}",
-                                    track
-                                        .get("title")
-                                        .and_then(Value::as_str)
-                                        .unwrap_or("Unknown Title"),
-                                    track
-                                        .get("subtitle")
-                                        .and_then(Value::as_str)
-                                        .unwrap_or("Unknown Artist")
-                                )
-                            })
-                            .unwrap_or_else(|| "No song recognized".to_string());
-
-                        if recognition_text != "No song recognized" {
-                            let _ = append_to_recognition_history(&recognition_text);
-                        }
+            let fetched = StreamsCollection::populate_collection().map_err(|err| err.to_string());
+            let _ = ui_tx.send(UIMessage::StreamsFetched(fetched));
+        });
+    }
 
-                        let _ = recognition_result_tx.send(recognition_text);
-                        let _ = ui_tx.send(UIMessage::RecognitionResult);
-                    }
-                }
+    /// Rebuilds the "Custom" section from the recent custom URLs.
+    fn sync_custom_streams(&mut self) {
+        self.streams_collection.custom = self
+            .state
+            .recent_custom_urls
+            .iter()
+            .map(|url| custom_url::to_stream(url))
+            .collect();
+        self.rebuild_stream_view();
+    }
+
+    /// Plays a pasted stream URL and keeps it in the "Custom" section.
+    fn play_custom_url(&mut self, input: &str) {
+        let url = match custom_url::parse(input) {
+            Ok(url) => url,
+            Err(err) => {
+                self.show_info(err);
+                return;
             }
+        };
+        custom_url::remember(&mut self.state.recent_custom_urls, &url);
+        self.save_state();
+        self.sync_custom_streams();
+        self.views.streams.selection = Some(StreamRef {
+            section: StreamType::Custom,
+            id: url,
         });
+        self.tune_in();
     }
 
-    fn start_recognition_info_timer(&self) {
+    /// Whether a followed show has an episode out since it was last played.
+    fn is_new_episode(&self, show: &str) -> bool {
+        self.followed_episodes.get(show).is_some_and(|episode| {
+            self.state
+                .show_listened
+                .get(show)
+                .is_none_or(|&listened| episode.broadcast > listened)
+        })
+    }
+
+    /// Follows the show on air on the selected station, or unfollows the
+    /// selected followed show. Mixtapes are favorited instead.
+    fn toggle_follow(&mut self) {
+        let Some(selected) = self.views.streams.selection.clone() else {
+            return;
+        };
+        if selected.section == StreamType::Mixtape {
+            self.toggle_favorite(selected);
+            return;
+        }
+        let show = match selected.section {
+            StreamType::Station => self.selected().map(|station| station.show_alias.clone()).unwrap_or_default(),
+            StreamType::Episode => selected.id.clone(),
+            StreamType::Archive => self.selected().map(|episode| episode.show_alias.clone()).unwrap_or_default(),
+            StreamType::Mixtape | StreamType::Custom => String::new(),
+        };
+        if show.is_empty() {
+            self.show_info("Select a station to follow the show on air".to_string());
+            return;
+        }
+        if let Some(index) = self.state.followed_shows.iter().position(|followed| *followed == show) {
+            self.state.followed_shows.remove(index);
+            self.state.show_listened.remove(&show);
+            self.followed_episodes.remove(&show);
+            self.show_info(format!("Unfollowed {}", show));
+        } else {
+            self.state.followed_shows.push(show.clone());
+            // Only episodes published from now on count as new
+            self.state.show_listened.insert(show.clone(), self.now().timestamp());
+            self.show_info(format!("Following {}", show));
+        }
+        self.save_state();
+        self.sync_episode_streams();
+        self.refresh_followed_episodes();
+    }
+
+    /// Pins a mixtape to the top of the list, or unpins it. Favorites are
+    /// kept by title in the state, so one that leaves the API and comes
+    /// back, under the same alias or another, is a favorite again.
+    fn toggle_favorite(&mut self, mixtape: StreamRef) {
+        let Some(title) = self.streams_collection.resolve(&mixtape).map(|mixtape| mixtape.title.clone()) else {
+            return;
+        };
+        if let Some(index) = self.state.favorites.iter().position(|favorite| *favorite == title) {
+            self.state.favorites.remove(index);
+            self.show_info(format!("Removed {} from favorites", title));
+        } else {
+            self.show_info(format!("Added {} to favorites", title));
+            self.state.favorites.push(title);
+        }
+        self.save_state();
+        self.rebuild_stream_view();
+    }
+
+    /// Opens the archive of the selected stream's show: the show on air for
+    /// stations, the followed or archived show otherwise. Asks which show
+    /// when the selection has none.
+    fn browse_archive(&mut self) {
+        let section = self.views.streams.selection.as_ref().map(|selected| selected.section);
+        let show = self.selected().and_then(|stream| {
+            let name = match section? {
+                StreamType::Station => &stream.subtitle,
+                StreamType::Episode => &stream.title,
+                StreamType::Archive => archive::show_name(stream),
+                StreamType::Mixtape | StreamType::Custom => return None,
+            };
+            (!stream.show_alias.is_empty()).then(|| (stream.show_alias.clone(), name.to_string()))
+        });
+        match show {
+            Some((alias, name)) => self.open_archive(alias, name),
+            None => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::ArchiveShow,
+                    input: String::new(),
+                })
+            }
+        }
+    }
+
+    fn open_archive(&mut self, show_alias: String, show_name: String) {
+        self.archive = Some(archive::Archive::new(show_alias, show_name));
+        self.fetch_archive_page();
+    }
+
+    /// Fetches the next page of the open archive on a worker thread, if it
+    /// wants one.
+    fn fetch_archive_page(&mut self) {
+        let Some(archive) = self.archive.as_mut() else {
+            return;
+        };
+        let Some(offset) = archive.next_page() else {
+            return;
+        };
+        archive.loading = true;
+        let show = archive.show_alias.clone();
         let ui_tx = self.ui_tx.clone();
         thread::spawn(move || {
-            thread::sleep(Duration::from_secs(RECOGNITION_INFO_TIMER));
-            let _ = ui_tx.send(UIMessage::UpdateUI);
+            let page = archive::fetch_page(&show, offset);
+            let _ = ui_tx.send(UIMessage::ArchivePage(show, offset, page));
         });
     }
-    
-    fn handle_recognition_result(&mut self) {
-        if let Ok(result) = self.recognition_result_rx.try_recv() {
-            self.recognition_result = Some(result);
-            let mut buf = String::new();
-            let history_file_path = get_history_file_path();
-            let _ = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .read(true)
-                .open(history_file_path)
-                .unwrap()
-                .read_to_string(&mut buf);
-            self.vertical_scroll_state = self.vertical_scroll_state.content_length(buf.lines().count());
-            self.recognition_list = buf;
-            self.recognition_result_display_timeout = Some(SystemTime::now());
-            self.start_recognition_info_timer();
+
+    fn handle_archive_page(&mut self, show: &str, offset: usize, page: Result<archive::Page, String>) {
+        if self.archive.as_ref().is_none_or(|archive| archive.show_alias != show) {
+            return;
+        }
+        if let Err(err) = &page {
+            self.log_status(format!("Could not load the archive of {}: {}", show, err));
+        }
+        if let Some(archive) = self.archive.as_mut() {
+            archive.add_page(offset, page);
         }
+        self.fetch_archive_page();
     }
 
-    fn render_ui(
-        &mut self,
-        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        terminal.draw(|f| {
-            let main_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(1)
-                .constraints(
-                    [
-                        Constraint::Percentage(10),
-                        Constraint::Fill(1),
-                        Constraint::Fill(1),
-                    ]
-                    .as_ref(),
-                )
-                .split(f.area());
-    
-            let top_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(25), Constraint::Percentage(50)].as_ref())
-                .split(main_chunks[1]);
-    
-            let bottom_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(10), Constraint::Fill(20)].as_ref())
-                .split(main_chunks[2]);
-    
-            let create_list_item = |title: &str, is_selected: bool| {
-                let style = if is_selected {
-                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
-                } else {
-                    Style::default().fg(Color::Red)
-                };
-                if is_selected {
-                    ListItem::new(vec![Line::from(Span::styled(title.to_string() + " •", style))])
-                } else {
-                    ListItem::new(vec![Line::from(Span::styled(title.to_string(), style))])
-                }
-            };
-    
-            // Create list items for mixtapes and stations
-            let stream_items_mixtapes: Vec<ListItem> = self.streams_collection
-                .mixtapes
-                .iter()
-                .enumerate()
-                .map(|(i, mixtape)| create_list_item(&mixtape.title, i + 2 == self.selected_stream_index))
-                .collect();
-    
-            let stream_items_stations: Vec<ListItem> = self.streams_collection
-                .stations
-                .iter()
-                .enumerate()
-                .map(|(i, station)| create_list_item(&station.title, i == self.selected_stream_index))
-                .collect();
-    
-            // Render live stations list
-            let live_stations_list = List::new(stream_items_stations)
-                .block(create_block("Stations"))
-                .highlight_style(
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
-                );
-    
-            f.render_widget(live_stations_list, main_chunks[0]);
-    
-            // Render mixtape list
-            let mixtape_list = List::new(stream_items_mixtapes)
-                .block(create_block("Mixtapes"))
-                .highlight_style(
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
-                );
-    
-            f.render_widget(mixtape_list, top_chunks[0]);
-    
-            let (description, subtitle) = if self.selected_stream_index < 2 {
-                let station = &self.streams_collection.stations[self.selected_stream_index];
-                (station.description.clone(), station.subtitle.clone())
-            } else {
-                let mixtape_index = (self.selected_stream_index - 2) % self.streams_collection.mixtapes.len();
-                let mixtape = &self.streams_collection.mixtapes[mixtape_index];
-                (mixtape.description.clone(), mixtape.subtitle.clone())
-            };
-    
-            // Render description
-            let description_paragraph = Paragraph::new(vec![
-                Line::from(vec![
-                    Span::styled(subtitle, Style::new().green().italic()),
-                ]),
-                Line::from(Span::styled("", Style::new().green())),
-                Line::from(Span::styled(description, Style::new().green())),
-            ])
-            .block(create_block("Description"))
-            .wrap(Wrap { trim: true });
-    
-            f.render_widget(description_paragraph, top_chunks[1]);
-    
-            // Render recognition result and list
-            let recognition_result_text = self.recognition_result
-                .clone()
-                .unwrap_or_else(|| "Recognizing...".to_string());
-            let recognition_list = self.recognition_list.clone().to_string();
-            self.vertical_scroll_state = self.vertical_scroll_state.content_length(recognition_list.lines().count());
-    
-            let recognition_list_paragraph = Paragraph::new(recognition_list)
-                .block(create_block("Recognized Tracks")).style(Style::default().fg(Color::Blue))
-                .wrap(Wrap { trim: true }).scroll((self.vertical_scroll as u16, 0));
-    
-            f.render_widget(recognition_list_paragraph, bottom_chunks[0]);
-            f.render_stateful_widget(
-                Scrollbar::new(ScrollbarOrientation::VerticalRight)
-                    .begin_symbol(Some("↑"))
-                    .end_symbol(Some("↓")),
-                bottom_chunks[0], &mut self.vertical_scroll_state);
-    
-            // Render recognition info
-            let mut recognition_info_text = String::new();
-            if let Some(timeout) = self.recognition_result_display_timeout {
-                if timeout.elapsed().unwrap() < Duration::from_secs(RECOGNITION_INFO_TIMER) {
-                    recognition_info_text = recognition_result_text.to_string();
-                } else {
-                    self.recognition_result_display_timeout = None;
+    /// Keys while the archive is open: moving, searching as it is typed,
+    /// and playing. Esc clears the search first, then closes.
+    fn handle_archive_key(&mut self, key: KeyEvent) {
+        let Some(archive) = self.archive.as_mut() else {
+            return;
+        };
+        if archive.searching {
+            match key.code {
+                KeyCode::Esc => {
+                    archive.searching = false;
+                    archive.set_search(String::new());
                 }
-            }
-            let recognition_info_paragraph = Paragraph::new(recognition_info_text)
-                .block(create_block("Info")).style(Style::default().fg(Color::Blue))
-                .wrap(Wrap { trim: true });
-            f.render_widget(recognition_info_paragraph, bottom_chunks[1]);
-    
-            // Render controls
-            let controls = "j/k: Scroll Recognized Tracks | Enter: Play | Space: Stop | </>: Volume | r: Recognise | =/-: Change duration | q: Quit".to_string();
-            let mut controls_text = controls.clone();
-            let current_volume = self.volume;
-            let volume_percentage = (current_volume * 100.0).round();
-            if let Some(timeout) = self.duration_display_timeout {
-                if timeout.elapsed().unwrap() < Duration::from_secs(DURATION_INFO_TIMER) {
-                    controls_text = format!("{}\nDuration: {}s", controls, self.duration);
-                } else {
-                    self.duration_display_timeout = None;
+                KeyCode::Enter | KeyCode::Up | KeyCode::Down => archive.searching = false,
+                KeyCode::Backspace => {
+                    let mut search = archive.search.clone();
+                    search.pop();
+                    archive.set_search(search);
                 }
-            }
-            if let Some(timeout) = self.volume_display_timeout {
-                if timeout.elapsed().unwrap() < Duration::from_secs(VOLUME_INFO_TIMER) {
-                    controls_text = format!("{}\nVolume: {}%", controls, volume_percentage);
-                } else {
-                    self.volume_display_timeout = None;
+                KeyCode::Char(c) => {
+                    let search = format!("{}{}", archive.search, c);
+                    archive.set_search(search);
                 }
+                _ => {}
             }
-            let controls_paragraph = Paragraph::new(controls_text).block(create_block("Controls")).style(Style::default().fg(Color::DarkGray)).wrap(Wrap { trim: true });
-            f.render_widget(controls_paragraph, bottom_chunks[2]);
-        })?;
+            self.fetch_archive_page();
+            return;
+        }
+        match key.code {
+            KeyCode::Esc if !archive.search.is_empty() => archive.set_search(String::new()),
+            KeyCode::Esc | KeyCode::Char('e') | KeyCode::Char('q') => self.archive = None,
+            KeyCode::Down | KeyCode::Char('j') => archive.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => archive.move_selection(-1),
+            KeyCode::PageDown => archive.move_selection(10),
+            KeyCode::PageUp => archive.move_selection(-10),
+            KeyCode::Char('/') => archive.searching = true,
+            KeyCode::Enter => self.play_archive_episode(),
+            _ => {}
+        }
+        self.fetch_archive_page();
+    }
+
+    /// Plays the selected archive episode and closes the archive, or says
+    /// where to hear it when it is only on Mixcloud.
+    fn play_archive_episode(&mut self) {
+        let Some(archive) = self.archive.as_ref() else {
+            return;
+        };
+        let Some(episode) = archive.selected() else {
+            return;
+        };
+        let Some(stream) = episode.to_stream(&archive.show_alias, &archive.show_name) else {
+            let message = match &episode.mixcloud {
+                Some(url) => format!("Only on Mixcloud: {}", url),
+                None => "This episode has no audio to play".to_string(),
+            };
+            self.show_info(message);
+            return;
+        };
+        self.archive = None;
+        let id = stream.id.clone();
+        let played = &mut self.streams_collection.archive;
+        played.retain(|episode| episode.id != stream.id);
+        played.insert(0, stream);
+        played.truncate(ARCHIVE_SECTION_CAPACITY);
+        self.rebuild_stream_view();
+        self.views.streams.selection = Some(StreamRef {
+            section: StreamType::Archive,
+            id,
+        });
+        self.tune_in();
+    }
+
+    /// Steps through the resampler qualities, restarting the playing stream
+    /// so it takes effect.
+    fn cycle_resampler(&mut self) {
+        let mut output = self.player.output().clone();
+        output.resampler = match output.resampler {
+            ResampleQuality::Linear => ResampleQuality::High,
+            ResampleQuality::High => ResampleQuality::Native,
+            ResampleQuality::Native => ResampleQuality::Linear,
+        };
+        let quality = format!("{:?}", output.resampler).to_lowercase();
+        self.player.set_output(output);
+        if self.playing_stream.is_some() || self.connecting.pending().is_some() {
+            self.reconnect(&format!("Resampler set to {}", quality));
+        } else {
+            self.show_info(format!("Resampler set to {}, used from the next play", quality));
+        }
+    }
+
+    /// Switches between the sound device and the pipe. A playing stream has
+    /// to be restarted to move over, so it is reconnected right away.
+    fn toggle_output(&mut self) {
+        let mut output = self.player.output().clone();
+        output.mode = match output.mode {
+            OutputMode::Device => OutputMode::Pipe,
+            OutputMode::Pipe => OutputMode::Device,
+        };
+        let target = match output.mode {
+            OutputMode::Device => "the sound device".to_string(),
+            OutputMode::Pipe => format!("pipe {}", output.pipe_path.display()),
+        };
+        self.player.set_output(output);
+        if self.playing_stream.is_some() || self.connecting.pending().is_some() {
+            self.reconnect(&format!("Output switched to {}", target));
+        } else {
+            self.show_info(format!("Output switched to {}, used from the next play", target));
+        }
+    }
+
+    fn show_info(&mut self, message: String) {
+        self.recognition_result = Some(message);
+        self.recognition_result_display_timeout = Some(self.now().into());
+        self.start_recognition_info_timer();
+    }
+
+    fn playing_station_broadcast(&self) -> Option<(String, String)> {
+        let playing = self.playing_stream.as_ref()?;
+        if playing.section != StreamType::Station {
+            return None;
+        }
+        let station = self.streams_collection.resolve(playing)?;
+        Some((station.title.clone(), station.subtitle.clone()))
+    }
+
+    fn announce_show_change(&mut self, station: &str, broadcast: &str) {
+        let settings = self.config.announcements.clone();
+        let message = format!("Now on {}: {}", station, broadcast);
+        if settings.banner {
+            self.announcement = Some(message.clone());
+            self.announcement_display_timeout = Some(self.now().into());
+        }
+        if settings.notification && !self.incognito {
+            notify::send(station, broadcast);
+        }
+        if settings.chime {
+            self.player.chime();
+        }
+        self.log_status(message);
+    }
+
+    /// Rebuilds the selection view after the collection or the filter
+    /// changed, keeping the cursor on the same stream if it still exists and
+    /// clamping it otherwise. While filtering, only matching mixtapes are in
+    /// the view; with none, the cursor stays put for when the filter is gone.
+    fn rebuild_stream_view(&mut self) {
+        let previous_position = self.selected_position();
+        self.stream_view = self.streams_collection.view(&self.state.favorites);
+        let filter = self.views.streams.filter.to_lowercase();
+        if !filter.is_empty() {
+            let collection = &self.streams_collection;
+            self.stream_view.retain(|stream_ref| {
+                stream_ref.section == StreamType::Mixtape
+                    && collection.resolve(stream_ref).is_some_and(|mixtape| mixtape.matches(&filter))
+            });
+            if self.stream_view.is_empty() {
+                return;
+            }
+        }
+        let still_present = self
+            .views
+            .streams
+            .selection
+            .as_ref()
+            .is_some_and(|selected| self.stream_view.contains(selected));
+        if !still_present {
+            self.views.streams.selection = previous_position
+                .map(|position| position.min(self.stream_view.len().saturating_sub(1)))
+                .and_then(|position| self.stream_view.get(position))
+                .or(self.stream_view.first())
+                .cloned();
+        }
+    }
+
+    /// The selection with its stream's title, for `reselect_by_title`.
+    fn selected_by_title(&self) -> Option<(StreamRef, String)> {
+        let selected = self.views.streams.selection.clone()?;
+        let title = self.streams_collection.resolve(&selected)?.title.clone();
+        Some((selected, title))
+    }
+
+    /// After a refresh lost the selected stream, puts the cursor on one of
+    /// the same title in the same section if there is one, as for a mixtape
+    /// listed under a new alias, rather than where `rebuild_stream_view`
+    /// clamped it.
+    fn reselect_by_title(&mut self, previous: Option<(StreamRef, String)>) {
+        let Some((previous, title)) = previous else {
+            return;
+        };
+        if self.views.streams.selection.as_ref() == Some(&previous) {
+            return;
+        }
+        let collection = &self.streams_collection;
+        let renamed = self.stream_view.iter().find(|stream_ref| {
+            stream_ref.section == previous.section
+                && collection.resolve(stream_ref).is_some_and(|stream| stream.title == title)
+        });
+        if let Some(renamed) = renamed {
+            self.views.streams.selection = Some(renamed.clone());
+        }
+    }
+
+    fn selected_position(&self) -> Option<usize> {
+        let selected = self.views.streams.selection.as_ref()?;
+        self.stream_view.iter().position(|stream_ref| stream_ref == selected)
+    }
+
+    fn selected(&self) -> Option<&Stream> {
+        self.streams_collection
+            .resolve(self.views.streams.selection.as_ref()?)
+    }
+
+    fn is_selected(&self, section: StreamType, stream: &Stream) -> bool {
+        self.views.streams.selection
+            .as_ref()
+            .is_some_and(|selected| selected.section == section && selected.id == stream.id)
+    }
+
+    fn move_selection(&mut self, forward: bool) {
+        let len = self.stream_view.len();
+        if len == 0 {
+            return;
+        }
+        let next = match self.selected_position() {
+            Some(position) if forward => (position + 1) % len,
+            Some(position) => (position + len - 1) % len,
+            None => 0,
+        };
+        self.views.streams.selection = Some(self.stream_view[next].clone());
+        self.views.streams.scroll = 0;
+    }
+
+    fn tune_in_station(&mut self, index: usize) {
+        let Some(station) = self.streams_collection.stations.get(index) else {
+            return;
+        };
+        self.views.streams.selection = Some(StreamRef {
+            section: StreamType::Station,
+            id: station.id.clone(),
+        });
+        self.tune_in();
+    }
+
+    /// Selects the mixtape listed `index` places down, as filtered. Does
+    /// nothing when fewer are listed.
+    fn select_mixtape(&mut self, index: usize) {
+        let Some(mixtape) = self
+            .stream_view
+            .iter()
+            .filter(|stream_ref| stream_ref.section == StreamType::Mixtape)
+            .nth(index)
+        else {
+            return;
+        };
+        self.views.streams.selection = Some(mixtape.clone());
+        self.views.streams.scroll = 0;
+    }
+
+    /// Writes what has been playing to the listening log.
+    fn end_listening(&mut self) {
+        if let Some((title, since)) = self.listening_since.take() {
+            if let Err(err) = listening::append(&self.listening_log, &title, since, self.now()) {
+                self.log_status(format!("Could not write listening log: {}", err));
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        self.end_listening();
+        self.player.stop();
+        self.playing_stream = None;
+        self.decode_load_sample = None;
+        self.decode_load = 0.0;
+        self.loudness_measure = None;
+        self.track_change.reset();
+        self.presence.clear();
+        if self.id_scheduler.cancel_scheduled().is_some() {
+            self.recognition_attempt.cancel();
+        }
+    }
+
+    /// Starts connecting to a stream on a worker thread. The UI stays live and
+    /// shows the connect phase; the result comes back as a UIMessage.
+    fn play(&mut self, stream_ref: Option<StreamRef>, stream: &Stream) {
+        self.stop();
+        self.auto_reconnect = None;
+        self.stream_lost = None;
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (generation, superseded) = self.connecting.begin(Connecting {
+            stream_ref: stream_ref.clone(),
+            title: stream.title.clone(),
+            phase: ConnectPhase::Connecting,
+            phase_started: Instant::now(),
+            cancel: cancel.clone(),
+        });
+        if let Some(superseded) = superseded {
+            superseded.cancel.store(true, Ordering::Relaxed);
+        }
+
+        let stream_url = stream.audio_stream_endpoint.clone();
+        let max_silence_skip = stream_ref
+            .as_ref()
+            .and_then(|stream_ref| self.config.playback.max_silence_skip(stream_ref.section));
+        let buffer_size = self.config.playback.buffer_size;
+        let stream_overrides = self.config.network.stream_overrides.clone();
+        let locate_edge = self.config.network.locate_edge;
+        let mixtape_title = stream_ref
+            .as_ref()
+            .filter(|stream_ref| stream_ref.section == StreamType::Mixtape)
+            .map(|_| stream.title.clone());
+        let ui_tx = self.ui_tx.clone();
+        thread::spawn(move || {
+            let connect = |stream_url: &str| {
+                endpoint_health::connect_healthiest(stream_url, &stream_overrides, |url| {
+                    let progress_tx = ui_tx.clone();
+                    player::connect(url, buffer_size, max_silence_skip, &cancel, move |phase| {
+                        let _ = progress_tx.send(UIMessage::ConnectProgress(generation, phase));
+                    })
+                })
+            };
+            let mut result = connect(&stream_url);
+            // A renamed mixtape 404s at its old endpoint while the API already
+            // lists the new one, so look it up again and retry once.
+            if let (Err(ConnectError::NotFound), Some(title)) = (&result, mixtape_title) {
+                let moved = api::fetch_mixtapes()
+                    .map_err(|err| format!("could not refresh mixtapes: {}", err))
+                    .and_then(|mixtapes| api::find_moved_mixtape(mixtapes, &title, &stream_url));
+                match moved {
+                    Ok(mixtape) => {
+                        let fresh_url = mixtape.audio_stream_endpoint;
+                        let _ = ui_tx.send(UIMessage::MixtapeMoved(title, stream_url, fresh_url.clone()));
+                        result = connect(&fresh_url);
+                    }
+                    Err(err) => {
+                        result = Err(ConnectError::Failed(format!("{}, {}", ConnectError::NotFound, err)));
+                    }
+                }
+            }
+            let edge = result.as_ref().ok().and_then(|prepared| prepared.info.remote_addr);
+            let _ = ui_tx.send(match result {
+                Ok(prepared) => UIMessage::PlaybackReady(generation, Box::new(prepared)),
+                Err(err) => UIMessage::PlaybackFailed(generation, err.to_string()),
+            });
+            // After the stream is handed over, so the lookup can't hold it up
+            if let Some(ip) = edge.map(|addr| addr.ip()).filter(|_| locate_edge) {
+                if let Some(location) = network::locate(ip) {
+                    let _ = ui_tx.send(UIMessage::EdgeLocated(ip, location));
+                }
+            }
+        });
+    }
+
+    /// Points the collection at a mixtape's new endpoint, so later plays and
+    /// the queue use it too.
+    fn handle_mixtape_moved(&mut self, title: &str, from: &str, to: String) {
+        self.log_status(format!("{} moved from {} to {}, retrying", title, from, to));
+        for mixtape in &mut self.streams_collection.mixtapes {
+            if mixtape.title == title && mixtape.audio_stream_endpoint == from {
+                mixtape.audio_stream_endpoint = to.clone();
+            }
+        }
+    }
+
+    fn cancel_connect(&mut self) {
+        if let Some(connecting) = self.connecting.cancel() {
+            connecting.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Starts a connected stream, unless a newer play request superseded it,
+    /// in which case dropping `prepared` closes its connection.
+    fn handle_playback_ready(&mut self, generation: u64, prepared: Box<PreparedStream>) {
+        let Some(connecting) = self.connecting.complete(generation) else {
+            return;
+        };
+        let timings = &prepared.timings;
+        let edge = prepared.info.edge().map(|edge| format!(" to {}", edge)).unwrap_or_default();
+        self.log_status(format!(
+            "{}: connected{} in {}, first audio after {}, prebuffered in {}",
+            connecting.title,
+            edge,
+            format::seconds(timings.connected, 2),
+            format::seconds(timings.first_byte, 2),
+            format::seconds(timings.prebuffered, 2)
+        ));
+        if !timings.skipped_silence.is_zero() {
+            self.log_status(format!(
+                "{}: skipped {} of leading silence",
+                connecting.title,
+                format::seconds(timings.skipped_silence, 1)
+            ));
+        }
+
+        let correction = self.loudness.correction_db(&connecting.title, &self.config.loudness);
+        if correction != 0.0 {
+            self.log_status(format!("{}: loudness correction {:+.1} dB", connecting.title, correction));
+        }
+        self.player.set_gain(loudness::gain(correction));
+        if let Err(err) = self.player.start(*prepared) {
+            // The output failed, which reconnecting won't help
+            self.auto_reconnect = None;
+            self.show_playback_error(&connecting.title, &err);
+            return;
+        }
+        if let Some((_, backoff)) = &mut self.auto_reconnect {
+            backoff.connected(Instant::now());
+        }
+        if self.config.loudness.normalize {
+            self.loudness_measure = Some((connecting.title.clone(), Instant::now()));
+        }
+        self.playing_stream = connecting.stream_ref;
+        let mut show = connecting.title.clone();
+        if let Some(stream) = self.playing_stream.as_ref().and_then(|playing| self.streams_collection.resolve(playing)) {
+            // Stations show the broadcast, everything else its own title
+            let details = match self.playing_stream.as_ref().map(|playing| playing.section) {
+                Some(StreamType::Station) if !stream.subtitle.is_empty() => &stream.subtitle,
+                _ => &stream.title,
+            };
+            if !self.incognito {
+                self.presence.playing(details);
+            }
+            show = details.clone();
+        }
+        self.session = Some(share::Session {
+            show,
+            stream: connecting.title.clone(),
+            since: self.now(),
+        });
+        if !self.incognito {
+            self.listening_since = Some((connecting.title.clone(), self.now()));
+        }
+        self.mark_episode_listened();
+
+        if self.light_mode {
+            return;
+        }
+        self.start_recognition(Trigger::Scheduled);
+        self.recognition_result_display_timeout = Some(self.now().into());
+        self.start_recognition_info_timer();
+    }
+
+    fn mark_episode_listened(&mut self) {
+        let Some(playing) = self.playing_stream.as_ref().filter(|playing| playing.section == StreamType::Episode) else {
+            return;
+        };
+        let show = playing.id.clone();
+        if let Some(episode) = self.followed_episodes.get(&show) {
+            self.log_status(format!("Listening to {} ({})", episode.name, episode.episode_alias));
+        }
+        self.state.show_listened.insert(show, self.now().timestamp());
+        self.save_state();
+    }
+
+    fn handle_playback_failed(&mut self, generation: u64, err: String) {
+        let Some(connecting) = self.connecting.complete(generation) else {
+            return;
+        };
+        match connecting.stream_ref.filter(|_| self.auto_reconnect.is_some()) {
+            Some(stream_ref) => {
+                self.log_error(format!("Reconnecting to {} failed: {}", connecting.title, err));
+                self.schedule_reconnect(stream_ref);
+            }
+            None => self.show_playback_error(&connecting.title, &err),
+        }
+    }
+
+    /// Notices the playing stream going without data, as when its
+    /// connection stalls or resets and the sound just stops, and reconnects
+    /// it, waiting longer after each attempt that fails.
+    fn watch_stream(&mut self) {
+        let now = Instant::now();
+        if self.auto_reconnect.as_ref().is_some_and(|(_, backoff)| backoff.recovered(now)) {
+            self.auto_reconnect = None;
+        }
+        let due = self
+            .auto_reconnect
+            .as_mut()
+            .and_then(|(stream_ref, backoff)| Some((stream_ref.clone(), backoff.start_due(now)?)));
+        if let Some((stream_ref, attempt)) = due {
+            self.reconnect_attempt(stream_ref, attempt);
+            return;
+        }
+        if self.connecting.is_pending() {
+            return;
+        }
+        // A stream that runs out as the shows change over comes straight
+        // back; anywhere else it waits for the stall watchdog below
+        let broke_off = self.playing_stream.clone().filter(|stream_ref| {
+            self.player.stream_ended() && reconnect::at_changeover(self.show_boundaries(stream_ref), self.schedule_now())
+        });
+        if let Some(stream_ref) = broke_off {
+            self.log_status(format!("{} broke off at the show changeover, reconnecting", self.stream_title(&stream_ref)));
+            self.stop();
+            self.schedule_reconnect(stream_ref);
+            return;
+        }
+        let stalled = self.player.stalled_for(now).is_some_and(|stalled| stalled >= reconnect::STALL_TIMEOUT);
+        let Some(stream_ref) = self.playing_stream.clone().filter(|_| stalled) else {
+            return;
+        };
+        let title = self.stream_title(&stream_ref);
+        self.log_error(format!(
+            "{} sent no audio for {}",
+            title,
+            format::duration_words(reconnect::STALL_TIMEOUT)
+        ));
+        self.stop();
+        self.schedule_reconnect(stream_ref);
+    }
+
+    /// Schedules the next attempt at reconnecting `stream_ref`, or gives up
+    /// with an error that stays until something plays.
+    fn schedule_reconnect(&mut self, stream_ref: StreamRef) {
+        let max = self.config.playback.reconnect_attempts;
+        let (_, backoff) = self.auto_reconnect.get_or_insert_with(|| (stream_ref.clone(), Backoff::new(max)));
+        let attempts = backoff.attempts();
+        match backoff.schedule(Instant::now()) {
+            Some(delay) if delay.is_zero() => {}
+            Some(delay) => self.show_info(format!("Stream dropped, reconnecting in {}", format::duration_words(delay))),
+            None => {
+                self.auto_reconnect = None;
+                let gave_up = if attempts > 0 { format!(", gave up after {} attempts", attempts) } else { String::new() };
+                let message = format!("{} dropped{} (Enter reconnects)", self.stream_title(&stream_ref), gave_up);
+                self.log_error(message.clone());
+                self.stream_lost = Some(message);
+            }
+        }
+    }
+
+    fn reconnect_attempt(&mut self, stream_ref: StreamRef, attempt: u32) {
+        let Some(stream) = self.streams_collection.resolve(&stream_ref).cloned() else {
+            self.auto_reconnect = None;
+            return;
+        };
+        self.log_status(format!("Reconnecting to {} (attempt {})", stream.title, attempt));
+        // Any other play starts the attempts over
+        let reconnect = self.auto_reconnect.take();
+        self.play(Some(stream_ref), &stream);
+        self.auto_reconnect = reconnect;
+    }
+
+    /// When shows start and end on `stream_ref`, from the schedule. Empty
+    /// for anything but a station.
+    fn show_boundaries(&self, stream_ref: &StreamRef) -> Vec<DateTime<Local>> {
+        let Some(stream) = self.streams_collection.resolve(stream_ref) else {
+            return Vec::new();
+        };
+        let on_air = stream.airtime.map(|airtime| (airtime.start, airtime.end));
+        let upcoming = stream.schedule.iter().map(|show| (show.start, show.end));
+        on_air
+            .into_iter()
+            .chain(upcoming)
+            .flat_map(|(start, end)| std::iter::once(start).chain(end))
+            .collect()
+    }
+
+    fn stream_title(&self, stream_ref: &StreamRef) -> String {
+        self.streams_collection
+            .resolve(stream_ref)
+            .map_or_else(|| "The stream".to_string(), |stream| stream.title.clone())
+    }
+
+    fn show_playback_error(&mut self, title: &str, err: &str) {
+        self.recognition_result = Some(format!("Could not play {}: {} (A: audio diagnostics)", title, err));
+        self.recognition_result_display_timeout = Some(self.now().into());
+        self.start_recognition_info_timer();
+        self.log_error(format!("Playback of {} failed: {}", title, err));
+    }
+
+    fn tune_in(&mut self) {
+        if self.player.paused_for().is_some() && self.playing_stream == self.views.streams.selection {
+            self.toggle_pause();
+            return;
+        }
+        let Some(stream) = self.selected().cloned() else {
+            return;
+        };
+        self.pause_rotation();
+        self.start_playback(&stream);
+    }
+
+    fn start_playback(&mut self, stream: &Stream) {
+        self.play(self.views.streams.selection.clone(), stream);
+    }
+
+    /// Pauses the playing stream, or resumes it where it was paused.
+    fn toggle_pause(&mut self) {
+        match self.player.paused_for() {
+            Some(paused) if paused >= STALE_PAUSE => self.reconnect("Paused too long to resume"),
+            Some(_) => self.player.resume(),
+            None => {
+                self.player.pause();
+            }
+        }
+    }
+
+    /// Connects again to whatever is playing or being connected to, e.g.
+    /// after the network changed under an open connection.
+    fn reconnect(&mut self, reason: &str) {
+        let target = self
+            .playing_stream
+            .clone()
+            .or_else(|| self.connecting.pending().and_then(|connecting| connecting.stream_ref.clone()));
+        let Some(stream_ref) = target else {
+            return;
+        };
+        let Some(stream) = self.streams_collection.resolve(&stream_ref).cloned() else {
+            return;
+        };
+        self.log_status(format!("{}, reconnecting to {}", reason, stream.title));
+        self.recognition_result = Some(format!("{}, reconnecting", reason));
+        self.recognition_result_display_timeout = Some(self.now().into());
+        self.start_recognition_info_timer();
+        self.play(Some(stream_ref), &stream);
+    }
+
+    fn log_status(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        if let Some(debug_log) = &self.debug_log {
+            debug_log.append(&message);
+        }
+        if self.status_log.len() == STATUS_LOG_CAPACITY {
+            self.status_log.pop_front();
+        }
+        self.status_log.push_back(format!("{} {}", self.now().format("%H:%M:%S"), message));
+    }
+
+    /// Logs an error and keeps it for the About screen, so bug reports carry
+    /// the last few things that went wrong.
+    fn log_error(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        if self.recent_errors.len() == RECENT_ERRORS_CAPACITY {
+            self.recent_errors.pop_front();
+        }
+        self.recent_errors.push_back(message.clone());
+        self.log_status(message);
+    }
+
+    fn about_report(&self) -> Vec<String> {
+        let recent_errors: Vec<String> = self.recent_errors.iter().cloned().collect();
+        about::report(&recent_errors)
+    }
+
+    fn copy_about_report(&mut self) {
+        let report = self.about_report().join("\n");
+        match (self.copy)(&report) {
+            Ok(()) => self.log_status("Copied build info to the clipboard"),
+            Err(err) => self.log_status(format!("Could not copy build info: {}", err)),
+        }
+    }
+
+    fn handle_tick(&mut self) {
+        if shutdown::signalled() {
+            self.quitting = true;
+            return;
+        }
+        self.update_decode_load();
+        self.watch_stream();
+        self.player.restore_after_duck();
+        if let Some(entry) = self.rotation.advance_if_due().cloned() {
+            self.save_state();
+            self.play_queue_entry(&entry);
+        }
+        self.auto_identify();
+        self.measure_loudness();
+    }
+
+    /// Once a stream has played for a minute, measures that minute on a
+    /// worker thread so its next start can be corrected.
+    fn measure_loudness(&mut self) {
+        let Some((title, started)) = &self.loudness_measure else {
+            return;
+        };
+        if started.elapsed() < loudness::MEASURE_FOR || self.player.paused_for().is_some() {
+            return;
+        }
+        // After a pause the tap may not have a full minute yet
+        let Some(clip) = self
+            .player
+            .tap()
+            .clip(loudness::MEASURE_FOR, loudness::MEASURE_FOR)
+            .filter(|clip| !clip.truncated)
+        else {
+            return;
+        };
+        let title = title.clone();
+        self.loudness_measure = None;
+        let ui_tx = self.ui_tx.clone();
+        thread::spawn(move || {
+            if let Some(dbfs) = loudness::rms_dbfs(&clip.samples) {
+                let _ = ui_tx.send(UIMessage::LoudnessMeasured(title, dbfs));
+            }
+        });
+    }
+
+    fn handle_loudness_measured(&mut self, title: &str, dbfs: f64) {
+        if !self.loudness.learn(title, dbfs) {
+            return;
+        }
+        self.log_status(format!("{}: measured {:.1} dBFS", title, dbfs));
+        if let Err(err) = loudness::save(&self.loudness) {
+            self.log_error(format!("Could not save loudness estimates: {}", err));
+        }
+    }
+
+    /// Starts a recognition on its own when the configured auto-ID mode says
+    /// it is time: after a fixed interval, or when the track seems to change.
+    fn auto_identify(&mut self) {
+        if !recognition::AVAILABLE || !self.player.is_playing() || self.player.paused_for().is_some() || self.light_mode {
+            return;
+        }
+        let due = match self.config.recognition.auto_id {
+            AutoId::Off => false,
+            AutoId::Interval => self.id_scheduler.is_due(Instant::now()),
+            AutoId::Change => {
+                let changed = self.track_change.update(self.player.tap());
+                if changed {
+                    self.log_status("Track change detected");
+                }
+                changed
+            }
+        };
+        if due && self.id_scheduler.running().is_none() {
+            self.start_recognition(Trigger::Scheduled);
+            self.recognition_result_display_timeout = Some(self.now().into());
+            self.start_recognition_info_timer();
+        }
+    }
+
+    /// The saved rotation, paused where it left off. One that was running
+    /// when the app closed is offered to be resumed.
+    fn restore_rotation(state: &State) -> (Rotation, Option<Prompt>) {
+        let Some(progress) = state.queue_progress else {
+            return (Rotation::new(state.queue.clone()), None);
+        };
+        let rotation = Rotation::restore(state.queue.clone(), progress, Local::now().timestamp());
+        let prompt = (progress.running && !rotation.entries.is_empty()).then(|| Prompt {
+            kind: PromptKind::ResumeRotation,
+            input: String::new(),
+        });
+        (rotation, prompt)
+    }
+
+    fn save_state(&mut self) {
+        self.state.queue = self.rotation.entries.clone();
+        self.state.queue_progress = (!self.rotation.entries.is_empty())
+            .then(|| self.rotation.progress(self.now().timestamp()));
+        if let Err(err) = self.state.save_to(&self.state_path) {
+            self.log_error(format!("Could not save state: {}", err));
+        }
+    }
+
+    fn enqueue_selected(&mut self) {
+        let (Some(stream_ref), Some(stream)) = (self.views.streams.selection.clone(), self.selected()) else {
+            return;
+        };
+        let title = stream.title.clone();
+        self.rotation.push(QueueEntry {
+            stream: stream_ref,
+            title,
+            minutes: DEFAULT_SLOT_MINUTES,
+        });
+        self.save_state();
+    }
+
+    fn resume_rotation(&mut self) {
+        if let Some(entry) = self.rotation.resume().cloned() {
+            self.log_status("Rotation started");
+            self.save_state();
+            self.play_queue_entry(&entry);
+        }
+    }
+
+    /// Manual stream changes take over from the rotation until it is resumed.
+    fn pause_rotation(&mut self) {
+        if self.rotation.is_running() {
+            self.rotation.pause();
+            self.log_status("Rotation paused, press N to resume");
+            self.save_state();
+        }
+    }
+
+    fn play_queue_entry(&mut self, entry: &QueueEntry) {
+        match self.streams_collection.resolve(&entry.stream).cloned() {
+            Some(stream) => {
+                self.views.streams.selection = Some(entry.stream.clone());
+                self.start_playback(&stream);
+            }
+            None => self.log_status(format!("Queued stream {} is no longer available", entry.title)),
+        }
+    }
+
+    /// Switches to light mode while decoding keeps the CPU busy. Off where
+    /// the decoder's CPU time can't be measured.
+    fn update_decode_load(&mut self) {
+        let Some(busy) = self.player.decode_stats().and_then(|stats| stats.busy()) else {
+            return;
+        };
+        let now = Instant::now();
+        if let Some((sampled_at, busy_before)) = self.decode_load_sample {
+            let wall = now.duration_since(sampled_at).as_secs_f32();
+            if wall > 0.0 {
+                self.decode_load = busy.saturating_sub(busy_before).as_secs_f32() / wall;
+            }
+        }
+        self.decode_load_sample = Some((now, busy));
+
+        if self.decode_load > DECODE_LOAD_THRESHOLD {
+            self.overloaded_ticks += 1;
+            self.recovered_ticks = 0;
+        } else if self.decode_load < DECODE_LOAD_THRESHOLD / 2.0 {
+            self.recovered_ticks += 1;
+            self.overloaded_ticks = 0;
+        }
+
+        if !self.light_mode && self.overloaded_ticks >= DECODE_LOAD_SUSTAIN_TICKS {
+            self.set_light_mode(true);
+            self.log_status(format!(
+                "Decoder using {} CPU, switching to light mode: slower UI ticks, no auto-recognition",
+                format::percent(self.decode_load as f64)
+            ));
+        } else if self.light_mode
+            && !self.forced_light_mode
+            && self.recovered_ticks >= DECODE_LOAD_SUSTAIN_TICKS
+        {
+            self.set_light_mode(false);
+            self.log_status("Decoder load back to normal, leaving light mode");
+        }
+    }
+
+    fn set_light_mode(&mut self, enabled: bool) {
+        self.light_mode = enabled;
+        self.overloaded_ticks = 0;
+        self.recovered_ticks = 0;
+        let interval = if enabled {
+            LIGHT_TICK_INTERVAL_MS
+        } else {
+            TICK_INTERVAL_MS
+        };
+        self.tick_interval.store(interval, Ordering::Relaxed);
+    }
+
+    /// Asks the scheduler whether a run may start, cancelling the scheduled
+    /// run a manual one supersedes. Returns the new run's id and the attempt
+    /// it reports its progress to, on a sample `sample` long.
+    fn begin_recognition(&mut self, trigger: Trigger, sample: Duration) -> Option<(u64, Arc<Attempt>)> {
+        let now = Instant::now();
+        let id = match trigger {
+            Trigger::Manual => self.id_scheduler.manual(now),
+            Trigger::Scheduled => self.id_scheduler.scheduled(now),
+        };
+        let id = match id {
+            Decision::Start(id) => id,
+            Decision::Supersede { start, .. } => {
+                self.recognition_attempt.cancel();
+                self.log_status("Scheduled recognition cancelled for a manual one");
+                start
+            }
+            Decision::Ignore => return None,
+        };
+        self.recognition_attempt = Arc::new(Attempt::new(sample, now));
+        self.recognition_result = None;
+        Some((id, self.recognition_attempt.clone()))
+    }
+
+    /// Cancels the recognition in flight, for Esc or a second `r`. What it
+    /// reports once it stops is dropped.
+    fn cancel_recognition(&mut self) {
+        if self.id_scheduler.cancel_running().is_some() {
+            self.recognition_attempt.cancel();
+            self.show_info(recognition_attempt::CANCELLED.to_string());
+        }
+    }
+
+    fn start_recognition(&mut self, trigger: Trigger) {
+        // Nothing reaches the tap while paused, so the sample would never fill
+        if self.player.paused_for().is_some() {
+            self.show_info("Resume the stream to identify what is playing".to_string());
+            return;
+        }
+        let Some(tap) = self.player.mp3_tap() else {
+            return;
+        };
+        let Some((id, attempt)) = self.begin_recognition(trigger, Duration::from_secs(self.duration)) else {
+            return;
+        };
+        let duration = self.duration;
+        let recognition_result_tx = self.recognition_result_tx.clone();
+        let ui_tx = self.ui_tx.clone();
+        let settings = self.config.recognition.clone();
+        let write_history = !self.incognito;
+        let timestamps = self.config.history_timestamps;
+
+        thread::spawn(move || {
+            let result = recognition::recognize(&tap, duration, &settings, write_history, timestamps, &attempt);
+            let _ = recognition_result_tx.send((id, result));
+            let _ = ui_tx.send(UIMessage::RecognitionResult);
+        });
+    }
+
+    /// Identifies what was playing `lookback_secs` ago from the decoded audio
+    /// still in the tap, for when the track that was wanted has just ended.
+    fn start_recognition_from_past(&mut self) {
+        if self.id_scheduler.running() == Some(Trigger::Manual) {
+            return;
+        }
+        let lookback = Duration::from_secs(self.config.recognition.lookback_secs);
+        let length = Duration::from_secs(self.duration);
+        let Some(clip) = self.player.tap().clip(lookback + length, length) else {
+            self.recognition_result = Some("No audio buffered yet".to_string());
+            return;
+        };
+        let Some((id, attempt)) = self.begin_recognition(Trigger::Manual, length) else {
+            return;
+        };
+        let recognition_result_tx = self.recognition_result_tx.clone();
+        let ui_tx = self.ui_tx.clone();
+        let settings = self.config.recognition.clone();
+        let write_history = !self.incognito;
+        let timestamps = self.config.history_timestamps;
+
+        thread::spawn(move || {
+            let result = recognition::recognize_clip(clip, &settings, write_history, timestamps, &attempt);
+            let _ = recognition_result_tx.send((id, result));
+            let _ = ui_tx.send(UIMessage::RecognitionResult);
+        });
+    }
+
+    /// Turning incognito on closes the listening log entry for what is
+    /// playing; turning it off starts a new one.
+    fn toggle_incognito(&mut self) {
+        self.incognito = !self.incognito;
+        if self.incognito {
+            self.end_listening();
+            self.presence.clear();
+            self.log_status("Incognito on: recognitions stay in this session");
+            return;
+        }
+        let playing_title = self
+            .playing_stream
+            .as_ref()
+            .and_then(|playing| self.streams_collection.resolve(playing))
+            .map(|stream| stream.title.clone());
+        if let Some(title) = playing_title {
+            self.listening_since = Some((title, self.now()));
+        }
+        self.log_status("Incognito off");
+    }
+
+    /// Copies the last recognized track, for pasting into a search.
+    fn copy_last_track(&mut self) {
+        let Some(track) = self.last_track.clone() else {
+            self.show_info("Nothing to copy".to_string());
+            return;
+        };
+        match (self.copy)(&track) {
+            Ok(()) => self.show_info(format!("Copied {}", track)),
+            Err(err) => self.show_info(format!("Could not copy: {}", err)),
+        }
+    }
+
+    /// Copies the tracks recognized since the stream started, with its show
+    /// and date, and saves them in the notes directory.
+    fn share_session(&mut self) {
+        let Some(session) = self.session.clone() else {
+            self.show_info("Nothing played this session".to_string());
+            return;
+        };
+        let tracks = share::tracks(&self.recognition_list, session.since.naive_local());
+        if tracks.is_empty() {
+            self.show_info(format!(
+                "No tracks recognized since {} started at {}",
+                session.stream,
+                session.since.format("%H:%M")
+            ));
+            return;
+        }
+        let count = tracks.len();
+        let template = self.config.share.template;
+        let text = share::build(&session, &tracks, template);
+        let saved = share::notes_dir(&self.config.share)
+            .ok_or_else(|| "no data directory".to_string())
+            .and_then(|dir| share::write(&dir, &text, template, self.now()).map_err(|err| err.to_string()));
+        let info = match ((self.copy)(&text), saved) {
+            (Ok(()), Ok(path)) => format!("Copied {} tracks, saved to {}", count, path.display()),
+            (Ok(()), Err(err)) => format!("Copied {} tracks, could not save them: {}", count, err),
+            (Err(err), Ok(path)) => format!("Could not copy: {}; saved {} tracks to {}", err, count, path.display()),
+            (Err(err), Err(_)) => format!("Could not copy: {}", err),
+        };
+        self.show_info(info);
+    }
+
+    fn reveal_last_sample(&mut self) {
+        let Some(sample) = self.last_saved_sample.clone() else {
+            return;
+        };
+        let opened = samples::reveal(&sample).is_ok();
+        self.show_info(if opened {
+            format!("Opened {}", sample.display())
+        } else {
+            format!("Sample saved at {}", sample.display())
+        });
+    }
+
+    fn start_recognition_info_timer(&self) {
+        let ui_tx = self.ui_tx.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(RECOGNITION_INFO_TIMER));
+            let _ = ui_tx.send(UIMessage::UpdateUI);
+        });
+    }
+    
+    fn handle_recognition_result(&mut self) {
+        if let Ok((id, result)) = self.recognition_result_rx.try_recv() {
+            if !self.id_scheduler.finished(id) {
+                // Superseded by a manual run, which reports on its own
+                return;
+            }
+            let outcome = match result {
+                Ok(outcome) => outcome,
+                Err(err) => {
+                    self.id_scheduler.report(false, Instant::now());
+                    self.log_error(format!("Recognition failed: {}", err));
+                    self.pending_ids = recognition::pending_count(self.config.retention.pending_id_max_age());
+                    self.recognition_result = Some(err);
+                    self.recognition_result_display_timeout = Some(self.now().into());
+                    self.start_recognition_info_timer();
+                    return;
+                }
+            };
+            self.id_scheduler.report(outcome.track.is_some(), Instant::now());
+            self.recognition_result = Some(outcome.text);
+            if outcome.track.is_some() {
+                self.last_track = outcome.track.clone();
+            }
+            self.retry_pending_ids();
+            // Kept for the session or written to the file as the run was told
+            // when it started, so toggling incognito meanwhile can't do both
+            // or neither
+            match &outcome.track {
+                Some(track) if !outcome.write_history => {
+                    let entry = history::format_entry(self.now().naive_local(), self.config.history_timestamps, track, track);
+                    self.incognito_entries.push_str(&format!("{}\n", entry));
+                }
+                Some(track) if !self.incognito => self.presence.track(track),
+                _ => {}
+            }
+            if outcome.saved_sample.is_some() {
+                self.last_saved_sample = outcome.saved_sample;
+            }
+            self.history_written.extend(outcome.history_line);
+            self.history_changed();
+            self.recognition_result_display_timeout = Some(self.now().into());
+            self.start_recognition_info_timer();
+        }
+    }
+
+    /// Identifies the recognitions queued while offline on a worker thread,
+    /// now that the network answers again.
+    fn retry_pending_ids(&mut self) {
+        if self.pending_ids == 0 || self.retrying_pending_ids {
+            return;
+        }
+        self.retrying_pending_ids = true;
+        let settings = self.config.recognition.clone();
+        let timestamps = self.config.history_timestamps;
+        let max_age = self.config.retention.pending_id_max_age();
+        let ui_tx = self.ui_tx.clone();
+        thread::spawn(move || {
+            let (matches, pending) = recognition::retry_pending(&settings, timestamps, max_age);
+            let _ = ui_tx.send(UIMessage::PendingIdsRetried(matches, pending));
+        });
+    }
+
+    fn handle_pending_ids_retried(&mut self, matches: Vec<PendingMatch>, pending: usize) {
+        self.retrying_pending_ids = false;
+        self.pending_ids = pending;
+        if matches.is_empty() {
+            return;
+        }
+        // One bulk change: the pane is brought up to date once at the end
+        self.history_refresh.suspend();
+        for found in matches {
+            if !found.write_history {
+                let entry = history::format_entry(found.heard_at.naive_local(), self.config.history_timestamps, &found.track, &found.track);
+                self.incognito_entries.push_str(&format!("{}\n", entry));
+            }
+            self.log_status(format!("Identified {} (heard at {})", found.track, found.heard_at.format("%H:%M")));
+            self.history_written.extend(found.history_line);
+            self.history_changed();
+        }
+        let turn = self.history_refresh.resume(Instant::now());
+        self.take_history_turn(turn);
+    }
+
+    /// Handles messages until the user quits, then shuts down.
+    fn run<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        ui_rx: &Receiver<UIMessage>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        while self.handle_queued(terminal, ui_rx)? {}
+        self.shut_down(terminal)
+    }
+
+    /// Applies everything that queued up while the last frame was drawn, in
+    /// order, then draws once so key repeat and resize storms don't lag. The
+    /// history pane is rebuilt once for all of it. Returns false once the
+    /// user quits.
+    fn handle_queued<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        ui_rx: &Receiver<UIMessage>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let message = ui_rx.recv()?;
+        self.history_refresh.suspend();
+        self.handle_message(message)?;
+        while let Ok(message) = ui_rx.try_recv() {
+            self.handle_message(message)?;
+        }
+        let turn = self.history_refresh.resume(Instant::now());
+        self.take_history_turn(turn);
+        if self.quitting {
+            return Ok(false);
+        }
+        self.render_ui(terminal)?;
+        Ok(true)
+    }
+
+    /// Stops playback, tells workers to wrap up and waits for critical writes
+    /// like the history append to land, showing a notice if that takes a
+    /// moment. Anything still running after the timeout is abandoned.
+    fn shut_down<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<(), Box<dyn std::error::Error>> {
+        shutdown::request();
+        self.cancel_connect();
+        self.stop();
+        // An unanswered resume prompt leaves the saved rotation as it was
+        let resume_pending = self
+            .prompt
+            .as_ref()
+            .is_some_and(|prompt| prompt.kind == PromptKind::ResumeRotation);
+        if !self.rotation.entries.is_empty() && !resume_pending {
+            self.save_state();
+        }
+        let timeout = Duration::from_millis(self.config.shutdown.timeout_ms);
+        if shutdown::wait_for_critical(timeout.min(SHUTDOWN_NOTICE_DELAY)) {
+            return Ok(());
+        }
+        terminal.draw(|f| self.draw_shutting_down(f))?;
+        shutdown::wait_for_critical(timeout.saturating_sub(SHUTDOWN_NOTICE_DELAY));
+        Ok(())
+    }
+
+    /// A resize to the size already drawn at changes nothing for ratatui,
+    /// but is what a tmux reattach or a reconnected SSH session sends, often
+    /// over a screen that no longer shows what ratatui thinks it does.
+    fn handle_resize(&mut self, width: u16, height: u16) {
+        let drawn = self.frame_area.get();
+        if (drawn.width, drawn.height) == (width, height) {
+            self.clear_screen = true;
+        }
+    }
+
+    fn render_ui<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<(), Box<dyn std::error::Error>> {
+        self.expire_display_timeouts();
+        if self.clear_screen {
+            self.clear_screen = false;
+            terminal.clear()?;
+        }
+        let mut render_error = None;
+        terminal.draw(|f| {
+            RENDERING.with(|rendering| rendering.set(true));
+            let result = panic::catch_unwind(AssertUnwindSafe(|| (self.draw)(self, f)));
+            RENDERING.with(|rendering| rendering.set(false));
+            if let Err(payload) = result {
+                self.draw_fallback(f);
+                render_error = Some(panic_message(payload.as_ref()));
+            }
+        })?;
+
+        match render_error {
+            Some(message) if !self.render_failed => {
+                self.render_failed = true;
+                self.log_error(format!("Render error: {}", message));
+            }
+            Some(_) => {}
+            None => self.render_failed = false,
+        }
         Ok(())
     }
 
-    fn handle_key_press(&mut self, key: KeyEvent) -> Result<(), Box<dyn std::error::Error>> {
-        match key.code {
-            KeyCode::Char('q') => {
-                self.stop();
-                disable_raw_mode()?;
-                execute!(io::stdout(), LeaveAlternateScreen)?;
-                std::process::exit(0);
-            }
-            KeyCode::Down => {
-                self.selected_stream_index =
-                    (self.selected_stream_index + 1) % (self.streams_collection.mixtapes.len() + 2)
+    fn expire_display_timeouts(&mut self) {
+        let now: SystemTime = self.now().into();
+        let expired = |timeout: Option<SystemTime>, secs: u64| {
+            timeout.is_some_and(|timeout| {
+                now.duration_since(timeout).unwrap_or_default() >= Duration::from_secs(secs)
+            })
+        };
+        if expired(self.recognition_result_display_timeout, RECOGNITION_INFO_TIMER) {
+            self.recognition_result_display_timeout = None;
+        }
+        if expired(self.duration_display_timeout, DURATION_INFO_TIMER) {
+            self.duration_display_timeout = None;
+        }
+        if expired(self.volume_display_timeout, VOLUME_INFO_TIMER) {
+            self.volume_display_timeout = None;
+        }
+        if expired(self.announcement_display_timeout, ANNOUNCEMENT_BANNER_TIMER) {
+            self.announcement_display_timeout = None;
+            self.announcement = None;
+        }
+    }
+
+    /// The banner across the top: a show change, or that there are no streams.
+    fn banner(&self) -> Option<&str> {
+        let streams_banner = match self.streams_load {
+            StreamsLoad::Loaded => None,
+            StreamsLoad::Failed => Some("Could not load streams — press u to retry"),
+            StreamsLoad::Retrying => Some("Loading streams…"),
+        };
+        self.announcement.as_deref().or(streams_banner)
+    }
+
+    fn screen_layout(&self, area: Rect) -> ScreenLayout {
+        // The panes start below the banner rather than under it
+        let area = match self.banner() {
+            Some(_) => Rect {
+                y: area.y + (BANNER_HEIGHT - 1).min(area.height),
+                height: area.height.saturating_sub(BANNER_HEIGHT - 1),
+                ..area
+            },
+            None => area,
+        };
+        let has_show_times = self
+            .streams_collection
+            .stations
+            .iter()
+            .any(|station| station.airtime.is_some_and(|airtime| airtime.end.is_some()));
+        let main_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints(
+                [
+                    // Every station's row, whatever the height
+                    Constraint::Length(self.streams_collection.stations.len().max(1) as u16 + 2),
+                    // Kept while not shown, so selecting a mixtape doesn't
+                    // move everything under the mouse
+                    Constraint::Length(has_show_times as u16),
+                    Constraint::Fill(1),
+                    Constraint::Fill(1),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let top_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(25), Constraint::Percentage(50), Constraint::Percentage(25)].as_ref())
+            .split(main_chunks[2]);
+
+        let bottom_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            // Info always keeps a row under its title for what it has to say
+            .constraints([Constraint::Fill(5), Constraint::Min(2), Constraint::Fill(4)].as_ref())
+            .split(main_chunks[3]);
+
+        let extra_sections: Vec<StreamType> = [
+            (StreamType::Episode, &self.streams_collection.episodes),
+            (StreamType::Custom, &self.streams_collection.custom),
+            (StreamType::Archive, &self.streams_collection.archive),
+        ]
+        .into_iter()
+        .filter(|(_, streams)| !streams.is_empty())
+        .map(|(section, _)| section)
+        .collect();
+        let (mixtapes, extra) = if extra_sections.is_empty() {
+            (top_chunks[0], Vec::new())
+        } else {
+            let extra_share = 40 / extra_sections.len() as u16;
+            let mut constraints = vec![Constraint::Percentage(100 - extra_share * extra_sections.len() as u16)];
+            constraints.extend(extra_sections.iter().map(|_| Constraint::Percentage(extra_share)));
+            let column = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(constraints)
+                .split(top_chunks[0]);
+            (column[0], extra_sections.into_iter().zip(column.iter().skip(1).copied()).collect())
+        };
+
+        ScreenLayout {
+            stations: main_chunks[0],
+            show_gauge: has_show_times.then_some(main_chunks[1]),
+            mixtapes,
+            extra,
+            description: top_chunks[1],
+            queue: top_chunks[2],
+            history: bottom_chunks[0],
+            info: bottom_chunks[1],
+            controls: bottom_chunks[2],
+        }
+    }
+
+    /// A click on a stream selects it and a second one plays it; the wheel
+    /// over the history scrolls it like j and k. Anything else is ignored.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let area = self.frame_area.get();
+        if self.prompt.is_some()
+            || self.archive.is_some()
+            || area.width < MIN_TERMINAL_WIDTH
+            || area.height < MIN_TERMINAL_HEIGHT
+        {
+            return;
+        }
+        let layout = self.screen_layout(area);
+        let position = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(clicked) = self.stream_at(&layout, position) else {
+                    return;
+                };
+                if self.views.streams.selection.as_ref() == Some(&clicked) {
+                    self.tune_in();
+                } else {
+                    self.views.streams.selection = Some(clicked);
+                    self.views.streams.scroll = 0;
+                }
+            }
+            MouseEventKind::ScrollDown if layout.history.contains(position) => {
+                self.views.history.scroll = self.views.history.scroll.saturating_add(1)
+            }
+            MouseEventKind::ScrollUp if layout.history.contains(position) => {
+                self.views.history.scroll = self.views.history.scroll.saturating_sub(1)
+            }
+            _ => {}
+        }
+    }
+
+    /// The stream drawn at `position`, if any. The lists aren't scrolled, so
+    /// the rows under a pane's title are its streams in order.
+    fn stream_at(&self, layout: &ScreenLayout, position: Position) -> Option<StreamRef> {
+        let filter = self.views.streams.filter.to_lowercase();
+        let mut panes = vec![(StreamType::Station, layout.stations), (StreamType::Mixtape, layout.mixtapes)];
+        panes.extend(layout.extra.iter().copied());
+        let (section, rows) = panes
+            .into_iter()
+            .map(|(section, pane)| (section, create_block("", &self.theme).inner(pane)))
+            .find(|(_, rows)| rows.contains(position))?;
+        let row = (position.y - rows.y) as usize;
+        let stream = match section {
+            StreamType::Station => self.streams_collection.stations.get(row),
+            StreamType::Mixtape => self
+                .streams_collection
+                .listed_mixtapes(&self.state.favorites)
+                .into_iter()
+                .filter(|mixtape| mixtape.matches(&filter))
+                .nth(row),
+            StreamType::Episode => self.streams_collection.episodes.get(row),
+            StreamType::Custom => self.streams_collection.custom.get(row),
+            StreamType::Archive => self.streams_collection.archive.get(row),
+        }?;
+        Some(StreamRef {
+            section,
+            id: stream.id.clone(),
+        })
+    }
+
+    fn draw(&self, f: &mut Frame) {
+        if f.area().width < MIN_TERMINAL_WIDTH || f.area().height < MIN_TERMINAL_HEIGHT {
+            self.draw_too_small(f);
+            return;
+        }
+
+        self.frame_area.set(f.area());
+        let layout = self.screen_layout(f.area());
+
+        let create_list_item = |prefix: &str, title: &str, is_selected: bool, accent: Option<Color>, badge: Option<&str>| {
+            let style = if is_selected {
+                Style::default().fg(self.theme.stream).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(self.theme.stream)
+            };
+            let mut spans = Vec::new();
+            if !prefix.is_empty() {
+                spans.push(Span::styled(prefix.to_string(), Style::default().fg(self.theme.muted)));
+            }
+            if let Some(accent) = accent {
+                spans.push(Span::styled(COLOR_IDENTITY_GLYPH, Style::default().fg(accent)));
+            }
+            if is_selected {
+                spans.push(Span::styled(title.to_string() + " •", style));
+            } else {
+                spans.push(Span::styled(title.to_string(), style));
+            }
+            if let Some(badge) = badge {
+                spans.push(Span::styled(format!(" {}", badge), Style::default().fg(self.theme.highlight).bold()));
+            }
+            ListItem::new(vec![Line::from(spans)])
+        };
+
+        // Create list items for mixtapes and stations
+        let filter = self.views.streams.filter.to_lowercase();
+        let mut stream_items_mixtapes: Vec<ListItem> = self.streams_collection
+            .listed_mixtapes(&self.state.favorites)
+            .into_iter()
+            .filter(|mixtape| mixtape.matches(&filter))
+            .enumerate()
+            .map(|(i, mixtape)| {
+                // The key that selects it, for the first few
+                let number = i + FIRST_MIXTAPE_NUMBER;
+                let prefix = if number <= 9 { format!("{} ", number) } else { "  ".to_string() };
+                create_list_item(
+                    &prefix,
+                    &mixtape.title,
+                    self.is_selected(StreamType::Mixtape, mixtape),
+                    self.color_support.identity_color(&mixtape.title),
+                    is_favorite(&self.state.favorites, mixtape).then_some(FAVORITE_MARKER),
+                )
+            })
+            .collect();
+
+        // "NTS 1 · <show>", the show dimmed and cut to fit. Room for the
+        // selection marker is kept on every row so titles don't jump.
+        let station_width = layout.stations.width.saturating_sub(2) as usize;
+        let stream_items_stations: Vec<ListItem> = self.streams_collection
+            .stations
+            .iter()
+            .enumerate()
+            .map(|(i, station)| {
+                let is_selected = self.is_selected(StreamType::Station, station);
+                let name = format!("NTS {}", i + 1);
+                let style = if is_selected {
+                    Style::default().fg(self.theme.stream).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(self.theme.stream)
+                };
+                let mut spans = vec![Span::styled(name.clone(), style)];
+                let room = station_width.saturating_sub(name.len() + " · ".len() + " •".chars().count());
+                if !station.subtitle.is_empty() && room > 0 {
+                    spans.push(Span::styled(" · ", Style::default().fg(self.theme.muted)));
+                    spans.push(Span::styled(
+                        format::truncate(&station.subtitle, room),
+                        Style::default().fg(self.theme.muted),
+                    ));
+                }
+                if is_selected {
+                    spans.push(Span::styled(" •", style));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+
+        // A section that failed to fetch says so, over its cached rows if any
+        let section_title = |section, title: &str, shown: &mut Vec<ListItem>| {
+            self.streams_collection.section_error(section)?;
+            if shown.is_empty() {
+                shown.push(
+                    ListItem::new(format!("{} unavailable — retrying", title)).style(Style::default().fg(self.theme.muted)),
+                );
+                return None;
+            }
+            Some(format!("{} (cached, retrying)", title))
+        };
+        let mut stream_items_stations = stream_items_stations;
+        let stations_title = section_title(StreamType::Station, "Stations", &mut stream_items_stations);
+
+        // Render live stations list
+        let live_stations_list = List::new(stream_items_stations)
+            .block(create_block(stations_title.as_deref().unwrap_or("Stations"), &self.theme))
+            .highlight_style(
+                Style::default()
+                    .fg(self.theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        f.render_widget(live_stations_list, layout.stations);
+        if let (Some(area), Some((title, airtime))) = (layout.show_gauge, self.gauge_station()) {
+            f.render_widget(self.show_gauge(title, airtime), area);
+        }
+
+        // Render mixtape list
+        let mixtapes_title = if filter.is_empty() {
+            section_title(StreamType::Mixtape, "Mixtapes", &mut stream_items_mixtapes)
+        } else {
+            None
+        };
+        if stream_items_mixtapes.is_empty() && !filter.is_empty() {
+            stream_items_mixtapes.push(ListItem::new("no results").style(Style::default().fg(self.theme.muted)));
+        }
+        let mixtape_list = List::new(stream_items_mixtapes)
+            .block(create_block(mixtapes_title.as_deref().unwrap_or("Mixtapes"), &self.theme))
+            .highlight_style(
+                Style::default()
+                    .fg(self.theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        // Followed shows, custom URLs and archive episodes share the mixtape
+        // column, each only once there are any
+        let mut extra_sections = Vec::new();
+        if !self.streams_collection.episodes.is_empty() {
+            let episode_items: Vec<ListItem> = self
+                .streams_collection
+                .episodes
+                .iter()
+                .map(|episode| {
+                    create_list_item(
+                        "",
+                        &episode.title,
+                        self.is_selected(StreamType::Episode, episode),
+                        None,
+                        self.is_new_episode(&episode.id).then_some("new"),
+                    )
+                })
+                .collect();
+            extra_sections.push(("Followed shows", episode_items));
+        }
+        if !self.streams_collection.custom.is_empty() {
+            let custom_items: Vec<ListItem> = self
+                .streams_collection
+                .custom
+                .iter()
+                .map(|custom| create_list_item("", &custom.title, self.is_selected(StreamType::Custom, custom), None, None))
+                .collect();
+            extra_sections.push(("Custom", custom_items));
+        }
+        if !self.streams_collection.archive.is_empty() {
+            let archive_items: Vec<ListItem> = self
+                .streams_collection
+                .archive
+                .iter()
+                .map(|episode| create_list_item("", &episode.title, self.is_selected(StreamType::Archive, episode), None, None))
+                .collect();
+            extra_sections.push(("Archive", archive_items));
+        }
+        for ((title, items), (_, area)) in extra_sections.into_iter().zip(&layout.extra) {
+            f.render_widget(List::new(items).block(create_block(title, &self.theme)), *area);
+        }
+        f.render_widget(mixtape_list, layout.mixtapes);
+
+        let (description, subtitle, schedule, airtime) = self
+            .selected()
+            .map(|stream| (stream.description.clone(), stream.subtitle.clone(), stream.schedule.clone(), stream.airtime))
+            .unwrap_or_default();
+        let accent = match &self.views.streams.selection {
+            Some(selected) if selected.section == StreamType::Mixtape => self
+                .selected()
+                .and_then(|mixtape| self.color_support.identity_color(&mixtape.title)),
+            _ => None,
+        };
+
+        // Render description, under the idle screen when nothing is playing
+        let mut subtitle_spans = Vec::new();
+        if let Some(accent) = accent {
+            subtitle_spans.push(Span::styled(COLOR_IDENTITY_GLYPH, Style::new().fg(accent)));
+        }
+        subtitle_spans.push(Span::styled(
+            subtitle.clone(),
+            Style::new().fg(accent.unwrap_or(self.theme.description)).italic(),
+        ));
+        let mut description_lines = vec![Line::from(subtitle_spans)];
+        if let Some(airtime) = airtime {
+            description_lines.push(self.show_progress_line(airtime, layout.description.width.saturating_sub(2)));
+        }
+        if self.views.streams.selection.as_ref().is_some_and(|selected| selected.section == StreamType::Station) {
+            description_lines.push(self.now_next_line(&subtitle, airtime, schedule.first()));
+        }
+        description_lines.extend(self.stream_health_line());
+        description_lines.extend([
+            Line::from(Span::styled("", Style::new().fg(self.theme.description))),
+            Line::from(Span::styled(description, Style::new().fg(self.theme.description))),
+        ]);
+        if !schedule.is_empty() {
+            let rule_width = layout.description.width.saturating_sub(2) as usize;
+            description_lines.push(Line::from(""));
+            description_lines.push(Line::styled("─".repeat(rule_width), Style::new().fg(self.theme.muted)));
+            for show in schedule {
+                description_lines.push(Line::from(vec![
+                    Span::styled(show.start.format("%H:%M  ").to_string(), Style::new().fg(self.theme.muted)),
+                    Span::styled(show.title, Style::new().fg(self.theme.description)),
+                ]));
+            }
+        }
+        if self.show_debug_overlay {
+            let rule_width = layout.description.width.saturating_sub(2) as usize;
+            description_lines.push(Line::from(""));
+            description_lines.push(Line::styled("─".repeat(rule_width), Style::new().fg(self.theme.muted)));
+            description_lines.extend(self.stream_debug_lines());
+        }
+        let description_paragraph = Paragraph::new(description_lines)
+            .wrap(Wrap { trim: true })
+            .scroll((self.views.streams.scroll as u16, 0));
+
+        if self.playing_stream.is_none() {
+            let title = format!("Nothing playing · {}", self.now().format("%H:%M:%S"));
+            let block = create_block(&title, &self.theme);
+            let inner = block.inner(layout.description);
+            f.render_widget(block, layout.description);
+            let idle = self.idle_lines(inner.width as usize, inner.height as usize, self.selected().is_some());
+            let [idle_area, description_area] =
+                Layout::vertical([Constraint::Length(idle.len() as u16), Constraint::Fill(1)]).areas(inner);
+            f.render_widget(Paragraph::new(idle).alignment(Alignment::Center), idle_area);
+            f.render_widget(description_paragraph, description_area);
+        } else {
+            f.render_widget(description_paragraph.block(create_block("Description", &self.theme)), layout.description);
+        }
+
+        // Render queue
+        f.render_widget(self.queue_list(), layout.queue);
+
+        // Render recognition list
+        let recognition_list: Vec<Line> = self
+            .history_rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let (timestamp, mut entry) = history::split_timestamp(&row.text);
+                let mut spans = vec![Span::styled(timestamp, Style::default().fg(self.theme.muted))];
+                // A dot after the time marks an entry from the same minute as
+                // the one above, kept in the order it was written
+                if row.same_minute {
+                    spans.push(Span::styled("·", Style::default().fg(self.theme.muted)));
+                    entry = entry.strip_prefix(' ').unwrap_or(entry);
+                }
+                spans.push(Span::styled(entry, Style::default().fg(self.theme.text)));
+                let line = Line::from(spans);
+                if self.views.is_focused(View::History) && i == self.views.history.scroll {
+                    line.patch_style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect();
+        let mut vertical_scroll_state = ScrollbarState::new(self.history_rows.len()).position(self.views.history.scroll);
+        let mut recognition_list_block = create_block("Recognized Tracks", &self.theme);
+        if self.views.is_focused(View::History) {
+            recognition_list_block = recognition_list_block.border_style(Style::default().fg(self.theme.highlight));
+        }
+
+        let recognition_list_paragraph = Paragraph::new(recognition_list)
+            .block(recognition_list_block).style(Style::default().fg(self.theme.text))
+            .wrap(Wrap { trim: true }).scroll((self.views.history.scroll as u16, 0));
+
+        f.render_widget(recognition_list_paragraph, layout.history);
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(Some("↑"))
+                .end_symbol(Some("↓")),
+            layout.history, &mut vertical_scroll_state);
+
+        // Render recognition info
+        let mut recognition_info_text = String::new();
+        if let Some(connecting) = self.connecting.pending() {
+            let waited = connecting.phase_started.elapsed().as_secs();
+            recognition_info_text = match connecting.phase {
+                ConnectPhase::Connecting => match &self.auto_reconnect {
+                    Some((_, backoff)) => format!("Reconnecting (attempt {})… {}s", backoff.attempts(), waited),
+                    None => format!("Connecting to {}… {}s", connecting.title, waited),
+                },
+                ConnectPhase::WaitingForAudio => format!("Waiting for audio… {}s", waited),
+                ConnectPhase::Prebuffering(percent) => format!("Prebuffering {}%", percent),
+            } + " (Esc to cancel)";
+        } else if self.id_scheduler.running() == Some(Trigger::Manual) {
+            recognition_info_text = self.recognition_attempt.describe() + " (Esc to cancel)";
+        } else if let Some(result) = self.recognition_result.as_ref().filter(|_| self.recognition_result_display_timeout.is_some()) {
+            recognition_info_text = result.clone();
+        } else if let Some(lost) = &self.stream_lost {
+            recognition_info_text = lost.clone();
+        }
+        if self.pending_ids > 0 {
+            let pending = format!("{} ID{} pending", self.pending_ids, if self.pending_ids == 1 { "" } else { "s" });
+            recognition_info_text = [recognition_info_text, pending].join("\n").trim_start().to_string();
+        }
+        let mut info_block = create_block("Info", &self.theme);
+        if let Some(indicator) = self.id_scheduler.indicator(Instant::now()) {
+            let color = match indicator {
+                Indicator::Failed => self.theme.muted,
+                _ => self.theme.highlight,
+            };
+            info_block = info_block.title_top(Line::styled(indicator.label(), Style::new().fg(color).bold()).right_aligned());
+        }
+        let recognition_info_paragraph = Paragraph::new(recognition_info_text)
+            .block(info_block).style(Style::default().fg(self.theme.text))
+            .wrap(Wrap { trim: true });
+        f.render_widget(recognition_info_paragraph, layout.info);
+
+        // Render controls
+        let controls = keymap::controls_line();
+        let mut controls_text = controls.clone();
+        if self.incognito {
+            controls_text = format!("INCOGNITO: recognitions are not saved (i ends it)\n{}", controls);
+        }
+        if self.duration_display_timeout.is_some() {
+            controls_text = format!(
+                "{}\nDuration: {}",
+                controls_text,
+                format::duration_words(Duration::from_secs(self.duration))
+            );
+        }
+        if self.volume_display_timeout.is_some() {
+            controls_text = format!("{}\nVolume: {}", controls_text, format::percent(self.player.volume() as f64));
+        }
+        if let Some(status) = self.id_status() {
+            controls_text = format!("{}\n{}", controls_text, status);
+        }
+        if let Some(paused) = self.player.paused_for() {
+            let resume = if paused >= STALE_PAUSE { "p reconnects" } else { "p resumes" };
+            controls_text = format!("{}\nPaused {} · {}", controls_text, format::clock(paused), resume);
+        }
+        let controls_paragraph = Paragraph::new(controls_text).block(create_block("Controls", &self.theme)).style(Style::default().fg(self.theme.muted)).wrap(Wrap { trim: true });
+        f.render_widget(controls_paragraph, layout.controls);
+
+        // Render debug overlay
+        if self.show_debug_overlay {
+            let area = top_right_rect(48, 11, f.area());
+            f.render_widget(Clear, area);
+            f.render_widget(self.debug_overlay_paragraph(), area);
+        }
+
+        // Render show change banner, or the one saying there are no streams
+        if let Some(announcement) = self.banner() {
+            let area = Rect::new(f.area().x, f.area().y, f.area().width, BANNER_HEIGHT.min(f.area().height));
+            f.render_widget(Clear, area);
+            f.render_widget(
+                Paragraph::new(announcement.to_string())
+                    .block(Block::default().borders(Borders::ALL))
+                    .style(Style::new().fg(self.theme.heading).bold())
+                    .alignment(Alignment::Center),
+                area,
+            );
+        }
+
+        // Render prompt
+        if let Some(prompt) = &self.prompt {
+            let area = f.area();
+            let line_area = Rect::new(area.x, area.bottom().saturating_sub(1), area.width, 1.min(area.height));
+            let label = match prompt.kind {
+                PromptKind::HistoryDate => "Jump to date (YYYY-MM): ",
+                PromptKind::CustomUrl => "Play URL: ",
+                PromptKind::StreamFilter => "Filter mixtapes: ",
+                PromptKind::ResumeRotation => "Resume the rotation where it left off? (y/n) ",
+                PromptKind::ArchiveShow => "Browse the archive of (show alias or link): ",
+                PromptKind::Export => "Export the history to (.csv or .json): ",
+            };
+            f.render_widget(Clear, line_area);
+            f.render_widget(
+                Paragraph::new(Line::from(vec![
+                    Span::styled(label, Style::new().fg(self.theme.heading).bold()),
+                    Span::raw(format!("{}▏", prompt.input)),
+                ])),
+                line_area,
+            );
+        }
+
+        // Render the hint after the first press of a guarded key
+        if let Some(hint) = self.guard.hint(Instant::now()).filter(|_| self.prompt.is_none()) {
+            let area = f.area();
+            let line_area = Rect::new(area.x, area.bottom().saturating_sub(1), area.width, 1.min(area.height));
+            f.render_widget(Clear, line_area);
+            f.render_widget(
+                Paragraph::new(hint).style(Style::new().fg(self.theme.heading).bold()).alignment(Alignment::Center),
+                line_area,
+            );
+        }
+
+        // Render status log popup
+        if self.show_status_log {
+            let area = centered_rect(70, 60, f.area());
+            f.render_widget(Clear, area);
+            f.render_widget(self.status_log_paragraph(), area);
+        }
+
+        // Render digest popup
+        if self.digest.is_some() {
+            let area = centered_rect(80, 80, f.area());
+            f.render_widget(Clear, area);
+            f.render_widget(self.digest_paragraph(), area);
+        }
+
+        // Render audio diagnostics popup
+        if self.diagnostics.is_some() {
+            let area = centered_rect(70, 40, f.area());
+            f.render_widget(Clear, area);
+            f.render_widget(self.diagnostics_paragraph(), area);
+        }
+
+        // Render archive popup
+        if let Some(archive) = &self.archive {
+            let area = centered_rect(80, 80, f.area());
+            f.render_widget(Clear, area);
+            self.draw_archive(f, archive, area);
+        }
+
+        // Render migration popup
+        if let Some(moves) = &self.migration {
+            let area = centered_rect(80, 50, f.area());
+            f.render_widget(Clear, area);
+            f.render_widget(self.migration_paragraph(moves), area);
+        }
+
+        // Render about popup
+        if self.show_about {
+            let area = centered_rect(70, 60, f.area());
+            f.render_widget(Clear, area);
+            f.render_widget(self.about_paragraph(), area);
+        }
+
+        // Render help popup
+        if self.show_help {
+            let area = centered_rect(80, 90, f.area());
+            f.render_widget(Clear, area);
+            f.render_widget(self.help_paragraph(), area);
+        }
+    }
+
+    /// Every key from the keymap, under its group.
+    fn help_paragraph(&self) -> Paragraph<'static> {
+        let width = keymap::listed().map(|binding| binding.label.len()).max().unwrap_or_default();
+        let mut lines = Vec::new();
+        for group in keymap::Group::ALL {
+            if !lines.is_empty() {
+                lines.push(Line::from(""));
             }
-            KeyCode::Up => {
-                self.selected_stream_index =
-                    (self.selected_stream_index + self.streams_collection.mixtapes.len() + 1)
-                        % (self.streams_collection.mixtapes.len() + 2)
+            lines.push(Line::styled(group.title(), Style::new().fg(self.theme.heading).bold()));
+            for binding in keymap::listed().filter(|binding| binding.group == group) {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{:<width$}  ", binding.label), Style::new().fg(self.theme.highlight)),
+                    Span::raw(binding.description),
+                ]));
             }
-            KeyCode::Enter => {
-                if self.selected_stream_index <= 1 {
-                    self.play(StreamType::Station);
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Any key: close", Style::new().fg(self.theme.muted))));
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled("Keys", Style::new().fg(self.theme.heading).bold())),
+            )
+            .wrap(Wrap { trim: false })
+    }
+
+    /// Opens the digest of the last seven days, built from the same files as
+    /// `nts_cli digest`, or closes it.
+    fn toggle_digest(&mut self) {
+        if self.digest.take().is_some() {
+            return;
+        }
+        let (from, to) = digest::last_week(self.now().date_naive());
+        let mut digest = digest::build(&self.recognition_list, &fs::read_to_string(&self.listening_log).unwrap_or_default(), from, to);
+        digest.push_str(&recognition_metrics::report(&recognition_metrics::parse(&recognition_metrics::read())));
+        self.digest = Some(digest);
+    }
+
+    fn digest_paragraph(&self) -> Paragraph<'_> {
+        let mut lines: Vec<Line> = self
+            .digest
+            .iter()
+            .flat_map(|digest| digest.lines())
+            .map(|line| {
+                if line.starts_with('#') {
+                    Line::styled(line, Style::new().fg(self.theme.heading).bold())
                 } else {
-                    self.play(StreamType::Mixtape);
+                    Line::raw(line)
+                }
+            })
+            .collect();
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Full digest: nts_cli digest --week | D/Esc: close",
+            Style::new().fg(self.theme.muted),
+        )));
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled("Weekly digest", Style::new().fg(self.theme.heading).bold())),
+            )
+            .wrap(Wrap { trim: false })
+    }
+
+    /// The archive popup: the episodes matching the search, newest first,
+    /// with the ones that can't be streamed greyed out, over a status line.
+    fn draw_archive(&self, f: &mut Frame, archive: &archive::Archive, area: Rect) {
+        let count = match archive.total {
+            Some(total) if total > archive.episodes.len() => format!(" ({} of {})", archive.episodes.len(), total),
+            Some(total) => format!(" ({})", total),
+            None => String::new(),
+        };
+        let block = Block::default().borders(Borders::ALL).title(Span::styled(
+            format!("Archive: {}{}", archive.show_name, count),
+            Style::new().fg(self.theme.heading).bold(),
+        ));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let [list_area, status_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(inner);
+
+        let items: Vec<ListItem> = archive
+            .visible()
+            .into_iter()
+            .map(|episode| {
+                let date = Span::styled(format!("{}  ", episode.date()), Style::new().fg(self.theme.muted));
+                if episode.audio_url.is_some() {
+                    ListItem::new(Line::from(vec![date, Span::styled(episode.name.clone(), Style::new().fg(self.theme.stream))]))
+                } else {
+                    let muted = Style::new().fg(self.theme.muted);
+                    ListItem::new(Line::from(vec![
+                        date,
+                        Span::styled(episode.name.clone(), muted),
+                        Span::styled(" (Mixcloud only)", muted.italic()),
+                    ]))
+                }
+            })
+            .collect();
+        let list = List::new(items)
+            .highlight_style(Style::new().fg(self.theme.highlight).bold())
+            .highlight_symbol("> ");
+        let mut state = ListState::default()
+            .with_offset(archive.offset.get())
+            .with_selected(Some(archive.selected));
+        f.render_stateful_widget(list, list_area, &mut state);
+        archive.offset.set(state.offset());
+
+        let status = if archive.searching {
+            Line::from(vec![
+                Span::styled("Search: ", Style::new().fg(self.theme.heading)),
+                Span::raw(format!("{}▏", archive.search)),
+            ])
+        } else if let Some(err) = &archive.error {
+            Line::styled(format!("Could not load episodes: {}", err), Style::new().fg(self.theme.muted))
+        } else if archive.loading {
+            Line::styled("Loading episodes…", Style::new().fg(self.theme.muted))
+        } else if archive.episodes.is_empty() {
+            Line::styled("No episodes yet | Esc: close", Style::new().fg(self.theme.muted))
+        } else if !archive.search.is_empty() {
+            Line::styled(
+                format!("Search: {} | Enter: play | Esc: clear", archive.search),
+                Style::new().fg(self.theme.muted),
+            )
+        } else {
+            Line::styled("Enter: play | /: search | Esc: close", Style::new().fg(self.theme.muted))
+        };
+        f.render_widget(Paragraph::new(status), status_area);
+    }
+
+    /// Opens the audio self-test and starts it, or closes it. The stages run
+    /// on a worker thread, one after another, and report as they finish. The
+    /// network stage reads from the selected stream, where it was overridden
+    /// to if it was.
+    fn toggle_diagnostics(&mut self) {
+        if self.diagnostics.take().is_some() {
+            return;
+        }
+        self.diagnostics = Some(Vec::new());
+        self.diagnostics_run += 1;
+        let run = self.diagnostics_run;
+        let ui_tx = self.ui_tx.clone();
+        let listed = self.selected().map_or(STREAM_URL_1, |stream| stream.audio_stream_endpoint.as_str());
+        let stream_url = audio_check::endpoint(listed, &self.config.network.stream_overrides).to_string();
+        thread::spawn(move || {
+            for stage in audio_check::STAGES {
+                let outcome = audio_check::check(stage, &stream_url);
+                if ui_tx.send(UIMessage::AudioCheck(run, stage, outcome)).is_err() {
+                    break;
                 }
-                self.start_recognition();
-                self.recognition_result_display_timeout = Some(SystemTime::now());
-                self.start_recognition_info_timer();
             }
-            KeyCode::Char(' ') => self.stop(),
-            KeyCode::Char('r') => {
-                if self.current_stream_url.is_some() {
-                    self.start_recognition();
-                    self.recognition_result_display_timeout = Some(SystemTime::now());
-                    self.start_recognition_info_timer();
+        });
+    }
+
+    fn diagnostics_paragraph(&self) -> Paragraph<'static> {
+        let results = self.diagnostics.as_deref().unwrap_or_default();
+        let mut lines: Vec<Line> = audio_check::report_lines(results)
+            .into_iter()
+            .map(|line| {
+                if line.contains(" FAIL ") {
+                    Line::styled(line, Style::new().fg(self.theme.heading).bold())
+                } else {
+                    Line::raw(line)
+                }
+            })
+            .collect();
+        for stage in audio_check::STAGES.iter().skip(results.len()) {
+            lines.push(Line::styled(format!("{:<8} …", stage), Style::new().fg(self.theme.muted)));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "The device stage plays a short tone | A/Esc: close",
+            Style::new().fg(self.theme.muted),
+        )));
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled("Audio diagnostics", Style::new().fg(self.theme.heading).bold())),
+            )
+            .wrap(Wrap { trim: false })
+    }
+
+    fn migration_paragraph(&self, moves: &[migrate::Move]) -> Paragraph<'static> {
+        let mut lines = vec![
+            Line::from("An older version left these files in your home directory. Move them to the data directory?"),
+            Line::from(""),
+        ];
+        lines.extend(moves.iter().map(|step| Line::from(format!("  {}", step.describe()))));
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "y: move them, keeping the originals as backups | n/Esc: leave them (nts_cli migrate moves them later)",
+            Style::new().fg(self.theme.muted),
+        )));
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled("Files from an older version", Style::new().fg(self.theme.heading).bold())),
+            )
+            .wrap(Wrap { trim: false })
+    }
+
+    fn about_paragraph(&self) -> Paragraph<'static> {
+        let mut lines: Vec<Line> = self.about_report().into_iter().map(Line::from).collect();
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "c: copy to clipboard | a/Esc: close",
+            Style::new().fg(self.theme.muted),
+        )));
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled("About", Style::new().fg(self.theme.heading).bold())),
+            )
+            .wrap(Wrap { trim: false })
+    }
+
+    /// Shown when quitting waits on critical work for longer than
+    /// `SHUTDOWN_NOTICE_DELAY`.
+    fn draw_shutting_down(&self, f: &mut Frame) {
+        f.render_widget(Clear, f.area());
+        let area = centered_rect(100, 10, f.area());
+        f.render_widget(
+            Paragraph::new("shutting down…")
+                .style(Style::new().fg(self.theme.heading))
+                .alignment(Alignment::Center),
+            area,
+        );
+    }
+
+    /// Drawn instead of the layout below `MIN_TERMINAL_WIDTH` by
+    /// `MIN_TERMINAL_HEIGHT`, which it would not fit in.
+    fn draw_too_small(&self, f: &mut Frame) {
+        let message = format!(
+            "Terminal too small ({}x{}), needs at least {}x{}",
+            f.area().width,
+            f.area().height,
+            MIN_TERMINAL_WIDTH,
+            MIN_TERMINAL_HEIGHT
+        );
+        f.render_widget(
+            Paragraph::new(message)
+                .style(Style::new().fg(self.theme.heading))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true }),
+            f.area(),
+        );
+    }
+
+    /// Minimal frame shown when the regular layout panicked, so the app stays
+    /// usable and the cause can be read from the status log.
+    fn draw_fallback(&self, f: &mut Frame) {
+        f.render_widget(Clear, f.area());
+        let mut lines = vec![Line::from(Span::styled(
+            "render error — press q to quit, l for logs",
+            Style::new().fg(self.theme.stream).bold(),
+        ))];
+        if self.show_status_log {
+            lines.push(Line::from(""));
+            lines.extend(self.status_log.iter().map(|entry| Line::from(entry.clone())));
+        }
+        f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: true }), f.area());
+    }
+
+    fn status_log_paragraph(&self) -> Paragraph<'static> {
+        let lines: Vec<Line> = if self.status_log.is_empty() {
+            vec![Line::from("Nothing logged yet")]
+        } else {
+            self.status_log
+                .iter()
+                .rev()
+                .map(|entry| Line::from(entry.clone()))
+                .collect()
+        };
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled("Status log", Style::new().fg(self.theme.heading).bold())),
+            )
+            .style(Style::default().fg(self.theme.muted))
+            .wrap(Wrap { trim: true })
+    }
+
+    /// How far through the show on air we are, as a thin bar with "1:12 /
+    /// 2:00" after it. The bar stays full when a show runs over; without an
+    /// end time there's only the elapsed counter.
+    fn show_progress_line(&self, airtime: Airtime, width: u16) -> Line<'static> {
+        let elapsed = (self.schedule_now() - airtime.start).to_std().unwrap_or_default();
+        let Some(length) = airtime.end.and_then(|end| (end - airtime.start).to_std().ok()) else {
+            return Line::styled(format!("{} on air", format::clock_minutes(elapsed)), Style::new().fg(self.theme.muted));
+        };
+        let label = format!(" {} / {}", format::clock_minutes(elapsed), format::clock_minutes(length));
+        let bar_width = (width as usize).saturating_sub(label.chars().count());
+        let fraction = if length.is_zero() {
+            1.0
+        } else {
+            (elapsed.as_secs_f64() / length.as_secs_f64()).clamp(0.0, 1.0)
+        };
+        let filled = ((bar_width as f64 * fraction).round() as usize).min(bar_width);
+        Line::from(vec![
+            Span::styled("━".repeat(filled), Style::new().fg(self.theme.highlight)),
+            Span::styled("─".repeat(bar_width - filled), Style::new().fg(self.theme.muted)),
+            Span::styled(label, Style::new().fg(self.theme.muted)),
+        ])
+    }
+
+    /// The live station the show gauge follows: the selected one, or else
+    /// the one playing, with its show's airtime. None when the show's end
+    /// isn't known.
+    fn gauge_station(&self) -> Option<(&str, Airtime)> {
+        let station = [self.views.streams.selection.as_ref(), self.playing_stream.as_ref()]
+            .into_iter()
+            .flatten()
+            .find(|stream_ref| stream_ref.section == StreamType::Station)
+            .and_then(|stream_ref| self.streams_collection.resolve(stream_ref))?;
+        let airtime = station.airtime.filter(|airtime| airtime.end.is_some())?;
+        Some((station.title.as_str(), airtime))
+    }
+
+    /// One row filling up over the show. A clock off from the API's stays
+    /// between empty and full.
+    fn show_gauge(&self, title: &str, airtime: Airtime) -> Gauge<'static> {
+        let elapsed = (self.schedule_now() - airtime.start).to_std().unwrap_or_default();
+        let length = airtime
+            .end
+            .and_then(|end| (end - airtime.start).to_std().ok())
+            .unwrap_or_default();
+        let ratio = if length.is_zero() {
+            1.0
+        } else {
+            (elapsed.as_secs_f64() / length.as_secs_f64()).clamp(0.0, 1.0)
+        };
+        Gauge::default()
+            .ratio(ratio)
+            .label(format!(
+                "{}: {} / {}",
+                title,
+                format::clock_minutes(elapsed.min(length)),
+                format::clock_minutes(length)
+            ))
+            .gauge_style(Style::new().fg(self.theme.highlight).bg(Color::Reset))
+            .use_unicode(true)
+    }
+
+    /// "Now: Show 08:00–10:00 / Next: Other Show 10:00–12:00" for a station,
+    /// in local time.
+    fn now_next_line(&self, title: &str, airtime: Option<Airtime>, next: Option<&UpcomingShow>) -> Line<'static> {
+        let today = self.schedule_now().date_naive();
+        let mut now = format!("Now: {}", title);
+        if let Some(airtime) = airtime {
+            let slot = format::time_slot(airtime.start.naive_local(), airtime.end.map(|end| end.naive_local()), today);
+            now = format!("{} {}", now, slot);
+        }
+        let mut spans = vec![Span::styled(now, Style::new().fg(self.theme.description))];
+        if let Some(next) = next {
+            let slot = format::time_slot(next.start.naive_local(), next.end.map(|end| end.naive_local()), today);
+            spans.push(Span::styled(" / ", Style::new().fg(self.theme.muted)));
+            spans.push(Span::styled(format!("Next: {} {}", next.title, slot), Style::new().fg(self.theme.description)));
+        }
+        Line::from(spans)
+    }
+
+    fn queue_list(&self) -> List<'static> {
+        let items: Vec<ListItem> = self
+            .rotation
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let is_current = i == self.rotation.position();
+                let mut spans = vec![Span::styled(
+                    format!("{} · {}", entry.title, format::duration_words(Duration::from_secs(entry.minutes * 60))),
+                    if is_current {
+                        Style::new().fg(self.theme.stream).bold()
+                    } else {
+                        Style::new().fg(self.theme.stream)
+                    },
+                )];
+                if is_current {
+                    let status = match self.rotation.remaining() {
+                        Some(remaining) if self.rotation.is_running() => {
+                            format!(" ▶ {} left", format::clock(remaining))
+                        }
+                        _ => " (paused)".to_string(),
+                    };
+                    spans.push(Span::styled(status, Style::new().fg(self.theme.muted)));
                 }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        List::new(items).block(create_block("Queue", &self.theme))
+    }
+
+    /// The idle screen over the description of the selection, `width` by
+    /// `height` at most: what's live with the keys to tune in and the last
+    /// recognition, with the wordmark on top when there's room to spare and
+    /// a blank row before the description when `selection` has one. The
+    /// clock goes in the panel title.
+    fn idle_lines(&self, width: usize, height: usize, selection: bool) -> Vec<Line<'static>> {
+        let summaries = self.streams_collection.live_summaries();
+        let mut lines: Vec<Line> = summaries
+            .iter()
+            .map(|(title, subtitle)| {
+                Line::from(vec![
+                    Span::styled(format!("{}: ", title), Style::new().fg(self.theme.description).bold()),
+                    Span::styled(subtitle.clone(), Style::new().fg(self.theme.description).italic()),
+                ])
+            })
+            .collect();
+        let keys: Vec<String> = (1..=summaries.len()).map(|key| key.to_string()).collect();
+        if let Some((last, rest)) = keys.split_last() {
+            let keys = if rest.is_empty() { last.clone() } else { format!("{} or {}", rest.join(", "), last) };
+            lines.push(Line::styled(format!("press {} to tune in", keys), Style::new().fg(self.theme.muted)));
+        }
+        if let Some(last) = self.recognition_list.lines().last() {
+            // Without its time, then on a row of its own, as it takes to fit
+            let label = Span::styled("Last recognized: ", Style::new().fg(self.theme.muted));
+            let fits = |track: &str| label.width() + track.chars().count() <= width;
+            let mut track = history::display_line(last);
+            if !fits(track) {
+                track = history::entry_text(last);
             }
-            KeyCode::Char('=') => {
-                self.duration += 1;
-                self.duration_display_timeout = Some(SystemTime::now());
+            let track = Span::styled(track.to_string(), Style::new().fg(self.theme.text));
+            if fits(&track.content) {
+                lines.push(Line::from(vec![label, track]));
+            } else {
+                lines.extend([Line::from(label), Line::from(track)]);
+            }
+        }
+
+        let description_room = if selection { IDLE_DESCRIPTION_ROOM } else { 0 };
+        if height >= NTS_WORDMARK.len() + 1 + lines.len() + description_room {
+            let wordmark = NTS_WORDMARK
+                .iter()
+                .map(|row| Line::from(Span::styled(*row, Style::new().fg(self.theme.stream).bold())))
+                .chain([Line::from("")]);
+            lines.splice(0..0, wordmark);
+        }
+        if selection {
+            lines.push(Line::from(""));
+        }
+        lines.truncate(height);
+        lines
+    }
+
+    /// What recognition is up to for the controls pane, like "ID in 2:30 ·
+    /// running": when the next auto-ID is due, and whether one is in flight.
+    fn id_status(&self) -> Option<String> {
+        let next = match self.config.recognition.auto_id {
+            _ if !self.player.is_playing() || self.player.paused_for().is_some() || self.light_mode => None,
+            AutoId::Off => None,
+            AutoId::Interval => self
+                .id_scheduler
+                .next_in(Instant::now())
+                .map(|next| format!("ID in {}", format::clock(next))),
+            AutoId::Change => Some("ID on track change".to_string()),
+        };
+        let running = self.id_scheduler.running().map(|trigger| match trigger {
+            Trigger::Manual => "running",
+            Trigger::Scheduled => "auto running",
+        });
+        match (next, running) {
+            (Some(next), Some(running)) => Some(format!("{} · {}", next, running)),
+            (Some(next), None) => Some(next),
+            (None, Some(running)) => Some(format!("ID {}", running)),
+            (None, None) => None,
+        }
+    }
+
+    fn debug_overlay_paragraph(&self) -> Paragraph<'static> {
+        let fetches = fetch_scheduler::counters();
+        let mut lines = vec![
+            Line::from(match self.decode_load_sample {
+                Some(_) => format!("Decode load: {}", format::percent_with(self.decode_load as f64, 1)),
+                None => "Decode load: not measured".to_string(),
+            }),
+            Line::from(format!(
+                "Tick: {}ms | Light mode: {}{}",
+                self.tick_interval.load(Ordering::Relaxed),
+                if self.light_mode { "on" } else { "off" },
+                if self.forced_light_mode { " (forced)" } else { "" }
+            )),
+            Line::from(match self.player.pipe_reader_connected() {
+                Some(true) => format!("Output: pipe {}", self.player.output().pipe_path.display()),
+                Some(false) => format!("Output: pipe {} (waiting for a reader)", self.player.output().pipe_path.display()),
+                None => "Output: sound device".to_string(),
+            }),
+            Line::from(match self.player.conversion() {
+                Some(conversion) => format!("Resampling: {}", conversion),
+                None => "Resampling: by the audio backend".to_string(),
+            }),
+            Line::from(format!(
+                "Fetches: {}/{} this minute, {} waiting | {} throttled, {} shared",
+                fetches.recent, fetches.budget, fetches.waiting, fetches.throttled, fetches.deduplicated
+            )),
+            Line::from(format!(
+                "History: {} refreshes, {} pane rebuilds",
+                self.history_refresh.runs(),
+                self.history_rebuilds
+            )),
+            Line::from(""),
+        ];
+        lines.extend(
+            self.status_log
+                .iter()
+                .rev()
+                .take(3)
+                .map(|entry| Line::from(entry.clone())),
+        );
+
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled("Debug", Style::new().fg(self.theme.heading).bold())),
+            )
+            .style(Style::default().fg(self.theme.muted))
+            .wrap(Wrap { trim: true })
+    }
+
+    /// The technical side of the playing stream, shown under its description
+    /// while debugging: where it actually came from and how it decodes.
+    /// The measured bitrate and the sample rate conversion of what is
+    /// playing, as far as they are known yet.
+    fn stream_health_line(&self) -> Option<Line<'static>> {
+        let mut parts = Vec::new();
+        if let Some(kbps) = self.player.decode_stats().and_then(|stats| stats.bitrate_kbps()) {
+            parts.push(format!("{} kbps", kbps));
+        }
+        if let Some(conversion) = self.player.conversion() {
+            parts.push(conversion.to_string());
+        }
+        (!parts.is_empty()).then(|| Line::styled(parts.join(" · "), Style::new().fg(self.theme.muted)))
+    }
+
+    fn stream_debug_lines(&self) -> Vec<Line<'static>> {
+        let muted = Style::new().fg(self.theme.muted);
+        let Some(url) = self.player.current_stream_url() else {
+            return vec![Line::styled("Endpoint: not connected", muted)];
+        };
+        let mut lines = vec![Line::styled(format!("Endpoint: {}", url), muted)];
+        if let Some(info) = self.player.connect_info() {
+            if let Some(addr) = info.remote_addr {
+                lines.push(Line::styled(format!("Address: {}", addr), muted));
+            }
+            if let Some(label) = network::edge_label(&info.headers) {
+                lines.push(Line::styled(format!("Edge: {}", label), muted));
+            }
+            let located = self
+                .edge_location
+                .as_ref()
+                .filter(|(ip, _)| info.remote_addr.is_some_and(|addr| addr.ip() == *ip));
+            if let Some((_, location)) = located {
+                lines.push(Line::styled(format!("Location: {} (approximate)", location), muted));
+            }
+            lines.push(Line::styled(format!("HTTP {}", info.status), muted));
+            for (name, value) in &info.headers {
+                lines.push(Line::styled(format!("  {}: {}", name, value), muted));
+            }
+        }
+        if let Some(stats) = self.player.decode_stats() {
+            if let Some(kbps) = stats.bitrate_kbps() {
+                lines.push(Line::styled(format!("Bitrate: {} kbps (measured)", kbps), muted));
+            }
+            let (sample_rate, channels) = stats.format();
+            lines.push(Line::styled(format!("Decoder: {} Hz, {} channels", sample_rate, channels), muted));
+            let (size, refills) = stats.buffer();
+            lines.push(Line::styled(format!("Buffer: {} samples, refilled {} times", size, refills), muted));
+        }
+        lines
+    }
+
+    /// Scrolls the history pane to the row showing the given file line.
+    fn scroll_history_to(&mut self, line: usize) {
+        let row = self
+            .history_rows
+            .iter()
+            .position(|row| row.line >= line)
+            .unwrap_or(self.history_rows.len().saturating_sub(1));
+        self.views.history.scroll = row;
+    }
+
+    /// File line of the row at the top of the history pane.
+    fn history_scroll_line(&self) -> usize {
+        self.history_rows
+            .get(self.views.history.scroll)
+            .map_or(self.views.history.scroll, |row| row.line)
+    }
+
+    /// Brings the history pane up to date after the history changed, right
+    /// away unless it was moments ago, in which case a burst of changes is
+    /// shown once.
+    fn history_changed(&mut self) {
+        let turn = self.history_refresh.request(Instant::now());
+        self.take_history_turn(turn);
+    }
+
+    fn take_history_turn(&mut self, turn: Turn) {
+        match turn {
+            Turn::Now => self.refresh_history(),
+            Turn::After(wait) => {
+                let ui_tx = self.ui_tx.clone();
+                thread::spawn(move || {
+                    thread::sleep(wait);
+                    let _ = ui_tx.send(UIMessage::HistoryRefreshDue);
+                });
+            }
+            Turn::Queued => {}
+        }
+    }
+
+    /// Puts the lines the app wrote into the pane's copy of the history file,
+    /// where the store put them by time, without reading the file again.
+    /// When its size says something else changed it as well, the file is
+    /// read in full instead.
+    fn refresh_history(&mut self) {
+        let written = std::mem::take(&mut self.history_written);
+        let mut contents = self.recognition_list[..self.history_file_len].to_string();
+        if !written.is_empty() {
+            let mut lines: Vec<&str> = contents.lines().collect();
+            for line in &written {
+                lines.insert(history::position_for(line, lines.iter().copied()), line);
+            }
+            let merged = lines.iter().map(|line| format!("{}\n", line)).collect();
+            contents = merged;
+        }
+        let size = fs::metadata(&self.history_path).map(|metadata| metadata.len()).ok();
+        if size == Some(contents.len() as u64) {
+            self.show_history(contents);
+        } else {
+            self.read_history();
+        }
+    }
+
+    fn read_history(&mut self) {
+        self.history_written.clear();
+        match HistoryStore::open(&self.history_path) {
+            Ok(store) => self.history_file_read(store.contents()),
+            Err(err) => self.log_error(format!("Could not read history: {}", err)),
+        }
+    }
+
+    /// Shows `contents`, read from the history file after entries were
+    /// added to it. A file shorter than what was read before was deleted or
+    /// cut short outside the app, so the pane starts over rather than keep
+    /// rows and a scroll position that point nowhere. Entries the app adds
+    /// only make it longer, even a late one put in mid-file by its time.
+    fn history_file_read(&mut self, contents: String) {
+        if contents.len() < self.history_file_len {
+            self.log_status("History file was reset externally");
+            self.expanded_streaks.clear();
+            self.views.history.scroll = 0;
+        }
+        self.show_history(contents);
+    }
+
+    /// Shows `contents`, the history file as it is now.
+    fn show_history(&mut self, contents: String) {
+        self.history_file_len = contents.len();
+        let contents = contents + &self.incognito_entries;
+        self.history_index.sync(&self.recognition_list, &contents);
+        self.recognition_list = contents;
+        self.rebuild_history_rows();
+    }
+
+    /// Deletes the history entry at the top of the history pane, as long as
+    /// the file still has it on the line it was read from. Entries kept
+    /// only for the session aren't in the file to delete.
+    fn delete_history_entry(&mut self) {
+        let Some(row) = self.history_rows.get(self.views.history.scroll) else {
+            return;
+        };
+        let line = row.line;
+        let Some(shown) = self.recognition_list[..self.history_file_len].lines().nth(line).map(str::to_string) else {
+            return;
+        };
+        let deleted = HistoryStore::open(&self.history_path).and_then(|mut store| {
+            let unchanged = store.lines().get(line) == Some(&shown);
+            if unchanged && store.delete(line) {
+                store.commit()?;
             }
-            KeyCode::Char('-') => {
-                if self.duration > 1 {
-                    self.duration -= 1;
-                    self.duration_display_timeout = Some(SystemTime::now());
+            Ok((unchanged, store.contents()))
+        });
+        match deleted {
+            Ok((true, contents)) => {
+                self.log_status(format!("Deleted from history: {}", history::display_line(&shown)));
+                self.expanded_streaks.clear();
+                self.show_history(contents);
+                self.scroll_history_to(line);
+            }
+            Ok((false, _)) => {
+                self.show_info("The history file changed since it was read, nothing deleted".to_string());
+                self.read_history();
+            }
+            Err(err) => self.log_error(format!("Could not delete history entry: {}", err)),
+        }
+    }
+
+    fn rebuild_history_rows(&mut self) {
+        self.history_rebuilds += 1;
+        self.history_rows = history::rows(&self.recognition_list, &self.expanded_streaks);
+    }
+
+    /// Expands or collapses the streak of repeated entries at the top of the
+    /// history pane, keeping it in view.
+    fn toggle_history_streak(&mut self) {
+        let Some(row) = self.history_rows.get(self.views.history.scroll) else {
+            return;
+        };
+        let streak = row.streak;
+        if !self.expanded_streaks.remove(&streak) {
+            self.expanded_streaks.insert(streak);
+        }
+        self.rebuild_history_rows();
+        self.scroll_history_to(streak);
+    }
+
+    fn handle_prompt_key(&mut self, key: KeyEvent) {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return;
+        };
+        if prompt.kind == PromptKind::ResumeRotation {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                    self.prompt = None;
+                    self.resume_rotation();
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    self.prompt = None;
+                    self.rotation.clear();
+                    self.save_state();
+                    self.log_status("Saved rotation discarded");
                 }
+                _ => {}
             }
-            KeyCode::Char('<') => {
-                if self.volume > 0.0 {
-                    self.volume -= 0.1;
-                    if let Some(sink) = &self.sink {
-                        sink.set_volume(self.volume);
-                        self.volume_display_timeout = Some(SystemTime::now());
+            return;
+        }
+        if prompt.kind == PromptKind::StreamFilter {
+            match key.code {
+                KeyCode::Esc => {
+                    self.prompt = None;
+                    self.set_stream_filter(String::new());
+                }
+                KeyCode::Enter => {
+                    self.prompt = None;
+                    let found = self.selected_position().is_some();
+                    self.set_stream_filter(String::new());
+                    if found {
+                        self.tune_in();
                     }
                 }
+                KeyCode::Up | KeyCode::Down => self.move_selection(key.code == KeyCode::Down),
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                    let input = prompt.input.clone();
+                    self.set_stream_filter(input);
+                }
+                KeyCode::Char(c) => {
+                    prompt.input.push(c);
+                    let input = prompt.input.clone();
+                    self.set_stream_filter(input);
+                }
+                _ => {}
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Enter => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.submit_prompt(prompt);
+                }
             }
-            KeyCode::Char('>') => {
-                if self.volume < 1.0 {
-                    self.volume += 0.1;
-                    if let Some(sink) = &self.sink {
-                        sink.set_volume(self.volume);
-                        self.volume_display_timeout = Some(SystemTime::now());
+            _ => {}
+        }
+    }
+
+    /// Narrows the mixtape list to `filter`, or shows all of it again when
+    /// empty. The collection itself is left alone.
+    fn set_stream_filter(&mut self, filter: String) {
+        self.views.streams.filter = filter;
+        self.rebuild_stream_view();
+        self.views.streams.scroll = 0;
+    }
+
+    /// y moves the files in the migration popup, n or Esc leaves them.
+    fn handle_migration_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                let moves = self.migration.take().unwrap_or_default();
+                let message = match migrate::migrate(&moves) {
+                    Ok(()) => {
+                        self.state_path = migrate::STATE.path();
+                        self.listening_log = listening::log_file_path();
+                        format!("Moved {} files into the data directory", moves.len())
+                    }
+                    Err(err) => format!("Could not migrate: {}", err),
+                };
+                self.log_status(message.clone());
+                self.show_info(message);
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                self.migration = None;
+                self.log_status("Left the files from an older version in place; `nts_cli migrate` moves them");
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes the history file to `path`, or the default in the current
+    /// directory when it is empty, and says where it went.
+    fn export_history(&mut self, path: &str) {
+        let path = Path::new(if path.is_empty() { export::DEFAULT_PATH } else { path });
+        let message = match export::write(path, export::Format::for_path(path)) {
+            Ok(count) => {
+                let written = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+                format!("Exported {} tracks to {}", count, written.display())
+            }
+            Err(err) => format!("Could not export to {}: {}", path.display(), err),
+        };
+        self.show_info(message);
+    }
+
+    fn submit_prompt(&mut self, prompt: Prompt) {
+        match prompt.kind {
+            PromptKind::ResumeRotation | PromptKind::StreamFilter => {}
+            PromptKind::CustomUrl => self.play_custom_url(&prompt.input),
+            PromptKind::ArchiveShow => match archive::parse_show(&prompt.input) {
+                Some(show) => self.open_archive(show.clone(), show),
+                None => self.show_info(format!("No show called {}", prompt.input.trim())),
+            },
+            PromptKind::Export => self.export_history(prompt.input.trim()),
+            PromptKind::HistoryDate => {
+                let target = history::parse_jump_target(&prompt.input)
+                    .and_then(|date| self.history_index.first_from(date));
+                match target {
+                    Some(line) => self.scroll_history_to(line),
+                    None => {
+                        self.recognition_result =
+                            Some(format!("No history entries from {}", prompt.input.trim()));
+                        self.recognition_result_display_timeout = Some(self.now().into());
+                        self.start_recognition_info_timer();
                     }
                 }
             }
-            KeyCode::Char('j') => {
-                self.vertical_scroll = self.vertical_scroll.saturating_add(1);
-                self.vertical_scroll_state =
-                    self.vertical_scroll_state.position(self.vertical_scroll);
+        }
+    }
+
+    /// Whether a guarded key goes ahead, as set in the `[guard]` config. A
+    /// first press that doesn't shows a hint until it expires.
+    fn guarded(&mut self, action: GuardedAction) -> bool {
+        let mode = match action {
+            GuardedAction::Stop => self.config.guard.stop,
+            GuardedAction::Quit => self.config.guard.quit,
+            GuardedAction::DeleteHistory => self.config.guard.delete_history,
+        };
+        let allowed = self.guard.allow(action, mode, Instant::now());
+        if let Some(window) = mode.window().filter(|_| !allowed) {
+            // Redraw once the hint has expired
+            let ui_tx = self.ui_tx.clone();
+            thread::spawn(move || {
+                thread::sleep(window + Duration::from_millis(10));
+                let _ = ui_tx.send(UIMessage::UpdateUI);
+            });
+        }
+        allowed
+    }
+
+    fn handle_key_press(&mut self, key: KeyEvent) -> Result<(), Box<dyn std::error::Error>> {
+        let action = keymap::action_for(&key);
+        // Works over prompts and popups alike, leaving them as they are
+        if action == Some(Action::Redraw) {
+            self.clear_screen = true;
+            return Ok(());
+        }
+        if self.prompt.is_some() {
+            self.handle_prompt_key(key);
+            return Ok(());
+        }
+        if self.show_help {
+            self.show_help = false;
+            return Ok(());
+        }
+        if self.archive.is_some() {
+            self.handle_archive_key(key);
+            return Ok(());
+        }
+        if self.migration.is_some() {
+            self.handle_migration_key(key);
+            return Ok(());
+        }
+        // Checks that a panic leaves a usable terminal behind
+        #[cfg(debug_assertions)]
+        if key.code == KeyCode::F(11) {
+            panic!("deliberate panic (F11, debug builds only)");
+        }
+        let Some(action) = action else {
+            return Ok(());
+        };
+        match action {
+            Action::Quit => self.quitting = self.guarded(GuardedAction::Quit),
+            Action::SelectNext => self.move_selection(true),
+            Action::SelectPrevious => self.move_selection(false),
+            Action::Play if self.views.is_focused(View::History) => self.toggle_history_streak(),
+            // Without recognition the history is only shown, as it was left
+            Action::Recognize
+            | Action::RecognizePast
+            | Action::LongerSample
+            | Action::ShorterSample
+            | Action::RevealSample
+            | Action::DeleteHistory
+                if !recognition::AVAILABLE =>
+            {
+                self.show_info(recognition::UNAVAILABLE.to_string())
+            }
+            Action::DeleteHistory
+                if self.views.is_focused(View::History) && self.guarded(GuardedAction::DeleteHistory) =>
+            {
+                self.delete_history_entry()
+            }
+            Action::Play => self.tune_in(),
+            Action::SwitchFocus => self.views.switch(),
+            Action::Pause => self.toggle_pause(),
+            Action::TuneIn(index) => self.tune_in_station(index),
+            Action::SelectMixtape(index) => self.select_mixtape(index),
+            Action::Stop if self.guarded(GuardedAction::Stop) => {
+                self.pause_rotation();
+                self.auto_reconnect = None;
+                self.cancel_connect();
+                self.stop();
+            }
+            Action::Help => self.show_help = true,
+            Action::Close if self.show_about => self.show_about = false,
+            Action::CopyAbout if self.show_about => self.copy_about_report(),
+            Action::About => self.show_about = !self.show_about,
+            Action::Close if self.digest.is_some() => self.digest = None,
+            Action::Digest => self.toggle_digest(),
+            Action::Close if self.diagnostics.is_some() => self.diagnostics = None,
+            Action::Diagnostics => self.toggle_diagnostics(),
+            Action::Incognito => self.toggle_incognito(),
+            Action::Close if self.connecting.is_pending() => {
+                self.auto_reconnect = None;
+                self.cancel_connect();
+                self.log_status("Connection cancelled");
+            }
+            Action::Close if self.id_scheduler.running().is_some() => self.cancel_recognition(),
+            Action::Enqueue => self.enqueue_selected(),
+            Action::LongerSlot | Action::ShorterSlot => {
+                self.rotation.adjust_last(action == Action::LongerSlot);
+                self.save_state();
+            }
+            Action::ClearQueue => {
+                self.rotation.clear();
+                self.save_state();
+            }
+            Action::Rotate => self.resume_rotation(),
+            Action::RevealSample => self.reveal_last_sample(),
+            Action::CopyTrack => self.copy_last_track(),
+            Action::ShareSession => self.share_session(),
+            Action::Follow => self.toggle_follow(),
+            Action::Output => self.toggle_output(),
+            Action::Resampler => self.cycle_resampler(),
+            Action::Logs => self.show_status_log = !self.show_status_log,
+            Action::Debug => self.show_debug_overlay = !self.show_debug_overlay,
+            Action::Recognize if self.id_scheduler.running() == Some(Trigger::Manual) => self.cancel_recognition(),
+            Action::Recognize if self.player.is_playing() => {
+                self.start_recognition(Trigger::Manual);
+                self.recognition_result_display_timeout = Some(self.now().into());
+                self.start_recognition_info_timer();
+            }
+            Action::RecognizePast if self.player.is_playing() => {
+                self.start_recognition_from_past();
+                self.recognition_result_display_timeout = Some(self.now().into());
+                self.start_recognition_info_timer();
+            }
+            Action::LongerSample if self.duration < mp3_tap::KEEP.as_secs() => {
+                self.duration += 1;
+                self.duration_display_timeout = Some(self.now().into());
+            }
+            Action::ShorterSample if self.duration > 1 => {
+                self.duration -= 1;
+                self.duration_display_timeout = Some(self.now().into());
+            }
+            Action::VolumeUp | Action::VolumeDown => {
+                let step = if action == Action::VolumeUp { 0.1 } else { -0.1 };
+                if self.player.set_volume(self.player.volume() + step) {
+                    self.volume_display_timeout = Some(self.now().into());
+                }
+            }
+            Action::DescriptionDown => self.views.streams.scroll = self.views.streams.scroll.saturating_add(1),
+            Action::DescriptionUp => self.views.streams.scroll = self.views.streams.scroll.saturating_sub(1),
+            Action::HistoryDown => self.views.history.scroll = self.views.history.scroll.saturating_add(1),
+            Action::HistoryUp => self.views.history.scroll = self.views.history.scroll.saturating_sub(1),
+            Action::NextDay => {
+                if let Some(line) = self.history_index.next_day(self.history_scroll_line()) {
+                    self.scroll_history_to(line);
+                }
+            }
+            Action::PreviousDay => {
+                if let Some(line) = self.history_index.previous_day(self.history_scroll_line()) {
+                    self.scroll_history_to(line);
+                }
+            }
+            Action::JumpToDate => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::HistoryDate,
+                    input: String::new(),
+                })
+            }
+            Action::RetryStreams => self.retry_streams(),
+            Action::PlayUrl => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::CustomUrl,
+                    input: String::new(),
+                })
+            }
+            Action::Filter => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::StreamFilter,
+                    input: String::new(),
+                })
             }
-            KeyCode::Char('k') => {
-                self.vertical_scroll = self.vertical_scroll.saturating_sub(1);
-                self.vertical_scroll_state =
-                    self.vertical_scroll_state.position(self.vertical_scroll);
+            Action::Archive => self.browse_archive(),
+            Action::Export => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Export,
+                    input: export::DEFAULT_PATH.to_string(),
+                })
             }
             _ => {}
         }
@@ -605,6 +4266,95 @@ impl Radio {
     }
 }
 
+// DEALING WITH COLORS
+
+/// Default colors, picked to stay readable on the terminal's background.
+#[derive(Clone, Copy, Debug)]
+struct Theme {
+    heading: Color,
+    highlight: Color,
+    stream: Color,
+    description: Color,
+    text: Color,
+    muted: Color,
+}
+
+impl Theme {
+    /// Replaces the colors set in the config's `[colors]` section; ones that
+    /// don't parse were warned about at load and keep the default.
+    fn with_overrides(mut self, colors: &ColorsConfig) -> Self {
+        for (key, value) in colors.entries() {
+            let Ok(Some(color)) = config::parse_color(value) else {
+                continue;
+            };
+            let slot = match key {
+                "heading" => &mut self.heading,
+                "highlight" => &mut self.highlight,
+                "stream" => &mut self.stream,
+                "description" => &mut self.description,
+                "text" => &mut self.text,
+                _ => &mut self.muted,
+            };
+            *slot = color;
+        }
+        self
+    }
+
+    fn for_background(background: Background) -> Self {
+        match background {
+            Background::Light => Theme {
+                heading: Color::Magenta,
+                highlight: Color::Magenta,
+                stream: Color::Red,
+                description: Color::Green,
+                text: Color::Blue,
+                muted: Color::Reset,
+            },
+            Background::Dark | Background::Auto => Theme {
+                heading: Color::Yellow,
+                highlight: Color::Yellow,
+                stream: Color::Red,
+                description: Color::Green,
+                text: Color::Blue,
+                muted: Color::DarkGray,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ColorSupport {
+    None,
+    Basic,
+    TrueColor,
+}
+
+impl ColorSupport {
+    fn detect() -> Self {
+        if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return ColorSupport::None;
+        }
+        match env::var("COLORTERM").as_deref() {
+            Ok("truecolor") | Ok("24bit") => ColorSupport::TrueColor,
+            _ => ColorSupport::Basic,
+        }
+    }
+
+    /// A color derived from the mixtape title. The hash is FNV-1a rather than
+    /// std's hasher so the same mixtape keeps its color across runs and builds.
+    fn identity_color(self, title: &str) -> Option<Color> {
+        let hash = title.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        let (rgb, basic) = MIXTAPE_PALETTE[(hash % MIXTAPE_PALETTE.len() as u64) as usize];
+        match self {
+            ColorSupport::None => None,
+            ColorSupport::Basic => Some(basic),
+            ColorSupport::TrueColor => Some(rgb),
+        }
+    }
+}
+
 //
 // UTILS
 //
@@ -617,35 +4367,117 @@ fn get_home_dir() -> Option<PathBuf> {
     }
 }
 
+static HISTORY_FILE_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `path` for the history this run, as `--history-file` asks. Has to
+/// come before anything reads the history.
+fn set_history_file_path(path: PathBuf) {
+    let _ = HISTORY_FILE_PATH.set(path);
+}
+
+/// The history file this run uses. The app settles it from its checked
+/// config at startup; subcommands, which don't, read `history_path` on
+/// first use. Either way one run never writes to two files.
 fn get_history_file_path() -> PathBuf {
+    HISTORY_FILE_PATH
+        .get_or_init(|| {
+            let configured = Config::load()
+                .map(|checked| checked.config.history_path)
+                .unwrap_or_default();
+            resolve_history_path(&configured)
+        })
+        .clone()
+}
+
+/// `history_path` with `~` expanded, or `history.txt` in the data directory
+/// when it is empty.
+fn resolve_history_path(configured: &Path) -> PathBuf {
+    if configured.as_os_str().is_empty() {
+        return dirs::data_dir()
+            .map(|dir| dir.join(HISTORY_FILE_NAME))
+            .or_else(legacy_history_file_path)
+            .expect("Could not find home directory");
+    }
     let mut home_dir = get_home_dir().expect("Could not find home directory");
-    home_dir.push(HISTORY_FILE_PATH);
+    match configured.strip_prefix("~") {
+        Ok(rest) => home_dir.push(rest),
+        Err(_) => return configured.to_path_buf(),
+    }
     home_dir
 }
 
-fn append_to_recognition_history(text: &str) -> io::Result<()> {
-    let history_file_path = get_history_file_path();
-    OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(history_file_path)?
-        .write_all(format!("{}\n", text).as_bytes())
+fn legacy_history_file_path() -> Option<PathBuf> {
+    get_home_dir().map(|home| home.join(LEGACY_HISTORY_FILE_NAME))
+}
+
+/// Appends an entry stamped in `style` to the history, unless it repeats the
+/// last one within `repeat_window`. `raw` is the recognizer output `text` was
+/// normalized from; it is kept after a tab when the two differ. Returns the
+/// line written, or None if it repeated.
+#[cfg(feature = "recognition")]
+fn append_to_recognition_history(
+    text: &str,
+    raw: &str,
+    heard_at: DateTime<Local>,
+    style: config::TimestampStyle,
+    repeat_window: Duration,
+) -> io::Result<Option<String>> {
+    let mut store = HistoryStore::open_default()?;
+    let heard_at = heard_at.naive_local();
+    if store.lines().last().is_some_and(|last| history::repeats(last, text, heard_at, repeat_window)) {
+        return Ok(None);
+    }
+    let line = store.append(history::format_entry(heard_at, style, text, raw));
+    store.commit()?;
+    Ok(Some(line))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+fn top_right_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(area.x + area.width - width, area.y, width, height)
 }
 
-fn duration_until_next_hour() -> Duration {
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-    let secs_since_epoch = now.as_secs();
-    let secs_in_hour = 3600;
-    let next_hour = (secs_since_epoch / secs_in_hour + 1) * secs_in_hour;
-    let duration_until_next_hour = (next_hour - secs_since_epoch) + 240;
-    Duration::from_secs(duration_until_next_hour)
+/// "stations" or "mixtapes", for messages about fetching them.
+fn section_name(section: StreamType) -> &'static str {
+    match section {
+        StreamType::Station => "stations",
+        _ => "mixtapes",
+    }
 }
 
-fn create_block(title: &str) -> Block {
+fn create_block<'a>(title: &'a str, theme: &Theme) -> Block<'a> {
     Block::default().borders(Borders::NONE).title(Span::styled(
         title,
         Style::default()
-            .fg(Color::Yellow)
+            .fg(theme.heading)
             .add_modifier(Modifier::BOLD),
     ))
 }
@@ -0,0 +1,76 @@
+use std::{path::Path, time::Duration};
+
+use crate::{config::RetentionConfig, debug_log::DebugLog, format, samples};
+
+/// How often maintenance runs. It is checked on the hourly stream refresh.
+pub const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Caps what a long-running session leaves on disk: rotates the debug log
+/// if it is full and deletes retained samples past their age. Reports what
+/// it did to the debug log. Touches only files, so it runs on a background
+/// thread.
+pub fn run(retention: &RetentionConfig, log: Option<&DebugLog>) {
+    if let Some(log) = log {
+        log.rotate_if_full();
+    }
+    let report = samples::retention_dir().and_then(|dir| prune_samples(&dir, retention));
+    if let (Some(log), Some(report)) = (log, report) {
+        log.append(&report);
+    }
+}
+
+/// Deletes the samples in `dir` older than the retention allows, and says
+/// what it did if it did anything.
+fn prune_samples(dir: &Path, retention: &RetentionConfig) -> Option<String> {
+    if retention.sample_max_age_days == 0 {
+        return None;
+    }
+    let max_age = Duration::from_secs(retention.sample_max_age_days * 24 * 60 * 60);
+    match samples::prune_older_than(dir, max_age) {
+        Ok((0, _)) => None,
+        Ok((removed, reclaimed)) => Some(format!(
+            "Maintenance: removed {} sample{} older than {} days, reclaimed {}",
+            removed,
+            if removed == 1 { "" } else { "s" },
+            retention.sample_max_age_days,
+            format::bytes(reclaimed)
+        )),
+        Err(err) => Some(format!("Maintenance: could not prune samples: {}", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        time::SystemTime,
+    };
+
+    use super::*;
+
+    #[test]
+    fn samples_past_the_retention_are_pruned_and_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let age = |days: u64| Duration::from_secs(days * 24 * 60 * 60);
+        for (name, days) in [("sample-a.mp3", 40), ("sample-b.mp3", 31), ("sample-c.mp3", 2)] {
+            fs::write(dir.path().join(name), vec![0; 2048]).unwrap();
+            let file = File::options().write(true).open(dir.path().join(name)).unwrap();
+            file.set_modified(SystemTime::now() - age(days)).unwrap();
+        }
+
+        let kept_forever = RetentionConfig {
+            sample_max_age_days: 0,
+            ..RetentionConfig::default()
+        };
+        assert_eq!(prune_samples(dir.path(), &kept_forever), None);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        let retention = RetentionConfig::default();
+        assert_eq!(
+            prune_samples(dir.path(), &retention).as_deref(),
+            Some("Maintenance: removed 2 samples older than 30 days, reclaimed 4.0 KiB")
+        );
+        assert!(dir.path().join("sample-c.mp3").exists());
+        assert_eq!(prune_samples(dir.path(), &retention), None);
+    }
+}
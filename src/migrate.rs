@@ -0,0 +1,258 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{config::Config, get_history_file_path, get_home_dir, legacy_history_file_path};
+
+pub const USAGE: &str = "Usage: nts_cli migrate [--dry-run]";
+/// Where the originals of migrated files are kept, in the data directory.
+const BACKUP_DIR_NAME: &str = "nts_cli/legacy";
+
+/// A file older versions kept in the home directory, which now lives in
+/// the data directory.
+pub struct Artifact {
+    legacy: &'static str,
+    current: &'static str,
+}
+
+pub const STATE: Artifact = Artifact {
+    legacy: ".nts_cli_state.json",
+    current: "nts_cli/state.json",
+};
+pub const LISTENING_LOG: Artifact = Artifact {
+    legacy: ".nts_cli_listening.log",
+    current: "nts_cli/listening.log",
+};
+pub const RECOGNITION_METRICS: Artifact = Artifact {
+    legacy: ".nts_cli_recognition_metrics.log",
+    current: "nts_cli/recognition_metrics.log",
+};
+
+const ARTIFACTS: [(&str, &Artifact); 3] = [
+    ("state", &STATE),
+    ("listening log", &LISTENING_LOG),
+    ("recognition metrics", &RECOGNITION_METRICS),
+];
+
+impl Artifact {
+    fn legacy_path(&self) -> Option<PathBuf> {
+        get_home_dir().map(|home| home.join(self.legacy))
+    }
+
+    fn current_path(&self) -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(self.current))
+    }
+
+    /// Where the file is read and written: the data directory, unless an
+    /// older version's file is still in the home directory and nothing is
+    /// in the data directory yet, so it keeps being used until migrated.
+    pub fn path(&self) -> PathBuf {
+        let legacy = self.legacy_path();
+        match self.current_path() {
+            Some(current) if current.exists() || !legacy.as_ref().is_some_and(|legacy| legacy.exists()) => current,
+            _ => legacy.expect("Could not find home directory"),
+        }
+    }
+
+    /// `path`, with the directory it goes in created.
+    #[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+    pub fn writable_path(&self) -> io::Result<PathBuf> {
+        let path = self.path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(path)
+    }
+}
+
+/// A file from an older version and where it goes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Move {
+    pub what: &'static str,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl Move {
+    pub fn describe(&self) -> String {
+        format!("{}: {} -> {}", self.what, self.from.display(), self.to.display())
+    }
+}
+
+/// The files from older versions still to be moved. Empty once they are,
+/// so migrating again does nothing.
+pub fn plan() -> Vec<Move> {
+    let mut candidates = Vec::new();
+    let config = Config::load().map(|checked| checked.config).unwrap_or_default();
+    if let (true, Some(from)) = (config.history_path.as_os_str().is_empty(), legacy_history_file_path()) {
+        candidates.push(Move {
+            what: "history",
+            from,
+            to: get_history_file_path(),
+        });
+    }
+    for (what, artifact) in ARTIFACTS {
+        if let (Some(from), Some(to)) = (artifact.legacy_path(), artifact.current_path()) {
+            candidates.push(Move { what, from, to });
+        }
+    }
+    pending(candidates)
+}
+
+/// The moves with a file to move and nothing in the way. A file already
+/// in the data directory is kept, and the old one left alone.
+fn pending(candidates: Vec<Move>) -> Vec<Move> {
+    candidates
+        .into_iter()
+        .filter(|step| step.from != step.to && step.from.exists() && !step.to.exists())
+        .collect()
+}
+
+pub fn backup_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(BACKUP_DIR_NAME))
+}
+
+/// Carries out `moves`, keeping the originals in the backup directory.
+pub fn migrate(moves: &[Move]) -> io::Result<()> {
+    let backup_dir = backup_dir().ok_or_else(|| io::Error::other("no data directory on this platform"))?;
+    apply(moves, &backup_dir)
+}
+
+/// Copies every file into place, then moves the originals to `backup_dir`.
+/// If a copy fails, the copies made so far are removed, leaving things as
+/// they were.
+fn apply(moves: &[Move], backup_dir: &Path) -> io::Result<()> {
+    let mut placed = Vec::new();
+    for step in moves {
+        if let Err(err) = place(&step.from, &step.to) {
+            for to in placed {
+                let _ = fs::remove_file(to);
+            }
+            return Err(io::Error::new(err.kind(), format!("{}: {}", step.from.display(), err)));
+        }
+        placed.push(&step.to);
+    }
+    fs::create_dir_all(backup_dir)?;
+    for step in moves {
+        let backup = backup_dir.join(step.from.file_name().unwrap_or_default());
+        // The data directory can be on another filesystem
+        if fs::rename(&step.from, &backup).is_err() {
+            fs::copy(&step.from, &backup)?;
+            fs::remove_file(&step.from)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies `from` next to `to` and renames it over, so `to` is either
+/// missing or whole.
+fn place(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let partial = to.with_extension("migrating");
+    fs::copy(from, &partial)?;
+    fs::rename(&partial, to).inspect_err(|_| {
+        let _ = fs::remove_file(&partial);
+    })
+}
+
+/// `nts_cli migrate`: moves the files older versions left in the home
+/// directory into the data directory.
+pub fn run(args: &[&str]) -> bool {
+    let dry_run = match args {
+        [] => false,
+        ["--dry-run"] => true,
+        _ => {
+            eprintln!("{}", USAGE);
+            return false;
+        }
+    };
+    let moves = plan();
+    if moves.is_empty() {
+        println!("Nothing to migrate");
+        return true;
+    }
+    println!("{}", if dry_run { "Would move:" } else { "Moving:" });
+    for step in &moves {
+        println!("  {}", step.describe());
+    }
+    if dry_run {
+        return true;
+    }
+    match migrate(&moves) {
+        Ok(()) => {
+            let backups = backup_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+            println!("Moved {} files; the originals are in {}", moves.len(), backups);
+            true
+        }
+        Err(err) => {
+            eprintln!("Could not migrate: {}", err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrating_copies_backs_up_and_then_has_nothing_left_to_do() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        let data = dir.path().join("data/nts_cli");
+        fs::create_dir_all(&home).unwrap();
+        let step = |what, legacy: &str, current: &str| Move {
+            what,
+            from: home.join(legacy),
+            to: data.join(current),
+        };
+        let candidates = || {
+            vec![
+                step("state", ".nts_cli_state.json", "state.json"),
+                step("listening log", ".nts_cli_listening.log", "listening.log"),
+                step("recognition metrics", ".nts_cli_recognition_metrics.log", "recognition_metrics.log"),
+            ]
+        };
+        fs::write(home.join(".nts_cli_state.json"), "{}").unwrap();
+        fs::write(home.join(".nts_cli_listening.log"), "log").unwrap();
+        let moves = pending(candidates());
+        assert_eq!(moves.iter().map(|step| step.what).collect::<Vec<_>>(), ["state", "listening log"]);
+
+        let backups = dir.path().join("data/nts_cli/legacy");
+        apply(&moves, &backups).unwrap();
+        assert_eq!(fs::read_to_string(data.join("state.json")).unwrap(), "{}");
+        assert_eq!(fs::read_to_string(backups.join(".nts_cli_listening.log")).unwrap(), "log");
+        assert!(!home.join(".nts_cli_state.json").exists());
+        assert!(!data.join("state.migrating").exists());
+        assert_eq!(pending(candidates()), []);
+
+        // A file already in place is kept, and the old one left alone
+        fs::write(home.join(".nts_cli_state.json"), "{\"old\": true}").unwrap();
+        assert_eq!(pending(candidates()), []);
+    }
+
+    #[test]
+    fn a_failed_copy_leaves_everything_as_it_was() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "a").unwrap();
+        let moves = [
+            Move {
+                what: "a",
+                from: dir.path().join("a"),
+                to: dir.path().join("data/a"),
+            },
+            Move {
+                what: "missing",
+                from: dir.path().join("missing"),
+                to: dir.path().join("data/missing"),
+            },
+        ];
+        assert!(apply(&moves, &dir.path().join("legacy")).is_err());
+        assert!(dir.path().join("a").exists());
+        assert!(!dir.path().join("data/a").exists());
+        assert!(!dir.path().join("legacy").exists());
+    }
+}
@@ -1,14 +1,26 @@
-use std::{collections::VecDeque, io::Read, time::Duration};
+use std::{
+    collections::VecDeque,
+    io::Read,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use minimp3::{Decoder, Frame};
+use minimp3::Frame;
 use rodio::Source;
 
+use crate::{
+    mp3_frames::Decoder,
+    pcm_tap::{PcmTap, TapWriter},
+};
+
 /// This is a modified version of [rodio's Mp3Decoder](https://github.com/RustAudio/rodio/blob/55d957f8b40c59fccea4162c4b03f6dd87a7a4d9/src/decoder/mp3.rs)
 /// which removes the "Seek" trait bound for streaming network audio.
 ///
 /// Related GitHub issue:
 /// https://github.com/RustAudio/rodio/issues/333
-
 pub struct Mp3StreamDecoder<R>
 where
     R: Read,
@@ -18,26 +30,134 @@ where
     current_frame_offset: usize,
     buffer: VecDeque<i16>,
     buffer_size: usize,
+    stats: DecodeStats,
+    skipped_silence: Duration,
+    tap: Option<TapWriter>,
+}
+
+/// RMS level, relative to full scale, below which a frame counts as silence
+/// (roughly -50 dBFS).
+const SILENCE_RMS_THRESHOLD: f32 = 0.003;
+/// Leading silence shorter than this is left alone.
+const MIN_SILENCE: Duration = Duration::from_millis(500);
+
+/// CPU time the decoder has spent filling its buffer, shared with the UI thread
+/// so it can estimate how much of the machine the audio path is using, along
+/// with what the frames decoded so far looked like.
+#[derive(Clone, Default)]
+pub struct DecodeStats {
+    busy_nanos: Arc<AtomicU64>,
+    /// Whether the platform told the decoder its CPU time at all.
+    busy_measured: Arc<AtomicBool>,
+    frames: Arc<AtomicU64>,
+    kbps_total: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    channels: Arc<AtomicU32>,
+    refills: Arc<AtomicU64>,
+    ended: Arc<AtomicBool>,
+    buffer_size: usize,
+}
+
+impl DecodeStats {
+    /// None where the decoder's CPU time can't be measured.
+    pub fn busy(&self) -> Option<Duration> {
+        self.busy_measured
+            .load(Ordering::Relaxed)
+            .then(|| Duration::from_nanos(self.busy_nanos.load(Ordering::Relaxed)))
+    }
+
+    /// The mean bitrate of the frames decoded so far, in kbps.
+    pub fn bitrate_kbps(&self) -> Option<u64> {
+        let frames = self.frames.load(Ordering::Relaxed);
+        (frames > 0).then(|| self.kbps_total.load(Ordering::Relaxed) / frames)
+    }
+
+    /// The sample rate and channel count of the last frame decoded.
+    pub fn format(&self) -> (u32, u32) {
+        (self.sample_rate.load(Ordering::Relaxed), self.channels.load(Ordering::Relaxed))
+    }
+
+    /// How many samples the buffer holds when full, and how many times it
+    /// has been refilled.
+    pub fn buffer(&self) -> (usize, u64) {
+        (self.buffer_size, self.refills.load(Ordering::Relaxed))
+    }
+
+    /// Whether the stream ran out: the reader hit its end or failed, and
+    /// nothing more will be decoded.
+    pub fn ended(&self) -> bool {
+        self.ended.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, elapsed: Option<Duration>) {
+        if let Some(elapsed) = elapsed {
+            self.busy_nanos
+                .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+            self.busy_measured.store(true, Ordering::Relaxed);
+        }
+        self.refills.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_frame(&self, frame: &Frame) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.kbps_total.fetch_add(frame.bitrate.max(0) as u64, Ordering::Relaxed);
+        self.sample_rate.store(frame.sample_rate.max(0) as u32, Ordering::Relaxed);
+        self.channels.store(frame.channels as u32, Ordering::Relaxed);
+    }
 }
 
 impl<R> Mp3StreamDecoder<R>
 where
     R: Read,
 {
-    pub fn new(mut data: R, buffer_size: usize) -> Result<Self, R> {
+    /// When `max_skip` is set, drops leading near-silence that lasts longer
+    /// than [`MIN_SILENCE`], up to `max_skip` of it. Shorter gaps are kept so
+    /// nothing audible is cut.
+    pub fn new(mut data: R, buffer_size: usize, max_skip: Option<Duration>) -> Result<Self, R> {
         if !is_mp3(data.by_ref()) {
             return Err(data);
         }
         let mut decoder = Decoder::new(data);
-        let current_frame = decoder.next_frame().unwrap();
+        let mut current_frame = decoder.next_frame().unwrap();
+
+        let mut buffer = VecDeque::with_capacity(buffer_size);
+        let mut skipped_silence = Duration::ZERO;
+        if let Some(max_skip) = max_skip {
+            let mut held = Vec::new();
+            let mut silent = Duration::ZERO;
+            while is_near_silent(&current_frame) {
+                let length = frame_duration(&current_frame);
+                if silent + length > max_skip {
+                    break;
+                }
+                silent += length;
+                let next = match decoder.next_frame() {
+                    Ok(frame) => frame,
+                    _ => break,
+                };
+                held.extend(std::mem::replace(&mut current_frame, next).data);
+            }
+            if silent >= MIN_SILENCE {
+                skipped_silence = silent;
+            } else {
+                buffer.extend(held);
+            }
+        }
 
         let mut decoder = Self {
             decoder,
             current_frame,
             current_frame_offset: 0,
-            buffer: VecDeque::with_capacity(buffer_size),
+            buffer,
             buffer_size,
+            stats: DecodeStats {
+                buffer_size,
+                ..Default::default()
+            },
+            skipped_silence,
+            tap: None,
         };
+        decoder.stats.record_frame(&decoder.current_frame);
 
         // Pre-fill the buffer
         decoder.fill_buffer();
@@ -49,14 +169,48 @@ where
     //     self.decoder.into_inner()
     // }
 
+    /// Copies every frame decoded from now on into `tap`.
+    pub fn set_tap(&mut self, tap: &PcmTap) {
+        self.tap = Some(tap.writer());
+    }
+
+    /// How much leading silence was dropped when the decoder was created.
+    pub fn skipped_silence(&self) -> Duration {
+        self.skipped_silence
+    }
+
+    pub fn stats(&self) -> DecodeStats {
+        self.stats.clone()
+    }
+
     fn fill_buffer(&mut self) {
+        let started = thread_cpu_time();
+        self.fill_buffer_inner();
+        let elapsed = started.zip(thread_cpu_time()).map(|(started, ended)| ended.saturating_sub(started));
+        self.stats.record(elapsed);
+    }
+
+    fn fill_buffer_inner(&mut self) {
         while self.buffer.len() < self.buffer_size {
             if self.current_frame_offset == self.current_frame.data.len() {
                 match self.decoder.next_frame() {
                     Ok(frame) => self.current_frame = frame,
-                    _ => break,
+                    // minimp3 skips bad frames itself, so this is the end of
+                    // the reader or a read that failed
+                    Err(_) => {
+                        self.stats.ended.store(true, Ordering::Relaxed);
+                        break;
+                    }
                 }
                 self.current_frame_offset = 0;
+                self.stats.record_frame(&self.current_frame);
+                if let Some(tap) = &mut self.tap {
+                    tap.push(
+                        &self.current_frame.data,
+                        self.current_frame.channels as u16,
+                        self.current_frame.sample_rate as u32,
+                    );
+                }
             }
 
             while self.current_frame_offset < self.current_frame.data.len() && self.buffer.len() < self.buffer_size {
@@ -67,6 +221,29 @@ where
     }
 }
 
+fn is_near_silent(frame: &Frame) -> bool {
+    if frame.data.is_empty() {
+        return true;
+    }
+    let sum_of_squares: f64 = frame
+        .data
+        .iter()
+        .map(|&sample| {
+            let sample = sample as f64 / i16::MAX as f64;
+            sample * sample
+        })
+        .sum();
+    ((sum_of_squares / frame.data.len() as f64).sqrt() as f32) < SILENCE_RMS_THRESHOLD
+}
+
+fn frame_duration(frame: &Frame) -> Duration {
+    if frame.sample_rate <= 0 || frame.channels == 0 {
+        return Duration::ZERO;
+    }
+    let samples_per_channel = frame.data.len() / frame.channels;
+    Duration::from_secs_f64(samples_per_channel as f64 / frame.sample_rate as f64)
+}
+
 impl<R> Source for Mp3StreamDecoder<R>
 where
     R: Read,
@@ -114,4 +291,24 @@ where
     R: Read,
 {
     true
-}
\ No newline at end of file
+}
+
+/// CPU time consumed by the calling thread. Unlike wall-clock time this excludes
+/// the time spent blocked on the network inside `next_frame`.
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid, writable timespec for the duration of the call.
+    let result = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    (result == 0).then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+/// Wall-clock time would count waiting on the network as load, so elsewhere
+/// the decoder's load isn't measured at all.
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
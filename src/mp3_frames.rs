@@ -0,0 +1,97 @@
+use std::{
+    io::{self, Read},
+    mem,
+};
+
+use minimp3::{ffi, Error, Frame, MAX_SAMPLES_PER_FRAME};
+
+/// Decoding starts topping up the input below this many bytes.
+const REFILL_TRIGGER: usize = MAX_SAMPLES_PER_FRAME * 8;
+/// How much one read asks the reader for.
+const REFILL_SIZE: usize = MAX_SAMPLES_PER_FRAME * 5;
+
+/// minimp3's frame reader with the input kept in a plain `Vec`. The crate's
+/// own keeps it in a slice-deque, which indexes one past its length on every
+/// refill, and debug builds abort on that.
+pub struct Decoder<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    /// Where the input not decoded yet starts in `buffer`.
+    start: usize,
+    decoder: Box<ffi::mp3dec_t>,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R) -> Self {
+        // SAFETY: mp3dec_t is plain data, which mp3dec_init then sets up.
+        let mut decoder: Box<ffi::mp3dec_t> = Box::new(unsafe { mem::zeroed() });
+        unsafe { ffi::mp3dec_init(&mut *decoder) };
+        Self {
+            reader,
+            buffer: Vec::with_capacity(REFILL_TRIGGER + REFILL_SIZE),
+            start: 0,
+            decoder,
+        }
+    }
+
+    /// The next frame, skipping anything that isn't one, like ID3 tags.
+    /// `Error::Eof` once the reader has nothing left that decodes.
+    pub fn next_frame(&mut self) -> Result<Frame, Error> {
+        loop {
+            let read = if self.buffer.len() - self.start < REFILL_TRIGGER {
+                Some(self.refill()?)
+            } else {
+                None
+            };
+            match self.decode_frame() {
+                Ok(frame) => return Ok(frame),
+                Err(Error::InsufficientData | Error::SkippedData) if read == Some(0) => return Err(Error::Eof),
+                Err(Error::InsufficientData | Error::SkippedData) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn refill(&mut self) -> io::Result<usize> {
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        let filled = self.buffer.len();
+        self.buffer.resize(filled + REFILL_SIZE, 0);
+        let read = self.reader.read(&mut self.buffer[filled..]);
+        self.buffer.truncate(filled + *read.as_ref().unwrap_or(&0));
+        read
+    }
+
+    fn decode_frame(&mut self) -> Result<Frame, Error> {
+        let input = &self.buffer[self.start..];
+        let mut pcm = vec![0; MAX_SAMPLES_PER_FRAME];
+        // SAFETY: the info is plain data the call fills in, `input` is valid
+        // for its length and `pcm` has room for the largest frame.
+        let mut info: ffi::mp3dec_frame_info_t = unsafe { mem::zeroed() };
+        let samples = unsafe {
+            ffi::mp3dec_decode_frame(
+                &mut *self.decoder,
+                input.as_ptr(),
+                input.len() as _,
+                pcm.as_mut_ptr(),
+                &mut info,
+            )
+        };
+        self.start += info.frame_bytes.max(0) as usize;
+        if samples <= 0 {
+            return Err(if info.frame_bytes > 0 {
+                Error::SkippedData
+            } else {
+                Error::InsufficientData
+            });
+        }
+        pcm.truncate(samples as usize * info.channels as usize);
+        Ok(Frame {
+            data: pcm,
+            sample_rate: info.hz,
+            channels: info.channels as usize,
+            layer: info.layer as usize,
+            bitrate: info.bitrate_kbps,
+        })
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How much of the raw stream the tap keeps, and so the longest sample.
+pub const KEEP: Duration = Duration::from_secs(60);
+
+type Chunk = (Instant, Arc<[u8]>);
+
+/// Rolling copy of the MP3 bytes the decoder reads, stamped with when it
+/// read them, so recognition can take the last seconds of what is playing
+/// instead of opening a connection of its own.
+///
+/// Like the PCM tap, the decoder's side never waits for the lock: chunks it
+/// can't publish are kept and published with the next read.
+#[derive(Clone, Default)]
+pub struct Mp3Tap {
+    inner: Arc<Mutex<VecDeque<Chunk>>>,
+}
+
+/// Reads through to `inner`, copying what it reads into the tap.
+pub struct TeeReader<R> {
+    inner: R,
+    tap: Mp3Tap,
+    pending: Vec<Chunk>,
+}
+
+impl<R> TeeReader<R> {
+    pub fn new(inner: R, tap: Mp3Tap) -> Self {
+        TeeReader {
+            inner,
+            tap,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read> Read for TeeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0 {
+            self.pending.push((Instant::now(), Arc::from(&buf[..read])));
+            if let Ok(mut chunks) = self.tap.inner.try_lock() {
+                for chunk in self.pending.drain(..) {
+                    publish(&mut chunks, chunk);
+                }
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// Drops the chunks older than `KEEP`, but keeps the one that straddles it
+/// so the tap always reaches a full `KEEP` back once it has been filled.
+fn publish(chunks: &mut VecDeque<Chunk>, chunk: Chunk) {
+    let at = chunk.0;
+    chunks.push_back(chunk);
+    while chunks.get(1).is_some_and(|(read_at, _)| at.saturating_duration_since(*read_at) >= KEEP) {
+        chunks.pop_front();
+    }
+}
+
+impl Mp3Tap {
+    /// Handles to the chunks read since `since`, cloned under the lock.
+    #[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+    fn chunks_since(&self, since: Option<Instant>) -> Vec<Chunk> {
+        let Ok(chunks) = self.inner.lock() else {
+            return Vec::new();
+        };
+        chunks
+            .iter()
+            .filter(|(read_at, _)| since.is_none_or(|since| *read_at >= since))
+            .cloned()
+            .collect()
+    }
+
+    /// How far back the tap reaches from `now`.
+    #[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+    pub fn reach(&self, now: Instant) -> Duration {
+        let oldest = self.inner.lock().ok().and_then(|chunks| chunks.front().map(|(read_at, _)| *read_at));
+        oldest.map_or(Duration::ZERO, |oldest| now.saturating_duration_since(oldest))
+    }
+
+    /// How long since the decoder last read anything, or None before it
+    /// read at all.
+    pub fn idle(&self, now: Instant) -> Option<Duration> {
+        let newest = self.inner.lock().ok()?.back().map(|(read_at, _)| *read_at)?;
+        Some(now.saturating_duration_since(newest))
+    }
+
+    /// The bytes read over the `length` before `now`. They start mid-frame,
+    /// which decoders skip past to the next frame header.
+    #[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+    pub fn last(&self, length: Duration, now: Instant) -> Vec<u8> {
+        let since = now.checked_sub(length);
+        self.chunks_since(since)
+            .iter()
+            .flat_map(|(_, bytes)| bytes.iter().copied())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn keeps_what_the_decoder_read_and_forgets_the_oldest() {
+        let tap = Mp3Tap::default();
+        let data: Vec<u8> = (0..=255).collect();
+        let mut reader = TeeReader::new(Cursor::new(data.clone()), tap.clone());
+        let mut read = Vec::new();
+        let mut buf = [0u8; 100];
+        loop {
+            match reader.read(&mut buf).unwrap() {
+                0 => break,
+                n => read.extend_from_slice(&buf[..n]),
+            }
+        }
+        let now = Instant::now();
+        assert_eq!(read, data);
+        assert_eq!(tap.last(Duration::from_secs(5), now), data);
+        assert!(tap.idle(now).is_some());
+        assert_eq!(Mp3Tap::default().idle(now), None);
+        assert_eq!(Mp3Tap::default().reach(now), Duration::ZERO);
+
+        let start = Instant::now();
+        let mut chunks = VecDeque::new();
+        for second in 0..=KEEP.as_secs() + 5 {
+            publish(&mut chunks, (start + Duration::from_secs(second), Arc::from(&[second as u8][..])));
+        }
+        assert_eq!(chunks.len(), KEEP.as_secs() as usize + 1);
+        assert_eq!(chunks.front().unwrap().1[0], 5);
+        let tap = Mp3Tap {
+            inner: Arc::new(Mutex::new(chunks)),
+        };
+        let end = start + KEEP + Duration::from_secs(5);
+        assert_eq!(tap.reach(end), KEEP);
+        assert_eq!(tap.last(Duration::from_secs(3), end), [62, 63, 64, 65]);
+    }
+}
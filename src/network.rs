@@ -0,0 +1,104 @@
+use std::{
+    net::{IpAddr, UdpSocket},
+    thread,
+    time::Duration,
+};
+
+use serde_json::Value;
+
+use crate::fetch_scheduler::{self, Priority};
+
+/// Looks up where an address is. Only asked when `locate_edge` is set.
+const GEOLOCATION_URL: &str = "https://ipinfo.io/";
+
+/// Public addresses used only to ask the routing table which local address
+/// would be used to reach the internet. Connecting a UDP socket sends nothing.
+const ROUTE_PROBES: [(&str, &str); 2] = [("0.0.0.0:0", "1.1.1.1:80"), ("[::]:0", "[2606:4700:4700::1111]:80")];
+
+/// The local address of the current default route, or None when offline.
+pub fn route_source() -> Option<IpAddr> {
+    ROUTE_PROBES.iter().find_map(|(bind, probe)| {
+        let socket = UdpSocket::bind(bind).ok()?;
+        socket.connect(probe).ok()?;
+        Some(socket.local_addr().ok()?.ip())
+    })
+}
+
+/// Watches for the default route moving to another interface or address
+/// (ethernet to wifi, VPN up or down) and calls `on_change` when it does.
+/// Losing the route entirely is not reported; the next route that appears
+/// is compared with the last one seen. Stops when `on_change` returns false.
+pub fn watch(interval: Duration, mut on_change: impl FnMut() -> bool + Send + 'static) {
+    thread::spawn(move || {
+        let mut last = route_source();
+        loop {
+            thread::sleep(interval);
+            let Some(current) = route_source() else {
+                continue;
+            };
+            if last.is_some_and(|last| last != current) && !on_change() {
+                break;
+            }
+            last = Some(current);
+        }
+    });
+}
+
+/// Which CDN node answered, from the headers it sent: the nearest cache in
+/// `x-served-by`, the datacenter in `cf-ray`, or the last hop in `via`.
+pub fn edge_label(headers: &[(String, String)]) -> Option<String> {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+    // Chains list the node nearest the listener last
+    let last = |value: &str| value.rsplit(',').next().unwrap_or(value).trim().to_string();
+    if let Some(served_by) = header("x-served-by") {
+        return Some(last(served_by));
+    }
+    if let Some((_, datacenter)) = header("cf-ray").and_then(|ray| ray.rsplit_once('-')) {
+        return Some(format!("Cloudflare {}", datacenter));
+    }
+    header("via").map(|via| {
+        let hop = last(via);
+        // "1.1 varnish" names the proxy after its protocol version
+        match hop.split_once(' ') {
+            Some((_, proxy)) => proxy.trim().to_string(),
+            None => hop,
+        }
+    })
+}
+
+/// "London, GB" for `ip`, or None when the lookup fails. Blocks on a
+/// request, so it runs on a worker thread.
+pub fn locate(ip: IpAddr) -> Option<String> {
+    let body = fetch_scheduler::get(&format!("{}{}/json", GEOLOCATION_URL, ip), Priority::Background).ok()?;
+    let json: Value = serde_json::from_str(&body).ok()?;
+    let place: Vec<&str> = ["city", "country"]
+        .iter()
+        .filter_map(|key| json[key].as_str().filter(|value| !value.is_empty()))
+        .collect();
+    (!place.is_empty()).then(|| place.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edge_labels_come_from_the_cdn_headers() {
+        let headers = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+        };
+        let fastly = headers(&[("via", "1.1 varnish"), ("x-served-by", "cache-ams21-AMS, cache-lhr7321-LHR")]);
+        assert_eq!(edge_label(&fastly), Some("cache-lhr7321-LHR".to_string()));
+        let cloudflare = headers(&[("cf-ray", "8a1b2c3d4e5f6789-CDG")]);
+        assert_eq!(edge_label(&cloudflare), Some("Cloudflare CDG".to_string()));
+        let proxied = headers(&[("Via", "1.1 google, 1.1 varnish")]);
+        assert_eq!(edge_label(&proxied), Some("varnish".to_string()));
+        assert_eq!(edge_label(&headers(&[("content-type", "audio/mpeg"), ("via", " ")])), None);
+    }
+}
@@ -0,0 +1,23 @@
+use std::process::{Command, Stdio};
+
+/// Shows a desktop notification using the platform's command line notifier.
+/// Failures are ignored: notifications are a nicety, never a requirement.
+pub fn send(summary: &str, body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {:?} with title {:?}",
+            body, summary
+        ));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name", "nts_cli", summary, body]);
+        command
+    };
+    let _ = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// How much decoded audio the tap keeps around.
+pub const TAP_SECONDS: usize = 60;
+
+/// Rolling copy of the decoded audio, written by the decoder and read by
+/// anything that wants to look at what is actually playing.
+///
+/// The writer never waits for readers: frames it can't publish because a
+/// reader holds the lock are kept and published with the next frame. Readers
+/// only hold the lock long enough to clone the frame handles and do their
+/// copying afterwards, so a slow consumer can't stall the decoder or open
+/// gaps for the others.
+#[derive(Clone, Default)]
+pub struct PcmTap {
+    inner: Arc<Mutex<TapBuffer>>,
+}
+
+type TapFrame = Arc<[i16]>;
+
+#[derive(Default)]
+struct TapBuffer {
+    frames: VecDeque<TapFrame>,
+    samples: usize,
+    channels: u16,
+    sample_rate: u32,
+    /// Bumped by `clear`, so writers of a stopped stream can't publish into
+    /// the next one.
+    generation: u64,
+}
+
+/// The decoder's end of the tap.
+pub struct TapWriter {
+    tap: PcmTap,
+    generation: u64,
+    pending: Vec<(TapFrame, u16, u32)>,
+}
+
+/// The newest frames copied out of the tap, read without holding its lock.
+struct TapSnapshot {
+    frames: Vec<TapFrame>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+/// A stretch of past audio copied out of the tap. Only recognition reads
+/// more than the samples.
+#[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+pub struct Clip {
+    pub samples: Vec<i16>,
+    pub channels: u16,
+    pub sample_rate: u32,
+    /// When the first sample of the clip was played.
+    pub heard_at: SystemTime,
+    /// How far back the clip starts.
+    pub starts_ago: Duration,
+    /// The tap didn't reach back far enough, so the clip starts at the
+    /// oldest audio there was instead.
+    pub truncated: bool,
+}
+
+impl TapBuffer {
+    fn publish(&mut self, frame: TapFrame, channels: u16, sample_rate: u32) {
+        if self.channels != channels || self.sample_rate != sample_rate {
+            self.frames.clear();
+            self.samples = 0;
+            self.channels = channels;
+            self.sample_rate = sample_rate;
+        }
+        self.samples += frame.len();
+        self.frames.push_back(frame);
+        let capacity = TAP_SECONDS * sample_rate as usize * channels as usize;
+        while self.samples > capacity {
+            let Some(oldest) = self.frames.pop_front() else {
+                break;
+            };
+            self.samples -= oldest.len();
+        }
+    }
+}
+
+impl PcmTap {
+    /// A writer for a new stream. Whatever it writes is dropped once the tap
+    /// is cleared.
+    pub fn writer(&self) -> TapWriter {
+        let generation = self.inner.lock().map_or(0, |buffer| buffer.generation);
+        TapWriter {
+            tap: self.clone(),
+            generation,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut buffer) = self.inner.lock() {
+            buffer.frames.clear();
+            buffer.samples = 0;
+            buffer.generation += 1;
+        }
+    }
+
+    /// At least the newest `frames(sample_rate)` frames of audio, or all of
+    /// it if the tap is shorter. Only clones frame handles under the lock.
+    fn tail(&self, frames: impl FnOnce(u32) -> usize) -> Option<TapSnapshot> {
+        let buffer = self.inner.lock().ok()?;
+        let wanted = frames(buffer.sample_rate) * buffer.channels.max(1) as usize;
+        let mut samples = 0;
+        let mut tail: Vec<_> = buffer
+            .frames
+            .iter()
+            .rev()
+            .take_while(|frame| {
+                let more = samples < wanted;
+                samples += frame.len();
+                more
+            })
+            .cloned()
+            .collect();
+        tail.reverse();
+        Some(TapSnapshot {
+            frames: tail,
+            channels: buffer.channels,
+            sample_rate: buffer.sample_rate,
+        })
+    }
+
+    /// Copies up to `length` of audio starting `ago` before the live edge. If
+    /// the tap doesn't go back that far, the clip starts at the oldest audio.
+    pub fn clip(&self, ago: Duration, length: Duration) -> Option<Clip> {
+        let wanted_back = |rate: u32| (ago.as_secs_f64() * rate as f64) as usize;
+        let snapshot = self.tail(wanted_back)?;
+        let channels = snapshot.channels.max(1) as usize;
+        let rate = snapshot.sample_rate as f64;
+        let frames = snapshot.len() / channels;
+        if frames == 0 || rate == 0.0 {
+            return None;
+        }
+        let wanted_back = wanted_back(snapshot.sample_rate);
+        let truncated = wanted_back > frames;
+        let back = wanted_back.min(frames);
+        let length = ((length.as_secs_f64() * rate) as usize).min(back);
+        let starts_ago = Duration::from_secs_f64(back as f64 / rate);
+        Some(Clip {
+            samples: snapshot.copy((frames - back) * channels, length * channels),
+            channels: snapshot.channels,
+            sample_rate: snapshot.sample_rate,
+            heard_at: SystemTime::now() - starts_ago,
+            starts_ago,
+            truncated,
+        })
+    }
+
+    /// The most recent `count` samples mixed down to mono, with the sample
+    /// rate, or None if not enough audio has been decoded yet.
+    pub fn latest_mono(&self, count: usize) -> Option<(Vec<f32>, u32)> {
+        let snapshot = self.tail(|_| count)?;
+        let channels = snapshot.channels.max(1) as usize;
+        let frames = snapshot.len() / channels;
+        if frames < count {
+            return None;
+        }
+        let mono = snapshot
+            .copy((frames - count) * channels, count * channels)
+            .chunks(channels)
+            .map(|frame| frame.iter().map(|&s| s as f32).sum::<f32>() / (channels as f32 * i16::MAX as f32))
+            .collect();
+        Some((mono, snapshot.sample_rate))
+    }
+}
+
+impl TapSnapshot {
+    fn len(&self) -> usize {
+        self.frames.iter().map(|frame| frame.len()).sum()
+    }
+
+    /// `length` samples starting `skip` samples into the snapshot.
+    fn copy(&self, mut skip: usize, length: usize) -> Vec<i16> {
+        let mut samples = Vec::with_capacity(length);
+        for frame in &self.frames {
+            if samples.len() == length {
+                break;
+            }
+            if skip >= frame.len() {
+                skip -= frame.len();
+                continue;
+            }
+            let take = (length - samples.len()).min(frame.len() - skip);
+            samples.extend_from_slice(&frame[skip..skip + take]);
+            skip = 0;
+        }
+        samples
+    }
+}
+
+impl TapWriter {
+    /// Appends a frame of interleaved samples. Called from the audio thread,
+    /// so it never waits: if a reader holds the lock, the frame is published
+    /// along with the next one instead.
+    pub fn push(&mut self, data: &[i16], channels: u16, sample_rate: u32) {
+        self.pending.push((Arc::from(data), channels, sample_rate));
+        if let Ok(mut buffer) = self.tap.inner.try_lock() {
+            Self::publish_pending(&mut self.pending, &mut buffer, self.generation);
+        }
+    }
+
+    fn publish_pending(pending: &mut Vec<(TapFrame, u16, u32)>, buffer: &mut TapBuffer, generation: u64) {
+        if buffer.generation != generation {
+            pending.clear();
+            return;
+        }
+        for (frame, channels, sample_rate) in pending.drain(..) {
+            buffer.publish(frame, channels, sample_rate);
+        }
+    }
+}
+
+impl Drop for TapWriter {
+    fn drop(&mut self) {
+        if let Ok(mut buffer) = self.tap.inner.lock() {
+            Self::publish_pending(&mut self.pending, &mut buffer, self.generation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    const CHANNELS: u16 = 2;
+    const SAMPLE_RATE: u32 = 44_100;
+    const FRAME_SAMPLES: usize = 1152 * CHANNELS as usize;
+    const FRAMES: usize = 1500;
+
+    fn source() -> Vec<i16> {
+        (0..FRAMES * FRAME_SAMPLES)
+            .map(|i| (i.wrapping_mul(7919) % 65_536) as u16 as i16)
+            .collect()
+    }
+
+    // Every frame must make it into the tap, in order, while recognition and
+    // playback-side readers hammer it at the same time.
+    #[test]
+    fn tap_is_byte_identical_under_contention() {
+        let source = source();
+        let tap = PcmTap::default();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..3)
+            .map(|i| {
+                let tap = tap.clone();
+                let done = done.clone();
+                thread::spawn(move || {
+                    while !done.load(Ordering::Acquire) {
+                        if i == 0 {
+                            let _ = tap.latest_mono(4096);
+                        } else {
+                            let _ = tap.clip(Duration::from_secs(10), Duration::from_secs(5));
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut writer = tap.writer();
+        for frame in source.chunks(FRAME_SAMPLES) {
+            writer.push(frame, CHANNELS, SAMPLE_RATE);
+        }
+        drop(writer);
+        done.store(true, Ordering::Release);
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        let clip = tap.clip(Duration::from_secs(TAP_SECONDS as u64), Duration::from_secs(TAP_SECONDS as u64)).unwrap();
+        assert!(clip.truncated);
+        assert!(clip.samples == source, "tap differs from the source");
+    }
+
+    #[test]
+    fn reads_come_from_the_live_edge() {
+        let source = source();
+        let tap = PcmTap::default();
+        let mut writer = tap.writer();
+        for frame in source.chunks(FRAME_SAMPLES) {
+            writer.push(frame, CHANNELS, SAMPLE_RATE);
+        }
+        drop(writer);
+
+        let clip = tap.clip(Duration::from_secs(2), Duration::from_secs(1)).unwrap();
+        let start = source.len() - 2 * SAMPLE_RATE as usize * CHANNELS as usize;
+        assert!(!clip.truncated);
+        assert!(clip.samples == source[start..start + SAMPLE_RATE as usize * CHANNELS as usize]);
+
+        let (mono, rate) = tap.latest_mono(3).unwrap();
+        let expected: Vec<f32> = source[source.len() - 6..]
+            .chunks(2)
+            .map(|frame| (frame[0] as f32 + frame[1] as f32) / (2.0 * i16::MAX as f32))
+            .collect();
+        assert_eq!((mono, rate), (expected, SAMPLE_RATE));
+    }
+
+    #[test]
+    fn cleared_tap_ignores_old_writers() {
+        let tap = PcmTap::default();
+        let mut writer = tap.writer();
+        tap.clear();
+        writer.push(&[1; FRAME_SAMPLES], CHANNELS, SAMPLE_RATE);
+        drop(writer);
+        assert!(tap.latest_mono(1).is_none());
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::samples;
+
+const PENDING_FILE_NAME: &str = "nts_cli/pending_ids.json";
+
+/// Held around every read-modify-write of the queue, since recognitions and
+/// the retry run on their own threads.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// A recognition whose sample was captured but couldn't be identified,
+/// most likely for want of a connection. The sample lives with the retained
+/// ones, so it counts toward their cap.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PendingId {
+    pub sample: PathBuf,
+    /// When the sample was heard, in Unix seconds.
+    pub heard_at: i64,
+    /// False for recognitions made in incognito.
+    pub write_history: bool,
+}
+
+impl PendingId {
+    pub fn heard_at(&self) -> DateTime<Local> {
+        Local.timestamp_opt(self.heard_at, 0).single().unwrap_or_else(Local::now)
+    }
+}
+
+fn pending_file_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(PENDING_FILE_NAME))
+}
+
+fn load() -> Vec<PendingId> {
+    pending_file_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save(pending: &[PendingId]) -> io::Result<()> {
+    let path = pending_file_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(pending)?)
+}
+
+/// Keeps `sample` with the retained samples and queues it to be identified
+/// later. Returns how many are pending now.
+pub fn enqueue(sample: &Path, heard_at: DateTime<Local>, write_history: bool, cap: usize) -> io::Result<usize> {
+    let sample = samples::retain(sample, cap)?;
+    let _lock = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut pending = load();
+    pending.push(PendingId {
+        sample,
+        heard_at: heard_at.timestamp(),
+        write_history,
+    });
+    save(&pending)?;
+    Ok(pending.len())
+}
+
+/// The queue, oldest first. Entries heard more than `max_age` ago are
+/// dropped with their samples, as are ones whose sample the retention cap
+/// already removed.
+pub fn pending(max_age: Duration) -> Vec<PendingId> {
+    let _lock = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut pending = load();
+    let (kept, dropped) = split_expired(pending.drain(..), Local::now().timestamp(), max_age);
+    if !dropped.is_empty() {
+        for id in &dropped {
+            let _ = fs::remove_file(&id.sample);
+        }
+        let _ = save(&kept);
+    }
+    kept
+}
+
+fn split_expired(
+    pending: impl Iterator<Item = PendingId>,
+    now: i64,
+    max_age: Duration,
+) -> (Vec<PendingId>, Vec<PendingId>) {
+    pending.partition(|id| now - id.heard_at <= max_age.as_secs() as i64 && id.sample.exists())
+}
+
+/// Takes an entry off the queue once it has been dealt with.
+pub fn remove(sample: &Path) {
+    let _lock = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut pending = load();
+    pending.retain(|id| id.sample != sample);
+    let _ = save(&pending);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_and_missing_samples_leave_the_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let sample = |name: &str, heard_at| {
+            let path = dir.path().join(name);
+            PendingId {
+                sample: path,
+                heard_at,
+                write_history: true,
+            }
+        };
+        let (fresh, old, gone) = (sample("a.wav", 1_000), sample("b.wav", 100), sample("c.wav", 1_000));
+        fs::write(&fresh.sample, b"").unwrap();
+        fs::write(&old.sample, b"").unwrap();
+
+        let (kept, dropped) = split_expired(
+            vec![old.clone(), fresh.clone(), gone.clone()].into_iter(),
+            1_100,
+            Duration::from_secs(600),
+        );
+        assert_eq!(kept, [fresh]);
+        assert_eq!(dropped, [old, gone]);
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use rodio::Source;
+
+/// How often to look for a reader while nobody has the pipe open.
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// Samples written per chunk, about 50 ms of 44.1 kHz stereo.
+const CHUNK_SAMPLES: usize = 4096;
+/// How often a paused writer checks whether to carry on.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Decoded audio written to a named pipe instead of a sound device, for
+/// running it through an external DSP chain. Each reader that opens the pipe
+/// first gets one header line, `NTSPCM s16le <sample rate> <channels>\n`,
+/// then interleaved signed 16-bit little-endian samples.
+///
+/// Output pauses while nobody is reading, including after a reader went
+/// away, and resumes when one opens the pipe again. Dropping it stops the
+/// writer thread.
+pub struct PipeOutput {
+    stop: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    volume: Arc<AtomicU32>,
+    reader_connected: Arc<AtomicBool>,
+}
+
+impl PipeOutput {
+    /// Creates the FIFO at `path` if needed and starts writing `source` to
+    /// it on a thread.
+    pub fn start<S>(path: &Path, source: S, volume: f32) -> Result<PipeOutput, String>
+    where
+        S: Source<Item = i16> + Send + 'static,
+    {
+        ensure_fifo(path)?;
+        let output = PipeOutput {
+            stop: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            volume: Arc::new(AtomicU32::new(volume.to_bits())),
+            reader_connected: Arc::new(AtomicBool::new(false)),
+        };
+        let writer = Writer {
+            path: path.to_path_buf(),
+            stop: output.stop.clone(),
+            paused: output.paused.clone(),
+            volume: output.volume.clone(),
+            reader_connected: output.reader_connected.clone(),
+        };
+        thread::spawn(move || writer.run(source));
+        Ok(output)
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Holds the writer, and with it the decoder, until unpaused.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Whether a reader has the pipe open, i.e. audio is flowing.
+    pub fn reader_connected(&self) -> bool {
+        self.reader_connected.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for PipeOutput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Writer {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    volume: Arc<AtomicU32>,
+    reader_connected: Arc<AtomicBool>,
+}
+
+impl Writer {
+    fn run<S: Source<Item = i16>>(self, mut source: S) {
+        let mut chunk = Vec::with_capacity(CHUNK_SAMPLES * 2);
+        while !self.stop.load(Ordering::Relaxed) {
+            let Ok(mut pipe) = open_writer(&self.path) else {
+                thread::sleep(RETRY_INTERVAL);
+                continue;
+            };
+            let header = format!("NTSPCM s16le {} {}\n", source.sample_rate(), source.channels());
+            if pipe.write_all(header.as_bytes()).is_err() {
+                continue;
+            }
+            self.reader_connected.store(true, Ordering::Relaxed);
+            while !self.stop.load(Ordering::Relaxed) {
+                if self.paused.load(Ordering::Relaxed) {
+                    thread::sleep(PAUSE_POLL_INTERVAL);
+                    continue;
+                }
+                let volume = f32::from_bits(self.volume.load(Ordering::Relaxed));
+                chunk.clear();
+                for sample in source.by_ref().take(CHUNK_SAMPLES) {
+                    let scaled = (sample as f32 * volume).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+                    chunk.extend_from_slice(&scaled.to_le_bytes());
+                }
+                if chunk.is_empty() {
+                    // The stream ended
+                    self.reader_connected.store(false, Ordering::Relaxed);
+                    return;
+                }
+                // A reader that went away fails the write with a broken pipe;
+                // wait for the next one rather than giving up
+                if pipe.write_all(&chunk).is_err() {
+                    break;
+                }
+            }
+            self.reader_connected.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Makes sure `path` is a named pipe, creating it if nothing is there.
+#[cfg(unix)]
+fn ensure_fifo(path: &Path) -> Result<(), String> {
+    use std::{ffi::CString, os::unix::fs::FileTypeExt};
+
+    match path.metadata() {
+        Ok(metadata) if metadata.file_type().is_fifo() => Ok(()),
+        Ok(_) => Err(format!("{} exists and is not a named pipe", path.display())),
+        Err(_) => {
+            let c_path = CString::new(path.as_os_str().as_encoded_bytes())
+                .map_err(|_| format!("Invalid pipe path {}", path.display()))?;
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                return Err(format!(
+                    "Could not create pipe {}: {}",
+                    path.display(),
+                    std::io::Error::last_os_error()
+                ));
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn ensure_fifo(_path: &Path) -> Result<(), String> {
+    Err("Pipe output needs a Unix system".to_string())
+}
+
+/// Opens the pipe for writing if a reader has it open. Opening without
+/// O_NONBLOCK would block until one shows up, with no way to stop; the flag
+/// is cleared again afterwards so writes wait for the reader to keep up.
+#[cfg(unix)]
+fn open_writer(path: &Path) -> std::io::Result<File> {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt, os::unix::io::AsRawFd};
+
+    let file = OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+    let fd = file.as_raw_fd();
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK);
+    }
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn open_writer(_path: &Path) -> std::io::Result<File> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::{BufRead, BufReader, Read};
+
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    #[test]
+    fn reader_gets_a_header_then_scaled_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.pcm");
+        let source = SamplesBuffer::new(2, 48_000, vec![1000i16, -1000, i16::MAX, 0]);
+        let output = PipeOutput::start(&path, source, 0.5).unwrap();
+
+        let mut reader = BufReader::new(File::open(&path).unwrap());
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        assert_eq!(header, "NTSPCM s16le 48000 2\n");
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        let samples: Vec<i16> = bytes.chunks(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+        assert_eq!(samples, [500, -500, i16::MAX / 2, 0]);
+        drop(output);
+    }
+
+    #[test]
+    fn refuses_a_path_that_is_not_a_pipe() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let source = SamplesBuffer::new(1, 8_000, vec![0i16]);
+        assert!(PipeOutput::start(file.path(), source, 1.0).is_err());
+    }
+}
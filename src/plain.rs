@@ -0,0 +1,160 @@
+use std::{
+    error::Error,
+    io::{self, BufRead},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use crate::{
+    config::{Config, RecognitionConfig, TimestampStyle},
+    events::Reporter,
+    format, mp3_tap::{self, Mp3Tap}, player::Player, recognition, recognition_attempt::Attempt, Stream, StreamType, StreamsCollection,
+};
+
+const HELP: &str = "Commands: list, play <number|name>, stop, vol <0-100>, id, help, quit";
+
+/// Line-oriented interface for screen readers: no alternate screen or redraws,
+/// just one line per state change and simple commands read from stdin. With
+/// `initial_stream` set, that stream is played before the first command.
+pub fn run(config: Config, initial_stream: Option<&str>, reporter: Reporter) -> Result<(), Box<dyn Error>> {
+    let collection = StreamsCollection::populate_collection()?;
+    let streams = all_streams(&collection);
+    let mut player = Player::new(config.playback.volume);
+    player.set_stream_overrides(config.network.stream_overrides.clone());
+    player.set_output(config.output.clone());
+
+    reporter.say(HELP);
+    let initial = initial_stream.map(|name| Ok(format!("play {}", name)));
+    for line in initial.into_iter().chain(io::stdin().lock().lines()) {
+        let line = line?;
+        let (command, argument) = line
+            .trim()
+            .split_once(' ')
+            .map(|(command, argument)| (command, argument.trim()))
+            .unwrap_or((line.trim(), ""));
+
+        match command {
+            "list" => {
+                for (i, (_, stream)) in streams.iter().enumerate() {
+                    reporter.say(&format!("{}. {} — {}", i + 1, stream.title, stream.subtitle));
+                }
+            }
+            "play" => {
+                match find_stream(&streams, argument) {
+                    Some((section, stream)) => {
+                        play(&mut player, stream, *section, &config, reporter);
+                    }
+                    _ => reporter.say("No such stream, type list to see them"),
+                }
+            }
+            "stop" => {
+                player.stop();
+                reporter.event("stopped", "Stopped", json!({}));
+            }
+            "vol" => match argument.parse::<u8>() {
+                Ok(percent) if percent <= 100 => {
+                    player.set_volume(percent as f32 / 100.0);
+                    let line = format!("Volume: {}", format::percent(percent as f64 / 100.0));
+                    reporter.event("volume", &line, json!({ "percent": percent }));
+                }
+                _ => reporter.say("Volume must be a number from 0 to 100"),
+            },
+            "id" => match player.mp3_tap() {
+                Some(tap) => identify(tap, &config.recognition, config.history_timestamps, reporter, || {}),
+                None => reporter.say("Nothing is playing"),
+            },
+            "help" => reporter.say(HELP),
+            "quit" | "exit" => break,
+            "" => {}
+            _ => reporter.say(&format!("Unknown command. {}", HELP)),
+        }
+    }
+
+    player.stop();
+    Ok(())
+}
+
+/// Connects to `stream` and starts it, reporting each step. Shared with the
+/// headless mode. Returns whether it is playing.
+pub fn play(player: &mut Player, stream: &Stream, section: StreamType, config: &Config, reporter: Reporter) -> bool {
+    let payload = json!({ "stream": stream.title, "url": stream.audio_stream_endpoint });
+    reporter.event("connecting", &format!("Connecting to {}...", stream.title), payload);
+    match player.play(stream, config.playback.buffer_size, config.playback.max_silence_skip(section)) {
+        Ok(()) => {
+            let line = format!("Playing: {} — {}", stream.title, stream.subtitle);
+            reporter.event("playing", &line, json!({ "stream": stream.title, "subtitle": stream.subtitle }));
+            true
+        }
+        Err(err) => {
+            let line = format!("Playback failed: {}", err);
+            reporter.event("error", &line, json!({ "during": "playback", "stream": stream.title, "error": err.to_string() }));
+            false
+        }
+    }
+}
+
+/// Recognizes the last seconds of the stream `tap` copies, on a thread of its
+/// own, reports the outcome, like "Recognized: Artist - Title", then calls
+/// `done`. A match goes in the history, stamped in `timestamps`, unless
+/// `start_incognito` is set, as there is no toggling incognito here. Shared
+/// with the headless mode.
+pub fn identify(
+    tap: Mp3Tap,
+    settings: &RecognitionConfig,
+    timestamps: TimestampStyle,
+    reporter: Reporter,
+    done: impl FnOnce() + Send + 'static,
+) {
+    reporter.event("recognizing", "Recognizing...", json!({}));
+    let settings = settings.clone();
+    thread::spawn(move || {
+        let duration = settings.duration_secs.clamp(1, mp3_tap::KEEP.as_secs());
+        let attempt = Attempt::new(Duration::from_secs(duration), Instant::now());
+        let write_history = !settings.start_incognito;
+        match recognition::recognize(&tap, duration, &settings, write_history, timestamps, &attempt) {
+            Ok(outcome) => match outcome.saved_sample {
+                Some(sample) => reporter.event(
+                    "recognized",
+                    &format!("Recognized: nothing, sample saved at {}", sample.display()),
+                    json!({ "track": null, "sample": sample }),
+                ),
+                None => reporter.event(
+                    "recognized",
+                    &format!("Recognized: {}", outcome.text),
+                    json!({ "track": outcome.track, "text": outcome.text }),
+                ),
+            },
+            Err(err) => reporter.event("error", &format!("Recognition failed: {}", err), json!({ "during": "recognition", "error": err })),
+        }
+        done();
+    });
+}
+
+/// Picks a stream by its 1-based number in the list, or by a case-insensitive
+/// part of its title.
+pub fn find_stream<'a>(streams: &'a [(StreamType, Stream)], argument: &str) -> Option<&'a (StreamType, Stream)> {
+    if argument.is_empty() {
+        return None;
+    }
+    match argument.parse::<usize>() {
+        Ok(number) => number.checked_sub(1).and_then(|i| streams.get(i)),
+        Err(_) => {
+            let needle = argument.to_lowercase();
+            streams
+                .iter()
+                .find(|(_, stream)| stream.title.to_lowercase().contains(&needle))
+        }
+    }
+}
+
+/// Stations first, then mixtapes, in the order they are listed and numbered.
+pub fn all_streams(collection: &StreamsCollection) -> Vec<(StreamType, Stream)> {
+    collection
+        .stations
+        .iter()
+        .map(|stream| (StreamType::Station, stream.clone()))
+        .chain(collection.mixtapes.iter().map(|stream| (StreamType::Mixtape, stream.clone())))
+        .collect()
+}
@@ -0,0 +1,152 @@
+/// Serializes play requests. Each request gets a generation number and only
+/// the latest one can complete: a connection that finishes after the user
+/// already asked for something else is reported as stale, so its caller drops
+/// what it produced instead of letting it play over the newer stream.
+pub struct PlayIntents<T> {
+    latest: u64,
+    pending: Option<(u64, T)>,
+}
+
+impl<T> Default for PlayIntents<T> {
+    fn default() -> Self {
+        PlayIntents {
+            latest: 0,
+            pending: None,
+        }
+    }
+}
+
+impl<T> PlayIntents<T> {
+    /// Starts a new intent and returns its generation, along with the
+    /// pending intent it supersedes so that work can be cancelled.
+    pub fn begin(&mut self, intent: T) -> (u64, Option<T>) {
+        self.latest += 1;
+        let superseded = self.pending.replace((self.latest, intent)).map(|(_, intent)| intent);
+        (self.latest, superseded)
+    }
+
+    pub fn pending(&self) -> Option<&T> {
+        self.pending.as_ref().map(|(_, intent)| intent)
+    }
+
+    /// The pending intent, if `generation` is still the one in flight.
+    pub fn pending_mut(&mut self, generation: u64) -> Option<&mut T> {
+        self.pending
+            .as_mut()
+            .filter(|(pending, _)| *pending == generation)
+            .map(|(_, intent)| intent)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Ends intent `generation`. Returns it if it is still the one in flight;
+    /// None means it was superseded or cancelled and its result must be
+    /// dropped.
+    pub fn complete(&mut self, generation: u64) -> Option<T> {
+        self.pending
+            .take_if(|(pending, _)| *pending == generation)
+            .map(|(_, intent)| intent)
+    }
+
+    /// Abandons the pending intent, returning it so its work can be stopped.
+    pub fn cancel(&mut self) -> Option<T> {
+        self.pending.take().map(|(_, intent)| intent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The playback side as the TUI drives it: a completion that comes back
+    /// current replaces whatever was playing, a stale one is dropped.
+    #[derive(Default)]
+    struct Model {
+        intents: PlayIntents<&'static str>,
+        generations: Vec<u64>,
+        playing: Option<&'static str>,
+        started: Vec<&'static str>,
+    }
+
+    impl Model {
+        fn request(&mut self, stream: &'static str) {
+            let (generation, _) = self.intents.begin(stream);
+            self.generations.push(generation);
+        }
+
+        fn finish(&mut self, index: usize) {
+            if let Some(stream) = self.intents.complete(self.generations[index]) {
+                self.playing = Some(stream);
+                self.started.push(stream);
+            }
+        }
+    }
+
+    fn permutations(items: Vec<usize>) -> Vec<Vec<usize>> {
+        if items.len() <= 1 {
+            return vec![items];
+        }
+        let mut all = Vec::new();
+        for i in 0..items.len() {
+            let mut rest = items.clone();
+            let first = rest.remove(i);
+            for mut tail in permutations(rest) {
+                tail.insert(0, first);
+                all.push(tail);
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn only_the_latest_request_ever_plays() {
+        let streams = ["enter", "enter again", "station 1"];
+        for order in permutations((0..streams.len()).collect()) {
+            let mut model = Model::default();
+            for stream in streams {
+                model.request(stream);
+            }
+            for index in order.clone() {
+                model.finish(index);
+            }
+            assert_eq!(model.started, ["station 1"], "completion order {:?}", order);
+            assert!(!model.intents.is_pending());
+        }
+    }
+
+    #[test]
+    fn completion_between_requests_is_replaced_not_doubled() {
+        let mut model = Model::default();
+        model.request("enter");
+        model.finish(0);
+        model.request("station 1");
+        // The first worker reporting again, e.g. a duplicate failure, is stale
+        model.finish(0);
+        assert_eq!(model.playing, Some("enter"));
+        model.finish(1);
+        assert_eq!(model.playing, Some("station 1"));
+        assert_eq!(model.started, ["enter", "station 1"]);
+    }
+
+    #[test]
+    fn cancelled_intent_cannot_complete() {
+        let mut model = Model::default();
+        model.request("enter");
+        assert_eq!(model.intents.cancel(), Some("enter"));
+        model.finish(0);
+        assert_eq!(model.playing, None);
+    }
+
+    #[test]
+    fn superseded_intent_is_handed_back_for_cancelling() {
+        let mut intents = PlayIntents::default();
+        let (first, superseded) = intents.begin("enter");
+        assert_eq!(superseded, None);
+        let (second, superseded) = intents.begin("station 1");
+        assert_eq!(superseded, Some("enter"));
+        assert!(intents.pending_mut(first).is_none());
+        assert_eq!(intents.pending_mut(second), Some(&mut "station 1"));
+    }
+}
@@ -0,0 +1,661 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{BufReader, Chain, Cursor, Read},
+    net::SocketAddr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use reqwest::{blocking::Response, StatusCode};
+use rodio::{
+    cpal::{self, traits::HostTrait},
+    source::SineWave,
+    DeviceTrait, OutputStream, OutputStreamHandle, Sink, Source, SupportedStreamConfig,
+};
+
+use crate::{
+    config::{OutputConfig, OutputMode, ResampleQuality},
+    endpoint_health, http,
+    mp3_decoder::{DecodeStats, Mp3StreamDecoder},
+    network,
+    mp3_tap::{Mp3Tap, TeeReader},
+    pcm_tap::PcmTap,
+    pipe_output::PipeOutput,
+    resample::{Conversion, Resampled},
+    tls, Stream,
+};
+
+const DUCK_LEVEL: f32 = 0.3;
+const DUCK_DURATION: Duration = Duration::from_secs(2);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const FIRST_BYTE_TIMEOUT: Duration = Duration::from_secs(20);
+const PREBUFFER_BYTES: usize = 64 * 1024;
+/// Bitrate assumed for streams that don't announce one.
+const DEFAULT_BITRATE_KBPS: u32 = 128;
+
+type StreamSource = Mp3StreamDecoder<TeeReader<BufReader<Chain<Cursor<Vec<u8>>, Response>>>>;
+
+/// Where a connection attempt currently is, reported to the UI as it happens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectPhase {
+    Connecting,
+    WaitingForAudio,
+    Prebuffering(u8),
+}
+
+/// How long each connect phase took, for diagnosing slow connections.
+#[derive(Debug)]
+pub struct ConnectTimings {
+    pub connected: Duration,
+    pub first_byte: Duration,
+    pub prebuffered: Duration,
+    pub skipped_silence: Duration,
+}
+
+/// Why a connection attempt failed. A 404 is told apart from the rest
+/// because it usually means the endpoint moved, which a fresh lookup fixes.
+#[derive(Debug)]
+pub enum ConnectError {
+    NotFound,
+    Cancelled,
+    Failed(String),
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectError::NotFound => write!(f, "Stream returned HTTP {}", StatusCode::NOT_FOUND),
+            ConnectError::Cancelled => f.write_str("Cancelled"),
+            ConnectError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for ConnectError {
+    fn from(message: String) -> Self {
+        ConnectError::Failed(message)
+    }
+}
+
+/// What the server answered when the stream was connected, for the debug
+/// details.
+#[derive(Clone, Debug)]
+pub struct ConnectInfo {
+    pub status: StatusCode,
+    pub remote_addr: Option<SocketAddr>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl ConnectInfo {
+    /// "151.101.2.3 (cache-lhr7321-LHR)": the address that served the
+    /// stream and the CDN node, as far as either is known.
+    pub fn edge(&self) -> Option<String> {
+        let label = network::edge_label(&self.headers);
+        match (self.remote_addr, label) {
+            (Some(addr), Some(label)) => Some(format!("{} ({})", addr.ip(), label)),
+            (Some(addr), None) => Some(addr.ip().to_string()),
+            (None, label) => label,
+        }
+    }
+}
+
+/// A stream that is connected and prebuffered, ready to be handed to a sink.
+pub struct PreparedStream {
+    stream_url: String,
+    source: StreamSource,
+    mp3_tap: Mp3Tap,
+    pub timings: ConnectTimings,
+    pub info: ConnectInfo,
+}
+
+/// Connects to a stream and prebuffers it, going through the connect phases
+/// with a timeout each. Blocks, so the TUI runs it on a worker thread; setting
+/// `cancel` abandons the attempt at the next phase boundary or read. With
+/// `max_silence_skip` set, leading silence is dropped before playback starts.
+/// `buffer_size` is how many decoded samples are kept ahead of the output.
+pub fn connect(
+    stream_url: &str,
+    buffer_size: usize,
+    max_silence_skip: Option<Duration>,
+    cancel: &AtomicBool,
+    mut on_phase: impl FnMut(ConnectPhase),
+) -> Result<PreparedStream, ConnectError> {
+    let cancelled = || cancel.load(Ordering::Relaxed);
+
+    on_phase(ConnectPhase::Connecting);
+    let started = Instant::now();
+    let client = http::client(stream_url)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(FIRST_BYTE_TIMEOUT)
+        .build()
+        .map_err(|err| format!("Could not set up HTTP client: {}", err))?;
+    let mut response = client
+        .get(stream_url)
+        .send()
+        .and_then(http::error_for_status)
+        .map_err(|err| match err.status() {
+            Some(StatusCode::NOT_FOUND) => ConnectError::NotFound,
+            _ => ConnectError::Failed(request_error(err)),
+        })?;
+    let connected = started.elapsed();
+    let info = ConnectInfo {
+        status: response.status(),
+        remote_addr: response.remote_addr(),
+        headers: response
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect(),
+    };
+    if cancelled() {
+        return Err(ConnectError::Cancelled);
+    }
+
+    on_phase(ConnectPhase::WaitingForAudio);
+    let waiting_since = Instant::now();
+    let mut prebuffer = Vec::with_capacity(PREBUFFER_BYTES);
+    let mut chunk = [0u8; 8192];
+    let read_error = |err: std::io::Error, since: Instant| {
+        if since.elapsed() >= FIRST_BYTE_TIMEOUT {
+            format!("No audio received within {}s", FIRST_BYTE_TIMEOUT.as_secs())
+        } else {
+            format!("Stream read failed: {}", err)
+        }
+    };
+    let read = response
+        .read(&mut chunk)
+        .map_err(|err| read_error(err, waiting_since))?;
+    if read == 0 {
+        return Err(ConnectError::Failed("Stream ended before any audio arrived".to_string()));
+    }
+    prebuffer.extend_from_slice(&chunk[..read]);
+    let first_byte = waiting_since.elapsed();
+
+    let prebuffering_since = Instant::now();
+    let mut last_percent = None;
+    while prebuffer.len() < PREBUFFER_BYTES {
+        if cancelled() {
+            return Err(ConnectError::Cancelled);
+        }
+        let percent = (prebuffer.len() * 100 / PREBUFFER_BYTES) as u8;
+        if last_percent != Some(percent) {
+            on_phase(ConnectPhase::Prebuffering(percent));
+            last_percent = Some(percent);
+        }
+        let reading_since = Instant::now();
+        let read = response
+            .read(&mut chunk)
+            .map_err(|err| read_error(err, reading_since))?;
+        if read == 0 {
+            break;
+        }
+        prebuffer.extend_from_slice(&chunk[..read]);
+    }
+    let prebuffered = prebuffering_since.elapsed();
+
+    let mp3_tap = Mp3Tap::default();
+    let reader = TeeReader::new(BufReader::new(Cursor::new(prebuffer).chain(response)), mp3_tap.clone());
+    let source = Mp3StreamDecoder::new(reader, buffer_size.max(1), max_silence_skip)
+        .map_err(|_| "Stream is not MP3 audio".to_string())?;
+    let skipped_silence = source.skipped_silence();
+
+    Ok(PreparedStream {
+        stream_url: stream_url.to_string(),
+        source,
+        mp3_tap,
+        timings: ConnectTimings {
+            connected,
+            first_byte,
+            prebuffered,
+            skipped_silence,
+        },
+        info,
+    })
+}
+
+/// What a quick look at a stream endpoint found, without playing it.
+pub struct Probe {
+    pub bitrate_kbps: u32,
+    /// False when the bitrate is [`DEFAULT_BITRATE_KBPS`] because the stream
+    /// didn't say.
+    pub bitrate_announced: bool,
+    pub content_type: Option<String>,
+}
+
+/// Checks that a stream answers and starts sending data, reading only the
+/// first chunk.
+pub fn probe(stream_url: &str) -> Result<Probe, String> {
+    let client = http::client(stream_url)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(FIRST_BYTE_TIMEOUT)
+        .build()
+        .map_err(|err| format!("Could not set up HTTP client: {}", err))?;
+    let mut response = client
+        .get(stream_url)
+        .send()
+        .and_then(http::error_for_status)
+        .map_err(request_error)?;
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let announced = header("icy-br").and_then(|bitrate| bitrate.split(',').next()?.trim().parse().ok());
+    let content_type = header("content-type");
+    let mut chunk = [0u8; 8192];
+    match response.read(&mut chunk) {
+        Ok(0) => return Err("Stream ended before any audio arrived".to_string()),
+        Ok(_) => {}
+        Err(err) => return Err(format!("Stream read failed: {}", err)),
+    }
+    Ok(Probe {
+        bitrate_kbps: announced.unwrap_or(DEFAULT_BITRATE_KBPS),
+        bitrate_announced: announced.is_some(),
+        content_type,
+    })
+}
+
+/// Roughly how much data a stream at `bitrate_kbps` uses over `duration`.
+pub fn estimated_size(bitrate_kbps: u32, duration: Duration) -> u64 {
+    bitrate_kbps as u64 * 1000 / 8 * duration.as_secs()
+}
+
+fn request_error(err: reqwest::Error) -> String {
+    let nts = err.url().is_some_and(|url| tls::is_nts_url(url.as_str()));
+    if err.is_connect() && err.is_timeout() {
+        format!("Could not connect within {}s", CONNECT_TIMEOUT.as_secs())
+    } else if err.is_timeout() {
+        format!("No response within {}s", FIRST_BYTE_TIMEOUT.as_secs())
+    } else if let Some(status @ StatusCode::FORBIDDEN) = err.status().filter(|_| nts) {
+        format!("Stream returned HTTP {} ({})", status, http::FORBIDDEN_HINT)
+    } else if let Some(status) = err.status() {
+        format!("Stream returned HTTP {}", status)
+    } else {
+        format!("Could not connect: {}", http::describe_error(&err))
+    }
+}
+
+/// The audio side of the app: owns the output device and the sink playing the
+/// current stream, or the pipe it is written to instead. Shared by the TUI
+/// and the plain line-oriented mode.
+pub struct Player {
+    output: OutputConfig,
+    stream_overrides: BTreeMap<String, String>,
+    pipe: Option<PipeOutput>,
+    sink: Option<Sink>,
+    _stream: Option<OutputStream>,
+    stream_handle: Option<OutputStreamHandle>,
+    current_stream_url: Option<String>,
+    volume: f32,
+    /// Loudness correction for the playing stream, as a factor.
+    gain: f32,
+    decode_stats: Option<DecodeStats>,
+    connect_info: Option<ConnectInfo>,
+    ducked_until: Option<Instant>,
+    paused_at: Option<Instant>,
+    /// When output last resumed, since nothing is read while paused.
+    resumed_at: Option<Instant>,
+    conversion: Option<Conversion>,
+    tap: PcmTap,
+    /// The raw MP3 bytes of the playing stream, for recognition.
+    mp3_tap: Option<Mp3Tap>,
+}
+
+impl Player {
+    pub fn new(volume: f32) -> Self {
+        Player {
+            output: OutputConfig::default(),
+            stream_overrides: BTreeMap::new(),
+            pipe: None,
+            sink: None,
+            _stream: None,
+            stream_handle: None,
+            current_stream_url: None,
+            volume: volume.clamp(0.0, 1.0),
+            gain: 1.0,
+            decode_stats: None,
+            connect_info: None,
+            ducked_until: None,
+            paused_at: None,
+            resumed_at: None,
+            conversion: None,
+            tap: PcmTap::default(),
+            mp3_tap: None,
+        }
+    }
+
+    /// Connects and starts playing in one blocking call, trying equivalent
+    /// endpoints healthiest first.
+    pub fn play(
+        &mut self,
+        stream: &Stream,
+        buffer_size: usize,
+        max_silence_skip: Option<Duration>,
+    ) -> Result<(), String> {
+        let cancel = AtomicBool::new(false);
+        let prepared = endpoint_health::connect_healthiest(&stream.audio_stream_endpoint, &self.stream_overrides, |url| {
+            connect(url, buffer_size, max_silence_skip, &cancel, |_| {})
+        })
+        .map_err(|err| err.to_string())?;
+        self.start(prepared)
+    }
+
+    /// Where the next stream started goes. The playing one keeps its output
+    /// until it is restarted.
+    pub fn set_output(&mut self, output: OutputConfig) {
+        self.output = output;
+    }
+
+    /// Endpoints to use in place of the ones the API lists, as configured.
+    pub fn set_stream_overrides(&mut self, overrides: BTreeMap<String, String>) {
+        self.stream_overrides = overrides;
+    }
+
+    pub fn output(&self) -> &OutputConfig {
+        &self.output
+    }
+
+    /// For pipe output, whether a reader is taking the audio.
+    pub fn pipe_reader_connected(&self) -> Option<bool> {
+        self.pipe.as_ref().map(PipeOutput::reader_connected)
+    }
+
+    /// Starts playing a stream prepared by `connect`. The output device is
+    /// opened here because it has to live on the thread that owns the player.
+    pub fn start(&mut self, prepared: PreparedStream) -> Result<(), String> {
+        self.stop();
+
+        if self.output.mode == OutputMode::Pipe {
+            let mut source = prepared.source;
+            let decode_stats = source.stats();
+            source.set_tap(&self.tap);
+            self.pipe = Some(PipeOutput::start(&self.output.pipe_path, source, self.output_volume())?);
+            self.current_stream_url = Some(prepared.stream_url);
+            self.mp3_tap = Some(prepared.mp3_tap);
+            self.decode_stats = Some(decode_stats);
+            self.connect_info = Some(prepared.info);
+            return Ok(());
+        }
+
+        let mut source = prepared.source;
+        let stream_rate = source.sample_rate();
+        let (_stream, stream_handle, device_rate) = open_device(self.output.resampler, stream_rate)?;
+        let sink = Sink::try_new(&stream_handle)
+            .map_err(|err| format!("Could not open audio output: {}", err))?;
+
+        let decode_stats = source.stats();
+        source.set_tap(&self.tap);
+        // Without a known device rate rodio converts on its own, as before
+        let quality = match self.output.resampler {
+            ResampleQuality::Native => ResampleQuality::Linear,
+            quality => quality,
+        };
+        match device_rate {
+            Some(rate) if rate != stream_rate => match Resampled::new(source, rate, quality) {
+                Ok(resampled) => {
+                    sink.append(resampled);
+                    self.conversion = Some(Conversion {
+                        from: stream_rate,
+                        to: rate,
+                        quality,
+                    });
+                }
+                Err(err) => return Err(format!("Could not set up resampling: {}", err)),
+            },
+            Some(rate) => {
+                sink.append(source);
+                self.conversion = Some(Conversion {
+                    from: stream_rate,
+                    to: rate,
+                    quality: self.output.resampler,
+                });
+            }
+            None => sink.append(source),
+        }
+        sink.set_volume(self.output_volume());
+
+        self.sink = Some(sink);
+        self.current_stream_url = Some(prepared.stream_url);
+        self.mp3_tap = Some(prepared.mp3_tap);
+        self._stream = Some(_stream);
+        self.stream_handle = Some(stream_handle);
+        self.decode_stats = Some(decode_stats);
+        self.connect_info = Some(prepared.info);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+        self.pipe = None;
+        self.current_stream_url = None;
+        self.mp3_tap = None;
+        self._stream = None;
+        self.stream_handle = None;
+        self.decode_stats = None;
+        self.connect_info = None;
+        self.ducked_until = None;
+        self.paused_at = None;
+        self.resumed_at = None;
+        self.conversion = None;
+        self.tap.clear();
+    }
+
+    /// Holds output where it is, keeping the connection open so resuming is
+    /// instant. Returns false when there is nothing to pause.
+    pub fn pause(&mut self) -> bool {
+        if self.paused_at.is_some() {
+            return true;
+        }
+        match (&self.sink, &self.pipe) {
+            (Some(sink), _) => sink.pause(),
+            (None, Some(pipe)) => pipe.set_paused(true),
+            (None, None) => return false,
+        }
+        self.paused_at = Some(Instant::now());
+        true
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused_at.take().is_none() {
+            return;
+        }
+        self.resumed_at = Some(Instant::now());
+        if let Some(sink) = &self.sink {
+            sink.play();
+        }
+        if let Some(pipe) = &self.pipe {
+            pipe.set_paused(false);
+        }
+    }
+
+    /// Marks output paused since `at`, for tests that have no device to pause.
+    #[cfg(test)]
+    pub fn pause_at(&mut self, at: Instant) {
+        self.paused_at = Some(at);
+    }
+
+    /// Sets the conversion ahead of the device, for tests that have none.
+    #[cfg(test)]
+    pub fn set_conversion(&mut self, conversion: Conversion) {
+        self.conversion = Some(conversion);
+    }
+
+    /// How long output has been paused, or None if it isn't.
+    pub fn paused_for(&self) -> Option<Duration> {
+        self.paused_at.map(|paused_at| paused_at.elapsed())
+    }
+
+    /// Briefly lowers the stream and plays a short two-tone chime over it.
+    pub fn chime(&mut self) {
+        let (Some(sink), Some(stream_handle)) = (&self.sink, &self.stream_handle) else {
+            return;
+        };
+        sink.set_volume(self.output_volume() * DUCK_LEVEL);
+        self.ducked_until = Some(Instant::now() + DUCK_DURATION);
+
+        let tone = |frequency: f32| {
+            SineWave::new(frequency)
+                .take_duration(Duration::from_millis(200))
+                .amplify(0.2)
+        };
+        let chime = tone(660.0).mix(tone(880.0).delay(Duration::from_millis(250)));
+        let _ = stream_handle.play_raw(chime.convert_samples());
+    }
+
+    /// Restores the volume once a chime's ducking period is over.
+    pub fn restore_after_duck(&mut self) {
+        if self.ducked_until.is_some_and(|until| Instant::now() >= until) {
+            self.ducked_until = None;
+            if let Some(sink) = &self.sink {
+                sink.set_volume(self.output_volume());
+            }
+        }
+    }
+
+    /// Recent decoded audio of the playing stream.
+    pub fn tap(&self) -> &PcmTap {
+        &self.tap
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.sink.is_some() || self.pipe.is_some()
+    }
+
+    /// The sample rate conversion ahead of the sound device, once known.
+    pub fn conversion(&self) -> Option<&Conversion> {
+        self.conversion.as_ref()
+    }
+
+    pub fn current_stream_url(&self) -> Option<&str> {
+        self.current_stream_url.as_deref()
+    }
+
+    /// How long the playing stream has gone without data, for the watchdog,
+    /// counted from the last resume at the earliest. None while paused or
+    /// while nobody reads the pipe, when nothing is read on purpose.
+    pub fn stalled_for(&self, now: Instant) -> Option<Duration> {
+        if self.paused_at.is_some() || self.pipe_reader_connected() == Some(false) {
+            return None;
+        }
+        let idle = self.mp3_tap.as_ref()?.idle(now)?;
+        let since_resume = self.resumed_at.map(|resumed_at| now.saturating_duration_since(resumed_at));
+        Some(since_resume.map_or(idle, |since_resume| idle.min(since_resume)))
+    }
+
+    /// Whether the playing stream ran out, as it can when a station hands
+    /// over between shows.
+    pub fn stream_ended(&self) -> bool {
+        self.decode_stats.as_ref().is_some_and(DecodeStats::ended)
+    }
+
+    /// The raw bytes of the playing stream, which recognition takes its
+    /// sample from.
+    pub fn mp3_tap(&self) -> Option<Mp3Tap> {
+        self.mp3_tap.clone()
+    }
+
+    pub fn decode_stats(&self) -> Option<&DecodeStats> {
+        self.decode_stats.as_ref()
+    }
+
+    /// The server's answer to the connect behind the playing stream.
+    pub fn connect_info(&self) -> Option<&ConnectInfo> {
+        self.connect_info.as_ref()
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Sets the volume, clamped to 0.0–1.0. Returns whether a sink picked it up.
+    pub fn set_volume(&mut self, volume: f32) -> bool {
+        self.volume = volume.clamp(0.0, 1.0);
+        self.apply_volume()
+    }
+
+    /// Sets the loudness correction applied on top of the volume.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+        self.apply_volume();
+    }
+
+    fn output_volume(&self) -> f32 {
+        self.volume * self.gain
+    }
+
+    fn apply_volume(&mut self) -> bool {
+        self.ducked_until = None;
+        if let Some(pipe) = &self.pipe {
+            pipe.set_volume(self.output_volume());
+            return true;
+        }
+        match &self.sink {
+            Some(sink) => {
+                sink.set_volume(self.output_volume());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Opens the default sound device, at the stream's own rate when `quality`
+/// is Native and the device supports it. Returns the rate the device runs
+/// at, or None when only rodio's own fallback across devices worked.
+fn open_device(
+    quality: ResampleQuality,
+    stream_rate: u32,
+) -> Result<(OutputStream, OutputStreamHandle, Option<u32>), String> {
+    if let Some(device) = cpal::default_host().default_output_device() {
+        let config = match quality {
+            ResampleQuality::Native => native_config(&device, stream_rate),
+            _ => None,
+        }
+        .or_else(|| device.default_output_config().ok());
+        if let Some(config) = config {
+            let rate = config.sample_rate().0;
+            if let Ok((stream, handle)) = OutputStream::try_from_device_config(&device, config) {
+                return Ok((stream, handle, Some(rate)));
+            }
+        }
+    }
+    let (stream, handle) = OutputStream::try_default().map_err(|err| format!("No audio output device: {}", err))?;
+    Ok((stream, handle, None))
+}
+
+fn native_config(device: &cpal::Device, rate: u32) -> Option<SupportedStreamConfig> {
+    device
+        .supported_output_configs()
+        .ok()?
+        .find(|range| (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&rate))
+        .map(|range| range.with_sample_rate(cpal::SampleRate(rate)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_long_pause_is_not_a_stall() {
+        let tap = Mp3Tap::default();
+        let mut reader = TeeReader::new(Cursor::new(vec![0u8; 16]), tap.clone());
+        reader.read_exact(&mut [0u8; 16]).unwrap();
+        let mut player = Player::new(1.0);
+        player.mp3_tap = Some(tap);
+        let read_at = Instant::now();
+        assert!(player.stalled_for(read_at + Duration::from_secs(12)).unwrap() >= Duration::from_secs(12));
+
+        // Paused for a minute, nothing was read, and that isn't a stall
+        player.pause_at(read_at);
+        assert_eq!(player.stalled_for(read_at + Duration::from_secs(60)), None);
+        player.resume();
+        let resumed_at = player.resumed_at.unwrap();
+        assert_eq!(player.stalled_for(resumed_at + Duration::from_secs(2)), Some(Duration::from_secs(2)));
+        assert_eq!(player.stalled_for(resumed_at + Duration::from_secs(11)), Some(Duration::from_secs(11)));
+    }
+}
@@ -0,0 +1,144 @@
+//! Discord Rich Presence: "Listening to NTS" with the show as details and the
+//! last recognized track as state. Built only with the `discord` feature;
+//! without it `Presence` does nothing and costs nothing.
+
+use crate::config::DiscordConfig;
+
+#[cfg(feature = "discord")]
+pub use enabled::Presence;
+
+#[cfg(not(feature = "discord"))]
+pub struct Presence;
+
+#[cfg(not(feature = "discord"))]
+impl Presence {
+    pub fn start(_config: &DiscordConfig) -> Presence {
+        Presence
+    }
+
+    pub fn off() -> Presence {
+        Presence
+    }
+
+    pub fn playing(&self, _details: &str) {}
+
+    pub fn track(&self, _track: &str) {}
+
+    pub fn clear(&self) {}
+}
+
+#[cfg(feature = "discord")]
+mod enabled {
+    use std::{
+        sync::mpsc::{self, RecvTimeoutError, Sender},
+        thread,
+        time::Duration,
+    };
+
+    use discord_rich_presence::{
+        activity::{Activity, ActivityType},
+        DiscordIpc, DiscordIpcClient,
+    };
+
+    use super::DiscordConfig;
+
+    /// How often to try reaching a Discord client that isn't running yet.
+    const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+    enum Update {
+        Playing(String),
+        Track(String),
+        Clear,
+    }
+
+    /// Talks to the local Discord client on a thread of its own, so a slow
+    /// or missing client never holds up the UI. Failures are silent; the
+    /// activity is sent again once the client can be reached.
+    pub struct Presence {
+        updates: Option<Sender<Update>>,
+    }
+
+    impl Presence {
+        pub fn start(config: &DiscordConfig) -> Presence {
+            if !config.enabled || config.client_id.is_empty() {
+                return Presence::off();
+            }
+            let (updates, rx) = mpsc::channel();
+            let client = DiscordIpcClient::new(&config.client_id);
+            thread::spawn(move || run(client, rx));
+            Presence { updates: Some(updates) }
+        }
+
+        /// One that never reaches Discord.
+        pub fn off() -> Presence {
+            Presence { updates: None }
+        }
+
+        /// A stream started: shows `details` and forgets the last track.
+        pub fn playing(&self, details: &str) {
+            self.send(Update::Playing(details.to_string()));
+        }
+
+        pub fn track(&self, track: &str) {
+            self.send(Update::Track(track.to_string()));
+        }
+
+        pub fn clear(&self) {
+            self.send(Update::Clear);
+        }
+
+        fn send(&self, update: Update) {
+            if let Some(updates) = &self.updates {
+                let _ = updates.send(update);
+            }
+        }
+    }
+
+    /// Applies updates until the sender is dropped. Connecting is retried on
+    /// every update and every `RETRY_INTERVAL` while something is playing.
+    fn run(mut client: DiscordIpcClient, updates: mpsc::Receiver<Update>) {
+        let mut connected = false;
+        let mut current: Option<(String, Option<String>)> = None;
+        loop {
+            match updates.recv_timeout(RETRY_INTERVAL) {
+                Ok(Update::Playing(details)) => current = Some((details, None)),
+                Ok(Update::Track(track)) => {
+                    if let Some((_, state)) = &mut current {
+                        *state = Some(track);
+                    }
+                }
+                Ok(Update::Clear) => current = None,
+                Err(RecvTimeoutError::Timeout) if connected || current.is_none() => continue,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if !connected {
+                connected = client.connect().is_ok();
+                if !connected {
+                    continue;
+                }
+            }
+            let sent = match &current {
+                Some((details, state)) => {
+                    let mut activity = Activity::new()
+                        .name("NTS")
+                        .activity_type(ActivityType::Listening)
+                        .details(details.as_str());
+                    if let Some(state) = state {
+                        activity = activity.state(state.as_str());
+                    }
+                    client.set_activity(activity)
+                }
+                None => client.clear_activity(),
+            };
+            if sent.is_err() {
+                let _ = client.close();
+                connected = false;
+            }
+        }
+        if connected {
+            let _ = client.clear_activity();
+            let _ = client.close();
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::StreamRef;
+
+pub const DEFAULT_SLOT_MINUTES: u64 = 45;
+pub const SLOT_STEP_MINUTES: u64 = 5;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QueueEntry {
+    pub stream: StreamRef,
+    pub title: String,
+    pub minutes: u64,
+}
+
+/// Where a rotation was when it was saved, so it can pick up from there
+/// after a restart.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RotationProgress {
+    pub position: usize,
+    pub slot_elapsed_secs: u64,
+    pub running: bool,
+    /// Unix seconds when the progress was saved.
+    pub saved_at: i64,
+}
+
+/// A rotation of streams that are played one after another, each for its own
+/// slot length, wrapping around at the end.
+#[derive(Default, Debug)]
+pub struct Rotation {
+    pub entries: Vec<QueueEntry>,
+    position: usize,
+    slot_elapsed: Duration,
+    resumed_at: Option<Instant>,
+}
+
+impl Rotation {
+    pub fn new(entries: Vec<QueueEntry>) -> Self {
+        Rotation {
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// Rebuilds a saved rotation, paused. If it was running when saved, the
+    /// time since then counts as played, so slots that would have run out
+    /// while the app was closed are skipped.
+    pub fn restore(entries: Vec<QueueEntry>, progress: RotationProgress, now: i64) -> Self {
+        if entries.is_empty() {
+            return Rotation::default();
+        }
+        let mut position = progress.position % entries.len();
+        let mut elapsed = progress.slot_elapsed_secs;
+        if progress.running {
+            elapsed += now.saturating_sub(progress.saved_at).max(0) as u64;
+        }
+        let slot_secs = |entry: &QueueEntry| entry.minutes.max(1) * 60;
+        let cycle_secs: u64 = entries.iter().map(slot_secs).sum();
+        if elapsed >= slot_secs(&entries[position]) {
+            // Whole rounds of the rotation change nothing
+            elapsed -= slot_secs(&entries[position]);
+            position = (position + 1) % entries.len();
+            elapsed %= cycle_secs;
+            while elapsed >= slot_secs(&entries[position]) {
+                elapsed -= slot_secs(&entries[position]);
+                position = (position + 1) % entries.len();
+            }
+        }
+        Rotation {
+            entries,
+            position,
+            slot_elapsed: Duration::from_secs(elapsed),
+            resumed_at: None,
+        }
+    }
+
+    pub fn progress(&self, now: i64) -> RotationProgress {
+        let elapsed = self.slot_elapsed
+            + self
+                .resumed_at
+                .map(|resumed_at| resumed_at.elapsed())
+                .unwrap_or_default();
+        RotationProgress {
+            position: self.position,
+            slot_elapsed_secs: elapsed.as_secs(),
+            running: self.is_running(),
+            saved_at: now,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.resumed_at.is_some()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn current(&self) -> Option<&QueueEntry> {
+        self.entries.get(self.position)
+    }
+
+    pub fn push(&mut self, entry: QueueEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Rotation::default();
+    }
+
+    /// Lengthens or shortens the most recently queued slot.
+    pub fn adjust_last(&mut self, longer: bool) {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.minutes = if longer {
+                entry.minutes + SLOT_STEP_MINUTES
+            } else {
+                entry.minutes.saturating_sub(SLOT_STEP_MINUTES).max(SLOT_STEP_MINUTES)
+            };
+        }
+    }
+
+    /// Starts or resumes the current slot. Returns the entry to play.
+    pub fn resume(&mut self) -> Option<&QueueEntry> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.resumed_at = Some(Instant::now());
+        self.current()
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.slot_elapsed += resumed_at.elapsed();
+        }
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        let entry = self.current()?;
+        let elapsed = self.slot_elapsed
+            + self
+                .resumed_at
+                .map(|resumed_at| resumed_at.elapsed())
+                .unwrap_or_default();
+        Some(Duration::from_secs(entry.minutes * 60).saturating_sub(elapsed))
+    }
+
+    /// Moves on to the next slot once the current one ran out. Returns the
+    /// entry that should start playing now, if any.
+    pub fn advance_if_due(&mut self) -> Option<&QueueEntry> {
+        if !self.is_running() || self.remaining()? > Duration::ZERO {
+            return None;
+        }
+        self.position = (self.position + 1) % self.entries.len();
+        self.slot_elapsed = Duration::ZERO;
+        self.resumed_at = Some(Instant::now());
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StreamType;
+
+    fn entries(minutes: &[u64]) -> Vec<QueueEntry> {
+        minutes
+            .iter()
+            .enumerate()
+            .map(|(i, &minutes)| QueueEntry {
+                stream: StreamRef {
+                    section: StreamType::Mixtape,
+                    id: i.to_string(),
+                },
+                title: i.to_string(),
+                minutes,
+            })
+            .collect()
+    }
+
+    fn progress(position: usize, slot_elapsed_secs: u64, running: bool) -> RotationProgress {
+        RotationProgress {
+            position,
+            slot_elapsed_secs,
+            running,
+            saved_at: 1_000_000,
+        }
+    }
+
+    #[test]
+    fn paused_rotation_ignores_time_closed() {
+        let rotation = Rotation::restore(entries(&[10, 20]), progress(1, 300, false), 1_000_000 + 86_400);
+        assert_eq!(rotation.position(), 1);
+        assert_eq!(rotation.progress(0).slot_elapsed_secs, 300);
+        assert!(!rotation.is_running());
+    }
+
+    #[test]
+    fn running_rotation_skips_slots_that_ran_out_while_closed() {
+        // 5 of 10 minutes in, closed for 20 minutes: slot 0 ends after 5,
+        // slot 1 (20 min) has 15 minutes played
+        let rotation = Rotation::restore(entries(&[10, 20, 30]), progress(0, 300, true), 1_000_000 + 1200);
+        assert_eq!(rotation.position(), 1);
+        assert_eq!(rotation.progress(0).slot_elapsed_secs, 900);
+    }
+
+    #[test]
+    fn running_rotation_wraps_around_whole_rounds() {
+        // Closed for 3 h 15 min from the start of the last slot: it ends after
+        // 30 min, two whole 60 min rounds pass, slots 0 and 1 take 30 more
+        let closed = 3 * 3600 + 15 * 60;
+        let rotation = Rotation::restore(entries(&[10, 20, 30]), progress(2, 0, true), 1_000_000 + closed);
+        assert_eq!(rotation.position(), 2);
+        assert_eq!(rotation.progress(0).slot_elapsed_secs, 15 * 60);
+    }
+}
@@ -0,0 +1,605 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "shazam")]
+use std::sync::mpsc::{self, RecvTimeoutError};
+
+use chrono::{DateTime, Local};
+use serde_json::Value;
+use tempfile::tempdir;
+
+use crate::{
+    append_to_recognition_history,
+    config::{RecognitionConfig, TimestampStyle},
+    history, pending_ids,
+    mp3_tap::Mp3Tap,
+    pcm_tap::Clip,
+    recognition_attempt::{Attempt, TIMED_OUT},
+    recognition_metrics::{self, RunResult},
+    samples, shutdown,
+};
+#[cfg(feature = "shazam")]
+use crate::shazam::{self, Signature};
+
+/// Whether this build can identify tracks.
+pub const AVAILABLE: bool = true;
+/// What the keys say in builds without the `recognition` feature.
+pub const UNAVAILABLE: &str = "Recognition not available in this build";
+
+pub struct RecognitionOutcome {
+    pub text: String,
+    /// The recognized track, as written to the history.
+    pub track: Option<String>,
+    pub saved_sample: Option<PathBuf>,
+    /// Whether the run was to write a match to the history, as decided when
+    /// it started. Incognito toggled since doesn't change it.
+    pub write_history: bool,
+    /// The line written to the history file, if one was.
+    pub history_line: Option<String>,
+}
+
+const RECOGNIZER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the playing stream may go without data before a capture waiting
+/// on it gives up.
+const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+/// What runs when `recognizer_command` is left empty, in builds without
+/// the built-in recognizer.
+#[cfg(not(feature = "shazam"))]
+const DEFAULT_COMMAND: [&str; 4] = ["vibra", "-R", "--file", "{file}"];
+/// Stands for the sample's path in the recognizer command.
+const FILE_PLACEHOLDER: &str = "{file}";
+
+/// What identifies the samples.
+enum Recognizer {
+    /// A command run on the sample that prints JSON, like vibra.
+    Command(Vec<String>),
+    /// The built-in Shazam client.
+    #[cfg(feature = "shazam")]
+    Shazam,
+}
+
+impl Recognizer {
+    fn from_settings(settings: &RecognitionConfig) -> Recognizer {
+        match settings.recognizer_command.is_empty() {
+            false => Recognizer::Command(settings.recognizer_command.clone()),
+            #[cfg(feature = "shazam")]
+            true => Recognizer::Shazam,
+            #[cfg(not(feature = "shazam"))]
+            true => Recognizer::Command(DEFAULT_COMMAND.map(String::from).to_vec()),
+        }
+    }
+
+    /// The backend name in the metrics log: the program run, or "shazam".
+    fn name(&self) -> String {
+        match self {
+            Recognizer::Command(command) => command
+                .first()
+                .map(|program| Path::new(program).file_name().map_or(program.clone(), |name| name.to_string_lossy().into_owned()))
+                .unwrap_or_default(),
+            #[cfg(feature = "shazam")]
+            Recognizer::Shazam => "shazam".to_string(),
+        }
+    }
+}
+
+/// How the sample was captured, for the metrics log.
+struct Capture {
+    duration: Duration,
+    bytes: u64,
+}
+
+/// Why the recognizer gave no answer. Only an offline run is worth repeating later: a
+/// stop was asked for, a recognizer that won't start won't by then either, and
+/// one that failed on the sample will fail on it again.
+enum RecognizerError {
+    Stopped(String),
+    Unavailable(String),
+    /// Exited with an error, or the service answered with one.
+    Failed(String),
+    /// Couldn't reach the service or timed out, as it does without a connection.
+    Offline(String),
+}
+
+impl RecognizerError {
+    /// Why `attempt` should stop now, if it should. Running out of time is
+    /// likely the network, so it counts as offline.
+    fn stopping(attempt: &Attempt) -> Option<RecognizerError> {
+        match attempt.stop_reason(Instant::now())? {
+            TIMED_OUT => Some(RecognizerError::Offline(TIMED_OUT.to_string())),
+            reason => Some(RecognizerError::Stopped(reason.to_string())),
+        }
+    }
+
+    fn message(self) -> String {
+        match self {
+            RecognizerError::Stopped(message)
+            | RecognizerError::Unavailable(message)
+            | RecognizerError::Failed(message)
+            | RecognizerError::Offline(message) => message,
+        }
+    }
+}
+
+/// A queued recognition that matched on retry.
+pub struct PendingMatch {
+    pub track: String,
+    pub heard_at: DateTime<Local>,
+    /// False when it was made in incognito, so it is only for the session.
+    pub write_history: bool,
+    /// The line written to the history file, if one was.
+    pub history_line: Option<String>,
+}
+
+/// Takes the last `duration` seconds of the playing stream from `tap`, runs it through the
+/// recognizer and records a match in the history unless `write_history` is off. Blocks, so
+/// callers run it on a worker thread; right after the stream starts it waits until the tap
+/// holds a whole sample. Returns an error message if the sample could not be captured or the
+/// recognizer could not run to completion; the temporary sample is removed on every path
+/// unless it is kept for a failed match. Cancelling `attempt` abandons the run at the next
+/// chance, recording nothing, and so does running past its deadline.
+pub fn recognize(
+    tap: &Mp3Tap,
+    duration: u64,
+    settings: &RecognitionConfig,
+    write_history: bool,
+    timestamps: TimestampStyle,
+    attempt: &Attempt,
+) -> Result<RecognitionOutcome, String> {
+    let length = Duration::from_secs(duration);
+    let started = Instant::now();
+    loop {
+        let now = Instant::now();
+        if let Some(reason) = attempt.stop_reason(now) {
+            return Err(reason.to_string());
+        }
+        let reach = tap.reach(now);
+        attempt.captured(reach.min(length));
+        if reach >= length {
+            break;
+        }
+        let idle = tap.idle(now).unwrap_or(now.saturating_duration_since(started));
+        if idle >= STALL_TIMEOUT {
+            return Err("The stream stopped before the sample was captured".to_string());
+        }
+        thread::sleep(RECOGNIZER_POLL_INTERVAL);
+    }
+    let bytes = tap.last(length, Instant::now());
+
+    let dir = tempdir().map_err(|err| format!("Could not create temp dir: {}", err))?;
+    let temp_file_path = dir.path().join("sample.mp3");
+    fs::write(&temp_file_path, &bytes).map_err(|err| format!("Could not write sample: {}", err))?;
+    let capture = Capture {
+        duration: started.elapsed(),
+        bytes: bytes.len() as u64,
+    };
+
+    identify_or_queue(&temp_file_path, settings, Local::now(), capture, write_history, timestamps, attempt)
+}
+
+/// Identifies audio that already played, copied out of the decoded-audio tap.
+/// The history entry is stamped with when the clip was heard, and the result
+/// says how far back it came from.
+pub fn recognize_clip(
+    clip: Clip,
+    settings: &RecognitionConfig,
+    write_history: bool,
+    timestamps: TimestampStyle,
+    attempt: &Attempt,
+) -> Result<RecognitionOutcome, String> {
+    if clip.samples.is_empty() {
+        return Err("No audio buffered yet".to_string());
+    }
+    let dir = tempdir().map_err(|err| format!("Could not create temp dir: {}", err))?;
+    let temp_file_path = dir.path().join("sample.wav");
+    let started = Instant::now();
+    write_wav(&temp_file_path, &clip).map_err(|err| format!("Could not write sample: {}", err))?;
+    let capture = Capture {
+        duration: started.elapsed(),
+        bytes: fs::metadata(&temp_file_path).map_or(0, |metadata| metadata.len()),
+    };
+
+    let length = Duration::from_secs_f64(
+        clip.samples.len() as f64 / (clip.channels.max(1) as f64 * clip.sample_rate.max(1) as f64),
+    );
+    attempt.captured(length);
+    let heard_at = DateTime::from(clip.heard_at);
+    let mut outcome = identify_or_queue(&temp_file_path, settings, heard_at, capture, write_history, timestamps, attempt)?;
+    outcome.text = format!(
+        "{} (identified from ~{}s ago{})",
+        outcome.text,
+        clip.starts_ago.saturating_sub(length).as_secs(),
+        if clip.truncated { ", the oldest audio buffered" } else { "" }
+    );
+    Ok(outcome)
+}
+
+/// Like `identify`, but a run that couldn't reach the recognizer or timed
+/// out queues the sample to be identified once the network is back.
+fn identify_or_queue(
+    sample: &Path,
+    settings: &RecognitionConfig,
+    heard_at: DateTime<Local>,
+    capture: Capture,
+    write_history: bool,
+    timestamps: TimestampStyle,
+    attempt: &Attempt,
+) -> Result<RecognitionOutcome, String> {
+    match identify(sample, settings, heard_at, capture, write_history, timestamps, attempt) {
+        Ok(outcome) => Ok(outcome),
+        Err(RecognizerError::Offline(err)) if !shutdown::requested() => {
+            match pending_ids::enqueue(sample, heard_at, write_history, settings.sample_retention_cap) {
+                Ok(pending) => Err(format!("{}, queued to retry when back online ({} pending)", err, pending)),
+                Err(_) => Err(err),
+            }
+        }
+        Err(err) => Err(err.message()),
+    }
+}
+
+/// How many recognitions are queued for when the network is back.
+pub fn pending_count(max_age: Duration) -> usize {
+    pending_ids::pending(max_age).len()
+}
+
+/// Runs the queued samples through the recognizer again, oldest first.
+/// Matches go into the history as heard when their sample was captured;
+/// samples that didn't match are kept or deleted like any other. One that
+/// is still offline stays queued and the next is tried anyway, while one the
+/// recognizer fails on leaves the queue with its sample kept. Returns the
+/// matches and how many are still pending.
+pub fn retry_pending(
+    settings: &RecognitionConfig,
+    timestamps: TimestampStyle,
+    max_age: Duration,
+) -> (Vec<PendingMatch>, usize) {
+    let attempt = Attempt::unbounded();
+    let mut matches = Vec::new();
+    for id in pending_ids::pending(max_age) {
+        let capture = Capture {
+            duration: Duration::ZERO,
+            bytes: fs::metadata(&id.sample).map_or(0, |metadata| metadata.len()),
+        };
+        let outcome = match identify(&id.sample, settings, id.heard_at(), capture, id.write_history, timestamps, &attempt) {
+            Ok(outcome) => outcome,
+            Err(RecognizerError::Offline(_)) => continue,
+            Err(RecognizerError::Stopped(_)) => break,
+            Err(RecognizerError::Failed(_) | RecognizerError::Unavailable(_)) => {
+                pending_ids::remove(&id.sample);
+                continue;
+            }
+        };
+        if outcome.saved_sample.is_none() {
+            let _ = fs::remove_file(&id.sample);
+        }
+        pending_ids::remove(&id.sample);
+        if let Some(track) = outcome.track {
+            matches.push(PendingMatch {
+                track,
+                heard_at: id.heard_at(),
+                write_history: id.write_history,
+                history_line: outcome.history_line,
+            });
+        }
+    }
+    (matches, pending_ids::pending(max_age).len())
+}
+
+/// Runs the sample through the recognizer and, with `write_history`, records a match
+/// in the history as heard at `heard_at`. A failed match keeps the sample if configured to. Every
+/// run that isn't cancelled goes into the metrics log.
+fn identify(
+    sample: &Path,
+    settings: &RecognitionConfig,
+    heard_at: DateTime<Local>,
+    capture: Capture,
+    write_history: bool,
+    timestamps: TimestampStyle,
+    attempt: &Attempt,
+) -> Result<RecognitionOutcome, RecognizerError> {
+    let timeout = Duration::from_secs(settings.timeout_secs);
+    let recognizer = Recognizer::from_settings(settings);
+    let started = Instant::now();
+    attempt.identifying();
+    let output = run_recognizer(&recognizer, sample, timeout, attempt);
+    let record = |result| {
+        let _ = recognition_metrics::append(&recognition_metrics::Run {
+            at: Local::now().naive_local(),
+            backend: recognizer.name(),
+            capture: capture.duration,
+            bytes: capture.bytes,
+            recognizer: started.elapsed(),
+            result,
+        });
+    };
+    let output = match output {
+        Ok(output) => output,
+        Err(err) => {
+            if !attempt.is_cancelled() && !shutdown::requested() {
+                record(RunResult::Error);
+            }
+            return Err(err);
+        }
+    };
+    let recognized = match parse_output(&output, settings) {
+        Ok(recognized) => recognized,
+        Err(err) => {
+            record(RunResult::Error);
+            // Output it can't read won't become readable on a retry
+            return Err(RecognizerError::Unavailable(err));
+        }
+    };
+    record(if recognized.is_some() { RunResult::Match } else { RunResult::NoMatch });
+
+    let outcome = match recognized {
+        Some(raw) => {
+            let track = history::normalize_title(&raw, &settings.strip_suffixes);
+            let mut text = track.clone();
+            let mut history_line = None;
+            if write_history {
+                let _critical = shutdown::critical();
+                match append_to_recognition_history(&track, &raw, heard_at, timestamps, settings.repeat_window()) {
+                    Ok(None) => text.push_str(" (already in history)"),
+                    Ok(line) => history_line = line,
+                    Err(_) => {}
+                }
+            }
+            RecognitionOutcome {
+                track: Some(track),
+                text,
+                saved_sample: None,
+                write_history,
+                history_line,
+            }
+        }
+        None => {
+            let saved_sample = (settings.keep_failed_samples && !shutdown::requested())
+                .then(|| samples::retain(sample, settings.sample_retention_cap).ok())
+                .flatten();
+            let text = if saved_sample.is_some() {
+                "No match — sample saved, press S to reveal".to_string()
+            } else {
+                "No song recognized".to_string()
+            };
+            RecognitionOutcome {
+                text,
+                track: None,
+                saved_sample,
+                write_history,
+                history_line: None,
+            }
+        }
+    };
+
+    Ok(outcome)
+}
+
+/// Writes 16-bit PCM as a WAV file, which vibra and the signature both read.
+fn write_wav(path: &Path, clip: &Clip) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let data_len = (clip.samples.len() * 2) as u32;
+    let block_align = clip.channels * 2;
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_len).to_le_bytes())?;
+    file.write_all(b"WAVEfmt ")?;
+    file.write_all(&16u32.to_le_bytes())?;
+    file.write_all(&1u16.to_le_bytes())?;
+    file.write_all(&clip.channels.to_le_bytes())?;
+    file.write_all(&clip.sample_rate.to_le_bytes())?;
+    file.write_all(&(clip.sample_rate * block_align as u32).to_le_bytes())?;
+    file.write_all(&block_align.to_le_bytes())?;
+    file.write_all(&16u16.to_le_bytes())?;
+    file.write_all(b"data")?;
+    file.write_all(&data_len.to_le_bytes())?;
+    for sample in &clip.samples {
+        file.write_all(&sample.to_le_bytes())?;
+    }
+    file.flush()
+}
+
+/// "Title - Artist" from the recognizer's JSON, read at the configured
+/// paths. None when it matched nothing, which vibra says with no output.
+fn parse_output(output: &str, settings: &RecognitionConfig) -> Result<Option<String>, String> {
+    if output.trim().is_empty() {
+        return Ok(None);
+    }
+    let json: Value = serde_json::from_str(output)
+        .map_err(|err| format!("Recognizer failed: its output is not JSON ({})", err))?;
+    let title = json_path(&json, &settings.title_path).and_then(Value::as_str);
+    let artist = json_path(&json, &settings.artist_path).and_then(Value::as_str);
+    if title.is_none() && artist.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "{} - {}",
+        title.unwrap_or("Unknown Title"),
+        artist.unwrap_or("Unknown Artist")
+    )))
+}
+
+/// The value at a dotted path like "track.title". A number picks from an
+/// array, as in "matches.0.id".
+fn json_path<'a>(json: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').filter(|key| !key.is_empty()).try_fold(json, |value, key| match value {
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => value.get(key),
+    })
+}
+
+/// The recognizer command for `sample`, with `{file}` in its arguments
+/// replaced by the path.
+fn build_command(command: &[String], sample: &Path) -> Result<Command, String> {
+    let Some((program, args)) = command.split_first().filter(|(program, _)| !program.trim().is_empty()) else {
+        return Err("Recognizer failed: recognizer_command has no program to run".to_string());
+    };
+    if !args.iter().any(|arg| arg.contains(FILE_PLACEHOLDER)) {
+        return Err(format!("Recognizer failed: recognizer_command has no {} for the sample", FILE_PLACEHOLDER));
+    }
+    let path = sample.to_string_lossy();
+    let mut built = Command::new(program);
+    built.args(args.iter().map(|arg| arg.replace(FILE_PLACEHOLDER, &path)));
+    Ok(built)
+}
+
+/// Checks that the recognizer can be started at all, and says which it is.
+pub fn check_recognizer(settings: &RecognitionConfig) -> Result<String, String> {
+    let recognizer = Recognizer::from_settings(settings);
+    match &recognizer {
+        Recognizer::Command(command) => {
+            build_command(command, Path::new("sample.mp3"))?;
+            Command::new(&command[0])
+                .arg("--help")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map(|_| format!("{} found", recognizer.name()))
+                .map_err(|err| format!("Could not run {}: {}", recognizer.name(), err))
+        }
+        #[cfg(feature = "shazam")]
+        Recognizer::Shazam => Ok("built in".to_string()),
+    }
+}
+
+fn run_recognizer(
+    recognizer: &Recognizer,
+    sample: &Path,
+    timeout: Duration,
+    attempt: &Attempt,
+) -> Result<String, RecognizerError> {
+    if let Some(err) = RecognizerError::stopping(attempt) {
+        return Err(err);
+    }
+    match recognizer {
+        Recognizer::Command(command) => run_command(command, sample, timeout, attempt),
+        #[cfg(feature = "shazam")]
+        Recognizer::Shazam => run_shazam(sample, timeout, attempt),
+    }
+}
+
+/// Computes the sample's signature and asks Shazam about it. The request
+/// runs on its own thread so a cancel or quit doesn't wait for its answer;
+/// `timeout` bounds it.
+#[cfg(feature = "shazam")]
+fn run_shazam(sample: &Path, timeout: Duration, attempt: &Attempt) -> Result<String, RecognizerError> {
+    // A sample that can't be read won't be readable later either
+    let signature = Signature::from_file(sample).map_err(RecognizerError::Unavailable)?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(shazam::lookup(&signature, timeout));
+    });
+    loop {
+        match rx.recv_timeout(RECOGNIZER_POLL_INTERVAL) {
+            Ok(result) => {
+                return result.map_err(|err| match err {
+                    shazam::LookupError::Unreachable(err) => RecognizerError::Offline(err),
+                    shazam::LookupError::Failed(err) => RecognizerError::Failed(err),
+                })
+            }
+            Err(RecvTimeoutError::Timeout) if shutdown::requested() => {
+                return Err(RecognizerError::Stopped("Recognition abandoned on quit".to_string()));
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if let Some(err) = RecognizerError::stopping(attempt) {
+                    return Err(err);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(RecognizerError::Failed("Recognizer failed".to_string()));
+            }
+        }
+    }
+}
+
+/// Runs the recognizer command on the sample and returns its output. A
+/// recognizer that is still running after `timeout` is killed and reaped so
+/// it doesn't linger.
+fn run_command(command: &[String], sample: &Path, timeout: Duration, attempt: &Attempt) -> Result<String, RecognizerError> {
+    let mut child = build_command(command, sample)
+        .map_err(RecognizerError::Unavailable)?
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| RecognizerError::Unavailable(format!("Recognizer failed: could not run {}: {}", command[0], err)))?;
+
+    // Drain stdout on the side so a chatty recognizer can't block on a full pipe.
+    let mut stdout = child.stdout.take();
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(stdout) = stdout.as_mut() {
+            let _ = stdout.read_to_string(&mut output);
+        }
+        output
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if shutdown::requested() => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(RecognizerError::Stopped("Recognition abandoned on quit".to_string()));
+            }
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(RecognizerError::Offline("Recognizer timed out".to_string()));
+            }
+            Ok(None) => {
+                if let Some(err) = RecognizerError::stopping(attempt) {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(err);
+                }
+                thread::sleep(RECOGNIZER_POLL_INTERVAL);
+            }
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(RecognizerError::Failed(format!("Recognizer failed: {}", err)));
+            }
+        }
+    };
+
+    let output = reader.join().unwrap_or_default();
+    if !status.success() {
+        return Err(RecognizerError::Failed(format!("Recognizer exited with {}", status)));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_take_the_sample_and_output_is_read_at_the_configured_paths() {
+        let songrec = ["songrec", "audio-file-to-recognized-song", "{file}"].map(String::from);
+        let built = build_command(&songrec, Path::new("/tmp/sample.mp3")).unwrap();
+        assert_eq!(built.get_program(), "songrec");
+        assert_eq!(built.get_args().collect::<Vec<_>>(), ["audio-file-to-recognized-song", "/tmp/sample.mp3"]);
+        let err = build_command(&songrec[..2], Path::new("sample.mp3")).err().unwrap();
+        assert_eq!(err, "Recognizer failed: recognizer_command has no {file} for the sample");
+        assert!(build_command(&["".to_string()], Path::new("sample.mp3")).is_err());
+        assert_eq!(Recognizer::Command(vec!["/usr/bin/songrec".to_string()]).name(), "songrec");
+
+        let mut settings = RecognitionConfig::default();
+        let shazam = r#"{"matches": [{"id": "1"}], "track": {"title": "Jelly", "subtitle": "Laurel Halo"}}"#;
+        assert_eq!(parse_output(shazam, &settings), Ok(Some("Jelly - Laurel Halo".to_string())));
+        assert_eq!(parse_output(r#"{"matches": []}"#, &settings), Ok(None));
+        assert_eq!(parse_output("", &settings), Ok(None));
+        assert!(parse_output("Error: no network", &settings).unwrap_err().starts_with("Recognizer failed: its output is not JSON"));
+
+        settings.title_path = "result.0.name".to_string();
+        settings.artist_path = "result.0.artist".to_string();
+        let other = r#"{"result": [{"name": "Jelly"}]}"#;
+        assert_eq!(parse_output(other, &settings), Ok(Some("Jelly - Unknown Artist".to_string())));
+    }
+}
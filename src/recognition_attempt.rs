@@ -0,0 +1,110 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+pub const CANCELLED: &str = "Recognition cancelled";
+#[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+pub const TIMED_OUT: &str = "Recognition timed out";
+/// How long past the length of its sample an attempt may run before it is
+/// abandoned, for the stream to fill the tap and for the recognizer.
+const GRACE: Duration = Duration::from_secs(15);
+
+/// A recognition in flight, shared by the app and the thread running it.
+/// The app can cancel it and show how far it got; the thread checks it
+/// between steps and gives up once it is past its deadline, so a hung
+/// recognizer can't keep it alive.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+pub struct Attempt {
+    cancel: AtomicBool,
+    deadline: Option<Instant>,
+    /// How much of the sample is captured, in milliseconds.
+    captured_ms: AtomicU64,
+    identifying: AtomicBool,
+}
+
+#[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+impl Attempt {
+    /// An attempt on a sample `sample` long, abandoned `GRACE` after that.
+    pub fn new(sample: Duration, now: Instant) -> Self {
+        Attempt {
+            deadline: Some(now + sample + GRACE),
+            ..Attempt::unbounded()
+        }
+    }
+
+    /// An attempt without a deadline, for retrying queued samples, where
+    /// the recognizer's own timeout is enough.
+    pub fn unbounded() -> Self {
+        Attempt {
+            cancel: AtomicBool::new(false),
+            deadline: None,
+            captured_ms: AtomicU64::new(0),
+            identifying: AtomicBool::new(false),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Release);
+    }
+
+    /// Whether it was cancelled, rather than run out of time.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Acquire)
+    }
+
+    /// Why it should stop now, if it should: cancelled or timed out.
+    pub fn stop_reason(&self, now: Instant) -> Option<&'static str> {
+        if self.is_cancelled() {
+            Some(CANCELLED)
+        } else if self.deadline.is_some_and(|deadline| now >= deadline) {
+            Some(TIMED_OUT)
+        } else {
+            None
+        }
+    }
+
+    pub fn captured(&self, length: Duration) {
+        self.captured_ms.store(length.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn identifying(&self) {
+        self.identifying.store(true, Ordering::Relaxed);
+    }
+
+    /// What the Info pane shows while it runs.
+    pub fn describe(&self) -> String {
+        let captured = self.captured_ms.load(Ordering::Relaxed) / 1000;
+        if self.identifying.load(Ordering::Relaxed) {
+            format!("Recognizing… (identifying {}s of sample)", captured)
+        } else if self.captured_ms.load(Ordering::Relaxed) == 0 {
+            "Recognizing… (waiting for audio)".to_string()
+        } else {
+            format!("Recognizing… ({}s of sample captured)", captured)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_when_cancelled_or_past_its_deadline() {
+        let start = Instant::now();
+        let attempt = Attempt::new(Duration::from_secs(10), start);
+        assert_eq!(attempt.stop_reason(start + Duration::from_secs(24)), None);
+        assert_eq!(attempt.stop_reason(start + Duration::from_secs(25)), Some(TIMED_OUT));
+        attempt.cancel();
+        assert_eq!(attempt.stop_reason(start), Some(CANCELLED));
+        assert_eq!(Attempt::unbounded().stop_reason(start + Duration::from_secs(3600)), None);
+
+        let attempt = Attempt::unbounded();
+        assert_eq!(attempt.describe(), "Recognizing… (waiting for audio)");
+        attempt.captured(Duration::from_millis(7400));
+        assert_eq!(attempt.describe(), "Recognizing… (7s of sample captured)");
+        attempt.identifying();
+        assert_eq!(attempt.describe(), "Recognizing… (identifying 7s of sample)");
+    }
+}
@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, time::Duration};
+#[cfg(feature = "recognition")]
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+};
+
+use chrono::NaiveDateTime;
+
+#[cfg(feature = "recognition")]
+use crate::shutdown;
+use crate::{format, migrate};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// How one recognition went, phase by phase, for comparing recognizers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub at: NaiveDateTime,
+    pub backend: String,
+    /// Time spent getting the sample: streaming it, or writing out a clip.
+    pub capture: Duration,
+    pub bytes: u64,
+    /// Wall time of the recognizer itself.
+    pub recognizer: Duration,
+    pub result: RunResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunResult {
+    Match,
+    NoMatch,
+    /// The recognizer failed, timed out or couldn't be started.
+    Error,
+}
+
+impl RunResult {
+    #[cfg(feature = "recognition")]
+    fn as_str(self) -> &'static str {
+        match self {
+            RunResult::Match => "match",
+            RunResult::NoMatch => "no-match",
+            RunResult::Error => "error",
+        }
+    }
+
+    fn parse(text: &str) -> Option<RunResult> {
+        match text {
+            "match" => Some(RunResult::Match),
+            "no-match" => Some(RunResult::NoMatch),
+            "error" => Some(RunResult::Error),
+            _ => None,
+        }
+    }
+}
+
+pub fn log_file_path() -> PathBuf {
+    migrate::RECOGNITION_METRICS.path()
+}
+
+/// Appends a run as `at<TAB>backend<TAB>capture ms<TAB>bytes<TAB>recognizer ms<TAB>result`.
+#[cfg(feature = "recognition")]
+pub fn append(run: &Run) -> io::Result<()> {
+    let _critical = shutdown::critical();
+    let line = format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\n",
+        run.at.format(TIMESTAMP_FORMAT),
+        run.backend.replace(['\t', '\n', '\r'], " "),
+        run.capture.as_millis(),
+        run.bytes,
+        run.recognizer.as_millis(),
+        run.result.as_str()
+    );
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(migrate::RECOGNITION_METRICS.writable_path()?)?
+        .write_all(line.as_bytes())
+}
+
+/// The whole log, or nothing if there is none yet.
+pub fn read() -> String {
+    fs::read_to_string(log_file_path()).unwrap_or_default()
+}
+
+/// Parses the log, skipping lines it can't make sense of.
+pub fn parse(contents: &str) -> Vec<Run> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(Run {
+                at: NaiveDateTime::parse_from_str(fields.next()?, TIMESTAMP_FORMAT).ok()?,
+                backend: fields.next()?.to_string(),
+                capture: Duration::from_millis(fields.next()?.parse().ok()?),
+                bytes: fields.next()?.parse().ok()?,
+                recognizer: Duration::from_millis(fields.next()?.parse().ok()?),
+                result: RunResult::parse(fields.next()?)?,
+            })
+        })
+        .collect()
+}
+
+/// One line per backend: runs, how many matched and the median time taken,
+/// e.g. "vibra: 12 runs, 75% matched, median 3.4s recognizing, 10.2s in all".
+pub fn summary_lines(runs: &[Run]) -> Vec<String> {
+    let mut backends: BTreeMap<&str, Vec<&Run>> = BTreeMap::new();
+    for run in runs {
+        backends.entry(&run.backend).or_default().push(run);
+    }
+    backends
+        .into_iter()
+        .map(|(backend, runs)| {
+            let matched = runs.iter().filter(|run| run.result == RunResult::Match).count();
+            let recognizer = median(runs.iter().map(|run| run.recognizer).collect());
+            let total = median(runs.iter().map(|run| run.capture + run.recognizer).collect());
+            format!(
+                "{}: {} run{}, {} matched, median {} recognizing, {} in all",
+                backend,
+                runs.len(),
+                if runs.len() == 1 { "" } else { "s" },
+                format::percent(matched as f64 / runs.len() as f64),
+                format::seconds(recognizer, 1),
+                format::seconds(total, 1)
+            )
+        })
+        .collect()
+}
+
+/// A markdown section with the summary, for the stats popup.
+pub fn report(runs: &[Run]) -> String {
+    let mut out = "\n## Recognizers\n\n".to_string();
+    let lines = summary_lines(runs);
+    if lines.is_empty() {
+        out.push_str("No recognitions timed yet.\n");
+    }
+    for line in lines {
+        out.push_str(&format!("- {}\n", line));
+    }
+    out
+}
+
+fn median(mut durations: Vec<Duration>) -> Duration {
+    durations.sort();
+    match durations.len() {
+        0 => Duration::ZERO,
+        len if len % 2 == 1 => durations[len / 2],
+        len => (durations[len / 2 - 1] + durations[len / 2]) / 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+2024-06-01 12:00:00\tvibra\t10000\t1310720\t3000\tmatch
+2024-06-01 12:05:00\tvibra\t10000\t1310720\t5000\tno-match
+2024-06-01 12:10:00\tvibra\t12\t441044\t4000\tmatch
+2024-06-01 12:15:00\tsongrec\t10000\t1310720\t2000\terror
+not a run
+";
+
+    #[test]
+    fn parses_log_lines() {
+        let runs = parse(LOG);
+        assert_eq!(runs.len(), 4);
+        assert_eq!(runs[2].capture, Duration::from_millis(12));
+        assert_eq!(runs[3].result, RunResult::Error);
+    }
+
+    #[test]
+    fn summary_is_per_backend() {
+        assert_eq!(
+            summary_lines(&parse(LOG)),
+            [
+                "songrec: 1 run, 0% matched, median 2.0s recognizing, 12.0s in all",
+                "vibra: 3 runs, 67% matched, median 4.0s recognizing, 13.0s in all",
+            ]
+        );
+    }
+}
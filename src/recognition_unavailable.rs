@@ -0,0 +1,63 @@
+//! Stands in for the recognition module in builds without the
+//! `recognition` feature, so callers compile unchanged: nothing is captured
+//! or identified, and every attempt says why.
+// The types match the real module's, but nothing builds them here
+#![allow(dead_code)]
+
+use std::{path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Local};
+
+use crate::{config::{RecognitionConfig, TimestampStyle}, mp3_tap::Mp3Tap, pcm_tap::Clip, recognition_attempt::Attempt};
+
+/// Whether this build can identify tracks.
+pub const AVAILABLE: bool = false;
+pub const UNAVAILABLE: &str = "Recognition not available in this build";
+
+pub struct RecognitionOutcome {
+    pub text: String,
+    pub track: Option<String>,
+    pub saved_sample: Option<PathBuf>,
+    pub write_history: bool,
+    pub history_line: Option<String>,
+}
+
+pub struct PendingMatch {
+    pub track: String,
+    pub heard_at: DateTime<Local>,
+    pub write_history: bool,
+    pub history_line: Option<String>,
+}
+
+pub fn recognize(
+    _tap: &Mp3Tap,
+    _duration: u64,
+    _settings: &RecognitionConfig,
+    _write_history: bool,
+    _timestamps: TimestampStyle,
+    _attempt: &Attempt,
+) -> Result<RecognitionOutcome, String> {
+    Err(UNAVAILABLE.to_string())
+}
+
+pub fn recognize_clip(
+    _clip: Clip,
+    _settings: &RecognitionConfig,
+    _write_history: bool,
+    _timestamps: TimestampStyle,
+    _attempt: &Attempt,
+) -> Result<RecognitionOutcome, String> {
+    Err(UNAVAILABLE.to_string())
+}
+
+pub fn retry_pending(_settings: &RecognitionConfig, _timestamps: TimestampStyle, _max_age: Duration) -> (Vec<PendingMatch>, usize) {
+    (Vec::new(), 0)
+}
+
+pub fn pending_count(_max_age: Duration) -> usize {
+    0
+}
+
+pub fn check_recognizer(_settings: &RecognitionConfig) -> Result<String, String> {
+    Err(UNAVAILABLE.to_string())
+}
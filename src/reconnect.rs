@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+/// How long the playing stream may go without data before it counts as
+/// dropped.
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+/// The wait before the second attempt, doubled for each one after.
+const FIRST_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+/// How close to a show boundary a stream that runs out counts as broken off
+/// by the changeover, either side of it.
+const CHANGEOVER_WINDOW: Duration = Duration::from_secs(2 * 60);
+/// How long a reconnected stream has to play before the attempts are
+/// forgotten, so one that drops again right away keeps backing off.
+const RECOVERED_AFTER: Duration = Duration::from_secs(60);
+
+/// Whether `now` falls within `CHANGEOVER_WINDOW` of one of `boundaries`,
+/// the times shows start and end on the station, when the stream is known
+/// to hiccup.
+pub fn at_changeover(boundaries: impl IntoIterator<Item = DateTime<Local>>, now: DateTime<Local>) -> bool {
+    boundaries
+        .into_iter()
+        .any(|boundary| (now - boundary).abs().to_std().is_ok_and(|apart| apart <= CHANGEOVER_WINDOW))
+}
+
+/// Paces reconnecting a stream that dropped: the first attempt goes at
+/// once, each failed one waits twice as long as the last before the next,
+/// and after `max` attempts it gives up.
+#[derive(Debug)]
+pub struct Backoff {
+    max: u32,
+    attempts: u32,
+    retry_at: Option<Instant>,
+    connected_at: Option<Instant>,
+}
+
+impl Backoff {
+    pub fn new(max: u32) -> Self {
+        Backoff {
+            max,
+            attempts: 0,
+            retry_at: None,
+            connected_at: None,
+        }
+    }
+
+    /// Schedules the next attempt after the stream dropped, or an attempt
+    /// failed, at `now`. Returns how long until it goes, or None when the
+    /// attempts are used up.
+    pub fn schedule(&mut self, now: Instant) -> Option<Duration> {
+        if self.attempts >= self.max {
+            return None;
+        }
+        let delay = match self.attempts {
+            0 => Duration::ZERO,
+            attempts => FIRST_DELAY.saturating_mul(1 << (attempts - 1).min(16)).min(MAX_DELAY),
+        };
+        self.retry_at = Some(now + delay);
+        self.connected_at = None;
+        Some(delay)
+    }
+
+    /// Starts the attempt scheduled, once it is due. Returns its number,
+    /// counting from 1.
+    pub fn start_due(&mut self, now: Instant) -> Option<u32> {
+        if self.retry_at.is_none_or(|at| now < at) {
+            return None;
+        }
+        self.retry_at = None;
+        self.attempts += 1;
+        Some(self.attempts)
+    }
+
+    /// The attempt in flight connected at `now`.
+    pub fn connected(&mut self, now: Instant) {
+        self.connected_at = Some(now);
+    }
+
+    /// Whether the stream has played long enough since it reconnected to
+    /// start over next time it drops.
+    pub fn recovered(&self, now: Instant) -> bool {
+        self.connected_at.is_some_and(|at| now.saturating_duration_since(at) >= RECOVERED_AFTER)
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn waits_twice_as_long_after_each_failure_then_gives_up() {
+        let start = Instant::now();
+        let mut backoff = Backoff::new(4);
+        let mut delays = Vec::new();
+        let mut now = start;
+        while let Some(delay) = backoff.schedule(now) {
+            delays.push(delay);
+            assert_eq!(backoff.start_due(now + delay - Duration::from_millis(1)), None);
+            now += delay;
+            assert_eq!(backoff.start_due(now), Some(delays.len() as u32));
+            assert_eq!(backoff.start_due(now), None);
+        }
+        assert_eq!(delays, [0, 1, 2, 4].map(Duration::from_secs));
+        assert_eq!(backoff.attempts(), 4);
+
+        let mut backoff = Backoff::new(3);
+        backoff.schedule(start);
+        backoff.start_due(start);
+        backoff.connected(start);
+        assert!(!backoff.recovered(start + Duration::from_secs(59)));
+        assert!(backoff.recovered(start + RECOVERED_AFTER));
+        assert_eq!(Backoff::new(0).schedule(start), None);
+    }
+
+    #[test]
+    fn only_a_show_boundary_close_by_is_a_changeover() {
+        let at = |minute, second| Local.with_ymd_and_hms(2024, 6, 1, 14, minute, second).unwrap();
+        let boundaries = [at(0, 0), at(59, 59)];
+        assert!(at_changeover(boundaries, at(1, 30)));
+        assert!(at_changeover(boundaries, at(58, 0)));
+        assert!(!at_changeover(boundaries, at(2, 1)));
+        assert!(!at_changeover([], at(0, 0)));
+    }
+}
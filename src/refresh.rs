@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::StreamsCollection;
+
+/// How long after a show's listed end the streams are fetched, so the API
+/// has moved on to the next one.
+const GRACE: Duration = Duration::from_secs(20);
+/// How soon the streams are fetched again when the API still listed a show
+/// that had ended. Only once; after that the hourly refresh takes over.
+const STALE_RETRY: Duration = Duration::from_secs(60);
+/// How far past the hour the hourly refresh runs, for listings that don't
+/// say when their shows end.
+const PAST_THE_HOUR: Duration = Duration::from_secs(240);
+
+/// When the refresh thread fetches the streams next.
+#[derive(Debug, PartialEq)]
+pub struct Next {
+    pub wait: Duration,
+    /// Whether this is the retry for a listing that hadn't moved on.
+    pub stale_retry: bool,
+}
+
+/// The ends of the shows on air in `collection`, as listed.
+pub fn show_ends(collection: &StreamsCollection) -> Vec<DateTime<Local>> {
+    collection
+        .stations
+        .iter()
+        .filter_map(|station| station.airtime?.end)
+        .collect()
+}
+
+/// Schedules the next refresh from `ends`, the show ends in the last
+/// listing: just after the earliest one still to come, or a minute from now
+/// if one had already passed and `retried` says that wasn't tried yet. Never
+/// later than the hourly refresh, which the mixtapes still rely on.
+pub fn next(ends: &[DateTime<Local>], now: DateTime<Local>, retried: bool) -> Next {
+    let hourly = until_next_hour(now);
+    if !retried && ends.iter().any(|end| *end <= now) {
+        return Next {
+            wait: STALE_RETRY.min(hourly),
+            stale_retry: true,
+        };
+    }
+    let wait = ends
+        .iter()
+        .filter(|end| **end > now)
+        .min()
+        .and_then(|end| (*end - now).to_std().ok())
+        .map_or(hourly, |until_end| (until_end + GRACE).min(hourly));
+    Next {
+        wait,
+        stale_retry: false,
+    }
+}
+
+fn until_next_hour(now: DateTime<Local>) -> Duration {
+    let into_hour = now.timestamp().rem_euclid(3600) as u64;
+    Duration::from_secs(3600 - into_hour) + PAST_THE_HOUR
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn follows_the_show_ends_and_retries_a_stale_listing_once() {
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 14, 32, 0).unwrap();
+        let hourly = until_next_hour(now);
+        assert!(hourly <= Duration::from_secs(3600) + PAST_THE_HOUR);
+        assert_eq!(next(&[], now, false).wait, hourly);
+
+        let ends = [now + TimeDelta::minutes(10), now + TimeDelta::minutes(5)];
+        assert_eq!(
+            next(&ends, now, false),
+            Next {
+                wait: Duration::from_secs(5 * 60) + GRACE,
+                stale_retry: false
+            }
+        );
+        assert_eq!(next(&[now + TimeDelta::hours(2)], now, false).wait, hourly);
+
+        let stale = [now - TimeDelta::seconds(30), now + TimeDelta::minutes(10)];
+        assert_eq!(
+            next(&stale, now, false),
+            Next {
+                wait: STALE_RETRY,
+                stale_retry: true
+            }
+        );
+        assert_eq!(next(&stale, now, true).wait, Duration::from_secs(10 * 60) + GRACE);
+        assert_eq!(next(&stale[..1], now, true).wait, hourly);
+    }
+}
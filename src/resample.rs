@@ -0,0 +1,220 @@
+use std::fmt;
+
+use rodio::Source;
+use rubato::{
+    FastFixedIn, PolynomialDegree, SincFixedIn, SincInterpolationParameters, SincInterpolationType, VecResampler,
+    WindowFunction,
+};
+
+use crate::config::ResampleQuality;
+
+/// Input frames converted at a time, about 23 ms at 44.1 kHz.
+const CHUNK_FRAMES: usize = 1024;
+
+/// The sample rate conversion in use, for the debug overlay: "44.1→48 kHz,
+/// linear", or "44.1 kHz, no resampling" when the device runs at the
+/// stream's rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Conversion {
+    pub from: u32,
+    pub to: u32,
+    pub quality: ResampleQuality,
+}
+
+impl fmt::Display for Conversion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.from == self.to {
+            return write!(f, "{} kHz, no resampling", khz(self.from));
+        }
+        let quality = match self.quality {
+            ResampleQuality::Linear | ResampleQuality::Native => "linear",
+            ResampleQuality::High => "high quality",
+        };
+        write!(f, "{}→{} kHz, {}", khz(self.from), khz(self.to), quality)
+    }
+}
+
+fn khz(rate: u32) -> String {
+    format!("{}", rate as f64 / 1000.0)
+}
+
+/// Converts a source to the output device's sample rate ahead of the sink,
+/// so rodio has nothing left to convert. Linear is cheap enough for slow
+/// boards; high quality uses a windowed sinc filter.
+pub struct Resampled<S> {
+    source: S,
+    channels: usize,
+    to: u32,
+    ratio: f64,
+    resampler: Box<dyn VecResampler<f32>>,
+    input: Vec<Vec<f32>>,
+    /// Converted samples, interleaved, and how many were handed out.
+    output: Vec<i16>,
+    position: usize,
+    finished: bool,
+}
+
+impl<S: Source<Item = i16>> Resampled<S> {
+    /// `quality` Native has nothing to do here and converts linearly: it
+    /// only ends up resampling when the device can't run at the stream's
+    /// rate.
+    pub fn new(source: S, to: u32, quality: ResampleQuality) -> Result<Resampled<S>, String> {
+        let channels = source.channels() as usize;
+        let ratio = to as f64 / source.sample_rate() as f64;
+        let resampler: Box<dyn VecResampler<f32>> = match quality {
+            ResampleQuality::High => {
+                let parameters = SincInterpolationParameters {
+                    sinc_len: 256,
+                    f_cutoff: 0.95,
+                    oversampling_factor: 256,
+                    interpolation: SincInterpolationType::Linear,
+                    window: WindowFunction::BlackmanHarris2,
+                };
+                Box::new(
+                    SincFixedIn::new(ratio, 1.0, parameters, CHUNK_FRAMES, channels)
+                        .map_err(|err| err.to_string())?,
+                )
+            }
+            ResampleQuality::Linear | ResampleQuality::Native => Box::new(
+                FastFixedIn::new(ratio, 1.0, PolynomialDegree::Linear, CHUNK_FRAMES, channels)
+                    .map_err(|err| err.to_string())?,
+            ),
+        };
+        Ok(Resampled {
+            source,
+            channels,
+            to,
+            ratio,
+            resampler,
+            input: vec![Vec::with_capacity(CHUNK_FRAMES); channels],
+            output: Vec::new(),
+            position: 0,
+            finished: false,
+        })
+    }
+
+    /// Converts the next chunk. Returns false once the source is exhausted.
+    fn refill(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+        let frames = self.resampler.input_frames_next();
+        for channel in &mut self.input {
+            channel.clear();
+        }
+        'frames: for _ in 0..frames {
+            for channel in 0..self.channels {
+                let Some(sample) = self.source.next() else {
+                    self.finished = true;
+                    break 'frames;
+                };
+                self.input[channel].push(sample as f32 / 32768.0);
+            }
+        }
+        // A partial last frame can't be converted
+        let complete = self.input.iter().map(Vec::len).min().unwrap_or(0);
+        for channel in &mut self.input {
+            channel.truncate(complete);
+        }
+        let converted = if self.finished {
+            self.resampler.process_partial(Some(&self.input), None)
+        } else {
+            self.resampler.process(&self.input, None)
+        };
+        let Ok(converted) = converted else {
+            self.finished = true;
+            return false;
+        };
+        self.output.clear();
+        self.position = 0;
+        let mut frames_out = converted.first().map_or(0, Vec::len);
+        if self.finished {
+            // The last chunk is padded with silence; keep what the input covers
+            frames_out = frames_out.min((complete as f64 * self.ratio).ceil() as usize);
+        }
+        for frame in 0..frames_out {
+            for channel in &converted {
+                self.output.push((channel[frame] * 32768.0).clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+            }
+        }
+        !self.output.is_empty() || !self.finished
+    }
+}
+
+impl<S: Source<Item = i16>> Iterator for Resampled<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        while self.position >= self.output.len() {
+            if !self.refill() {
+                return None;
+            }
+        }
+        self.position += 1;
+        Some(self.output[self.position - 1])
+    }
+}
+
+impl<S: Source<Item = i16>> Source for Resampled<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.to
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    fn tone(rate: u32, seconds: f32) -> SamplesBuffer<i16> {
+        let frames = (rate as f32 * seconds) as usize;
+        let samples = (0..frames)
+            .flat_map(|i| {
+                let sample = ((i as f32 * 440.0 * std::f32::consts::TAU / rate as f32).sin() * 8000.0) as i16;
+                [sample, sample]
+            })
+            .collect::<Vec<_>>();
+        SamplesBuffer::new(2, rate, samples)
+    }
+
+    #[test]
+    fn converts_to_the_device_rate() {
+        for quality in [ResampleQuality::Linear, ResampleQuality::High] {
+            let resampled = Resampled::new(tone(44_100, 1.0), 48_000, quality).unwrap();
+            assert_eq!((resampled.sample_rate(), resampled.channels()), (48_000, 2));
+            let frames = resampled.count() / 2;
+            // One second in, about one second out; the sinc filter's delay
+            // costs a little at the end
+            assert!((46_000..=48_200).contains(&frames), "{:?}: {} frames", quality, frames);
+        }
+    }
+
+    #[test]
+    fn conversion_reads_like_the_overlay() {
+        let conversion = Conversion {
+            from: 44_100,
+            to: 48_000,
+            quality: ResampleQuality::Linear,
+        };
+        assert_eq!(conversion.to_string(), "44.1→48 kHz, linear");
+        let native = Conversion {
+            to: 44_100,
+            quality: ResampleQuality::Native,
+            ..conversion
+        };
+        assert_eq!(native.to_string(), "44.1 kHz, no resampling");
+    }
+}
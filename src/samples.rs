@@ -0,0 +1,226 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(feature = "recognition")]
+use chrono::Local;
+
+const SAMPLES_DIR_NAME: &str = "nts_cli/samples";
+
+/// Directory where samples of failed recognitions are kept for manual lookup.
+pub fn retention_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(SAMPLES_DIR_NAME))
+}
+
+/// Moves a captured sample into the retention directory and drops the oldest
+/// samples beyond `cap`. Returns the new location of the sample.
+#[cfg(feature = "recognition")]
+pub fn retain(sample: &Path, cap: usize) -> io::Result<PathBuf> {
+    let dir = retention_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    retain_in(&dir, sample, cap)
+}
+
+/// `retain` into `dir`. With `cap` 0 nothing is kept, so the sample stays
+/// where it is and this fails.
+#[cfg(feature = "recognition")]
+fn retain_in(dir: &Path, sample: &Path, cap: usize) -> io::Result<PathBuf> {
+    if cap == 0 {
+        return Err(io::Error::other("sample_retention_cap is 0"));
+    }
+    fs::create_dir_all(dir)?;
+
+    // Samples saved within the same second are numbered after the first,
+    // whatever their format, so they keep the order they came in
+    let extension = sample.extension().and_then(|ext| ext.to_str()).unwrap_or("mp3");
+    let name = format!("sample-{}", Local::now().format("%Y%m%d-%H%M%S"));
+    let taken = |stem: &str| ["mp3", "wav"].iter().any(|ext| dir.join(format!("{}.{}", stem, ext)).exists());
+    let mut stem = name.clone();
+    let mut number = 1;
+    while taken(&stem) {
+        number += 1;
+        stem = format!("{}_{}", name, number);
+    }
+    let target = dir.join(format!("{}.{}", stem, extension));
+    // The sample usually lives in a temp dir on another filesystem
+    if fs::rename(sample, &target).is_err() {
+        fs::copy(sample, &target)?;
+    }
+
+    let mut retained = list(dir)?;
+    retained.sort_by_key(|sample| saved_order(sample));
+    let excess = retained.len().saturating_sub(cap);
+    for old in retained.into_iter().take(excess) {
+        let _ = fs::remove_file(old);
+    }
+
+    Ok(target)
+}
+
+/// Orders retained samples oldest first: by the time in the name, then by
+/// the number samples from the same second get.
+#[cfg(feature = "recognition")]
+fn saved_order(sample: &Path) -> (String, u32) {
+    let stem = sample.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    match stem.split_once('_') {
+        Some((name, number)) => (name.to_string(), number.parse().unwrap_or(0)),
+        None => (stem.to_string(), 1),
+    }
+}
+
+/// Number of retained samples and their total size in bytes.
+pub fn usage() -> io::Result<(usize, u64)> {
+    let Some(dir) = retention_dir().filter(|dir| dir.exists()) else {
+        return Ok((0, 0));
+    };
+    let samples = list(&dir)?;
+    let size = samples
+        .iter()
+        .filter_map(|sample| fs::metadata(sample).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    Ok((samples.len(), size))
+}
+
+/// Deletes the samples in `dir` last modified more than `max_age` ago.
+/// Returns how many went and the space they took.
+pub fn prune_older_than(dir: &Path, max_age: Duration) -> io::Result<(usize, u64)> {
+    if !dir.exists() {
+        return Ok((0, 0));
+    }
+    let now = SystemTime::now();
+    let (mut removed, mut reclaimed) = (0, 0);
+    for sample in list(dir)? {
+        let Ok(metadata) = fs::metadata(&sample) else {
+            continue;
+        };
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if age > max_age && fs::remove_file(&sample).is_ok() {
+            removed += 1;
+            reclaimed += metadata.len();
+        }
+    }
+    Ok((removed, reclaimed))
+}
+
+/// Opens the folder containing `sample` in the platform file manager. The
+/// opener is waited on from a thread of its own so it doesn't linger as a
+/// zombie once it exits.
+pub fn reveal(sample: &Path) -> io::Result<()> {
+    let folder = sample.parent().unwrap_or(sample);
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    let mut child = Command::new(opener)
+        .arg(folder)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+fn list(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "mp3" || ext == "wav"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// A sample of `size` bytes last modified `age` ago.
+    fn write_sample(dir: &Path, name: &str, size: usize, age: Duration) {
+        fs::write(dir.join(name), vec![0; size]).unwrap();
+        File::options()
+            .write(true)
+            .open(dir.join(name))
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    #[test]
+    fn pruning_deletes_only_old_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        write_sample(dir.path(), "sample-old.mp3", 300, 10 * DAY);
+        write_sample(dir.path(), "sample-old.wav", 200, 8 * DAY);
+        write_sample(dir.path(), "sample-new.mp3", 100, DAY);
+        write_sample(dir.path(), "notes.txt", 50, 10 * DAY);
+
+        assert_eq!(prune_older_than(dir.path(), 7 * DAY).unwrap(), (2, 500));
+        let mut left: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["notes.txt", "sample-new.mp3"]);
+        assert_eq!(prune_older_than(&dir.path().join("missing"), 7 * DAY).unwrap(), (0, 0));
+    }
+
+    #[test]
+    #[cfg(feature = "recognition")]
+    fn samples_saved_in_the_same_second_are_all_kept_up_to_the_cap() {
+        let captured = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let capture = |name: &str| {
+            let sample = captured.path().join(name);
+            fs::write(&sample, name).unwrap();
+            sample
+        };
+
+        // Fast enough to land in one second most of the time; the names
+        // stay apart and in order either way
+        let kept: Vec<_> = ["a.mp3", "b.mp3", "c.wav"]
+            .iter()
+            .map(|name| retain_in(dir.path(), &capture(name), 2).unwrap())
+            .collect();
+        assert!(!kept[0].exists());
+        assert_eq!(fs::read_to_string(&kept[1]).unwrap(), "b.mp3");
+        assert_eq!(fs::read_to_string(&kept[2]).unwrap(), "c.wav");
+        assert_eq!(list(dir.path()).unwrap().len(), 2);
+
+        let mut names = [
+            "sample-20240601-143200_10.mp3",
+            "sample-20240601-143200_2.mp3",
+            "sample-20240601-143201.mp3",
+            "sample-20240601-143200.wav",
+        ];
+        names.sort_by_key(|name| saved_order(Path::new(name)));
+        assert_eq!(names[0], "sample-20240601-143200.wav");
+        assert_eq!(names[2], "sample-20240601-143200_10.mp3");
+    }
+
+    #[test]
+    #[cfg(feature = "recognition")]
+    fn a_cap_of_zero_keeps_nothing() {
+        let captured = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let sample = captured.path().join("sample.mp3");
+        fs::write(&sample, "audio").unwrap();
+
+        assert!(retain_in(dir.path(), &sample, 0).is_err());
+        assert!(sample.exists());
+        assert_eq!(list(dir.path()).unwrap().len(), 0);
+    }
+}
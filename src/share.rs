@@ -0,0 +1,126 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, Timelike};
+
+use crate::{
+    config::{ShareConfig, ShareTemplate},
+    history,
+};
+
+const NOTES_DIR_NAME: &str = "nts_cli/notes";
+
+/// What `E` shares the tracks of: the stream playing, the show on it when
+/// it started, and since when.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub show: String,
+    pub stream: String,
+    pub since: DateTime<Local>,
+}
+
+/// The history entries heard since `since`, oldest first, as their time and
+/// track. The history keeps minutes, so the minute `since` falls in counts.
+pub fn tracks(history_contents: &str, since: NaiveDateTime) -> Vec<(NaiveTime, &str)> {
+    let since = since.with_second(0).unwrap_or(since);
+    let mut lines: Vec<&str> = history_contents
+        .lines()
+        .filter(|line| history::parse_timestamp(line).is_some_and(|heard_at| heard_at >= since))
+        .collect();
+    lines.sort_by_key(|line| history::order_key(line));
+    lines
+        .into_iter()
+        .filter_map(|line| Some((history::parse_timestamp(line)?.time(), history::entry_text(line))))
+        .collect()
+}
+
+/// The session as a block to paste: the show, the date and stream, then
+/// the tracks numbered with their times.
+pub fn build(session: &Session, tracks: &[(NaiveTime, &str)], template: ShareTemplate) -> String {
+    let date = session.since.format("%a %Y-%m-%d");
+    let subtitle = if session.stream == session.show {
+        date.to_string()
+    } else {
+        format!("{} · {}", date, session.stream)
+    };
+    let mut out = match template {
+        ShareTemplate::Plain => format!("{}\n{}\n\n", session.show, subtitle),
+        ShareTemplate::Markdown => format!("## {}\n\n*{}*\n\n", session.show, subtitle),
+    };
+    for (number, (time, track)) in tracks.iter().enumerate() {
+        let time = time.format("%H:%M");
+        out.push_str(&match template {
+            ShareTemplate::Plain => format!("{}. {} {}\n", number + 1, time, track),
+            ShareTemplate::Markdown => format!("{}. **{}** {}\n", number + 1, time, track),
+        });
+    }
+    out
+}
+
+/// `share.notes_dir`, or `nts_cli/notes` in the data directory when it is
+/// left empty.
+pub fn notes_dir(config: &ShareConfig) -> Option<PathBuf> {
+    if config.notes_dir.as_os_str().is_empty() {
+        return dirs::data_dir().map(|dir| dir.join(NOTES_DIR_NAME));
+    }
+    match config.notes_dir.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir().map(|home| home.join(rest)),
+        Err(_) => Some(config.notes_dir.clone()),
+    }
+}
+
+/// Writes `text` to a file in `dir` named for `now`, like
+/// `session-20240601-1432.md`. Returns its path.
+pub fn write(dir: &Path, text: &str, template: ShareTemplate, now: DateTime<Local>) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let extension = match template {
+        ShareTemplate::Plain => "txt",
+        ShareTemplate::Markdown => "md",
+    };
+    let path = dir.join(format!("session-{}.{}", now.format("%Y%m%d-%H%M%S"), extension));
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn only_the_sessions_tracks_are_numbered_in_order() {
+        let history = "\
+2024-06-01 12:59  Before - Someone
+Untimed - Someone
+2024-06-01 13:05  Jelly - Laurel Halo\tJelly - Laurel Halo (Official)\t8
+2024-06-01 13:05  Hubble - Actress\t\t9
+2024-06-01 13:00  Right At The Start - Someone
+";
+        let session = Session {
+            show: "Breakfast Show".to_string(),
+            stream: "NTS 1".to_string(),
+            since: Local.with_ymd_and_hms(2024, 6, 1, 13, 0, 40).unwrap(),
+        };
+        let tracks = tracks(history, session.since.naive_local());
+        assert_eq!(
+            build(&session, &tracks, ShareTemplate::Plain),
+            "\
+Breakfast Show
+Sat 2024-06-01 · NTS 1
+
+1. 13:00 Right At The Start - Someone
+2. 13:05 Jelly - Laurel Halo
+3. 13:05 Hubble - Actress
+"
+        );
+        assert_eq!(
+            build(&session, &tracks[..1], ShareTemplate::Markdown),
+            "## Breakfast Show\n\n*Sat 2024-06-01 · NTS 1*\n\n1. **13:00** Right At The Start - Someone\n"
+        );
+        let later = Local.with_ymd_and_hms(2024, 6, 1, 14, 0, 0).unwrap();
+        assert!(super::tracks(history, later.naive_local()).is_empty());
+    }
+}
@@ -0,0 +1,457 @@
+//! Identifies a sample the way the Shazam app does, without vibra: the
+//! audio is turned into a signature of spectral peaks, which the Shazam
+//! endpoint matches. The signature follows SongRec's reimplementation of
+//! the app's algorithm.
+
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    f32::consts::PI,
+    fs::File,
+    hash::{BuildHasher, Hasher},
+    io::{self, BufReader, Read},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use minimp3::{Error as Mp3Error, Frame};
+use reqwest::blocking::Client;
+use rodio::{buffer::SamplesBuffer, source::UniformSourceIterator};
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde_json::json;
+
+use crate::mp3_frames::Decoder;
+
+/// The rate signatures are computed at.
+const SAMPLE_RATE: u32 = 16_000;
+/// How much of the sample goes into the signature, from its middle; the app
+/// sends about this much too.
+const MAX_SIGNATURE_SECONDS: usize = 12;
+const FFT_SIZE: usize = 2048;
+const BINS: usize = FFT_SIZE / 2 + 1;
+/// New samples per FFT pass.
+const HOP: usize = 128;
+/// FFT passes kept around for finding peaks in time.
+const HISTORY: usize = 256;
+const ENDPOINT: &str = "https://amp.shazam.com/discovery/v5/en/US/android/-/tag";
+const USER_AGENT: &str = "Dalvik/2.1.0 (Linux; U; Android 5.0.2; VS980 4G Build/LRX22G)";
+
+/// A spectral peak: when, how loud and at what frequency.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Peak {
+    pass: u32,
+    magnitude: u16,
+    bin: u16,
+}
+
+/// The peaks found in a sample, by frequency band.
+#[derive(Debug, Default)]
+pub struct Signature {
+    samples: u32,
+    bands: BTreeMap<u32, Vec<Peak>>,
+}
+
+impl Signature {
+    /// Computes the signature of 16 kHz mono audio.
+    pub fn from_samples(samples: &[i16]) -> Signature {
+        let mut generator = Generator::new();
+        for chunk in samples.chunks_exact(HOP) {
+            generator.fft(chunk);
+            generator.spread_peaks();
+            if generator.spread_written >= 46 {
+                generator.find_peaks();
+            }
+        }
+        Signature {
+            samples: samples.len() as u32,
+            bands: generator.bands,
+        }
+    }
+
+    /// Reads an MP3 capture or a WAV clip and computes the signature of
+    /// the middle of it.
+    pub fn from_file(path: &Path) -> Result<Signature, String> {
+        let is_wav = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("wav"));
+        let (samples, channels, rate) = if is_wav { read_wav(path) } else { read_mp3(path) }
+            .map_err(|err| format!("Could not read sample: {}", err))?;
+        if samples.is_empty() {
+            return Err("Sample has no audio".to_string());
+        }
+        let mono: Vec<i16> = UniformSourceIterator::new(SamplesBuffer::new(channels, rate, samples), 1, SAMPLE_RATE).collect();
+        let length = mono.len().min(MAX_SIGNATURE_SECONDS * SAMPLE_RATE as usize);
+        let start = (mono.len() - length) / 2;
+        Ok(Signature::from_samples(&mono[start..start + length]))
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.samples as u64 * 1000 / SAMPLE_RATE as u64)
+    }
+
+    /// The signature in the binary form the endpoint takes.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let put = |out: &mut Vec<u8>, value: u32| out.extend_from_slice(&value.to_le_bytes());
+        put(&mut out, 0xcafe_2580);
+        put(&mut out, 0); // checksum, filled in below
+        put(&mut out, 0); // size, likewise
+        put(&mut out, 0x9411_9c00);
+        for _ in 0..3 {
+            put(&mut out, 0);
+        }
+        // 16 kHz is sample rate 3
+        put(&mut out, 3 << 27);
+        for _ in 0..2 {
+            put(&mut out, 0);
+        }
+        put(&mut out, self.samples + (SAMPLE_RATE as f32 * 0.24) as u32);
+        put(&mut out, (15 << 19) + 0x40000);
+        put(&mut out, 0x4000_0000);
+        put(&mut out, 0); // size again
+        for (band, peaks) in &self.bands {
+            let mut encoded = Vec::new();
+            let mut pass = 0;
+            for peak in peaks {
+                if peak.pass - pass >= 255 {
+                    encoded.push(0xff);
+                    encoded.extend_from_slice(&peak.pass.to_le_bytes());
+                    pass = peak.pass;
+                }
+                encoded.push((peak.pass - pass) as u8);
+                encoded.extend_from_slice(&peak.magnitude.to_le_bytes());
+                encoded.extend_from_slice(&peak.bin.to_le_bytes());
+                pass = peak.pass;
+            }
+            put(&mut out, 0x6003_0040 + band);
+            put(&mut out, encoded.len() as u32);
+            out.extend_from_slice(&encoded);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        let size = (out.len() - 48) as u32;
+        out[8..12].copy_from_slice(&size.to_le_bytes());
+        out[52..56].copy_from_slice(&size.to_le_bytes());
+        let checksum = crc32(&out[8..]);
+        out[4..8].copy_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    pub fn uri(&self) -> String {
+        format!("data:audio/vnd.shazam.sig;base64,{}", STANDARD.encode(self.encode()))
+    }
+}
+
+/// The rolling state of the signature: the last 2048 samples, and the
+/// spectra of the last 256 passes as computed and with peaks spread over
+/// their neighbours.
+struct Generator {
+    samples: Vec<i16>,
+    position: usize,
+    window: Vec<f32>,
+    fft: std::sync::Arc<dyn rustfft::Fft<f32>>,
+    spectra: Vec<Vec<f32>>,
+    spectra_position: usize,
+    spread: Vec<Vec<f32>>,
+    spread_position: usize,
+    spread_written: usize,
+    bands: BTreeMap<u32, Vec<Peak>>,
+}
+
+impl Generator {
+    fn new() -> Self {
+        Generator {
+            samples: vec![0; FFT_SIZE],
+            position: 0,
+            window: (0..FFT_SIZE)
+                .map(|i| 0.5 * (1.0 - (2.0 * PI * (i + 1) as f32 / (FFT_SIZE + 1) as f32).cos()))
+                .collect(),
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            spectra: vec![vec![0.0; BINS]; HISTORY],
+            spectra_position: 0,
+            spread: vec![vec![0.0; BINS]; HISTORY],
+            spread_position: 0,
+            spread_written: 0,
+            bands: BTreeMap::new(),
+        }
+    }
+
+    /// The pass `offset` away from the next one to be written, which can
+    /// reach back or, wrapping around, forward.
+    fn back(position: usize, offset: isize) -> usize {
+        (position as isize + offset).rem_euclid(HISTORY as isize) as usize
+    }
+
+    fn fft(&mut self, chunk: &[i16]) {
+        self.samples[self.position..self.position + HOP].copy_from_slice(chunk);
+        self.position = (self.position + HOP) % FFT_SIZE;
+        let mut buffer: Vec<Complex<f32>> = (0..FFT_SIZE)
+            .map(|i| Complex::new(self.samples[(i + self.position) % FFT_SIZE] as f32 * self.window[i], 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        let spectrum = &mut self.spectra[self.spectra_position];
+        for (bin, value) in spectrum.iter_mut().zip(&buffer) {
+            *bin = (value.norm_sqr() / (1 << 17) as f32).max(1e-10);
+        }
+        self.spectra_position = (self.spectra_position + 1) % HISTORY;
+    }
+
+    fn spread_peaks(&mut self) {
+        let mut spread = self.spectra[Self::back(self.spectra_position, -1)].clone();
+        for bin in 0..BINS - 2 {
+            spread[bin] = spread[bin].max(spread[bin + 1]).max(spread[bin + 2]);
+        }
+        for former in [1, 3, 6] {
+            let earlier = &mut self.spread[Self::back(self.spread_position, -former)];
+            for (earlier, value) in earlier.iter_mut().zip(&spread) {
+                *earlier = earlier.max(*value);
+            }
+        }
+        self.spread[self.spread_position] = spread;
+        self.spread_position = (self.spread_position + 1) % HISTORY;
+        self.spread_written += 1;
+    }
+
+    fn find_peaks(&mut self) {
+        let spectrum = &self.spectra[Self::back(self.spectra_position, -46)];
+        let spread = &self.spread[Self::back(self.spread_position, -49)];
+        let magnitude = |value: f32| value.ln().max(1.0 / 64.0) * 1477.3 + 6144.0;
+        for bin in 10..=1014 {
+            let value = spectrum[bin];
+            if value < 1.0 / 64.0 || value < spread[bin - 1] {
+                continue;
+            }
+            // A peak among its neighbouring frequencies...
+            let neighbours = [-10, -7, -4, -3, 1, 2, 5, 8]
+                .iter()
+                .map(|offset| spread[(bin as isize + offset) as usize])
+                .fold(0.0f32, f32::max);
+            if value <= neighbours {
+                continue;
+            }
+            // ...and over the passes around it
+            let around = [-53, -45, 165, 172, 179, 186, 193, 200, 214, 221, 228, 235, 242, 249]
+                .iter()
+                .map(|offset| self.spread[Self::back(self.spread_position, *offset)][bin - 1])
+                .fold(neighbours, f32::max);
+            if value <= around {
+                continue;
+            }
+            let (before, peak, after) = (magnitude(spectrum[bin - 1]), magnitude(value), magnitude(spectrum[bin + 1]));
+            let curvature = peak * 2.0 - before - after;
+            if curvature <= 0.0 {
+                continue;
+            }
+            let corrected = ((bin * 64) as f32 + (after - before) * 32.0 / curvature) as u16;
+            let hz = corrected as f32 * (SAMPLE_RATE as f32 / 2.0 / 1024.0 / 64.0);
+            let band = match hz as u32 {
+                250..=519 => 0,
+                520..=1449 => 1,
+                1450..=3499 => 2,
+                3500..=5500 => 3,
+                _ => continue,
+            };
+            self.bands.entry(band).or_default().push(Peak {
+                pass: (self.spread_written - 46) as u32,
+                magnitude: peak as u16,
+                bin: corrected,
+            });
+        }
+    }
+}
+
+/// Decodes an MP3 capture. A capture cut off mid-frame just ends there.
+fn read_mp3(path: &Path) -> io::Result<(Vec<i16>, u16, u32)> {
+    let mut decoder = Decoder::new(BufReader::new(File::open(path)?));
+    let (mut samples, mut channels, mut rate) = (Vec::new(), 0, 0);
+    loop {
+        match decoder.next_frame() {
+            Ok(Frame {
+                data,
+                sample_rate,
+                channels: frame_channels,
+                ..
+            }) => {
+                if rate == 0 {
+                    (rate, channels) = (sample_rate as u32, frame_channels as u16);
+                }
+                if frame_channels as u16 == channels {
+                    samples.extend_from_slice(&data);
+                }
+            }
+            Err(Mp3Error::Eof) | Err(Mp3Error::InsufficientData) => break,
+            Err(Mp3Error::SkippedData) => continue,
+            Err(Mp3Error::Io(err)) => return Err(err),
+        }
+    }
+    Ok((samples, channels.max(1), rate.max(1)))
+}
+
+/// Reads the 16-bit PCM WAV files `recognize_clip` writes.
+fn read_wav(path: &Path) -> io::Result<(Vec<i16>, u16, u32)> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a 16-bit PCM WAV file");
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(invalid());
+    }
+    let (mut format, mut offset) = (None, 12);
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let length = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let body = &bytes[offset + 8..(offset + 8 + length).min(bytes.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let rate = u32::from_le_bytes(body[4..8].try_into().unwrap());
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if bits != 16 {
+                    return Err(invalid());
+                }
+                format = Some((channels.max(1), rate.max(1)));
+            }
+            b"data" => {
+                let (channels, rate) = format.ok_or_else(invalid)?;
+                let samples = body.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+                return Ok((samples, channels, rate));
+            }
+            _ => {}
+        }
+        offset += 8 + length + length % 2;
+    }
+    Err(invalid())
+}
+
+/// Why a lookup got no answer.
+pub enum LookupError {
+    /// Shazam couldn't be reached or didn't answer in time, as without a
+    /// connection.
+    Unreachable(String),
+    /// Shazam answered with an error, or the request couldn't be made.
+    Failed(String),
+}
+
+/// Asks the Shazam endpoint about the signature and returns its answer,
+/// with the matched track, if any, under "track".
+pub fn lookup(signature: &Signature, timeout: Duration) -> Result<String, LookupError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u32;
+    let body = json!({
+        "geolocation": { "altitude": 300, "latitude": 51.5, "longitude": -0.1 },
+        "signature": {
+            "samplems": signature.duration().as_millis() as u32,
+            "timestamp": now,
+            "uri": signature.uri(),
+        },
+        "timestamp": now,
+        "timezone": "Europe/London",
+    });
+    let client = Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|err| LookupError::Failed(format!("Could not set up HTTP client: {}", err)))?;
+    let response = client
+        .post(format!("{}/{}/{}", ENDPOINT, random_uuid(), random_uuid()))
+        .query(&[
+            ("sync", "true"),
+            ("webv3", "true"),
+            ("sampling", "true"),
+            ("connected", ""),
+            ("shazamapiversion", "v3"),
+            ("sharehub", "true"),
+            ("video", "v3"),
+        ])
+        .header("User-Agent", USER_AGENT)
+        .header("Content-Language", "en_US")
+        .json(&body)
+        .send()
+        .map_err(|err| {
+            LookupError::Unreachable(if err.is_timeout() {
+                "Shazam did not answer in time".to_string()
+            } else {
+                format!("Could not reach Shazam: {}", err)
+            })
+        })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(LookupError::Failed(format!("Shazam answered {}", status)));
+    }
+    response
+        .text()
+        .map_err(|err| LookupError::Unreachable(format!("Could not read Shazam's answer: {}", err)))
+}
+
+/// An uppercase version 4 UUID, as the app puts in the URL.
+fn random_uuid() -> String {
+    let random = || RandomState::new().build_hasher().finish();
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&random().to_le_bytes());
+    bytes[8..].copy_from_slice(&random().to_le_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// CRC-32 (IEEE), over the signature after its first two fields.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_tone_makes_peaks_in_its_band_and_a_well_formed_signature() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        // Two seconds of a 1 kHz tone that comes and goes
+        let samples: Vec<i16> = (0..2 * SAMPLE_RATE as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let swell = (PI * t).sin().powi(2);
+                ((2.0 * PI * 1000.0 * t).sin() * 12000.0 * swell) as i16
+            })
+            .collect();
+        let signature = Signature::from_samples(&samples);
+        let peak = signature.bands.values().flatten().max_by_key(|peak| peak.magnitude).unwrap();
+        assert!(signature.bands[&1].contains(peak));
+        let hz = peak.bin as f32 * (SAMPLE_RATE as f32 / 2.0 / 1024.0 / 64.0);
+        assert!((hz - 1000.0).abs() < 10.0, "{} Hz", hz);
+
+        let encoded = signature.encode();
+        assert_eq!(encoded.len() % 4, 0);
+        assert_eq!(&encoded[0..4], &0xcafe_2580u32.to_le_bytes());
+        assert_eq!(u32::from_le_bytes(encoded[8..12].try_into().unwrap()) as usize, encoded.len() - 48);
+        assert_eq!(u32::from_le_bytes(encoded[4..8].try_into().unwrap()), crc32(&encoded[8..]));
+        assert_eq!(&encoded[56..60], &0x6003_0041u32.to_le_bytes());
+        assert!(signature.uri().starts_with("data:audio/vnd.shazam.sig;base64,gCX+"));
+        assert_eq!(signature.duration(), Duration::from_secs(2));
+
+        let uuid = random_uuid();
+        assert_eq!((uuid.len(), &uuid[14..15]), (36, "4"));
+        assert_ne!(uuid, random_uuid());
+    }
+
+    #[test]
+    fn wav_clips_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.wav");
+        let mut bytes = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 0, 2, 0]);
+        bytes.extend_from_slice(&44_100u32.to_le_bytes());
+        bytes.extend_from_slice(&(44_100u32 * 4).to_le_bytes());
+        bytes.extend_from_slice(&[4, 0, 16, 0]);
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&4u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 0, 0xff, 0xff]);
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(read_wav(&path).unwrap(), (vec![1, -1], 2, 44_100));
+        assert!(Signature::from_file(&dir.path().join("missing.mp3")).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+static CRITICAL: Mutex<usize> = Mutex::new(0);
+static CRITICAL_DONE: Condvar = Condvar::new();
+
+/// Held while writing something that must not be cut off by quitting, like
+/// a history append or the state file. Quitting waits for these to drop.
+pub struct CriticalWork(());
+
+/// Marks the start of critical work, finished when the guard is dropped.
+pub fn critical() -> CriticalWork {
+    if let Ok(mut count) = CRITICAL.lock() {
+        *count += 1;
+    }
+    CriticalWork(())
+}
+
+impl Drop for CriticalWork {
+    fn drop(&mut self) {
+        if let Ok(mut count) = CRITICAL.lock() {
+            *count = count.saturating_sub(1);
+        }
+        CRITICAL_DONE.notify_all();
+    }
+}
+
+/// Tells workers the app is quitting, so they can stop early and skip
+/// anything that isn't worth finishing.
+pub fn request() {
+    REQUESTED.store(true, Ordering::Release);
+}
+
+#[cfg_attr(not(feature = "recognition"), allow(dead_code))]
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Acquire)
+}
+
+/// Waits up to `timeout` for critical work to finish. Returns false if some
+/// was still running when the time ran out.
+pub fn wait_for_critical(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let Ok(mut count) = CRITICAL.lock() else {
+        return false;
+    };
+    while *count > 0 {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return false;
+        }
+        count = match CRITICAL_DONE.wait_timeout(count, remaining) {
+            Ok((count, _)) => count,
+            Err(_) => return false,
+        };
+    }
+    true
+}
+
+/// Turns SIGTERM, SIGHUP and SIGINT into a request for the usual orderly
+/// quit, so the terminal is restored instead of left in raw mode. The TUI
+/// checks [`signalled`] on every tick; in raw mode Ctrl+C arrives as a key
+/// instead, so SIGINT only matters for the headless mode.
+#[cfg(unix)]
+pub fn install_signal_handlers() {
+    extern "C" fn on_signal(_: libc::c_int) {
+        SIGNALLED.store(true, Ordering::Release);
+    }
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGHUP, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_signal_handlers() {}
+
+pub fn signalled() -> bool {
+    SIGNALLED.load(Ordering::Acquire)
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    migrate,
+    queue::{QueueEntry, RotationProgress},
+    shutdown,
+};
+
+/// Bumped when the state format changes in a way older builds can't read.
+const STATE_VERSION: u32 = 1;
+
+/// Everything the app remembers between sessions apart from the history file.
+/// It is also the export format, so it should only hold things that make
+/// sense on another machine.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct State {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub queue: Vec<QueueEntry>,
+    /// Where the rotation through `queue` was. Left out of exports, where a
+    /// half-played slot means nothing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_progress: Option<RotationProgress>,
+    /// Favorite mixtapes by title, which stays put when the API moves a
+    /// mixtape to a new alias.
+    #[serde(default, deserialize_with = "titles")]
+    pub favorites: Vec<String>,
+    #[serde(default)]
+    pub followed_shows: Vec<String>,
+    /// When each followed show was last listened to, in Unix seconds, so
+    /// episodes published after that can be marked new.
+    #[serde(default)]
+    pub show_listened: BTreeMap<String, i64>,
+    /// Stream URLs played from the `U` prompt, newest first.
+    #[serde(default)]
+    pub recent_custom_urls: Vec<String>,
+    /// Per-stream volume offsets keyed by stream id.
+    #[serde(default)]
+    pub volume_offsets: BTreeMap<String, VolumeOffset>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct VolumeOffset {
+    pub offset: f32,
+    /// Unix seconds of the last change, so merges can keep the newer one.
+    pub updated_at: i64,
+}
+
+/// What an import changed, for printing back to the user.
+#[derive(Default, Debug)]
+pub struct MergeSummary {
+    pub queue_added: usize,
+    pub favorites_added: usize,
+    pub follows_added: usize,
+    pub offsets_updated: usize,
+}
+
+/// The titles in a list, skipping anything else, like the ids favorites
+/// were once kept by, so they can't make the whole state unreadable.
+fn titles<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let entries = Vec::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| entry.as_str().map(str::to_string))
+        .collect())
+}
+
+impl State {
+    /// Loads the state file, falling back to an empty state if it is missing
+    /// or unreadable so a corrupt file never prevents startup.
+    pub fn load() -> State {
+        fs::read_to_string(migrate::STATE.path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        self.save_to(&migrate::STATE.path())
+    }
+
+    /// `save`, to `path` in place of the state file.
+    pub fn save_to(&mut self, path: &Path) -> io::Result<()> {
+        self.version = STATE_VERSION;
+        let _critical = shutdown::critical();
+        let contents = serde_json::to_string_pretty(self)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+
+    /// Parses exported state, rejecting files written by a newer build.
+    pub fn parse_export(contents: &str) -> Result<State, String> {
+        let state: State =
+            serde_json::from_str(contents).map_err(|err| format!("Not an nts_cli state file: {}", err))?;
+        if state.version > STATE_VERSION {
+            return Err(format!(
+                "State file is version {}, but this nts_cli only understands up to version {}; update nts_cli to import it",
+                state.version, STATE_VERSION
+            ));
+        }
+        Ok(state)
+    }
+
+    /// Merges imported state into this one: favorites, follows and queued
+    /// streams are unioned, and the newer of two volume offsets wins.
+    pub fn merge(&mut self, other: State) -> MergeSummary {
+        let mut summary = MergeSummary::default();
+        for entry in other.queue {
+            if !self.queue.iter().any(|queued| queued.stream == entry.stream) {
+                self.queue.push(entry);
+                summary.queue_added += 1;
+            }
+        }
+        for favorite in other.favorites {
+            if !self.favorites.contains(&favorite) {
+                self.favorites.push(favorite);
+                summary.favorites_added += 1;
+            }
+        }
+        for show in other.followed_shows {
+            if !self.followed_shows.contains(&show) {
+                self.followed_shows.push(show);
+                summary.follows_added += 1;
+            }
+        }
+        for (show, listened) in other.show_listened {
+            let current = self.show_listened.entry(show).or_insert(listened);
+            *current = (*current).max(listened);
+        }
+        for (stream_id, offset) in other.volume_offsets {
+            let newer = self
+                .volume_offsets
+                .get(&stream_id)
+                .is_none_or(|current| offset.updated_at > current.updated_at);
+            if newer {
+                self.volume_offsets.insert(stream_id, offset);
+                summary.offsets_updated += 1;
+            }
+        }
+        summary
+    }
+}
+
+/// `nts_cli state export`: writes the state to stdout.
+pub fn run_export() -> bool {
+    let mut state = State::load();
+    state.version = STATE_VERSION;
+    state.queue_progress = None;
+    match serde_json::to_string_pretty(&state) {
+        // A closed pipe (e.g. `| head`) is the reader's choice, not an error
+        Ok(contents) => {
+            let _ = writeln!(io::stdout(), "{}", contents);
+            true
+        }
+        Err(err) => {
+            eprintln!("Could not export state: {}", err);
+            false
+        }
+    }
+}
+
+/// `nts_cli state import <file>`: merges an export into the local state and
+/// prints what changed.
+pub fn run_import(path: &str) -> bool {
+    let imported = match fs::read_to_string(path)
+        .map_err(|err| format!("Could not read {}: {}", path, err))
+        .and_then(|contents| State::parse_export(&contents))
+    {
+        Ok(imported) => imported,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+
+    let mut state = State::load();
+    let summary = state.merge(imported);
+    if let Err(err) = state.save() {
+        eprintln!("Could not save state: {}", err);
+        return false;
+    }
+    println!("Imported {}:", path);
+    println!("  {} queued streams added", summary.queue_added);
+    println!("  {} favorites added", summary.favorites_added);
+    println!("  {} followed shows added", summary.follows_added);
+    println!("  {} volume offsets updated", summary.offsets_updated);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn favorites_kept_by_id_are_dropped_without_losing_the_rest() {
+        let state: State = serde_json::from_str(
+            r#"{"favorites": [{"section": "Mixtape", "id": "poolside"}, "Slow Focus"], "followed_shows": ["breakfast"]}"#,
+        )
+        .unwrap();
+        assert_eq!(state.favorites, ["Slow Focus"]);
+        assert_eq!(state.followed_shows, ["breakfast"]);
+    }
+}
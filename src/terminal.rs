@@ -0,0 +1,54 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor,
+    event::DisableMouseCapture,
+    queue,
+    style::{Attribute, ResetColor, SetAttribute},
+    terminal::{disable_raw_mode, LeaveAlternateScreen},
+};
+
+/// Writes what leaving the TUI takes, in order: reset colors and attributes,
+/// show the cursor and stop mouse reporting while still on the alternate
+/// screen, then leave it.
+/// Some multiplexers (older tmux) carry the last drawn style over to the
+/// main screen if the reset comes after.
+pub fn write_restore_sequence(out: &mut impl Write) -> io::Result<()> {
+    queue!(
+        out,
+        ResetColor,
+        SetAttribute(Attribute::Reset),
+        cursor::Show,
+        DisableMouseCapture,
+        LeaveAlternateScreen
+    )?;
+    out.flush()
+}
+
+/// Puts the terminal back the way the shell expects it. Used on every way
+/// out of the TUI: quitting, panics and termination signals.
+pub fn restore() {
+    let _ = write_restore_sequence(&mut io::stdout());
+    let _ = disable_raw_mode();
+}
+
+/// `nts_cli --reset-terminal`: fixes up a terminal left in a bad state.
+pub fn run_reset() {
+    restore();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn style_is_reset_before_leaving_the_alternate_screen() {
+        let mut out = Vec::new();
+        write_restore_sequence(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let position = |sequence: &str| out.find(sequence).unwrap_or_else(|| panic!("{:?} missing from {:?}", sequence, out));
+        let leave = position("\x1b[?1049l");
+        assert!(position("\x1b[0m") < leave);
+        assert!(position("\x1b[?25h") < leave);
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    env,
+    error::Error,
+    io,
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::Url;
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    crypto::{ring, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+
+use crate::config::{Config, TlsConfig};
+
+/// Set to 0, turns strict TLS off for one run whatever the config says, for
+/// when NTS rotated its keys before `tls.pins` caught up.
+pub const ESCAPE_ENV: &str = "NTS_CLI_PIN_NTS_CERTS";
+/// What a failed check adds to its error, since the way past it is in the config.
+pub const PIN_HINT: &str =
+    "tls.pin_nts_certs is on: if NTS changed certificates, update tls.pins from `nts_cli doctor` or run with NTS_CLI_PIN_NTS_CERTS=0";
+/// The domains held to strict TLS, with their subdomains: the API and the streams.
+const NTS_DOMAINS: [&str; 2] = ["nts.live", "ntslive.net"];
+/// The hosts `nts_cli doctor` shakes hands with.
+pub const DOCTOR_HOSTS: [&str; 3] = ["www.nts.live", "stream-mixtape-geo.ntslive.net", "stream-mixtape.ntslive.net"];
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const PIN_PREFIX: &str = "sha256/";
+
+type Pin = [u8; 32];
+
+static STRICT: OnceLock<Option<ClientConfig>> = OnceLock::new();
+
+impl TlsConfig {
+    /// Whether NTS is held to strict TLS this run.
+    pub fn enabled(&self) -> bool {
+        self.pin_nts_certs && !env::var(ESCAPE_ENV).is_ok_and(|value| value == "0")
+    }
+
+    /// The pins that are well formed, and a warning for each one that isn't.
+    fn parsed_pins(&self) -> (Vec<Pin>, Vec<String>) {
+        let mut pins = Vec::new();
+        let mut warnings = Vec::new();
+        for pin in &self.pins {
+            match parse_pin(pin) {
+                Some(parsed) => pins.push(parsed),
+                None => warnings.push(format!(
+                    "tls.pins: '{}' is not {}<base64 of a SHA-256>, leaving it out",
+                    pin, PIN_PREFIX
+                )),
+            }
+        }
+        (pins, warnings)
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        self.parsed_pins().1
+    }
+
+    /// One line for the doctor.
+    pub fn describe(&self) -> String {
+        let pins = self.parsed_pins().0.len();
+        match (self.pin_nts_certs, self.enabled()) {
+            (false, _) => "off".to_string(),
+            (true, false) => format!("off for this run ({}=0)", ESCAPE_ENV),
+            (true, true) if pins == 0 => "on, checking the chain and name only (no tls.pins)".to_string(),
+            (true, true) => format!("on, {} pins", pins),
+        }
+    }
+}
+
+fn parse_pin(pin: &str) -> Option<Pin> {
+    let encoded = pin.trim().strip_prefix(PIN_PREFIX)?;
+    STANDARD.decode(encoded).ok()?.try_into().ok()
+}
+
+fn format_pin(pin: &Pin) -> String {
+    format!("{}{}", PIN_PREFIX, STANDARD.encode(pin))
+}
+
+/// Whether `host` is nts.live or one of the stream hosts.
+pub fn is_nts_host(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    NTS_DOMAINS.iter().any(|domain| {
+        host == *domain || host.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.'))
+    })
+}
+
+/// Whether `url` is on an NTS host.
+pub fn is_nts_url(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(is_nts_host))
+        .unwrap_or(false)
+}
+
+/// The rustls config to reach `url` with, when it is an NTS host and
+/// `[tls]` asks for strict TLS. Read from the config on first use.
+pub fn strict_config_for(url: &str) -> Option<ClientConfig> {
+    let strict = STRICT.get_or_init(|| {
+        let config = Config::load().map(|checked| checked.config).unwrap_or_default();
+        config.tls.enabled().then(|| client_config(config.tls.parsed_pins().0))
+    });
+    strict.as_ref().filter(|_| is_nts_url(url)).cloned()
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// TLS 1.2 or later, checked against the roots built into nts_cli rather
+/// than the system's, so a CA added on this machine can't stand in for NTS.
+fn client_config(pins: Vec<Pin>) -> ClientConfig {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider())
+        .build()
+        .expect("the built-in roots are not empty");
+    ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default versions")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedVerifier { webpki, pins }))
+        .with_no_client_auth()
+}
+
+/// The usual chain and name checks, then for an NTS host with pins, that a
+/// key somewhere in the chain is pinned. Pinning the root or intermediate
+/// survives NTS renewing its own certificate.
+#[derive(Debug)]
+struct PinnedVerifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    pins: Vec<Pin>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self
+            .webpki
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        let host = server_name.to_str();
+        if self.pins.is_empty() || !is_nts_host(&host) {
+            return Ok(verified);
+        }
+        let chain = chain_keys(end_entity, intermediates);
+        if chain.iter().any(|(_, key)| self.pins.contains(key)) {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::General(format!("no key in the certificate chain of {} is in tls.pins", host)))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.webpki.supported_verify_schemes()
+    }
+}
+
+/// The SHA-256 of each public key in the chain, from the server's own to
+/// the built-in root it chains to.
+fn chain_keys(end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>]) -> Vec<(&'static str, Pin)> {
+    let mut keys = Vec::new();
+    let mut issuer = None;
+    for (i, cert) in std::iter::once(end_entity).chain(intermediates).enumerate() {
+        let Ok(parsed) = webpki::EndEntityCert::try_from(cert) else {
+            continue;
+        };
+        let role = if i == 0 { "leaf" } else { "intermediate" };
+        keys.push((role, hash(parsed.subject_public_key_info().as_ref())));
+        issuer = Some(parsed.issuer().to_vec());
+    }
+    let root = webpki_roots::TLS_SERVER_ROOTS
+        .iter()
+        .find(|anchor| issuer.as_deref() == Some(anchor.subject.as_ref()));
+    if let Some(root) = root {
+        keys.push(("root", hash(&der_sequence(root.subject_public_key_info.as_ref()))));
+    }
+    keys
+}
+
+fn hash(bytes: &[u8]) -> Pin {
+    Sha256::digest(bytes).into()
+}
+
+/// `contents` wrapped in a DER SEQUENCE, as a trust anchor's key is stored
+/// without it.
+fn der_sequence(contents: &[u8]) -> Vec<u8> {
+    let mut der = vec![0x30];
+    let len = contents.len();
+    if len < 0x80 {
+        der.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|byte| *byte == 0).collect();
+        der.push(0x80 | bytes.len() as u8);
+        der.extend(bytes);
+    }
+    der.extend_from_slice(contents);
+    der
+}
+
+/// Why a request failed its TLS checks, if that is why: the rustls error,
+/// which reqwest keeps a few sources down.
+pub fn failure(err: &reqwest::Error) -> Option<String> {
+    let mut source = err.source();
+    while let Some(err) = source {
+        let tls = err.downcast_ref::<rustls::Error>().or_else(|| {
+            err.downcast_ref::<io::Error>()
+                .and_then(io::Error::get_ref)
+                .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        });
+        if let Some(tls) = tls {
+            return Some(tls.to_string());
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// What the doctor reports about one host.
+pub struct Handshake {
+    pub version: String,
+    pub cipher_suite: String,
+    keys: Vec<(&'static str, Pin)>,
+    /// Whether a key in the chain is in `tls.pins`, when there are any.
+    pub pinned: Option<bool>,
+}
+
+impl Handshake {
+    pub fn summary(&self) -> String {
+        let pins = match self.pinned {
+            None => "no tls.pins to check",
+            Some(true) => "a key is pinned",
+            Some(false) => "no key is in tls.pins",
+        };
+        format!("{}, {}, chain and name ok, {}", self.version, self.cipher_suite, pins)
+    }
+
+    /// The pins to put in `tls.pins`, leaf first.
+    pub fn keys(&self) -> String {
+        self.keys
+            .iter()
+            .map(|(role, key)| format!("{} ({})", format_pin(key), role))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Shakes hands with `host` the way strict TLS would, whether or not it is
+/// on, and reports what was negotiated.
+pub fn handshake(host: &str, settings: &TlsConfig) -> Result<Handshake, String> {
+    let name = ServerName::try_from(host.to_string()).map_err(|err| err.to_string())?;
+    let mut connection = ClientConnection::new(Arc::new(client_config(Vec::new())), name)
+        .map_err(|err| err.to_string())?;
+    let address = (host, 443)
+        .to_socket_addrs()
+        .map_err(|err| format!("Could not resolve: {}", err))?
+        .next()
+        .ok_or("Could not resolve: no addresses")?;
+    let mut socket = TcpStream::connect_timeout(&address, HANDSHAKE_TIMEOUT)
+        .map_err(|err| format!("Could not connect: {}", err))?;
+    socket
+        .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+        .and_then(|_| socket.set_write_timeout(Some(HANDSHAKE_TIMEOUT)))
+        .map_err(|err| err.to_string())?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut socket).map_err(|err| format!("Handshake failed: {}", err))?;
+    }
+    let chain = connection.peer_certificates().unwrap_or_default();
+    let keys = match chain.split_first() {
+        Some((end_entity, intermediates)) => chain_keys(end_entity, intermediates),
+        None => Vec::new(),
+    };
+    let pins = settings.parsed_pins().0;
+    Ok(Handshake {
+        version: connection
+            .protocol_version()
+            .map_or("unknown".to_string(), |version| format!("{:?}", version)),
+        cipher_suite: connection
+            .negotiated_cipher_suite()
+            .map_or("unknown".to_string(), |suite| format!("{:?}", suite.suite())),
+        pinned: (!pins.is_empty()).then(|| keys.iter().any(|(_, key)| pins.contains(key))),
+        keys,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_nts_and_its_subdomains_are_strict() {
+        assert!(is_nts_host("nts.live"));
+        assert!(is_nts_host("www.nts.live"));
+        assert!(is_nts_host("stream-mixtape-geo.ntslive.net."));
+        assert!(is_nts_host("Stream-Relay-Geo.NTSLive.net"));
+        assert!(!is_nts_host("notnts.live"));
+        assert!(!is_nts_host("nts.live.example.com"));
+        assert!(is_nts_url("https://www.nts.live/api/v2/live"));
+        assert!(!is_nts_url("https://radio.example.com/stream"));
+        assert!(!is_nts_url("not a url"));
+    }
+
+    #[test]
+    fn pins_are_hashes_of_the_whole_key() {
+        let key = [0xabu8; 32];
+        let pin = format_pin(&key);
+        let config = TlsConfig {
+            pin_nts_certs: true,
+            pins: vec![pin.clone(), "sha1/abc".to_string(), "sha256/c2hvcnQ=".to_string()],
+        };
+        let (pins, warnings) = config.parsed_pins();
+        assert_eq!(pins, [key]);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("tls.pins: 'sha1/abc'"));
+        assert_eq!(parse_pin(&pin), Some(key));
+
+        // A trust anchor's key is stored without its SEQUENCE header
+        assert_eq!(der_sequence(&[1, 2]), [0x30, 2, 1, 2]);
+        let long = der_sequence(&[0; 300]);
+        assert_eq!(long[..4], [0x30, 0x82, 0x01, 0x2c]);
+        assert_eq!(long.len(), 304);
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::pcm_tap::PcmTap;
+
+const FFT_SIZE: usize = 4096;
+const BANDS: usize = 24;
+const LOWEST_BAND_HZ: f32 = 60.0;
+const HIGHEST_BAND_HZ: f32 = 12_000.0;
+/// How quickly the short-term fingerprint follows the audio, per update.
+const RECENT_SMOOTHING: f32 = 0.3;
+/// How quickly the reference drifts with slow changes inside a track.
+const REFERENCE_SMOOTHING: f32 = 0.02;
+/// Cosine distance between the two fingerprints that counts as a shift.
+const CHANGE_THRESHOLD: f32 = 0.15;
+/// How long a shift has to hold before it is taken as a new track.
+const SUSTAIN: Duration = Duration::from_secs(20);
+
+/// Guesses track boundaries from a coarse spectral fingerprint of the playing
+/// audio: a short-term fingerprint is compared with a slowly moving reference
+/// and a change is reported once they have differed for [`SUSTAIN`].
+pub struct TrackChangeDetector {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    recent: Option<Vec<f32>>,
+    reference: Option<Vec<f32>>,
+    shifted_since: Option<Instant>,
+}
+
+impl TrackChangeDetector {
+    pub fn new() -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        TrackChangeDetector {
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            window,
+            recent: None,
+            reference: None,
+            shifted_since: None,
+        }
+    }
+
+    /// Forgets the current track, e.g. after switching streams.
+    pub fn reset(&mut self) {
+        self.recent = None;
+        self.reference = None;
+        self.shifted_since = None;
+    }
+
+    /// Takes the latest audio from the tap and returns true once a new track
+    /// has most likely started. Meant to be called about once a second.
+    pub fn update(&mut self, tap: &PcmTap) -> bool {
+        let Some((samples, sample_rate)) = tap.latest_mono(FFT_SIZE) else {
+            return false;
+        };
+        self.observe(&samples, sample_rate, Instant::now())
+    }
+
+    fn observe(&mut self, samples: &[f32], sample_rate: u32, now: Instant) -> bool {
+        let Some(fingerprint) = self.fingerprint(samples, sample_rate) else {
+            return false;
+        };
+
+        let recent = match self.recent.take() {
+            Some(recent) => blend(&recent, &fingerprint, RECENT_SMOOTHING),
+            None => fingerprint,
+        };
+        let Some(reference) = self.reference.take() else {
+            self.reference = Some(recent.clone());
+            self.recent = Some(recent);
+            return false;
+        };
+
+        let shifted = cosine_distance(&recent, &reference) > CHANGE_THRESHOLD;
+        let mut changed = false;
+        self.reference = Some(if !shifted {
+            self.shifted_since = None;
+            blend(&reference, &recent, REFERENCE_SMOOTHING)
+        } else {
+            let since = *self.shifted_since.get_or_insert(now);
+            if now.saturating_duration_since(since) >= SUSTAIN {
+                self.shifted_since = None;
+                changed = true;
+                recent.clone()
+            } else {
+                reference
+            }
+        });
+        self.recent = Some(recent);
+        changed
+    }
+
+    /// Log-energy in log-spaced bands, normalized to unit length. None for
+    /// silence, which says nothing about the track. Bands above the Nyquist
+    /// frequency, which low sample rates have, stay empty.
+    fn fingerprint(&self, samples: &[f32], sample_rate: u32) -> Option<Vec<f32>> {
+        let mut spectrum: Vec<Complex<f32>> = samples
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        self.fft.process(&mut spectrum);
+
+        let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+        let ratio = (HIGHEST_BAND_HZ / LOWEST_BAND_HZ).powf(1.0 / BANDS as f32);
+        let mut bands = vec![0.0f32; BANDS];
+        for (band, energy) in bands.iter_mut().enumerate() {
+            let low = LOWEST_BAND_HZ * ratio.powi(band as i32);
+            let high = low * ratio;
+            let first = ((low / bin_hz) as usize).max(1);
+            if first >= FFT_SIZE / 2 {
+                continue;
+            }
+            let last = ((high / bin_hz) as usize).clamp(first + 1, FFT_SIZE / 2);
+            let sum: f32 = spectrum[first..last].iter().map(Complex::norm_sqr).sum();
+            *energy = (1.0 + sum / (last - first) as f32).ln();
+        }
+
+        let length = bands.iter().map(|band| band * band).sum::<f32>().sqrt();
+        if length < f32::EPSILON {
+            return None;
+        }
+        Some(bands.into_iter().map(|band| band / length).collect())
+    }
+}
+
+fn blend(from: &[f32], towards: &[f32], amount: f32) -> Vec<f32> {
+    from.iter()
+        .zip(towards)
+        .map(|(a, b)| a + (b - a) * amount)
+        .collect()
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms < f32::EPSILON {
+        0.0
+    } else {
+        1.0 - dot / norms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequencies: &[f32], sample_rate: u32) -> Vec<f32> {
+        (0..FFT_SIZE)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                frequencies.iter().map(|hz| (2.0 * std::f32::consts::PI * hz * t).sin()).sum::<f32>() * 0.2
+            })
+            .collect()
+    }
+
+    #[test]
+    fn low_sample_rates_leave_the_high_bands_empty() {
+        let detector = TrackChangeDetector::new();
+        for sample_rate in [8_000, 11_025, 16_000, 19_000] {
+            let fingerprint = detector.fingerprint(&tone(&[440.0], sample_rate), sample_rate).unwrap();
+            assert_eq!(fingerprint.len(), BANDS);
+            assert_eq!(*fingerprint.last().unwrap(), 0.0);
+        }
+    }
+
+    #[test]
+    fn a_new_track_is_reported_once_the_change_holds() {
+        let mut detector = TrackChangeDetector::new();
+        let start = Instant::now();
+        let bass = tone(&[80.0, 160.0], 44_100);
+        let hats = tone(&[6_000.0, 9_000.0], 44_100);
+        let at = |secs| start + Duration::from_secs(secs);
+        for secs in 0..30 {
+            assert!(!detector.observe(&bass, 44_100, at(secs)));
+        }
+        let changed: Vec<u64> = (30..80).filter(|&secs| detector.observe(&hats, 44_100, at(secs))).collect();
+        assert_eq!(changed.len(), 1, "{:?}", changed);
+        assert!(changed[0] >= 30 + SUSTAIN.as_secs());
+    }
+}
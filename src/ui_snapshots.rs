@@ -0,0 +1,1002 @@
+//! Snapshot tests for the TUI layout, rendered into a `TestBackend` from fixed
+//! fixture data and a fixed clock so the output is the same on every run.
+
+use std::{
+    cell::RefCell,
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local, TimeZone};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{backend::TestBackend, style::Color, Terminal};
+
+use crate::{
+    archive,
+    config::{Background, Config, GuardMode, ResampleQuality, TimestampStyle},
+    history,
+    history_store::HistoryStore,
+    id_schedule::Trigger,
+    migrate,
+    recognition::PendingMatch,
+    resample::Conversion,
+    share,
+    state::State,
+    Airtime, Radio, Stream, StreamRef, StreamType, StreamsCollection, StreamsLoad, Theme, UIMessage, UpcomingShow,
+    HISTORY_REFRESH_INTERVAL,
+};
+
+const SIZES: [(u16, u16); 3] = [(80, 24), (120, 40), (60, 20)];
+
+const HISTORY: &str = "\
+2024-05-31 22:10  Alice Coltrane - Journey in Satchidananda
+2024-05-31 22:41  Pharoah Sanders - The Creator Has a Master Plan
+2024-06-01 13:05  Laurel Halo - Jelly
+2024-06-01 13:52  Actress - Hubble
+2024-06-01 14:20  Carl Craig - At Les
+";
+
+thread_local! {
+    /// What the fixture's radio copied, in place of the terminal's clipboard.
+    static COPIED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn copy_to_list(text: &str) -> io::Result<()> {
+    COPIED.with(|copied| copied.borrow_mut().push(text.to_string()));
+    Ok(())
+}
+
+fn copied() -> Vec<String> {
+    COPIED.with(|copied| copied.borrow().clone())
+}
+
+fn fixed_clock() -> DateTime<Local> {
+    Local.with_ymd_and_hms(2024, 6, 1, 14, 32, 0).unwrap()
+}
+
+fn stream(id: &str, title: &str, subtitle: &str, description: &str) -> Stream {
+    Stream {
+        id: id.to_string(),
+        title: title.to_string(),
+        subtitle: subtitle.to_string(),
+        description: description.to_string(),
+        audio_stream_endpoint: String::new(),
+        show_alias: String::new(),
+        schedule: Vec::new(),
+        airtime: None,
+    }
+}
+
+/// A copy of `HISTORY` for one fixture, so what a test does to the history
+/// never reaches the real file. The directory is left to the system.
+/// A path of its own for each fixture under a shared temp dir, so no test
+/// writes where the user's files are.
+fn temp_path(name: &str) -> PathBuf {
+    static DIR: OnceLock<tempfile::TempDir> = OnceLock::new();
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = DIR.get_or_init(|| tempfile::tempdir().unwrap());
+    dir.path().join(format!("{}-{}", NEXT.fetch_add(1, Ordering::Relaxed), name))
+}
+
+fn history_file() -> PathBuf {
+    let path = temp_path("history.txt");
+    fs::write(&path, HISTORY).unwrap();
+    path
+}
+
+fn fixture() -> Radio {
+    let mut station = stream("1", "NTS 1", "Breakfast Show", "Live from London.");
+    station.airtime = Some(Airtime {
+        start: Local.with_ymd_and_hms(2024, 6, 1, 13, 0, 0).unwrap(),
+        end: Some(Local.with_ymd_and_hms(2024, 6, 1, 15, 0, 0).unwrap()),
+    });
+    station.schedule = vec![
+        UpcomingShow {
+            title: "Lunch Show".to_string(),
+            start: Local.with_ymd_and_hms(2024, 6, 1, 15, 0, 0).unwrap(),
+            end: Some(Local.with_ymd_and_hms(2024, 6, 1, 17, 0, 0).unwrap()),
+        },
+        UpcomingShow {
+            title: "Afternoon Tea".to_string(),
+            start: Local.with_ymd_and_hms(2024, 6, 1, 17, 0, 0).unwrap(),
+            end: None,
+        },
+    ];
+    let collection = StreamsCollection {
+        stations: vec![
+            station,
+            stream("2", "NTS 2", "Late Junction", "Live from Manchester."),
+        ],
+        mixtapes: vec![
+            stream("poolside", "Poolside", "Sun-drenched sounds", "Balearic, boogie and beyond."),
+            stream("slow-focus", "Slow Focus", "Ambient and drone", "For deep work."),
+            stream("memory-lane", "Memory Lane", "Lost classics", "Golden-era selections."),
+        ],
+        ..Default::default()
+    };
+    let (ui_tx, _ui_rx) = mpsc::channel();
+    let mut radio = Radio::from_parts(
+        ui_tx,
+        Config::default(),
+        collection,
+        HISTORY.to_string(),
+        State::default(),
+    );
+    radio.clock = fixed_clock;
+    radio.copy = copy_to_list;
+    COPIED.with(|copied| copied.borrow_mut().clear());
+    radio.history_path = history_file();
+    radio.state_path = temp_path("state.json");
+    radio.listening_log = temp_path("listening.log");
+    radio
+}
+
+fn render(radio: &mut Radio, width: u16, height: u16) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    radio.render_ui(&mut terminal).unwrap();
+    terminal.backend().to_string()
+}
+
+fn assert_sizes(name: &str, radio: &mut Radio) {
+    for (width, height) in SIZES {
+        insta::assert_snapshot!(format!("{}_{}x{}", name, width, height), render(radio, width, height));
+    }
+}
+
+fn start_playing(radio: &mut Radio) {
+    radio.playing_stream = Some(StreamRef {
+        section: StreamType::Station,
+        id: "1".to_string(),
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn nothing_playing() {
+    assert_sizes("nothing_playing", &mut fixture());
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn playing_with_announcement() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    radio.announce_show_change("NTS 1", "Lunch Show");
+    assert_sizes("playing_with_announcement", &mut radio);
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn playback_error() {
+    let mut radio = fixture();
+    radio.show_playback_error("NTS 2", "Could not connect within 10s");
+    radio.show_status_log = true;
+    assert_sizes("playback_error", &mut radio);
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn small_terminal() {
+    insta::assert_snapshot!("small_terminal_30x8", render(&mut fixture(), 30, 8));
+}
+
+#[test]
+fn tab_switches_keep_each_view_in_place() {
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    let mut radio = fixture();
+    let press = |radio: &mut Radio, code| radio.handle_key_press(KeyEvent::from(code)).unwrap();
+    press(&mut radio, KeyCode::Down);
+    press(&mut radio, KeyCode::Down);
+    press(&mut radio, KeyCode::Char('J'));
+    press(&mut radio, KeyCode::Tab);
+    press(&mut radio, KeyCode::Char('k'));
+    press(&mut radio, KeyCode::Char('k'));
+    let before = render(&mut radio, 80, 24);
+    let (streams, history) = (radio.views.streams.clone(), radio.views.history.clone());
+
+    for _ in 0..4 {
+        press(&mut radio, KeyCode::Tab);
+        render(&mut radio, 80, 24);
+    }
+    assert_eq!(radio.views.streams, streams);
+    assert_eq!(radio.views.history, history);
+    assert_eq!(render(&mut radio, 80, 24), before);
+    assert_eq!(streams.selection.unwrap().id, "poolside");
+    assert_eq!(streams.scroll, 1);
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn info_toast() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    radio.show_info("Copied Carl Craig - At Les".to_string());
+    let screen = render(&mut radio, 80, 24);
+    assert!(screen.contains("Copied Carl Craig - At Les"));
+    insta::assert_snapshot!("info_toast_80x24", screen);
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn custom_urls_section() {
+    let mut radio = fixture();
+    radio.state.recent_custom_urls = vec!["https://radio.example.com/live.mp3".to_string()];
+    radio.sync_custom_streams();
+    insta::assert_snapshot!("custom_urls_section_80x24", render(&mut radio, 80, 24));
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn streams_failed_to_load() {
+    let (ui_tx, _ui_rx) = mpsc::channel();
+    let mut radio = Radio::from_parts(
+        ui_tx,
+        Config::default(),
+        StreamsCollection::default(),
+        HISTORY.to_string(),
+        State::default(),
+    );
+    radio.clock = fixed_clock;
+    // As Radio::new leaves it, then a retry that failed as well
+    radio.streams_load = StreamsLoad::Failed;
+    radio.update_collection(Err("error sending request".to_string()));
+    assert_eq!(radio.streams_load, StreamsLoad::Failed);
+    insta::assert_snapshot!("streams_failed_to_load_80x24", render(&mut radio, 80, 24));
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn long_wide_show_title_is_cut_before_the_marker() {
+    let mut radio = fixture();
+    radio.streams_collection.stations[1].subtitle = "坂本龍一 Radio Sakamoto w/ Guests: a very long broadcast title".to_string();
+    radio.views.streams.selection = Some(StreamRef {
+        section: StreamType::Station,
+        id: "2".to_string(),
+    });
+    let screen = render(&mut radio, 60, 20);
+    assert!(screen.contains("NTS 2 · 坂本龍一 Radio Sakamoto w/ Guests: a very lo… •"), "{}", screen);
+    insta::assert_snapshot!("long_wide_show_title_60x20", screen);
+}
+
+#[test]
+fn guarded_stop_shows_a_hint_first() {
+    let mut radio = fixture();
+    radio.config.guard.stop = GuardMode::Confirm;
+    start_playing(&mut radio);
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char(' '))).unwrap();
+    assert!(radio.playing_stream.is_some());
+    assert!(render(&mut radio, 80, 24).contains("Press Space again to stop"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char(' '))).unwrap();
+    assert!(radio.playing_stream.is_none());
+}
+
+#[test]
+fn incognito_keeps_recognitions_in_the_session() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    radio.listening_since = Some(("NTS 1".to_string(), fixed_clock() - chrono::Duration::minutes(30)));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('i'))).unwrap();
+    assert!(radio.listening_since.is_none());
+    assert!(render(&mut radio, 80, 24).contains("INCOGNITO"));
+    // What played before is logged, and nothing while incognito
+    let log = radio.listening_log.clone();
+    let logged = || fs::read_to_string(&log).unwrap();
+    assert_eq!(logged(), "2024-06-01 14:02:00\t2024-06-01 14:32:00\tNTS 1\n");
+    radio.end_listening();
+    assert_eq!(logged().lines().count(), 1);
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('i'))).unwrap();
+    assert!(radio.listening_since.is_some());
+    assert!(!render(&mut radio, 80, 24).contains("INCOGNITO"));
+
+    // A run goes by what incognito was when it started, toggled or not since
+    let finish = |radio: &mut Radio, write_history: bool| {
+        let (id, _) = radio.begin_recognition(Trigger::Manual, Duration::from_secs(10)).unwrap();
+        radio.handle_key_press(KeyEvent::from(KeyCode::Char('i'))).unwrap();
+        let outcome = crate::recognition::RecognitionOutcome {
+            text: "Laurel Halo - Jelly".to_string(),
+            track: Some("Laurel Halo - Jelly".to_string()),
+            saved_sample: None,
+            write_history,
+            history_line: None,
+        };
+        radio.recognition_result_tx.send((id, Ok(outcome))).unwrap();
+        radio.handle_recognition_result();
+    };
+    finish(&mut radio, true);
+    assert!(radio.incognito && radio.incognito_entries.is_empty());
+    finish(&mut radio, false);
+    assert!(!radio.incognito && radio.incognito_entries.contains("Laurel Halo - Jelly"));
+}
+
+#[test]
+fn filter_narrows_the_mixtapes_without_touching_the_collection() {
+    let mut radio = fixture();
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('/'))).unwrap();
+    for c in "DRONE".chars() {
+        radio.handle_key_press(KeyEvent::from(KeyCode::Char(c))).unwrap();
+    }
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("slow-focus"));
+    let screen = render(&mut radio, 80, 24);
+    assert!(screen.contains("Slow Focus") && !screen.contains("Poolside"));
+
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('x'))).unwrap();
+    assert!(render(&mut radio, 80, 24).contains("no results"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Down)).unwrap();
+    assert_eq!(radio.streams_collection.mixtapes.len(), 3);
+
+    radio.handle_key_press(KeyEvent::from(KeyCode::Esc)).unwrap();
+    assert!(radio.prompt.is_none());
+    assert_eq!(radio.stream_view.len(), 5);
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("slow-focus"));
+}
+
+#[test]
+fn clicking_a_stream_selects_it_and_scrolling_moves_the_history() {
+    let mut radio = fixture();
+    let screen = render(&mut radio, 80, 24);
+    let mouse = |kind, column, row| MouseEvent {
+        kind,
+        column,
+        row,
+        modifiers: KeyModifiers::NONE,
+    };
+    let (row, line) = screen.lines().enumerate().find(|(_, line)| line.contains("Slow Focus")).unwrap();
+    let column = line.find("Slow Focus").unwrap() as u16;
+    radio.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), column, row as u16));
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("slow-focus"));
+
+    // Titles and the space below the rows aren't streams
+    radio.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), column, row as u16 - 2));
+    radio.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), column, row as u16 + 2));
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("slow-focus"));
+
+    let scroll = radio.views.history.scroll;
+    radio.handle_mouse(mouse(MouseEventKind::ScrollDown, 10, 15));
+    assert_eq!(radio.views.history.scroll, scroll + 1);
+    radio.handle_mouse(mouse(MouseEventKind::ScrollDown, 10, 2));
+    assert_eq!(radio.views.history.scroll, scroll + 1);
+}
+
+#[test]
+fn failed_section_says_so_while_the_other_works() {
+    let mut radio = fixture();
+    radio.streams_collection.stations.clear();
+    radio.streams_collection.stations_error = Some("timed out".to_string());
+    radio.rebuild_stream_view();
+    let screen = render(&mut radio, 80, 24);
+    assert!(screen.contains("Stations unavailable — retrying"), "{}", screen);
+    assert!(screen.contains("Poolside"));
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("poolside"));
+
+    radio.retrying_sections.push(StreamType::Station);
+    let (ui_tx, _ui_rx) = mpsc::channel();
+    radio.ui_tx = ui_tx;
+    radio.handle_section_fetched(StreamType::Station, Ok(vec![stream("1", "NTS 1", "Back Again", "")]));
+    assert!(radio.streams_collection.stations_error.is_none());
+    assert!(radio.retrying_sections.is_empty());
+    assert!(render(&mut radio, 80, 24).contains("NTS 1 · Back Again"));
+}
+
+#[test]
+fn help_lists_the_keymap_and_any_key_closes_it() {
+    let mut radio = fixture();
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('?'))).unwrap();
+    let screen = render(&mut radio, 120, 60);
+    for heading in ["Playback", "Navigation", "Recognition"] {
+        assert!(screen.contains(heading), "{}", screen);
+    }
+    assert!(screen.contains("Filter the mixtapes"));
+
+    // The key that closes it does nothing else
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('/'))).unwrap();
+    assert!(radio.prompt.is_none());
+    assert!(!render(&mut radio, 120, 60).contains("Any key: close"));
+}
+
+#[test]
+fn debug_details_follow_the_description_only_when_asked() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    assert!(!render(&mut radio, 120, 40).contains("Endpoint:"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::F(12))).unwrap();
+    assert!(render(&mut radio, 120, 40).contains("Endpoint: not connected"));
+}
+
+#[test]
+fn the_idle_screen_keeps_the_selection_described() {
+    let mut radio = fixture();
+    radio.views.streams.selection = Some(StreamRef {
+        section: StreamType::Mixtape,
+        id: "poolside".to_string(),
+    });
+    let screen = render(&mut radio, 120, 40);
+    assert!(screen.contains("press 1 or 2 to tune in") && screen.contains("Balearic, boogie and beyond."));
+
+    radio.views.streams.selection = None;
+    let screen = render(&mut radio, 120, 40);
+    assert!(screen.contains("NTS 2: Late Junction") && !screen.contains("Balearic"));
+}
+
+#[test]
+fn show_gauge_follows_the_selected_station_and_stays_in_range() {
+    let mut radio = fixture();
+    assert!(render(&mut radio, 80, 24).contains("NTS 1: 1:32 / 2:00"));
+    // A clock behind the API's shows an empty gauge rather than a negative one
+    radio.clock = || Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+    assert!(render(&mut radio, 80, 24).contains("NTS 1: 0:00 / 2:00"));
+    radio.clock = || Local.with_ymd_and_hms(2024, 6, 1, 16, 0, 0).unwrap();
+    assert!(render(&mut radio, 80, 24).contains("NTS 1: 2:00 / 2:00"));
+
+    radio.handle_key_press(KeyEvent::from(KeyCode::Down)).unwrap();
+    radio.handle_key_press(KeyEvent::from(KeyCode::Down)).unwrap();
+    assert!(!render(&mut radio, 80, 24).contains("/ 2:00"));
+}
+
+#[test]
+fn info_counts_the_recognitions_waiting_for_the_network() {
+    let mut radio = fixture();
+    radio.pending_ids = 2;
+    assert!(render(&mut radio, 120, 60).contains("2 IDs pending"));
+    // Still offline: the retry matched nothing and one expired
+    radio.retrying_pending_ids = true;
+    radio.handle_pending_ids_retried(Vec::new(), 1);
+    assert!(!radio.retrying_pending_ids);
+    assert!(render(&mut radio, 120, 60).contains("1 ID pending"));
+}
+
+#[test]
+fn ctrl_l_and_a_same_size_resize_repaint_without_closing_anything() {
+    let mut radio = fixture();
+    render(&mut radio, 80, 24);
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('?'))).unwrap();
+    radio.handle_key_press(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL)).unwrap();
+    assert!(radio.clear_screen && radio.show_help && !radio.show_status_log);
+
+    radio.clear_screen = false;
+    radio.handle_resize(100, 30);
+    assert!(!radio.clear_screen);
+    radio.handle_resize(80, 24);
+    assert!(radio.clear_screen);
+}
+
+#[test]
+#[cfg_attr(not(feature = "recognition"), ignore = "the snapshots show the keys of the default build")]
+fn archive_searches_its_episodes_and_greys_out_mixcloud_only_ones() {
+    let mut radio = fixture();
+    let mut archive = archive::Archive::new("breakfast".to_string(), "Breakfast Show".to_string());
+    archive.loading = true;
+    radio.archive = Some(archive);
+    let episode = |alias: &str, name: &str, broadcast, audio_url: Option<&str>| archive::Episode {
+        alias: alias.to_string(),
+        name: name.to_string(),
+        description: String::new(),
+        broadcast,
+        audio_url: audio_url.map(str::to_string),
+        mixcloud: Some(format!("https://www.mixcloud.com/NTSRadio/{}/", alias)),
+    };
+    let page = archive::Page {
+        episodes: vec![
+            episode("b-31", "Breakfast w/ Guest", 1_717_142_400, None),
+            episode("b-30", "Breakfast Show", 1_717_056_000, Some("https://example.com/b-30.mp3")),
+        ],
+        total: 2,
+    };
+    // A page for another show, opened before, is dropped
+    radio.handle_archive_page("other", 0, Ok(archive::Page { episodes: Vec::new(), total: 0 }));
+    radio.handle_archive_page("breakfast", 0, Ok(page));
+    insta::assert_snapshot!("archive_80x24", render(&mut radio, 80, 24));
+
+    radio.handle_key_press(KeyEvent::from(KeyCode::Enter)).unwrap();
+    assert!(radio.archive.is_some());
+    assert_eq!(
+        radio.recognition_result.as_deref(),
+        Some("Only on Mixcloud: https://www.mixcloud.com/NTSRadio/b-31/")
+    );
+
+    for key in [KeyCode::Char('/'), KeyCode::Char('0'), KeyCode::Char('5'), KeyCode::Char('-'), KeyCode::Char('3'), KeyCode::Char('0')] {
+        radio.handle_key_press(KeyEvent::from(key)).unwrap();
+    }
+    let screen = render(&mut radio, 80, 24);
+    assert!(screen.contains("Search: 05-30▏"), "{}", screen);
+    assert!(!screen.contains("Guest"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Esc)).unwrap();
+    radio.handle_key_press(KeyEvent::from(KeyCode::Esc)).unwrap();
+    assert!(radio.archive.is_none());
+}
+
+#[test]
+fn played_archive_episodes_outlive_a_refresh() {
+    let mut radio = fixture();
+    let fetched = radio.streams_collection.clone();
+    radio.streams_collection.archive = vec![stream("b-30", "Breakfast Show", "30.05.24", "")];
+    radio.update_collection(Ok(fetched));
+    assert_eq!(radio.streams_collection.archive.len(), 1);
+    assert!(render(&mut radio, 80, 24).contains("Breakfast Show"));
+}
+
+#[test]
+fn favorites_lead_the_mixtapes_and_outlive_a_refresh() {
+    let mut radio = fixture();
+    let mixtape = |id: &str| StreamRef {
+        section: StreamType::Mixtape,
+        id: id.to_string(),
+    };
+    // One that has left the API stays on file without being listed
+    radio.state.favorites = vec!["Memory Lane".to_string(), "Gone".to_string()];
+    radio.rebuild_stream_view();
+    let listed = |radio: &mut Radio| {
+        let screen = render(radio, 80, 24);
+        let starred = screen.lines().find(|line| line.contains("Memory Lane"));
+        assert!(starred.is_some_and(|line| line.contains('★')));
+        ["Memory Lane", "Poolside", "Slow Focus"].map(|title| screen.find(title).expect(title))
+    };
+    let [memory_lane, poolside, slow_focus] = listed(&mut radio);
+    assert!(memory_lane < poolside && poolside < slow_focus);
+
+    let collection = radio.streams_collection.clone();
+    radio.update_collection(Ok(collection));
+    let [memory_lane, poolside, _] = listed(&mut radio);
+    assert!(memory_lane < poolside);
+    assert_eq!(radio.state.favorites.len(), 2);
+
+    // Moving down from the last station lands on the favorite
+    radio.views.streams.selection = Some(StreamRef {
+        section: StreamType::Station,
+        id: "2".to_string(),
+    });
+    radio.handle_key_press(KeyEvent::from(KeyCode::Down)).unwrap();
+    assert_eq!(radio.views.streams.selection, Some(mixtape("memory-lane")));
+
+    // Listed under a new alias, it is the same favorite
+    let mut collection = radio.streams_collection.clone();
+    collection.mixtapes[2].id = "memory-lane-2".to_string();
+    radio.update_collection(Ok(collection));
+    let [memory_lane, poolside, _] = listed(&mut radio);
+    assert!(memory_lane < poolside);
+
+    // f goes by title too
+    radio.views.streams.selection = Some(mixtape("slow-focus"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('f'))).unwrap();
+    assert_eq!(radio.state.favorites, ["Memory Lane", "Gone", "Slow Focus"]);
+    radio.views.streams.selection = Some(mixtape("memory-lane-2"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('f'))).unwrap();
+    assert_eq!(radio.state.favorites, ["Gone", "Slow Focus"]);
+    let saved: State = serde_json::from_str(&fs::read_to_string(&radio.state_path).unwrap()).unwrap();
+    assert_eq!(saved.favorites, ["Gone", "Slow Focus"]);
+}
+
+#[test]
+fn a_refresh_that_drops_the_selected_mixtape_keeps_the_cursor_in_range() {
+    let mut radio = fixture();
+    let mixtape = |id: &str| StreamRef {
+        section: StreamType::Mixtape,
+        id: id.to_string(),
+    };
+    let refresh = |radio: &mut Radio, mixtapes: Vec<Stream>| {
+        let mut collection = radio.streams_collection.clone();
+        collection.mixtapes = mixtapes;
+        radio.update_collection(Ok(collection));
+    };
+    radio.views.streams.selection = Some(mixtape("memory-lane"));
+
+    // Listed under a new alias, it is the same mixtape
+    refresh(&mut radio, vec![
+        stream("memory-lane-2", "Memory Lane", "", ""),
+        stream("poolside", "Poolside", "", ""),
+        stream("slow-focus", "Slow Focus", "", ""),
+    ]);
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("memory-lane-2"));
+
+    refresh(&mut radio, vec![stream("poolside", "Poolside", "", "")]);
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("poolside"));
+
+    refresh(&mut radio, Vec::new());
+    assert!(radio.selected().is_some_and(|stream| stream.title.starts_with("NTS")));
+    render(&mut radio, 80, 24);
+    for code in [KeyCode::Down, KeyCode::Down, KeyCode::Up, KeyCode::End, KeyCode::Home] {
+        radio.handle_key_press(KeyEvent::from(code)).unwrap();
+        render(&mut radio, 80, 24);
+    }
+}
+
+#[test]
+fn the_selection_follows_its_stream_through_a_reorder_and_a_removal() {
+    let mut radio = fixture();
+    let refresh = |radio: &mut Radio, ids: &[&str]| {
+        let mut collection = radio.streams_collection.clone();
+        collection.mixtapes.sort_by_key(|mixtape| ids.iter().position(|id| *id == mixtape.id));
+        collection.mixtapes.retain(|mixtape| ids.contains(&mixtape.id.as_str()));
+        radio.update_collection(Ok(collection));
+    };
+    radio.views.streams.selection = Some(StreamRef {
+        section: StreamType::Mixtape,
+        id: "slow-focus".to_string(),
+    });
+    assert_eq!(radio.selected_position(), Some(3));
+
+    refresh(&mut radio, &["memory-lane", "slow-focus", "poolside"]);
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("slow-focus"));
+    assert_eq!(radio.selected_position(), Some(3));
+
+    refresh(&mut radio, &["slow-focus", "poolside"]);
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("slow-focus"));
+    assert_eq!(radio.selected_position(), Some(2));
+
+    // Moving on goes by the new order
+    radio.handle_key_press(KeyEvent::from(KeyCode::Down)).unwrap();
+    assert_eq!(radio.selected().map(|stream| stream.id.as_str()), Some("poolside"));
+}
+
+#[test]
+fn a_layout_panic_draws_the_fallback_and_logs_it_once() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    radio.streams_collection.stations[0].description = "x".repeat(100_000);
+    let logged = radio.status_log.len();
+    assert!(render(&mut radio, 80, 24).contains(&"x".repeat(30)));
+    assert_eq!(radio.status_log.len(), logged);
+
+    // Should laying it out ever panic, the fallback is drawn instead
+    radio.draw = |_, _| panic!("description too long to lay out");
+    for _ in 0..3 {
+        assert!(render(&mut radio, 80, 24).contains("render error — press q to quit, l for logs"));
+    }
+    assert_eq!(radio.status_log.len(), logged + 1);
+    assert!(radio.status_log.back().unwrap().contains("Render error: description too long to lay out"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('l'))).unwrap();
+    assert!(render(&mut radio, 80, 24).contains("Render error: description too long"));
+
+    radio.draw = Radio::draw;
+    assert!(!render(&mut radio, 80, 24).contains("render error"));
+}
+
+#[test]
+fn a_storm_of_queued_messages_is_drawn_once_in_order() {
+    let mut radio = fixture();
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    let (ui_tx, ui_rx) = mpsc::channel();
+    let key = |code| UIMessage::KeyPress(KeyEvent::from(code));
+    for _ in 0..1_000 {
+        ui_tx.send(key(KeyCode::Down)).unwrap();
+        ui_tx.send(UIMessage::UpdateUI).unwrap();
+        ui_tx.send(UIMessage::Resize(80, 24)).unwrap();
+    }
+    ui_tx.send(key(KeyCode::Char('/'))).unwrap();
+    for c in "DRONE".chars() {
+        ui_tx.send(key(KeyCode::Char(c))).unwrap();
+    }
+    assert!(radio.handle_queued(&mut terminal, &ui_rx).unwrap());
+    assert_eq!(terminal.get_frame().count(), 1);
+    assert_eq!(radio.views.streams.filter, "DRONE");
+    assert!(terminal.backend().to_string().contains("Slow Focus"));
+
+    ui_tx.send(UIMessage::UpdateUI).unwrap();
+    assert!(radio.handle_queued(&mut terminal, &ui_rx).unwrap());
+    assert_eq!(terminal.get_frame().count(), 2);
+}
+
+#[test]
+fn the_sample_rate_conversion_shows_under_the_playing_show() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    assert!(!render(&mut radio, 80, 24).contains("kHz"));
+    radio.player.set_conversion(Conversion {
+        from: 44_100,
+        to: 48_000,
+        quality: ResampleQuality::Linear,
+    });
+    assert!(render(&mut radio, 80, 24).contains("44.1→48 kHz, linear"));
+}
+
+#[test]
+fn history_deleted_outside_the_app_starts_over_on_the_next_recognition() {
+    let mut radio = fixture();
+    radio.views.history.scroll = 3;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.txt");
+    radio.history_path = path.clone();
+
+    // What a recognition does: append through the store, then hand the
+    // line to the pane
+    let append = |radio: &mut Radio, heard_at, track: &str| {
+        let mut store = HistoryStore::open(&path).unwrap();
+        let line = store.append(history::format_entry(heard_at, TimestampStyle::Local, track, track));
+        store.commit().unwrap();
+        radio.history_written.push(line);
+        radio.refresh_history();
+    };
+    let recognize = |radio: &mut Radio, track: &str| append(radio, fixed_clock().naive_local(), track);
+    recognize(&mut radio, "Burial - Archangel");
+    assert_eq!(radio.recognition_list, "2024-06-01 14:32  Burial - Archangel\t\t1\n");
+    assert_eq!((radio.history_rows.len(), radio.views.history.scroll), (1, 0));
+    assert!(radio.status_log.back().unwrap().ends_with("History file was reset externally"));
+    let screen = render(&mut radio, 80, 24);
+    assert!(screen.contains("Archangel") && !screen.contains("Hubble"));
+
+    // The recreated file carries on as usual
+    let logged = radio.status_log.len();
+    recognize(&mut radio, "Four Tet - Parallel Jalebi");
+    assert_eq!(HistoryStore::open(&path).unwrap().lines().len(), 2);
+    assert_eq!(radio.history_rows.len(), 2);
+    assert_eq!(radio.status_log.len(), logged);
+
+    // One heard earlier goes in mid-file, in memory as in the file, which is
+    // no reset either
+    radio.views.history.scroll = 1;
+    append(&mut radio, fixed_clock().naive_local() - chrono::Duration::hours(1), "Late - Pending");
+    assert!(radio.recognition_list.starts_with("2024-06-01 13:32  Late - Pending"));
+    assert_eq!(radio.recognition_list, fs::read_to_string(&path).unwrap());
+    assert_eq!((radio.views.history.scroll, radio.status_log.len()), (1, logged));
+}
+
+#[test]
+fn history_timestamps_are_dimmed_and_tracks_blue() {
+    let mut radio = fixture();
+    radio.theme = Theme::for_background(Background::Dark);
+    let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+    radio.render_ui(&mut terminal).unwrap();
+    let buffer = terminal.backend().buffer();
+    // The pane is the last place each text shows, below "Last recognized"
+    let color_of = |text: &str| {
+        let width = buffer.area.width as usize;
+        let cells = buffer.content();
+        let screen: String = cells.iter().map(|cell| cell.symbol().chars().next().unwrap_or(' ')).collect();
+        let at = screen[..screen.rfind(text).unwrap()].chars().count();
+        assert!(at % width + text.len() <= width);
+        cells[at].fg
+    };
+    assert_eq!(color_of("2024-06-01 14:20"), Color::DarkGray);
+    assert_eq!(color_of("Carl Craig - At Les"), Color::Blue);
+}
+
+#[test]
+fn number_keys_select_the_mixtapes_as_listed() {
+    let mut radio = fixture();
+    let mixtape = |id: &str| {
+        Some(StreamRef {
+            section: StreamType::Mixtape,
+            id: id.to_string(),
+        })
+    };
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('4'))).unwrap();
+    assert_eq!(radio.views.streams.selection, mixtape("slow-focus"));
+    assert!(radio.playing_stream.is_none());
+    // Only three are listed
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('9'))).unwrap();
+    assert_eq!(radio.views.streams.selection, mixtape("slow-focus"));
+
+    radio.views.streams.filter = "memory".to_string();
+    radio.rebuild_stream_view();
+    assert!(render(&mut radio, 80, 24).contains("3 ■ Memory Lane"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('3'))).unwrap();
+    assert_eq!(radio.views.streams.selection, mixtape("memory-lane"));
+}
+
+#[test]
+fn recognition_indicator_follows_the_run_not_the_info_text() {
+    let mut radio = fixture();
+    radio.recognition_result = Some("Laurel Halo - Jelly".to_string());
+    let (id, _attempt) = radio.begin_recognition(Trigger::Manual, Duration::from_secs(10)).unwrap();
+    let screen = render(&mut radio, 80, 24);
+    assert!(["⠋ ID", "⠙ ID"].iter().any(|spinner| screen.contains(spinner)), "{}", screen);
+
+    radio.recognition_result_tx.send((id, Err("No match".to_string()))).unwrap();
+    radio.handle_recognition_result();
+    let screen = render(&mut radio, 120, 40);
+    assert!(screen.contains("✗ ID") && screen.contains("No match"), "{}", screen);
+}
+
+#[test]
+fn a_recognition_shows_how_far_it_got_and_esc_cancels_it() {
+    let mut radio = fixture();
+    let (id, attempt) = radio.begin_recognition(Trigger::Manual, Duration::from_secs(10)).unwrap();
+    assert!(render(&mut radio, 120, 40).contains("Recognizing… (waiting for audio) (Esc to cancel)"));
+    attempt.captured(Duration::from_millis(7200));
+    assert!(render(&mut radio, 120, 40).contains("Recognizing… (7s of sample captured)"));
+
+    radio.handle_key_press(KeyEvent::from(KeyCode::Esc)).unwrap();
+    assert!(attempt.is_cancelled());
+    assert_eq!(radio.recognition_result.as_deref(), Some("Recognition cancelled"));
+    // What the run reports once it stops is dropped
+    radio.recognition_result_tx.send((id, Err("Recognizer failed".to_string()))).unwrap();
+    radio.handle_recognition_result();
+    assert_eq!(radio.recognition_result.as_deref(), Some("Recognition cancelled"));
+    assert_eq!(radio.id_scheduler.running(), None);
+}
+
+#[test]
+fn a_pause_resumes_on_the_open_connection_until_it_goes_stale() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    let logged = radio.status_log.len();
+    radio.player.pause_at(Instant::now() - Duration::from_secs(30));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('p'))).unwrap();
+    assert_eq!(radio.player.paused_for(), None);
+    assert!(radio.connecting.pending().is_none());
+    assert_eq!(radio.status_log.len(), logged);
+
+    // After two minutes the server has likely dropped it, so `p` connects
+    // again, here to a port nothing listens on
+    radio.config.network.stream_overrides.insert(String::new(), "http://127.0.0.1:9/".to_string());
+    radio.player.pause_at(Instant::now() - Duration::from_secs(121));
+    assert!(render(&mut radio, 120, 40).contains("p reconnects"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('p'))).unwrap();
+    assert_eq!(radio.player.paused_for(), None);
+    assert!(radio.connecting.pending().is_some_and(|connecting| connecting.title == "NTS 1"));
+    assert!(radio.status_log.back().unwrap().ends_with("Paused too long to resume, reconnecting to NTS 1"));
+}
+
+#[test]
+fn a_paused_stream_is_not_sampled() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    radio.player.pause_at(Instant::now());
+    radio.start_recognition(Trigger::Manual);
+    assert_eq!(radio.id_scheduler.running(), None);
+    assert_eq!(radio.recognition_result.as_deref(), Some("Resume the stream to identify what is playing"));
+}
+
+#[test]
+#[cfg(not(feature = "recognition"))]
+fn without_recognition_the_keys_say_so_and_the_history_stays() {
+    let mut radio = fixture();
+    start_playing(&mut radio);
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('r'))).unwrap();
+    assert_eq!(radio.recognition_result.as_deref(), Some(crate::recognition::UNAVAILABLE));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Tab)).unwrap();
+    radio.handle_key_press(KeyEvent::from(KeyCode::Delete)).unwrap();
+    assert_eq!(radio.recognition_list, HISTORY);
+
+    let screen = render(&mut radio, 120, 40);
+    assert!(screen.contains("Carl Craig - At Les") && !screen.contains("r: Recognise"), "{}", screen);
+}
+
+#[test]
+fn deleting_a_history_entry_checks_the_file_still_has_it() {
+    let mut radio = fixture();
+    let shown = |radio: &Radio| radio.history_rows.iter().map(|row| row.text.clone()).collect::<Vec<_>>();
+    let actress = shown(&radio).iter().position(|text| text.contains("Actress")).unwrap();
+
+    // An entry added outside the app moves every line down by one
+    fs::write(&radio.history_path, format!("2024-05-30 09:00  Someone - Earlier\n{}", HISTORY)).unwrap();
+    radio.views.history.scroll = actress;
+    radio.delete_history_entry();
+    assert_eq!(radio.recognition_result.as_deref(), Some("The history file changed since it was read, nothing deleted"));
+    assert_eq!(fs::read_to_string(&radio.history_path).unwrap().lines().count(), 6);
+    assert!(shown(&radio)[0].contains("Someone - Earlier"));
+
+    // Read again, the same entry goes
+    radio.views.history.scroll = actress + 1;
+    radio.delete_history_entry();
+    let contents = fs::read_to_string(&radio.history_path).unwrap();
+    assert!(!contents.contains("Actress") && contents.contains("Someone - Earlier"), "{}", contents);
+    assert_eq!(radio.recognition_list, contents);
+}
+
+#[test]
+fn the_migration_popup_lists_the_moves_and_n_leaves_them() {
+    let mut radio = fixture();
+    radio.migration = Some(vec![migrate::Move {
+        what: "state",
+        from: PathBuf::from("/home/listener/.nts_cli_state.json"),
+        to: PathBuf::from("/home/listener/.local/share/nts_cli/state.json"),
+    }]);
+    let screen = render(&mut radio, 120, 40);
+    assert!(screen.contains("Files from an older version"));
+    assert!(screen.contains("state: /home/listener/.nts_cli_state.json"));
+    // Other keys wait for an answer
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('?'))).unwrap();
+    assert!(!radio.show_help && radio.migration.is_some());
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('n'))).unwrap();
+    assert!(radio.migration.is_none());
+    assert!(radio.status_log.iter().any(|line| line.contains("nts_cli migrate")));
+}
+
+#[test]
+fn y_copies_only_a_recognized_track() {
+    let mut radio = fixture();
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('y'))).unwrap();
+    assert_eq!(radio.recognition_result.as_deref(), Some("Nothing to copy"));
+    assert!(copied().is_empty());
+
+    radio.last_track = Some("Hubble - Actress".to_string());
+    radio.show_info("Recognizing...".to_string());
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('y'))).unwrap();
+    assert_eq!(radio.recognition_result.as_deref(), Some("Copied Hubble - Actress"));
+    assert_eq!(copied(), ["Hubble - Actress"]);
+}
+
+#[test]
+fn e_shares_the_sessions_tracks_or_says_there_are_none() {
+    let mut radio = fixture();
+    let dir = tempfile::tempdir().unwrap();
+    radio.config.share.notes_dir = dir.path().to_path_buf();
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('E'))).unwrap();
+    assert_eq!(radio.recognition_result.as_deref(), Some("Nothing played this session"));
+
+    radio.session = Some(share::Session {
+        show: "Breakfast Show".to_string(),
+        stream: "NTS 1".to_string(),
+        since: Local.with_ymd_and_hms(2024, 6, 1, 14, 25, 0).unwrap(),
+    });
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('E'))).unwrap();
+    assert_eq!(radio.recognition_result.as_deref(), Some("No tracks recognized since NTS 1 started at 14:25"));
+    assert!(copied().is_empty());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    radio.session.as_mut().unwrap().since = Local.with_ymd_and_hms(2024, 6, 1, 13, 30, 0).unwrap();
+    radio.handle_key_press(KeyEvent::from(KeyCode::Char('E'))).unwrap();
+    let saved = dir.path().join("session-20240601-143200.txt");
+    assert_eq!(
+        radio.recognition_result.as_deref(),
+        Some(format!("Copied 2 tracks, saved to {}", saved.display()).as_str())
+    );
+    let saved = fs::read_to_string(saved).unwrap();
+    assert!(saved.ends_with("1. 13:52 Actress - Hubble\n2. 14:20 Carl Craig - At Les\n"), "{}", saved);
+    assert_eq!(copied(), [saved]);
+}
+
+#[test]
+fn a_dropped_stream_that_is_not_reconnected_says_so_until_something_plays() {
+    let mut radio = fixture();
+    radio.config.playback.reconnect_attempts = 0;
+    radio.schedule_reconnect(StreamRef {
+        section: StreamType::Station,
+        id: "1".to_string(),
+    });
+    assert!(radio.auto_reconnect.is_none());
+    assert!(render(&mut radio, 120, 40).contains("NTS 1 dropped (Enter reconnects)"));
+    radio.handle_key_press(KeyEvent::from(KeyCode::Enter)).unwrap();
+    assert!(radio.stream_lost.is_none());
+}
+
+#[test]
+fn a_burst_of_history_changes_rebuilds_the_pane_a_bounded_number_of_times() {
+    let mut radio = fixture();
+    let mut store = HistoryStore::open(&radio.history_path).unwrap();
+    let start = fixed_clock().naive_local();
+    let mut write = |minute: i64| {
+        let heard_at = start + chrono::Duration::minutes(minute);
+        let line = store.append(history::format_entry(heard_at, TimestampStyle::Local, "Track - Artist", "Track - Artist"));
+        store.commit().unwrap();
+        (heard_at, line)
+    };
+
+    // 10,000 pending IDs identified at once come in as one bulk change
+    let matches = (0..10_000)
+        .map(|minute| {
+            let (heard_at, line) = write(minute);
+            PendingMatch {
+                track: "Track - Artist".to_string(),
+                heard_at: Local.from_local_datetime(&heard_at).unwrap(),
+                write_history: true,
+                history_line: Some(line),
+            }
+        })
+        .collect();
+    let rebuilds = radio.history_rebuilds;
+    radio.handle_pending_ids_retried(matches, 0);
+    assert_eq!(radio.history_rebuilds - rebuilds, 1);
+    assert_eq!(radio.recognition_list.lines().count(), 10_005);
+
+    // Results arriving one after another fold into a few rebuilds
+    for minute in 10_000..11_000 {
+        let (_, line) = write(minute);
+        radio.history_written.push(line);
+        radio.history_changed();
+    }
+    assert!(radio.history_rebuilds - rebuilds <= 3, "{} rebuilds", radio.history_rebuilds - rebuilds);
+
+    thread::sleep(HISTORY_REFRESH_INTERVAL);
+    radio.handle_message(UIMessage::HistoryRefreshDue).unwrap();
+    let contents = HistoryStore::open(&radio.history_path).unwrap().contents();
+    assert_eq!(radio.recognition_list, contents);
+    assert_eq!(radio.history_rows.len(), history::rows(&contents, &radio.expanded_streaks).len());
+}
@@ -0,0 +1,92 @@
+/// The panes that take the cursor keys, one focused at a time. Tab moves
+/// the focus between them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum View {
+    #[default]
+    Streams,
+    History,
+}
+
+/// Where the user is in one view. Kept for the whole session, so leaving a
+/// view and coming back lands exactly where they were.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ViewState<S> {
+    pub selection: Option<S>,
+    pub scroll: usize,
+    pub filter: String,
+}
+
+impl<S> Default for ViewState<S> {
+    fn default() -> Self {
+        ViewState {
+            selection: None,
+            scroll: 0,
+            filter: String::new(),
+        }
+    }
+}
+
+/// Every view's state and which view has focus. Switching views only moves
+/// the focus; nothing is reset.
+#[derive(Debug)]
+pub struct Views<S> {
+    pub focus: View,
+    /// The stream list; `scroll` is how far the description is scrolled.
+    pub streams: ViewState<S>,
+    /// The history pane, which highlights its top row, so only `scroll` is
+    /// used.
+    pub history: ViewState<usize>,
+}
+
+impl<S> Views<S> {
+    pub fn new(streams: ViewState<S>, history: ViewState<usize>) -> Self {
+        Views {
+            focus: View::default(),
+            streams,
+            history,
+        }
+    }
+
+    pub fn is_focused(&self, view: View) -> bool {
+        self.focus == view
+    }
+
+    /// Moves the focus to the next view, wrapping around.
+    pub fn switch(&mut self) {
+        self.focus = match self.focus {
+            View::Streams => View::History,
+            View::History => View::Streams,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switching_keeps_every_view_where_it_was() {
+        let mut views = Views::new(
+            ViewState {
+                selection: Some("poolside"),
+                scroll: 3,
+                filter: "pool".to_string(),
+            },
+            ViewState {
+                scroll: 12,
+                ..Default::default()
+            },
+        );
+        let (streams, history) = (views.streams.clone(), views.history.clone());
+        for _ in 0..5 {
+            views.switch();
+        }
+        assert!(views.is_focused(View::History));
+        views.history.scroll = 4;
+        views.switch();
+        assert!(views.is_focused(View::Streams));
+        assert_eq!(views.streams, streams);
+        views.switch();
+        assert_eq!(views.history, ViewState { scroll: 4, ..history });
+    }
+}